@@ -0,0 +1,165 @@
+//! # oaidl-derive
+//! Derive macros for [`oaidl`](https://docs.rs/oaidl), enabled through its `derive` feature.
+//!
+//! `#[derive(VariantRecord)]` implements `oaidl::Record` the way `com_record!` does,
+//! mapping every field of a struct to the UDT field of the same name. A field can be
+//! mapped to a differently named UDT field with `#[record(name = "...")]`.
+//!
+//! For a `#[repr(C)]` struct the derive also reports the byte offset of each field,
+//! so `oaidl::check_record_layout` can check the struct against the offsets the UDT
+//! declares, and not only its field names and order.
+//!
+//! `#[derive(SafeArrayRow)]` implements `oaidl::SafeArrayRow` the way `com_row!` does,
+//! one `VT_VARIANT` element per field in declaration order.
+#![deny(missing_docs)]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use] extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{Data, DeriveInput, Fields, Ident, Lit, Meta, NestedMeta};
+
+/// Implements `oaidl::Record` for a struct with named fields
+#[proc_macro_derive(VariantRecord, attributes(record))]
+pub fn derive_variant_record(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match variant_record(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Implements `oaidl::SafeArrayRow` for a struct with named fields
+#[proc_macro_derive(SafeArrayRow)]
+pub fn derive_safearray_row(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match safearray_row(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// A struct field and the name it goes by on the COM side
+struct Field {
+    ident: Ident,
+    name: String,
+}
+
+fn variant_record(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = named_fields(input, "VariantRecord")?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let idents: Vec<&Ident> = fields.iter().map(|f| &f.ident).collect();
+    let names: Vec<&String> = fields.iter().map(|f| &f.name).collect();
+
+    //only a struct laid out like C can be compared with the UDT's offsets
+    let offsets = if is_repr_c(input) {
+        quote! {
+            fn field_offsets() -> ::std::option::Option<::std::vec::Vec<usize>> {
+                let base = ::std::mem::MaybeUninit::<Self>::uninit();
+                let p = base.as_ptr();
+                ::std::option::Option::Some(vec![
+                    #(unsafe { ::std::ptr::addr_of!((*p).#idents) as usize - p as usize }),*
+                ])
+            }
+        }
+    } else {
+        TokenStream2::new()
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::oaidl::Record for #name #ty_generics #where_clause {
+            const FIELDS: &'static [&'static str] = &[#(#names),*];
+
+            fn into_fields(self) -> ::std::result::Result<::std::vec::Vec<(&'static str, ::oaidl::Ptr<::oaidl::__VARIANT>)>, ::oaidl::IntoSafeArrElemError> {
+                let mut fields = ::std::vec::Vec::new();
+                #(::oaidl::__push_field(&mut fields, #names, self.#idents)?;)*
+                ::std::result::Result::Ok(fields)
+            }
+
+            fn from_fields(fields: &::oaidl::RecordFields) -> ::std::result::Result<Self, ::oaidl::FromVariantError> {
+                ::std::result::Result::Ok(#name {
+                    #(#idents: fields.get(#names)?,)*
+                })
+            }
+
+            #offsets
+        }
+    })
+}
+
+fn safearray_row(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = named_fields(input, "SafeArrayRow")?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let idents: Vec<&Ident> = fields.iter().map(|f| &f.ident).collect();
+    let names: Vec<&String> = fields.iter().map(|f| &f.name).collect();
+
+    Ok(quote! {
+        impl #impl_generics ::oaidl::SafeArrayRow for #name #ty_generics #where_clause {
+            const FIELDS: &'static [&'static str] = &[#(#names),*];
+
+            fn into_row(self) -> ::std::result::Result<::oaidl::Ptr<::oaidl::__SAFEARRAY>, ::oaidl::IntoSafeArrayError> {
+                let mut row = ::oaidl::__RowWriter::new(<Self as ::oaidl::SafeArrayRow>::FIELDS.len())?;
+                #(row.push(self.#idents)?;)*
+                ::std::result::Result::Ok(row.finish())
+            }
+
+            fn from_row(psa: *mut ::oaidl::__SAFEARRAY) -> ::std::result::Result<Self, ::oaidl::FromSafeArrayError> {
+                let mut row = ::oaidl::__RowReader::new(psa, <Self as ::oaidl::SafeArrayRow>::FIELDS.len())?;
+                ::std::result::Result::Ok(#name {
+                    #(#idents: row.next()?,)*
+                })
+            }
+        }
+    })
+}
+
+/// The named fields of a struct, with any `#[record(name = "...")]` applied
+fn named_fields(input: &DeriveInput, derive: &str) -> syn::Result<Vec<Field>> {
+    let data = match input.data {
+        Data::Struct(ref data) => data,
+        _ => return Err(syn::Error::new_spanned(&input.ident, format!("{} can only be derived for structs", derive))),
+    };
+    let named = match data.fields {
+        Fields::Named(ref named) => named,
+        _ => return Err(syn::Error::new_spanned(&input.ident, format!("{} needs a struct with named fields", derive))),
+    };
+    named.named.iter().map(|field| {
+        let ident = field.ident.clone().expect("named fields have names");
+        let mut name = ident.to_string();
+        for attr in field.attrs.iter().filter(|a| a.path.is_ident("record")) {
+            name = record_name(&attr.parse_meta()?)?;
+        }
+        Ok(Field { ident, name })
+    }).collect()
+}
+
+/// Reads the name out of `#[record(name = "...")]`
+fn record_name(meta: &Meta) -> syn::Result<String> {
+    if let Meta::List(ref list) = *meta {
+        if list.nested.len() == 1 {
+            if let NestedMeta::Meta(Meta::NameValue(ref nv)) = list.nested[0] {
+                if let Lit::Str(ref s) = nv.lit {
+                    if nv.path.is_ident("name") {
+                        return Ok(s.value());
+                    }
+                }
+            }
+        }
+    }
+    Err(syn::Error::new_spanned(meta, "expected #[record(name = \"...\")]"))
+}
+
+fn is_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().filter(|a| a.path.is_ident("repr")).any(|attr| match attr.parse_meta() {
+        Ok(Meta::List(ref list)) => list.nested.iter().any(|nested| match *nested {
+            NestedMeta::Meta(Meta::Path(ref path)) => path.is_ident("C"),
+            _ => false,
+        }),
+        _ => false,
+    })
+}
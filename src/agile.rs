@@ -0,0 +1,206 @@
+//! # Agile
+//! Moving automation results between threads and apartments. 
+//! 
+//! Interface pointers obtained in a single-threaded apartment may only be used 
+//! on that apartment's thread. An async service that holds a result across an 
+//! `.await` can resume on any thread, so the pointer has to be marshaled. 
+//! [`AgileVariant`] registers `VT_UNKNOWN`/`VT_DISPATCH` payloads in the Global 
+//! Interface Table (GIT) and hands back a proxy for whichever apartment asks, 
+//! while other payloads are held as plain [`Variants`] values. It is `Send` and 
+//! `Sync` and doesn't depend on any particular executor. 
+//! 
+//! COM must be initialised on every thread that creates or fetches an `AgileVariant`. 
+//! 
+//! [`AgileVariant`]: struct.AgileVariant.html
+//! [`Variants`]: enum.Variants.html
+use std::mem;
+use std::ptr::null_mut;
+
+use winapi::ctypes::c_void;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::guiddef::GUID;
+use winapi::shared::wtypes::{VT_DISPATCH, VT_UNKNOWN};
+use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+use winapi::um::cguid::CLSID_StdGlobalInterfaceTable;
+use winapi::um::combaseapi::CoCreateInstance;
+use winapi::um::oaidl::{IDispatch, VARIANT, VARIANT_n3};
+use winapi::um::objidlbase::IGlobalInterfaceTable;
+use winapi::um::unknwnbase::IUnknown;
+use winapi::Interface;
+
+use super::dtor::{FreeVariant, PtrDestructor};
+use super::errors::MarshalError;
+use super::ptr::Ptr;
+use super::variant::{new_variant, Variants};
+
+/// A VARIANT payload that can be sent to, and used from, any thread
+#[derive(Debug)]
+pub struct AgileVariant {
+    inner: Agile,
+}
+
+#[derive(Debug)]
+enum Agile {
+    /// Never an interface arm - those are registered in the GIT instead
+    Value(Variants), 
+    Interface { cookie: DWORD, vt: u32 },
+}
+
+// `Value` holds plain data only and `Interface` a GIT cookie, which any apartment may use
+unsafe impl Send for AgileVariant {}
+unsafe impl Sync for AgileVariant {}
+
+impl AgileVariant {
+    /// Takes ownership of `var`. Interface payloads are registered in the GIT 
+    /// from the current apartment; anything else is decoded into a `Variants`.
+    pub fn new(var: Ptr<VARIANT>) -> Result<AgileVariant, MarshalError> {
+        let pvar = var.as_ptr();
+        let vt = unsafe { (*pvar).n1.n2().vt } as u32;
+        if vt != VT_UNKNOWN && vt != VT_DISPATCH {
+            let val = Variants::from_variant(var);
+            FreeVariant::destroy(var);
+            let val = val.map_err(|e| MarshalError::Decode(Box::new(e)))?;
+            if has_interface(&val) {
+                return Err(MarshalError::NestedInterface);
+            }
+            return Ok(AgileVariant { inner: Agile::Value(val) });
+        }
+
+        let punk = unsafe { *(*pvar).n1.n2().n3.punkVal() };
+        let git = match Git::new() {
+            Ok(git) => git,
+            Err(e) => {
+                FreeVariant::destroy(var);
+                return Err(e);
+            }
+        };
+        let mut cookie: DWORD = 0;
+        let hr = if punk.is_null() {
+            0
+        } else {
+            unsafe { (*git.0).RegisterInterfaceInGlobal(punk, &iid_for(vt), &mut cookie) }
+        };
+        //the GIT now holds its own reference; release the VARIANT's
+        FreeVariant::destroy(var);
+        if hr != 0 {
+            return Err(MarshalError::RegisterFailed{hr: hr});
+        }
+        Ok(AgileVariant { inner: Agile::Interface { cookie: cookie, vt: vt } })
+    }
+
+    /// Wraps a `Variants` value. The interface arms are registered in the GIT 
+    /// from the current apartment, as with `new`.
+    pub fn from_value(val: Variants) -> Result<AgileVariant, MarshalError> {
+        match val {
+            Variants::Unknown(_) | Variants::Dispatch(_) => {
+                let var = val.into_variant().map_err(|e| MarshalError::Encode(Box::new(e)))?;
+                AgileVariant::new(var)
+            },
+            ref val if has_interface(val) => Err(MarshalError::NestedInterface),
+            val => Ok(AgileVariant { inner: Agile::Value(val) }),
+        }
+    }
+
+    /// Creates a VARIANT usable from the current thread's apartment. 
+    /// Interface payloads are unmarshaled from the GIT, so this yields a proxy when needed.
+    pub fn fetch(&self) -> Result<Ptr<VARIANT>, MarshalError> {
+        match self.inner {
+            Agile::Value(ref val) => {
+                val.clone().into_variant().map_err(|e| MarshalError::Encode(Box::new(e)))
+            }, 
+            Agile::Interface { cookie, vt } => {
+                let mut punk: *mut c_void = null_mut();
+                if cookie != 0 {
+                    let git = Git::new()?;
+                    let hr = unsafe { (*git.0).GetInterfaceFromGlobal(cookie, &iid_for(vt), &mut punk) };
+                    if hr != 0 {
+                        return Err(MarshalError::GetFailed{hr: hr});
+                    }
+                }
+                let mut n3: VARIANT_n3 = unsafe { mem::zeroed() };
+                unsafe { *n3.punkVal_mut() = punk as *mut IUnknown };
+                Ok(new_variant(vt, n3))
+            },
+        }
+    }
+
+    /// Runs `f` with a VARIANT fetched for the current apartment, releasing it afterwards
+    pub fn with_variant<F, R>(&self, f: F) -> Result<R, MarshalError> 
+    where F: FnOnce(Ptr<VARIANT>) -> R
+    {
+        let var = self.fetch()?;
+        let r = f(var);
+        FreeVariant::destroy(var);
+        Ok(r)
+    }
+}
+
+impl Drop for AgileVariant {
+    fn drop(&mut self) {
+        if let Agile::Interface { cookie, .. } = self.inner {
+            if cookie == 0 {
+                return;
+            }
+            if let Ok(git) = Git::new() {
+                unsafe { (*git.0).RevokeInterfaceFromGlobal(cookie) };
+            }
+        }
+    }
+}
+
+fn has_interface(val: &Variants) -> bool {
+    match *val {
+        Variants::Unknown(_) | Variants::Dispatch(_) => true,
+        Variants::Array(ref items) => items.iter().any(has_interface),
+        _ => false,
+    }
+}
+
+fn iid_for(vt: u32) -> GUID {
+    if vt == VT_DISPATCH { IDispatch::uuidof() } else { IUnknown::uuidof() }
+}
+
+/// The process-wide Global Interface Table, released on drop
+struct Git(*mut IGlobalInterfaceTable);
+
+impl Git {
+    fn new() -> Result<Git, MarshalError> {
+        let mut p: *mut c_void = null_mut();
+        let hr = unsafe {
+            CoCreateInstance(
+                &CLSID_StdGlobalInterfaceTable, 
+                null_mut(), 
+                CLSCTX_INPROC_SERVER, 
+                &IGlobalInterfaceTable::uuidof(), 
+                &mut p
+            )
+        };
+        if hr != 0 || p.is_null() {
+            return Err(MarshalError::GitUnavailable{hr: hr});
+        }
+        Ok(Git(p as *mut IGlobalInterfaceTable))
+    }
+}
+
+impl Drop for Git {
+    fn drop(&mut self) {
+        unsafe { (*self.0).Release() };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+    use super::super::variant::VariantExt;
+    fn assert_send_sync<T: Send + Sync>() {}
+    #[test]
+    fn test_value_crosses_threads() {
+        assert_send_sync::<AgileVariant>();
+        let agile = AgileVariant::from_value(Variants::from("payload")).unwrap();
+        let handle = thread::spawn(move || {
+            agile.with_variant(|pvar| String::from_variant(pvar).unwrap()).unwrap()
+        });
+        assert_eq!(handle.join().unwrap(), "payload");
+    }
+}
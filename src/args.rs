@@ -0,0 +1,232 @@
+//! # Args
+//! Argument buffers for `IDispatch::Invoke`. 
+//! 
+//! `DISPPARAMS::rgvarg` is a contiguous array of `VARIANTARG` structures, stored 
+//! in *reverse* order (the last argument comes first). [`VariantArgs`] owns such 
+//! a buffer, built from the boxed VARIANTs returned by `into_variant`, and clears 
+//! every element when dropped. [`variants_from_args`] goes the other way for the 
+//! server side of `Invoke`. 
+//! 
+//! [`VariantArgs`]: struct.VariantArgs.html
+//! [`variants_from_args`]: fn.variants_from_args.html
+use std::mem;
+use std::ptr;
+
+use winapi::um::oaidl::{DISPPARAMS, VARIANT, VARIANTARG};
+use winapi::um::oleauto::{VariantClear, VariantCopy};
+
+use super::dtor::{FreeVariant, PtrDestructor};
+use super::errors::{FromVariantError, IntoVariantError};
+use super::leak_tracking;
+use super::ptr::Ptr;
+use super::variant::VariantExt;
+
+/// Owned `rgvarg` buffer for `IDispatch::Invoke`
+/// 
+/// Arguments are pushed in call order, and the buffer is reversed into the order 
+/// `Invoke` expects once it is handed out.
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::VariantArgs;
+/// 
+/// fn main() {
+///     let mut args = VariantArgs::new();
+///     args.push_value(String::from("Sheet1")).unwrap();
+///     args.push_value(2i32).unwrap();
+///     let params = args.disp_params();
+///     assert_eq!(params.cArgs, 2);
+///     // pass &mut params to IDispatch::Invoke while `args` is alive
+/// }
+/// ```
+#[derive(Default)]
+pub struct VariantArgs {
+    args: Vec<VARIANTARG>,
+    //whether `args` is currently in rgvarg (reversed) order rather than call order
+    reversed: bool,
+}
+
+impl VariantArgs {
+    /// Creates an empty argument buffer
+    pub fn new() -> VariantArgs {
+        VariantArgs { args: Vec::new(), reversed: false }
+    }
+
+    /// Builds a buffer from variants given in call order, taking ownership of them
+    pub fn from_variants(vars: Vec<Ptr<VARIANT>>) -> VariantArgs {
+        let mut args = VariantArgs::new();
+        for var in vars {
+            args.push(var);
+        }
+        args
+    }
+
+    /// Appends an argument, taking ownership of the boxed VARIANT produced by `into_variant`
+    pub fn push(&mut self, var: Ptr<VARIANT>) {
+        leak_tracking::variant_freed(var.as_ptr());
+        let var = unsafe { *Box::from_raw(var.as_ptr()) };
+        self.order(false);
+        self.args.push(var);
+    }
+
+    /// Converts `value` and appends it as the next argument
+    pub fn push_value<T: VariantExt>(&mut self, value: T) -> Result<(), IntoVariantError> {
+        let var = value.into_variant()?;
+        self.push(var);
+        Ok(())
+    }
+
+    /// Number of arguments
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    /// True if there are no arguments
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    /// The arguments in `rgvarg` (reversed) order
+    pub fn as_slice(&mut self) -> &[VARIANTARG] {
+        self.order(true);
+        &self.args
+    }
+
+    /// The arguments in `rgvarg` (reversed) order, mutably - byref out parameters are written here
+    pub fn as_mut_slice(&mut self) -> &mut [VARIANTARG] {
+        self.order(true);
+        &mut self.args
+    }
+
+    /// A `DISPPARAMS` pointing at this buffer, with no named arguments. 
+    /// It must not outlive `self`.
+    pub fn disp_params(&mut self) -> DISPPARAMS {
+        self.order(true);
+        DISPPARAMS {
+            rgvarg: if self.args.is_empty() { ptr::null_mut() } else { self.args.as_mut_ptr() }, 
+            rgdispidNamedArgs: ptr::null_mut(), 
+            cArgs: self.args.len() as u32, 
+            cNamedArgs: 0,
+        }
+    }
+
+    /// Gives the arguments back as boxed VARIANTs in call order, e.g. to read out parameters
+    pub fn into_variants(mut self) -> Vec<Ptr<VARIANT>> {
+        self.order(false);
+        let args = mem::replace(&mut self.args, Vec::new());
+        args.into_iter().map(|var| {
+            leak_tracking::variant_adopted(&var);
+            Ptr::with_checked(Box::into_raw(Box::new(var))).unwrap()
+        }).collect()
+    }
+
+    /// Puts the buffer in `rgvarg` order if `reversed`, in call order otherwise
+    fn order(&mut self, reversed: bool) {
+        if self.reversed != reversed {
+            self.args.reverse();
+            self.reversed = reversed;
+        }
+    }
+}
+
+impl Drop for VariantArgs {
+    fn drop(&mut self) {
+        for var in self.args.iter_mut() {
+            unsafe { VariantClear(var) };
+        }
+    }
+}
+
+/// Copies an `rgvarg` slice received by `Invoke` into boxed VARIANTs, in call order. 
+/// 
+/// The slice is left untouched; it still belongs to the caller.
+pub fn variants_from_args(args: &[VARIANTARG]) -> Result<Vec<Ptr<VARIANT>>, FromVariantError> {
+    let mut vars = Vec::with_capacity(args.len());
+    for arg in args.iter().rev() {
+        let mut dest: VARIANT = unsafe { mem::zeroed() };
+        let hr = unsafe { VariantCopy(&mut dest, arg as *const VARIANTARG as *mut VARIANTARG) };
+        if hr != 0 {
+            for var in vars {
+                FreeVariant::destroy(var);
+            }
+            return Err(FromVariantError::VariantCopyFailed{hr: hr});
+        }
+        leak_tracking::variant_adopted(&dest);
+        vars.push(Ptr::with_checked(Box::into_raw(Box::new(dest))).unwrap());
+    }
+    Ok(vars)
+}
+
+/// Writes `result` into the `pVarResult` out parameter of `Invoke`. 
+/// 
+/// A null `var_result` means the caller does not want the value, which is not an error. 
+/// Whatever the slot held before is cleared, then the new VARIANT is written in place.
+pub fn put_retval<T: VariantExt>(result: T, var_result: *mut VARIANT) -> Result<(), IntoVariantError> {
+    if var_result.is_null() {
+        return Ok(());
+    }
+    let var = result.into_variant()?;
+    leak_tracking::variant_freed(var.as_ptr());
+    let var = unsafe { *Box::from_raw(var.as_ptr()) };
+    unsafe {
+        VariantClear(var_result);
+        ptr::write(var_result, var);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_order() {
+        let mut args = VariantArgs::new();
+        args.push_value(1i32).unwrap();
+        args.push_value(String::from("two")).unwrap();
+        {
+            let params = args.disp_params();
+            assert_eq!(params.cArgs, 2);
+            assert_eq!(params.cNamedArgs, 0);
+        }
+        let copies = variants_from_args(args.as_slice()).unwrap();
+        let vars = args.into_variants();
+        assert_eq!(i32::from_variant(vars[0]).unwrap(), 1);
+        assert_eq!(String::from_variant(vars[1]).unwrap(), "two");
+        assert_eq!(i32::from_variant(copies[0]).unwrap(), 1);
+        assert_eq!(String::from_variant(copies[1]).unwrap(), "two");
+    }
+
+    #[test]
+    fn test_push_after_handout() {
+        let mut args = VariantArgs::new();
+        args.push_value(1i32).unwrap();
+        args.push_value(2i32).unwrap();
+        assert_eq!(unsafe { *args.as_slice()[0].n1.n2().n3.lVal() }, 2);
+        args.push_value(3i32).unwrap();
+        assert_eq!(args.disp_params().cArgs, 3);
+        assert_eq!(unsafe { *args.as_slice()[0].n1.n2().n3.lVal() }, 3);
+        let vars = args.into_variants();
+        let vals: Vec<i32> = vars.into_iter().map(|var| i32::from_variant(var).unwrap()).collect();
+        assert_eq!(vals, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_put_retval() {
+        let mut slot: VARIANT = unsafe { mem::zeroed() };
+        put_retval(String::from("first"), &mut slot).unwrap();
+        put_retval(42i32, &mut slot).unwrap();
+        let var = Ptr::with_checked(Box::into_raw(Box::new(slot))).unwrap();
+        assert_eq!(i32::from_variant(var).unwrap(), 42);
+        assert!(put_retval(1i32, ptr::null_mut()).is_ok());
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut args = VariantArgs::new();
+        assert!(args.is_empty());
+        assert!(args.disp_params().rgvarg.is_null());
+    }
+}
@@ -0,0 +1,144 @@
+//! # Arith
+//! VB compatible arithmetic on `VARIANT`s through the oleaut32 `Var*` functions. 
+//! 
+//! OLE Automation picks the result vartype the way VB does - `VT_I2 + VT_I2` 
+//! widens to `VT_I4` when it overflows, a `VT_BSTR` holding a number is parsed, 
+//! `VT_NULL` propagates and `/` always gives a `VT_R8` (or `VT_DECIMAL`/`VT_CY`). 
+//! The operands are never modified. 
+use std::mem;
+
+use winapi::shared::ntdef::HRESULT;
+use winapi::um::oaidl::{LPVARIANT, VARIANT};
+
+use super::codes;
+use super::dtor::{FreeVariant, PtrDestructor};
+use super::errors::ArithmeticError;
+use super::ptr::Ptr;
+use super::stats::ConversionStats;
+use super::types::SCode;
+use super::variant::Variants;
+
+macro_rules! arith_impl {
+    ($($(#[$attr:meta])* $name:ident, $method:ident => $func:ident;)*) => {
+        $(
+            $(#[$attr])*
+            pub fn $name(left: &Ptr<VARIANT>, right: &Ptr<VARIANT>) -> Result<Ptr<VARIANT>, ArithmeticError> {
+                binary(left, right, $func)
+            }
+        )*
+
+        impl Variants {
+            $(
+                $(#[$attr])*
+                pub fn $method(&self, rhs: &Variants) -> Result<Variants, ArithmeticError> {
+                    binary_variants(self, rhs, $func)
+                }
+            )*
+        }
+    };
+}
+
+arith_impl! {
+    /// Adds two values with `VarAdd`. Two strings are concatenated. 
+    variant_add, var_add => VarAdd;
+    /// Subtracts `right` from `left` with `VarSub`. 
+    variant_sub, var_sub => VarSub;
+    /// Multiplies two values with `VarMul`. 
+    variant_mul, var_mul => VarMul;
+    /// Divides `left` by `right` with `VarDiv`, the VB `/` operator. 
+    variant_div, var_div => VarDiv;
+}
+
+type VarBinaryFn = unsafe extern "system" fn(LPVARIANT, LPVARIANT, LPVARIANT) -> HRESULT;
+
+fn binary(left: &Ptr<VARIANT>, right: &Ptr<VARIANT>, func: VarBinaryFn) -> Result<Ptr<VARIANT>, ArithmeticError> {
+    let mut result: VARIANT = unsafe { mem::zeroed() };
+    let hr = unsafe { func(left.as_ptr(), right.as_ptr(), &mut result) };
+    let sc = SCode::from(hr);
+    if sc == codes::DISP_E_OVERFLOW {
+        return Err(ArithmeticError::Overflow);
+    }
+    if sc == codes::DISP_E_TYPEMISMATCH {
+        return Err(ArithmeticError::TypeMismatch{left: vt_of(left), right: vt_of(right)});
+    }
+    if sc == codes::DISP_E_DIVBYZERO {
+        return Err(ArithmeticError::DivisionByZero);
+    }
+    if hr != 0 {
+        return Err(ArithmeticError::OperationFailed{hr: hr});
+    }
+    let var = Box::new(result);
+    ConversionStats::record_variant();
+    Ok(Ptr::with_checked(Box::into_raw(var)).unwrap())
+}
+
+fn binary_variants(left: &Variants, right: &Variants, func: VarBinaryFn) -> Result<Variants, ArithmeticError> {
+    let pleft = left.clone().into_variant()?;
+    let pright = match right.clone().into_variant() {
+        Ok(p) => p, 
+        Err(ive) => {
+            FreeVariant::destroy(pleft);
+            return Err(ArithmeticError::from(ive));
+        }
+    };
+    let ret = binary(&pleft, &pright, func);
+    FreeVariant::destroy(pleft);
+    FreeVariant::destroy(pright);
+    let presult = ret?;
+    let value = Variants::from_variant(presult);
+    FreeVariant::destroy(presult);
+    Ok(value?)
+}
+
+fn vt_of(var: &Ptr<VARIANT>) -> u32 {
+    unsafe {(*var.as_ptr()).n1.n2()}.vt as u32
+}
+
+#[link(name="OleAut32")]
+extern "system" {
+    fn VarAdd(pvarLeft: LPVARIANT, pvarRight: LPVARIANT, pvarResult: LPVARIANT) -> HRESULT;
+    fn VarSub(pvarLeft: LPVARIANT, pvarRight: LPVARIANT, pvarResult: LPVARIANT) -> HRESULT;
+    fn VarMul(pvarLeft: LPVARIANT, pvarRight: LPVARIANT, pvarResult: LPVARIANT) -> HRESULT;
+    fn VarDiv(pvarLeft: LPVARIANT, pvarRight: LPVARIANT, pvarResult: LPVARIANT) -> HRESULT;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rust_decimal::Decimal;
+    use super::super::leak_tracking;
+    use super::super::variant::VariantExt;
+    #[test]
+    fn test_variant_add() {
+        leak_tracking::assert_balanced(|| {
+            let a = 2i32.into_variant().unwrap();
+            let b = String::from("40").into_variant().unwrap();
+            let sum = variant_add(&a, &b).unwrap();
+            assert_eq!(Variants::from_variant(sum).unwrap(), Variants::F64(42.0));
+            FreeVariant::destroy(sum);
+            FreeVariant::destroy(a);
+            FreeVariant::destroy(b);
+        });
+    }
+
+    #[test]
+    fn test_variants_arith() {
+        assert_eq!(Variants::I32(6).var_mul(&Variants::I32(7)).unwrap(), Variants::I32(42));
+        assert_eq!(Variants::I16(5).var_sub(&Variants::I16(7)).unwrap(), Variants::I16(-2));
+        assert_eq!(Variants::I32(1).var_div(&Variants::I32(4)).unwrap(), Variants::F64(0.25));
+        assert_eq!(Variants::I16(i16::max_value()).var_add(&Variants::I16(1)).unwrap(), Variants::I32(32768));
+        assert_eq!(Variants::Null.var_add(&Variants::I32(1)).unwrap(), Variants::Null);
+        match Variants::I32(1).var_div(&Variants::I32(0)) {
+            Err(ArithmeticError::DivisionByZero) => {}, 
+            _ => panic!("Error")
+        }
+        match Variants::decimal(Decimal::max_value()).var_mul(&Variants::I32(2)) {
+            Err(ArithmeticError::Overflow) => {}, 
+            _ => panic!("Error")
+        }
+        match Variants::String(String::from("x")).var_mul(&Variants::I32(2)) {
+            Err(ArithmeticError::TypeMismatch{..}) => {}, 
+            _ => panic!("Error")
+        }
+    }
+}
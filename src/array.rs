@@ -5,6 +5,7 @@ use std::ptr::null_mut;
 use rust_decimal::Decimal;
 
 use winapi::ctypes::{c_long, c_void};
+use winapi::shared::guiddef::IID;
 use winapi::shared::minwindef::{UINT, ULONG,};
 use winapi::shared::ntdef::HRESULT;
 use winapi::shared::wtypes::{
@@ -13,7 +14,9 @@ use winapi::shared::wtypes::{
     DECIMAL,  
     VARTYPE,
     VARIANT_BOOL,
+    VT_ARRAY,
     VT_BOOL,
+    VT_BYREF,
     VT_CY,
     VT_DATE,
     VT_DECIMAL, 
@@ -25,6 +28,7 @@ use winapi::shared::wtypes::{
     VT_INT,
     VT_R4, 
     VT_R8, 
+    VT_RECORD,
     VT_UI1,
     VT_UI2,
     VT_UI4,
@@ -33,18 +37,30 @@ use winapi::shared::wtypes::{
     VT_VARIANT,   
 };
 
-use winapi::um::oaidl::{IDispatch, LPSAFEARRAY, LPSAFEARRAYBOUND, SAFEARRAY, SAFEARRAYBOUND, VARIANT};
+use winapi::um::oaidl::{
+    IDispatch, IRecordInfo, LPSAFEARRAY, LPSAFEARRAYBOUND, SAFEARRAY, SAFEARRAYBOUND, VARIANT, 
+    FADF_BSTR, FADF_DISPATCH, FADF_FIXEDSIZE, FADF_HAVEIID, FADF_HAVEVARTYPE, FADF_RECORD, FADF_UNKNOWN, FADF_VARIANT,
+};
+use winapi::um::oleauto::VariantClear;
 use winapi::um::unknwnbase::IUnknown;
+use winapi::Interface;
 
+use super::dtor::{ComDestructor, FreeVariant, OwnedPtr, PtrDestructor};
 use super::errors::{
     FromSafeArrayError, 
     FromSafeArrElemError, 
+    FromVariantError, 
     IntoSafeArrayError, 
     IntoSafeArrElemError,
+    SafeArrayError,
 };
+use super::leak_tracking;
+use super::limits::DecodeLimits;
+use super::options::ConvertOptions;
 use super::ptr::Ptr;
+use super::stats::ConversionStats;
 use super::types::{Currency, Date, DecWrapper, Int, SCode, UInt, VariantBool};
-use super::variant::{Variant, VariantExt};
+use super::variant::{Variant, VariantExt, Variants};
 
 /// Helper trait implemented for types that can be converted into a safe array. 
 /// 
@@ -54,10 +70,18 @@ use super::variant::{Variant, VariantExt};
 /// * `bool`, `f32`, `f64`
 /// * `String`, [`Variant<T>`], 
 /// * [`Ptr<IUnknown>`], [`Ptr<IDispatch>`]
+/// * [`OwnedPtr<IUnknown, ComDestructor>`], [`OwnedPtr<IDispatch, ComDestructor>`]
 ///  
+/// Interface elements are read with `SafeArrayGetElement`, which `AddRef`s the pointer it 
+/// hands out, so the caller owns one reference per element read. A `Ptr` leaves releasing 
+/// it to the caller; the `OwnedPtr` forms call `Release` when dropped and are the safer choice. 
+/// Writing either form has the array take its own reference.
+/// 
 /// [`Variant<T>`]: struct.Variant.html
 /// [`Ptr<IUnknown>`]: struct.Ptr.html
 /// [`Ptr<IDispatch>`]: struct.Ptr.html
+/// [`OwnedPtr<IUnknown, ComDestructor>`]: struct.OwnedPtr.html
+/// [`OwnedPtr<IDispatch, ComDestructor>`]: struct.OwnedPtr.html
 /// 
 /// ## Example usage
 /// 
@@ -97,16 +121,95 @@ pub trait SafeArrayElement: Sized {
     
     /// gets a type from the safearray at the specified index (default impls use SafeArrayGetElement)
     fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError>;
+
+    /// Puts a type into a multi-dimensional safearray at `ixs`, one index per dimension in dimension order. 
+    /// The default impl only handles one dimension, through `into_safearray`.
+    fn into_safearray_at(self, psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<(), IntoSafeArrElemError> {
+        match ixs {
+            [ix] => self.into_safearray(psa, *ix), 
+            _ => Err(IntoSafeArrElemError::DimsUnsupported{dims: ixs.len()}),
+        }
+    }
+
+    /// Gets a type from a multi-dimensional safearray at `ixs`, one index per dimension in dimension order. 
+    /// The default impl only handles one dimension, through `from_safearray`.
+    fn from_safearray_at(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<Self, FromSafeArrElemError> {
+        match ixs {
+            [ix] => Self::from_safearray(psa, *ix), 
+            _ => Err(FromSafeArrElemError::DimsUnsupported{dims: ixs.len()}),
+        }
+    }
 }
 
 /// Workhorse trait and main interface for converting to/from SAFEARRAY. 
 /// Default impl is on `ExactSizeIterator<Item=SafeArrayElement>` 
+/// 
+/// Elements are moved one at a time. For large arrays of a `BulkElement` type, 
+/// `bulk_into_safearray`/`bulk_from_safearray` copy the whole buffer at once instead.
 pub trait SafeArrayExt<T: SafeArrayElement> {
     /// Use `t.into_safearray()` to convert a type into a SAFEARRAY
-    fn into_safearray(&mut self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError>;
+    fn into_safearray(&mut self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+        self.into_safearray_with_lbound(0)
+    }
+
+    /// Like `into_safearray`, but the array's lower bound is `l_bound` instead of 0, 
+    /// e.g. 1 for VB6 and VBA callers that index from 1.
+    fn into_safearray_with_lbound(&mut self, l_bound: i32) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError>;
     
-    /// Use `T::from_safearray(psa)` to convert a safearray pointer into the relevant T
+    /// Use `T::from_safearray(psa)` to convert a safearray pointer into the relevant T. 
+    /// The result is allocated once, at the length [`safearray_len`](fn.safearray_len.html) reports.
     fn from_safearray(psa: *mut SAFEARRAY) -> Result<Vec<T>, FromSafeArrayError>;
+
+    /// Like `from_safearray`, but also returns the array's lower bound, so it can be 
+    /// handed back to `into_safearray_with_lbound` for a faithful round trip.
+    fn from_safearray_with_lbound(psa: *mut SAFEARRAY) -> Result<(i32, Vec<T>), FromSafeArrayError> {
+        if psa.is_null() {
+            return Err(FromSafeArrayError::SafeArrayPtrNull);
+        }
+        let l_bound = match get_bounds(psa, 1) {
+            Ok((l_bound, _)) => l_bound, 
+            Err(e) => {
+                drop(SafeArrayDestructor::new(psa));
+                return Err(e);
+            }
+        };
+        Ok((l_bound, Self::from_safearray(psa)?))
+    }
+
+    /// Like `from_safearray`, but calls `on_error(index, &err)` when an element fails 
+    /// to convert and lets it skip the slot, substitute a value or abort. 
+    /// Useful for sparse arrays whose producer never initialized some slots.
+    fn from_safearray_recover<F>(psa: *mut SAFEARRAY, on_error: F) -> Result<Vec<T>, FromSafeArrayError> 
+        where F: FnMut(usize, &FromSafeArrElemError) -> ElementRecovery<T>;
+
+    /// Like `into_safearray`, with `opts` in force for the conversion
+    fn into_safearray_with(&mut self, opts: &ConvertOptions) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+        opts.scoped(|| self.into_safearray())
+    }
+
+    /// Like `from_safearray`, with `opts` in force for the conversion. 
+    /// (`from_safearray_recover` is the per-element recovery variant.)
+    fn from_safearray_with(psa: *mut SAFEARRAY, opts: &ConvertOptions) -> Result<Vec<T>, FromSafeArrayError> {
+        opts.scoped(|| Self::from_safearray(psa))
+    }
+}
+
+/// What `SafeArrayExt::from_safearray_recover` does with an element that failed to convert
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ElementRecovery<T> {
+    /// Leave the element out of the result
+    Skip, 
+    /// Put this value in its place
+    Substitute(T), 
+    /// Fail the whole conversion, as `from_safearray` does
+    Abort,
+}
+
+impl<T: Default> ElementRecovery<T> {
+    /// Substitutes `T::default()`
+    pub fn default_value() -> ElementRecovery<T> {
+        ElementRecovery::Substitute(T::default())
+    }
 }
 
 macro_rules! check_and_throw {
@@ -118,13 +221,13 @@ macro_rules! check_and_throw {
     };
 }
 
-struct SafeArrayDestructor {
-    inner: *mut SAFEARRAY, 
+pub(crate) struct SafeArrayDestructor {
+    pub(crate) inner: *mut SAFEARRAY, 
     _marker: PhantomData<SAFEARRAY>
 }
 
 impl SafeArrayDestructor {
-    fn new(p: *mut SAFEARRAY) -> SafeArrayDestructor {
+    pub(crate) fn new(p: *mut SAFEARRAY) -> SafeArrayDestructor {
         assert!(!p.is_null(), "SafeArrayDestructor initialized with null *mut SAFEARRAY pointer.");
         SafeArrayDestructor{
             inner: p, 
@@ -138,6 +241,7 @@ impl Drop for SafeArrayDestructor {
         if self.inner.is_null(){
             return;
         }
+        leak_tracking::safearray_freed();
         unsafe {
             SafeArrayDestroy(self.inner)
         };
@@ -145,20 +249,338 @@ impl Drop for SafeArrayDestructor {
     }
 }
 
+/// Element storage that `SafeArrayGetElement` fills and `SafeArrayPutElement` reads from, 
+/// for element types whose storage owns something: a VARIANT, or an interface pointer.
+pub(crate) trait Stage {
+    /// Empty storage for `SafeArrayGetElement` to write into
+    fn empty() -> Self;
+    /// Whether a successful conversion keeps what was staged instead of copying out of it. 
+    /// Interfaces and arrays are handed on; strings and scalars are copied.
+    fn handed_on(&self) -> bool;
+    /// Frees or releases what was staged
+    fn release(&mut self);
+    /// Disposes of the value given to `SafeArrayPutElement`, which stores its own copy
+    fn put_done(pv: *mut c_void);
+}
+
+impl Stage for VARIANT {
+    fn empty() -> VARIANT {
+        unsafe { mem::zeroed() }
+    }
+
+    fn handed_on(&self) -> bool {
+        let vt = unsafe { self.n1.n2().vt } as u32;
+        vt & VT_ARRAY != 0 || vt & VT_BYREF != 0 || vt == VT_UNKNOWN || vt == VT_DISPATCH || vt == VT_RECORD
+    }
+
+    fn release(&mut self) {
+        unsafe { VariantClear(self) };
+    }
+
+    fn put_done(pv: *mut c_void) {
+        if let Some(pvar) = Ptr::with_checked(pv as *mut VARIANT) {
+            FreeVariant::destroy(pvar);
+        }
+    }
+}
+
+macro_rules! interface_stage {
+    ($i:ty) => {
+        impl Stage for *mut $i {
+            fn empty() -> *mut $i {
+                null_mut()
+            }
+
+            fn handed_on(&self) -> bool {
+                true
+            }
+
+            fn release(&mut self) {
+                if !self.is_null() {
+                    unsafe { (**self).Release() };
+                    *self = null_mut();
+                }
+            }
+
+            fn put_done(_pv: *mut c_void) {}
+        }
+    };
+}
+interface_stage!(IUnknown);
+interface_stage!(IDispatch);
+
+/// Holds the copy `SafeArrayGetElement` hands out - a fresh BSTR, an AddRef'd interface 
+/// or a `VariantCopy`'d VARIANT - and releases it on drop unless it was taken over. 
+/// This way an element whose conversion fails never leaks what was staged for it.
+pub(crate) struct ElementStage<S: Stage> {
+    slot: S, 
+    taken: bool
+}
+
+impl<S: Stage> ElementStage<S> {
+    pub(crate) fn new() -> ElementStage<S> {
+        ElementStage { slot: S::empty(), taken: false }
+    }
+
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut S {
+        &mut self.slot
+    }
+
+    pub(crate) fn handed_on(&self) -> bool {
+        self.slot.handed_on()
+    }
+
+    /// The conversion result now owns what was staged
+    pub(crate) fn take(&mut self) {
+        self.taken = true;
+    }
+}
+
+impl<S: Stage> Drop for ElementStage<S> {
+    fn drop(&mut self) {
+        if !self.taken {
+            self.slot.release();
+        }
+    }
+}
+
+/// Creates a 1-D array of `vt` with a lower bound of `l_bound` through `SafeArrayCreateVector`. 
+/// Interface arrays go through `SafeArrayCreateVectorEx` instead, so they carry the IID 
+/// of their element interface (`FADF_HAVEIID`) as COM servers expect. 
+/// Returns null if creation failed.
+pub(crate) fn create_vector(vt: u32, l_bound: i32, c_elements: ULONG) -> *mut SAFEARRAY {
+    let mut iid = match vt {
+        VT_UNKNOWN => IUnknown::uuidof(), 
+        VT_DISPATCH => IDispatch::uuidof(), 
+        _ => return unsafe { SafeArrayCreateVector(vt as u16, l_bound, c_elements) },
+    };
+    unsafe { SafeArrayCreateVectorEx(vt as u16, l_bound, c_elements, &mut iid as *mut IID as *mut c_void) }
+}
+
+/// Makes an independent deep copy of `psa` with `SafeArrayCopy`: strings are 
+/// reallocated, interfaces get an extra reference and variants are copied. 
+/// 
+/// `psa` is left alone, so one copy can be handed to a COM method that takes 
+/// ownership while the other stays on the Rust side.
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{copy_safearray, SafeArrayExt};
+/// 
+/// fn main() {
+///     let psa = vec![String::from("kept")].into_iter().into_safearray().unwrap();
+///     let copy = copy_safearray(psa.as_ptr()).unwrap();
+///     let _ = ExactSizeIterator::<Item=String>::from_safearray(psa.as_ptr()).unwrap();
+///     let v = ExactSizeIterator::<Item=String>::from_safearray(copy.as_ptr()).unwrap();
+///     assert_eq!(v, vec![String::from("kept")]);
+/// }
+/// ```
+pub fn copy_safearray(psa: *mut SAFEARRAY) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+    let mut out: *mut SAFEARRAY = null_mut();
+    let hr = unsafe { SafeArrayCopy(psa, &mut out) };
+    if hr != 0 {
+        return Err(IntoSafeArrayError::CopyFailed{hr: hr});
+    }
+    let out = match Ptr::with_checked(out) {
+        Some(out) => out, 
+        None => return Err(IntoSafeArrayError::SafeArrayCreateFailed),
+    };
+    let len = safearray_len(out.as_ptr()).unwrap_or(0);
+    ConversionStats::record_safearray(len * unsafe { SafeArrayGetElemSize(out.as_ptr()) } as usize);
+    Ok(out)
+}
+
+/// Reads the lower and upper bound of dimension `dim` (1-based)
+pub(crate) fn get_bounds(psa: *mut SAFEARRAY, dim: u32) -> Result<(i32, i32), FromSafeArrayError> {
+    unsafe {
+        let mut l_bound: c_long = 0;
+        let mut r_bound: c_long = 0;
+        let hr = SafeArrayGetLBound(psa, dim, &mut l_bound);
+        check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayLBoundFailed{hr: hr})});
+        let hr = SafeArrayGetUBound(psa, dim, &mut r_bound);
+        check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayRBoundFailed{hr: hr})});
+        Ok((l_bound, r_bound))
+    }
+}
+
+/// Returns the lower bound and element count of each dimension, leftmost dimension first. 
+/// 
+/// Only the array descriptor is read - no elements are decoded, so this is cheap 
+/// enough to size buffers or reject oversized payloads up front.
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{safearray_len, safearray_shape, SafeArrayExt};
+/// 
+/// fn main() {
+///     let psa = vec![1i32, 2, 3].into_iter().into_safearray().unwrap();
+///     assert_eq!(safearray_shape(psa.as_ptr()).unwrap(), vec![(0, 3)]);
+///     assert_eq!(safearray_len(psa.as_ptr()).unwrap(), 3);
+/// }
+/// ```
+pub fn safearray_shape(psa: *mut SAFEARRAY) -> Result<Vec<(i32, u32)>, FromSafeArrayError> {
+    if psa.is_null() {
+        return Err(FromSafeArrayError::SafeArrayPtrNull);
+    }
+    let sa_dims = unsafe { SafeArrayGetDim(psa) };
+    let mut shape = Vec::with_capacity(sa_dims as usize);
+    for dim in 1..=sa_dims {
+        let (l_bound, r_bound) = get_bounds(psa, dim)?;
+        shape.push((l_bound, (r_bound as i64 - l_bound as i64 + 1).max(0) as u32));
+    }
+    Ok(shape)
+}
+
+/// What a SAFEARRAY descriptor says about itself, read by [`safearray_info`](fn.safearray_info.html)
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SafeArrayInfo {
+    /// Number of dimensions
+    pub dims: u32, 
+    /// Size of one element in bytes (`cbElements`)
+    pub element_size: u32, 
+    /// The raw `fFeatures` flags, a combination of `FADF_*` values
+    pub features: u16, 
+    /// Element vartype, if the array records one
+    pub vartype: Option<u32>,
+}
+
+impl SafeArrayInfo {
+    /// Whether all the `FADF_*` bits in `flags` are set
+    pub fn has(&self, flags: u32) -> bool {
+        self.features as u32 & flags == flags
+    }
+
+    /// Elements are BSTRs (`FADF_BSTR`)
+    pub fn is_bstr(&self) -> bool {
+        self.has(FADF_BSTR)
+    }
+
+    /// Elements are IUnknown pointers (`FADF_UNKNOWN`)
+    pub fn is_unknown(&self) -> bool {
+        self.has(FADF_UNKNOWN)
+    }
+
+    /// Elements are IDispatch pointers (`FADF_DISPATCH`)
+    pub fn is_dispatch(&self) -> bool {
+        self.has(FADF_DISPATCH)
+    }
+
+    /// Elements are VARIANTs (`FADF_VARIANT`)
+    pub fn is_variant(&self) -> bool {
+        self.has(FADF_VARIANT)
+    }
+
+    /// Elements are records (`FADF_RECORD`)
+    pub fn is_record(&self) -> bool {
+        self.has(FADF_RECORD)
+    }
+
+    /// The array may not be resized or reallocated (`FADF_FIXEDSIZE`)
+    pub fn is_fixed_size(&self) -> bool {
+        self.has(FADF_FIXEDSIZE)
+    }
+
+    /// The array carries the IID of its element interface (`FADF_HAVEIID`)
+    pub fn has_iid(&self) -> bool {
+        self.has(FADF_HAVEIID)
+    }
+
+    /// The array carries its element vartype (`FADF_HAVEVARTYPE`)
+    pub fn has_vartype(&self) -> bool {
+        self.has(FADF_HAVEVARTYPE)
+    }
+}
+
+/// Reads the dimensions, element size, `fFeatures` flags and vartype of `psa`. 
+/// 
+/// Nothing is decoded and the array is left alone, so a SAFEARRAY from a third-party 
+/// server can be checked before it is handed to a typed conversion.
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// extern crate winapi;
+/// 
+/// use oaidl::{safearray_info, safearray_to_strings, strings_to_safearray, Utf16Mode};
+/// use winapi::shared::wtypes::VT_BSTR;
+/// 
+/// fn main() {
+///     let psa = strings_to_safearray(&["a"]).unwrap();
+///     let info = safearray_info(psa.as_ptr()).unwrap();
+///     assert!(info.is_bstr());
+///     assert_eq!((info.dims, info.vartype), (1, Some(VT_BSTR)));
+///     safearray_to_strings(psa.as_ptr(), Utf16Mode::Strict).unwrap();
+/// }
+/// ```
+pub fn safearray_info(psa: *mut SAFEARRAY) -> Result<SafeArrayInfo, FromSafeArrayError> {
+    if psa.is_null() {
+        return Err(FromSafeArrayError::SafeArrayPtrNull);
+    }
+    Ok(SafeArrayInfo {
+        dims: unsafe { SafeArrayGetDim(psa) }, 
+        element_size: unsafe { SafeArrayGetElemSize(psa) }, 
+        features: unsafe { (*psa).fFeatures }, 
+        vartype: get_vartype(psa).ok(),
+    })
+}
+
+/// Returns the total number of elements across all dimensions, without decoding any of them.
+pub fn safearray_len(psa: *mut SAFEARRAY) -> Result<usize, FromSafeArrayError> {
+    let shape = safearray_shape(psa)?;
+    if shape.is_empty() {
+        return Ok(0);
+    }
+    Ok(shape.iter().fold(1usize, |len, &(_, count)| len.saturating_mul(count as usize)))
+}
+
+// The data copied under `SafeArrayAccessData` is reinterpreted through these types, 
+// so a binding that lays them out differently must fail to build rather than corrupt memory.
+#[cfg(target_pointer_width = "64")]
+const _: [(); 24] = [(); mem::size_of::<VARIANT>()];
+#[cfg(target_pointer_width = "32")]
+const _: [(); 16] = [(); mem::size_of::<VARIANT>()];
+const _: [(); 16] = [(); mem::size_of::<DECIMAL>()];
+const _: [(); 8] = [(); mem::size_of::<CY>()];
+
+/// Checks that the `cbElements` of `psa` is `expected` before its data is reinterpreted
+pub(crate) fn check_elem_size(psa: *mut SAFEARRAY, expected: usize) -> Result<(), FromSafeArrayError> {
+    let found = unsafe { SafeArrayGetElemSize(psa) } as usize;
+    if found != expected {
+        return Err(FromSafeArrayError::ElementSizeMismatch{expected: expected, found: found});
+    }
+    Ok(())
+}
+
+/// Reads the vartype of the elements held by the array
+pub(crate) fn get_vartype(psa: *mut SAFEARRAY) -> Result<u32, FromSafeArrayError> {
+    let mut vt: VARTYPE = 0;
+    let hr = unsafe { SafeArrayGetVartype(psa, &mut vt) };
+    check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayGetVartypeFailed{hr: hr})});
+    Ok(vt as u32)
+}
+
 impl<I> SafeArrayExt<I::Item> for I 
 where I: ExactSizeIterator + ?Sized, 
       I::Item: SafeArrayElement
 {
-    fn into_safearray(&mut self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError > {
+    fn into_safearray_with_lbound(&mut self, l_bound: i32) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError > {
         let c_elements: ULONG = self.len() as u32;
-        let vartype = I::Item::SFTYPE;
-        let mut sab = SAFEARRAYBOUND { cElements: c_elements, lLbound: 0i32};
-        let psa = unsafe { SafeArrayCreate(vartype as u16, 1, &mut sab)};
-        assert!(!psa.is_null());
+        let psa = create_vector(I::Item::SFTYPE, l_bound, c_elements);
+        if psa.is_null() {
+            return Err(IntoSafeArrayError::SafeArrayCreateFailed);
+        }
         let mut sad = SafeArrayDestructor::new(psa);
+        ConversionStats::record_safearray(c_elements as usize * unsafe { SafeArrayGetElemSize(psa) } as usize);
 
         for (ix, mut elem) in self.enumerate() {
-            match elem.into_safearray(psa, ix as i32) {
+            match elem.into_safearray(psa, l_bound.wrapping_add(ix as i32)) {
                 Ok(()) => continue, 
                 Err(e) => return Err(IntoSafeArrayError::from_element_err(e, ix))
             }
@@ -169,37 +591,42 @@ where I: ExactSizeIterator + ?Sized,
     }
 
     fn from_safearray(psa: *mut SAFEARRAY) -> Result<Vec<I::Item>, FromSafeArrayError> {
+        Self::from_safearray_recover(psa, |_, _| ElementRecovery::Abort)
+    }
+
+    fn from_safearray_recover<F>(psa: *mut SAFEARRAY, mut on_error: F) -> Result<Vec<I::Item>, FromSafeArrayError> 
+        where F: FnMut(usize, &FromSafeArrElemError) -> ElementRecovery<I::Item>
+    {
+        if psa.is_null() {
+            return Err(FromSafeArrayError::SafeArrayPtrNull);
+        }
         //Stack sentinel to ensure safearray is released even if there is a panic or early return.
         let _sad = SafeArrayDestructor::new(psa);
         let sa_dims = unsafe { SafeArrayGetDim(psa) };
-        assert!(sa_dims > 0); //Assert its not a dimensionless safe array
-        let vt = unsafe {
-            let mut vt: VARTYPE = 0;
-            let hr = SafeArrayGetVartype(psa, &mut vt);
-            check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayGetVartypeFailed{hr: hr})});
-            vt
-        };
+        if sa_dims == 0 {
+            return Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims});
+        }
+        let vt = get_vartype(psa)?;
 
-        if vt as u32 != I::Item::SFTYPE {
-            return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: I::Item::SFTYPE, found: vt as u32});
+        if vt != I::Item::SFTYPE {
+            return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: I::Item::SFTYPE, found: vt});
         }
+        let _depth = DecodeLimits::enter_array()?;
+        let len = safearray_len(psa)?;
+        DecodeLimits::check_elements(len)?;
 
         if sa_dims == 1 {
-            let (l_bound, r_bound) = unsafe {
-                let mut l_bound: c_long = 0;
-                let mut r_bound: c_long = 0;
-                let hr = SafeArrayGetLBound(psa, 1, &mut l_bound);
-                check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayLBoundFailed{hr: hr})});
-                let hr = SafeArrayGetUBound(psa, 1, &mut r_bound);
-                check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayRBoundFailed{hr: hr})});
-                (l_bound, r_bound)
-            };
-
-            let mut vc: Vec<I::Item> = Vec::new();
+            let (l_bound, r_bound) = get_bounds(psa, 1)?;
+
+            let mut vc: Vec<I::Item> = Vec::with_capacity(len);
             for ix in l_bound..=r_bound {
                 match I::Item::from_safearray(psa, ix) {
                     Ok(val) => vc.push(val), 
-                    Err(e) => return Err(FromSafeArrayError::from_element_err(e, ix as usize))
+                    Err(e) => match on_error(ix as usize, &e) {
+                        ElementRecovery::Skip => {}, 
+                        ElementRecovery::Substitute(val) => vc.push(val), 
+                        ElementRecovery::Abort => return Err(FromSafeArrayError::from_element_err(e, ix as usize)),
+                    }
                 }
             }
             Ok(vc)
@@ -209,12 +636,438 @@ where I: ExactSizeIterator + ?Sized,
     }
 } 
 
+/// `SafeArrayExt` conversions called on a `Vec<T>` directly. 
+/// 
+/// `SafeArrayExt` is implemented for iterators, and a blanket impl over them rules 
+/// out one for `Vec`. This forwards to it, so `v.into_safearray()` and 
+/// `Vec::<T>::from_safearray(psa)` behave exactly like the iterator forms.
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{SafeArraySliceExt, SafeArrayVecExt};
+/// 
+/// fn main() {
+///     let v = vec![1i32, 2, 3];
+///     let psa = v.to_safearray().unwrap();
+///     assert_eq!(Vec::<i32>::from_safearray(psa.as_ptr()).unwrap(), v);
+///     let psa = v.into_safearray().unwrap();
+///     assert_eq!(Vec::<i32>::from_safearray(psa.as_ptr()).unwrap().len(), 3);
+/// }
+/// ```
+pub trait SafeArrayVecExt<T: SafeArrayElement>: Sized {
+    /// Consumes the vector and converts it into a SAFEARRAY
+    fn into_safearray(self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError>;
+
+    /// Reads a 1-D SAFEARRAY into a vector. The array is released afterwards.
+    fn from_safearray(psa: *mut SAFEARRAY) -> Result<Self, FromSafeArrayError>;
+}
+
+impl<T: SafeArrayElement> SafeArrayVecExt<T> for Vec<T> {
+    fn into_safearray(self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+        SafeArrayExt::into_safearray(&mut self.into_iter())
+    }
+
+    fn from_safearray(psa: *mut SAFEARRAY) -> Result<Vec<T>, FromSafeArrayError> {
+        ::std::vec::IntoIter::<T>::from_safearray(psa)
+    }
+}
+
+/// Converts a borrowed slice into a SAFEARRAY, cloning each element
+pub trait SafeArraySliceExt<T: SafeArrayElement> {
+    /// Clones the elements into a new SAFEARRAY, leaving `self` as it was
+    fn to_safearray(&self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError>;
+}
+
+impl<T: SafeArrayElement + Clone> SafeArraySliceExt<T> for [T] {
+    fn to_safearray(&self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+        SafeArrayExt::into_safearray(&mut self.iter().cloned())
+    }
+}
+
+/// Converts rows of equal length into a 2-D SAFEARRAY: dimension 1 is the row, dimension 2 the column. 
+/// Both dimensions have a lower bound of 0.
+pub fn into_safearray_2d<T: SafeArrayElement>(rows: Vec<Vec<T>>) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+    let n_rows = rows.len();
+    let n_cols = rows.first().map_or(0, |r| r.len());
+    if let Some((ix, row)) = rows.iter().enumerate().find(|&(_, r)| r.len() != n_cols) {
+        return Err(IntoSafeArrayError::JaggedRows{row: ix, expected: n_cols, found: row.len()});
+    }
+
+    let mut sab = [
+        SAFEARRAYBOUND { cElements: n_rows as u32, lLbound: 0i32}, 
+        SAFEARRAYBOUND { cElements: n_cols as u32, lLbound: 0i32},
+    ];
+    let psa = unsafe { SafeArrayCreate(T::SFTYPE as u16, 2, sab.as_mut_ptr()) };
+    if psa.is_null() {
+        return Err(IntoSafeArrayError::SafeArrayCreateFailed);
+    }
+    let mut sad = SafeArrayDestructor::new(psa);
+    ConversionStats::record_safearray(n_rows * n_cols * unsafe { SafeArrayGetElemSize(psa) } as usize);
+
+    for (r, row) in rows.into_iter().enumerate() {
+        for (c, elem) in row.into_iter().enumerate() {
+            if let Err(e) = elem.into_safearray_at(psa, &[r as i32, c as i32]) {
+                return Err(IntoSafeArrayError::from_element_err(e, r * n_cols + c));
+            }
+        }
+    }
+    sad.inner = null_mut();
+
+    Ok(Ptr::with_checked(psa).unwrap())
+}
+
+/// Reads a 2-D SAFEARRAY into rows: dimension 1 is the row, dimension 2 the column. 
+/// Any lower bounds are accepted. The array is released afterwards.
+pub fn from_safearray_2d<T: SafeArrayElement>(psa: *mut SAFEARRAY) -> Result<Vec<Vec<T>>, FromSafeArrayError> {
+    if psa.is_null() {
+        return Err(FromSafeArrayError::SafeArrayPtrNull);
+    }
+    let _sad = SafeArrayDestructor::new(psa);
+    let sa_dims = unsafe { SafeArrayGetDim(psa) };
+    if sa_dims != 2 {
+        return Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims});
+    }
+    let vt = get_vartype(psa)?;
+    if vt != T::SFTYPE {
+        return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: T::SFTYPE, found: vt});
+    }
+    let _depth = DecodeLimits::enter_array()?;
+    DecodeLimits::check_elements(safearray_len(psa)?)?;
+
+    let (r_lo, r_hi) = get_bounds(psa, 1)?;
+    let (c_lo, c_hi) = get_bounds(psa, 2)?;
+    let n_cols = (c_hi - c_lo + 1).max(0) as usize;
+    let mut rows = Vec::with_capacity((r_hi - r_lo + 1).max(0) as usize);
+    for r in r_lo..=r_hi {
+        let mut row = Vec::with_capacity(n_cols);
+        for c in c_lo..=c_hi {
+            match T::from_safearray_at(psa, &[r, c]) {
+                Ok(val) => row.push(val), 
+                Err(e) => {
+                    let index = (r - r_lo) as usize * n_cols + (c - c_lo) as usize;
+                    return Err(FromSafeArrayError::from_element_err(e, index));
+                }
+            }
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Owning wrapper around a SAFEARRAY of any number of dimensions. 
+/// 
+/// Unlike the `Vec` conversions, nothing is flattened: the dimensions, the per-dimension 
+/// bounds and the element vartype stay as the SAFEARRAY records them, and elements are 
+/// read and written in place by index. The array is destroyed when this is dropped, 
+/// unless it is released with [`into_ptr`](#method.into_ptr).
+/// 
+/// Indices are given in dimension order, dimension 1 first, as `SafeArrayGetLBound` counts them.
+pub struct SafeArrayN<T: SafeArrayElement> {
+    psa: Ptr<SAFEARRAY>, 
+    _marker: PhantomData<T>
+}
+
+impl<T: SafeArrayElement> SafeArrayN<T> {
+    /// Creates an array of `T::SFTYPE` elements with the given inclusive `(lower, upper)` bounds per dimension. 
+    /// An upper bound below its lower bound gives an empty dimension.
+    pub fn new(bounds: &[(i32, i32)]) -> Result<SafeArrayN<T>, IntoSafeArrayError> {
+        let mut sab: Vec<SAFEARRAYBOUND> = bounds.iter().map(|&(lo, hi)| SAFEARRAYBOUND {
+            cElements: (hi as i64 - lo as i64 + 1).max(0) as u32, 
+            lLbound: lo
+        }).collect();
+        let psa = unsafe { SafeArrayCreate(T::SFTYPE as u16, sab.len() as u32, sab.as_mut_ptr()) };
+        let psa = match Ptr::with_checked(psa) {
+            Some(psa) => psa, 
+            None => return Err(IntoSafeArrayError::SafeArrayCreateFailed),
+        };
+        let len = sab.iter().fold(1usize, |len, b| len.saturating_mul(b.cElements as usize));
+        ConversionStats::record_safearray(len * unsafe { SafeArrayGetElemSize(psa.as_ptr()) } as usize);
+        Ok(SafeArrayN { psa: psa, _marker: PhantomData })
+    }
+
+    /// Takes ownership of `psa`, checking that its elements are `T::SFTYPE`. 
+    /// The array is destroyed on error, as the other decoders do.
+    pub fn from_ptr(psa: *mut SAFEARRAY) -> Result<SafeArrayN<T>, FromSafeArrayError> {
+        if psa.is_null() {
+            return Err(FromSafeArrayError::SafeArrayPtrNull);
+        }
+        let mut sad = SafeArrayDestructor::new(psa);
+        let vt = get_vartype(psa)?;
+        if vt != T::SFTYPE {
+            return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: T::SFTYPE, found: vt});
+        }
+        sad.inner = null_mut();
+        Ok(SafeArrayN { psa: Ptr::with_checked(psa).unwrap(), _marker: PhantomData })
+    }
+
+    /// Number of dimensions
+    pub fn dims(&self) -> u32 {
+        unsafe { SafeArrayGetDim(self.psa.as_ptr()) }
+    }
+
+    /// Inclusive `(lower, upper)` bounds of each dimension, dimension 1 first
+    pub fn bounds(&self) -> Result<Vec<(i32, i32)>, FromSafeArrayError> {
+        (1..=self.dims()).map(|dim| get_bounds(self.psa.as_ptr(), dim)).collect()
+    }
+
+    /// Element vartype recorded in the SAFEARRAY
+    pub fn vartype(&self) -> Result<u32, FromSafeArrayError> {
+        get_vartype(self.psa.as_ptr())
+    }
+
+    /// Total number of elements across all dimensions
+    pub fn len(&self) -> Result<usize, FromSafeArrayError> {
+        safearray_len(self.psa.as_ptr())
+    }
+
+    /// Whether any dimension is empty
+    pub fn is_empty(&self) -> Result<bool, FromSafeArrayError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Reads the element at `ixs`
+    pub fn get(&self, ixs: &[i32]) -> Result<T, FromSafeArrElemError> {
+        let dims = self.dims();
+        if ixs.len() != dims as usize {
+            return Err(FromSafeArrElemError::IndexDimsMismatch{expected: dims, found: ixs.len()});
+        }
+        T::from_safearray_at(self.psa.as_ptr(), ixs)
+    }
+
+    /// Writes `val` at `ixs`, releasing whatever was stored there
+    pub fn set(&mut self, ixs: &[i32], val: T) -> Result<(), IntoSafeArrElemError> {
+        let dims = self.dims();
+        if ixs.len() != dims as usize {
+            return Err(IntoSafeArrElemError::IndexDimsMismatch{expected: dims, found: ixs.len()});
+        }
+        val.into_safearray_at(self.psa.as_ptr(), ixs)
+    }
+
+    /// Borrows the underlying pointer. It stays owned by this wrapper.
+    pub fn as_ptr(&self) -> *mut SAFEARRAY {
+        self.psa.as_ptr()
+    }
+
+    /// Releases ownership of the underlying SAFEARRAY, e.g. to hand it to a COM server
+    pub fn into_ptr(self) -> Ptr<SAFEARRAY> {
+        let psa = self.psa;
+        mem::forget(self);
+        psa
+    }
+}
+
+impl<T: SafeArrayElement> Drop for SafeArrayN<T> {
+    fn drop(&mut self) {
+        drop(SafeArrayDestructor::new(self.psa.as_ptr()));
+    }
+}
+
+/// Builds a 1-D SAFEARRAY one element at a time, for sources whose length isn't known up front. 
+/// 
+/// The array is created with spare room and grown with `SafeArrayRedim`, doubling each time, 
+/// then trimmed to the pushed length by [`finish`](#method.finish). Dropping an unfinished 
+/// builder destroys the array.
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{SafeArrayBuilder, SafeArrayExt};
+/// 
+/// fn main() {
+///     let mut builder = SafeArrayBuilder::new().unwrap();
+///     for line in "a\nb\nc".lines() {
+///         builder.push(String::from(line)).unwrap();
+///     }
+///     let psa = builder.finish().unwrap();
+///     let v = ExactSizeIterator::<Item=String>::from_safearray(psa.as_ptr()).unwrap();
+///     assert_eq!(v, vec!["a", "b", "c"]);
+/// }
+/// ```
+pub struct SafeArrayBuilder<T: SafeArrayElement> {
+    sad: SafeArrayDestructor, 
+    len: usize, 
+    cap: usize, 
+    _marker: PhantomData<T>
+}
+
+impl<T: SafeArrayElement> SafeArrayBuilder<T> {
+    /// Starts an empty array with room for a few elements
+    pub fn new() -> Result<SafeArrayBuilder<T>, IntoSafeArrayError> {
+        SafeArrayBuilder::with_capacity(16)
+    }
+
+    /// Starts an empty array with room for `cap` elements before it has to grow
+    pub fn with_capacity(cap: usize) -> Result<SafeArrayBuilder<T>, IntoSafeArrayError> {
+        let cap = cap.max(1);
+        let psa = create_vector(T::SFTYPE, 0, cap as u32);
+        if psa.is_null() {
+            return Err(IntoSafeArrayError::SafeArrayCreateFailed);
+        }
+        let sad = SafeArrayDestructor::new(psa);
+        ConversionStats::record_safearray(cap * unsafe { SafeArrayGetElemSize(psa) } as usize);
+        Ok(SafeArrayBuilder { sad: sad, len: 0, cap: cap, _marker: PhantomData })
+    }
+
+    /// Number of elements pushed so far
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// No elements have been pushed
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends `elem`, growing the array if it is full
+    pub fn push(&mut self, elem: T) -> Result<(), IntoSafeArrayError> {
+        if self.len == self.cap {
+            let cap = self.cap.saturating_mul(2);
+            self.redim(cap)?;
+            self.cap = cap;
+        }
+        match elem.into_safearray(self.sad.inner, self.len as i32) {
+            Ok(()) => {
+                self.len += 1;
+                Ok(())
+            }, 
+            Err(e) => Err(IntoSafeArrayError::from_element_err(e, self.len)),
+        }
+    }
+
+    /// Trims the array to the pushed elements and hands it over
+    pub fn finish(mut self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+        if self.len < self.cap {
+            let len = self.len;
+            self.redim(len)?;
+        }
+        let psa = self.sad.inner;
+        self.sad.inner = null_mut();
+        Ok(Ptr::with_checked(psa).unwrap())
+    }
+
+    fn redim(&mut self, c_elements: usize) -> Result<(), IntoSafeArrayError> {
+        redim_vector(self.sad.inner, 0, c_elements)
+    }
+}
+
+/// Sets the 1-D array at `psa` to `len` elements from `l_bound` with `SafeArrayRedim`. 
+/// Every in-place grow and trim in the crate goes through here.
+pub(crate) fn redim_vector(psa: *mut SAFEARRAY, l_bound: i32, len: usize) -> Result<(), IntoSafeArrayError> {
+    let mut sab = SAFEARRAYBOUND { cElements: len as u32, lLbound: l_bound };
+    let hr = unsafe { SafeArrayRedim(psa, &mut sab) };
+    if hr != 0 {
+        return Err(IntoSafeArrayError::RedimFailed{hr: hr});
+    }
+    Ok(())
+}
+
+/// Grows or truncates the 1-D SAFEARRAY at `psa` in place to `len` elements, keeping its lower bound. 
+/// 
+/// New slots hold the empty value of the element type; truncated elements are released. 
+/// The array must not be fixed-size or locked, as `SafeArrayRedim` requires.
+pub fn resize_safearray(psa: *mut SAFEARRAY, len: usize) -> Result<(), SafeArrayError> {
+    if psa.is_null() {
+        return Err(FromSafeArrayError::SafeArrayPtrNull.into());
+    }
+    let sa_dims = unsafe { SafeArrayGetDim(psa) };
+    if sa_dims != 1 {
+        return Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims}.into());
+    }
+    let (l_bound, _) = get_bounds(psa, 1)?;
+    redim_vector(psa, l_bound, len)?;
+    Ok(())
+}
+
+/// Appends `items` to the 1-D SAFEARRAY of `T` at `psa`, growing it in place with `SafeArrayRedim`. 
+/// 
+/// Handy for accumulating results into an array destined for a COM out-parameter. 
+/// If an element fails to convert, the array is truncated back to its original length. 
+/// For plain numeric elements, `append_safearray` copies in bulk instead.
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{extend_safearray, SafeArrayExt};
+/// 
+/// fn main() {
+///     let psa = vec![String::from("a")].into_iter().into_safearray().unwrap();
+///     extend_safearray(psa.as_ptr(), vec![String::from("b"), String::from("c")]).unwrap();
+///     let v = ExactSizeIterator::<Item=String>::from_safearray(psa.as_ptr()).unwrap();
+///     assert_eq!(v, vec!["a", "b", "c"]);
+/// }
+/// ```
+pub fn extend_safearray<T, I>(psa: *mut SAFEARRAY, items: I) -> Result<(), SafeArrayError> 
+    where T: SafeArrayElement, 
+          I: IntoIterator<Item=T>
+{
+    if psa.is_null() {
+        return Err(FromSafeArrayError::SafeArrayPtrNull.into());
+    }
+    let vt = get_vartype(psa)?;
+    if vt != T::SFTYPE {
+        return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: T::SFTYPE, found: vt}.into());
+    }
+    let items: Vec<T> = items.into_iter().collect();
+    if items.is_empty() {
+        return Ok(());
+    }
+    let len = safearray_len(psa)?;
+    resize_safearray(psa, len + items.len())?;
+
+    let (l_bound, _) = get_bounds(psa, 1)?;
+    for (ix, elem) in items.into_iter().enumerate() {
+        if let Err(e) = elem.into_safearray(psa, l_bound + (len + ix) as i32) {
+            resize_safearray(psa, len)?;
+            return Err(IntoSafeArrayError::from_element_err(e, ix).into());
+        }
+    }
+    Ok(())
+}
+
+/// Converts any iterator into a 1-D SAFEARRAY, not just an `ExactSizeIterator`. 
+/// 
+/// Filtered, chained and flattened pipelines don't know their length, so the array is 
+/// sized from the iterator's lower size hint and grown and trimmed by a [`SafeArrayBuilder`]. 
+/// Exact-size sources are better served by `SafeArrayExt::into_safearray`, which never redims.
+/// 
+/// [`SafeArrayBuilder`]: struct.SafeArrayBuilder.html
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{collect_safearray, SafeArrayExt};
+/// 
+/// fn main() {
+///     let psa = collect_safearray((0..10i32).filter(|i| i % 3 == 0).chain(Some(100))).unwrap();
+///     let v = ExactSizeIterator::<Item=i32>::from_safearray(psa.as_ptr()).unwrap();
+///     assert_eq!(v, vec![0, 3, 6, 9, 100]);
+/// }
+/// ```
+pub fn collect_safearray<I>(items: I) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> 
+    where I: IntoIterator, I::Item: SafeArrayElement
+{
+    let items = items.into_iter();
+    let mut builder = SafeArrayBuilder::with_capacity(items.size_hint().0)?;
+    for elem in items {
+        builder.push(elem)?;
+    }
+    builder.finish()
+}
+
 macro_rules! safe_arr_impl {
     (
         impl $(< $tn:ident : $tc:ident >)* SafeArrayElement for $t:ty {
             SFTYPE = $vt:expr;
-            ptr
-            def  => {$def:expr}
+            stage => $stage:ty;
             from => {$from:expr}
             into => {$into:expr}
         }
@@ -222,14 +1075,33 @@ macro_rules! safe_arr_impl {
         impl $(<$tn:$tc>)* SafeArrayElement for $t {
             const SFTYPE: u32 = $vt;
              fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError> {
-                let val = $def;
-                let hr = unsafe {SafeArrayGetElement(psa, &ix, val as *mut _ as *mut c_void)};
-                check_and_throw!(hr, $from(val), {return Err(FromSafeArrElemError::GetElementFailed{hr: hr})})
+                Self::from_safearray_at(psa, &[ix])
             }
             
             fn into_safearray(self, psa: *mut SAFEARRAY, ix: i32) -> Result<(), IntoSafeArrElemError> {
+                self.into_safearray_at(psa, &[ix])
+            }
+
+            fn from_safearray_at(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<Self, FromSafeArrElemError> {
+                let prg = ixs.as_ptr();
+                let mut staged = ElementStage::<$stage>::new();
+                let hr = unsafe {SafeArrayGetElement(psa, prg, staged.as_mut_ptr() as *mut c_void)};
+                if hr != 0 {
+                    return Err(FromSafeArrElemError::GetElementFailed{hr: hr});
+                }
+                let handed_on = staged.handed_on();
+                let ret = $from(staged.as_mut_ptr());
+                if ret.is_ok() && handed_on {
+                    staged.take();
+                }
+                ret
+            }
+
+            fn into_safearray_at(self, psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<(), IntoSafeArrElemError> {
+                let prg = ixs.as_ptr();
                 let slf = $into(self)?;
-                let hr = unsafe {SafeArrayPutElement(psa, &ix, slf as *mut _ as *mut c_void)};
+                let hr = unsafe {SafeArrayPutElement(psa, prg, slf as *mut c_void)};
+                <$stage as Stage>::put_done(slf as *mut c_void);
                 check_and_throw!(hr, {return Ok(())}, {Err(IntoSafeArrElemError::PutElementFailed{hr: hr})})
             }
         }
@@ -245,14 +1117,24 @@ macro_rules! safe_arr_impl {
         impl $(<$tn:$tc>)* SafeArrayElement for $t {
             const SFTYPE: u32 = $vt;
              fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError> {
-                let mut val = $def;
-                let hr = unsafe {SafeArrayGetElement(psa, &ix, &mut val as *mut _ as *mut c_void)};
-                check_and_throw!(hr, $from(val), {return Err(FromSafeArrElemError::GetElementFailed{hr: hr})})
+                Self::from_safearray_at(psa, &[ix])
             }
             
             fn into_safearray(self, psa: *mut SAFEARRAY, ix: i32) -> Result<(), IntoSafeArrElemError> {
+                self.into_safearray_at(psa, &[ix])
+            }
+
+            fn from_safearray_at(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<Self, FromSafeArrElemError> {
+                let prg = ixs.as_ptr();
+                let mut val = $def;
+                let hr = unsafe {SafeArrayGetElement(psa, prg, &mut val as *mut _ as *mut c_void)};
+                check_and_throw!(hr, $from(val), {return Err(FromSafeArrElemError::GetElementFailed{hr: hr})})
+            }
+
+            fn into_safearray_at(self, psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<(), IntoSafeArrElemError> {
+                let prg = ixs.as_ptr();
                 let mut slf = $into(self)?;
-                let hr = unsafe {SafeArrayPutElement(psa, &ix, &mut slf as *mut _ as *mut c_void)};
+                let hr = unsafe {SafeArrayPutElement(psa, prg, &mut slf as *mut _ as *mut c_void)};
                 check_and_throw!(hr, {return Ok(())}, {Err(IntoSafeArrElemError::PutElementFailed{hr: hr})})
             }
         }
@@ -274,14 +1156,26 @@ safe_arr_impl!{impl SafeArrayElement for i32 {
 safe_arr_impl!{impl SafeArrayElement for f32 {
     SFTYPE = VT_R4;
     def => { 0.0f32 }
-    from => {|i| Ok(i)}
-    into => { |slf: _| -> Result<_, IntoSafeArrElemError> { Ok(slf) }}
+    from => {|i: f32| {
+        ConvertOptions::check_float(f64::from(i)).map_err(|v| FromSafeArrElemError::NonFiniteFloat{value: v})?;
+        Ok(i)
+    }}
+    into => { |slf: f32| -> Result<_, IntoSafeArrElemError> {
+        ConvertOptions::check_float(f64::from(slf)).map_err(|v| IntoSafeArrElemError::NonFiniteFloat{value: v})?;
+        Ok(slf)
+    }}
 }}
 safe_arr_impl!{impl SafeArrayElement for f64 { 
     SFTYPE = VT_R8; 
     def => { 0.0f64 }
-    from => {|i| Ok(i)}
-    into => { |slf: _| -> Result<_, IntoSafeArrElemError> { Ok(slf) }}
+    from => {|i: f64| {
+        ConvertOptions::check_float(i).map_err(|v| FromSafeArrElemError::NonFiniteFloat{value: v})?;
+        Ok(i)
+    }}
+    into => { |slf: f64| -> Result<_, IntoSafeArrElemError> {
+        ConvertOptions::check_float(slf).map_err(|v| IntoSafeArrElemError::NonFiniteFloat{value: v})?;
+        Ok(slf)
+    }}
 }}
 safe_arr_impl!{impl SafeArrayElement for Currency{
     SFTYPE = VT_CY; 
@@ -295,44 +1189,33 @@ safe_arr_impl!{impl SafeArrayElement for Date{
     from => { |dt| Ok(Date::from(dt)) } 
     into => { |slf: Date| -> Result<_, IntoSafeArrElemError> {Ok(DATE::from(slf)) }}
 }}
-// Need to wrap the string in a variant because its not working otherwise. 
+// Strings go in VT_VARIANT arrays as VT_BSTR variants, the shape COM servers return them in. 
 safe_arr_impl!{impl SafeArrayElement for String {
     SFTYPE = VT_VARIANT;
-    ptr
-    def => {{
-        let mut var: VARIANT = unsafe {mem::zeroed()};
-        &mut var as *mut VARIANT
-    }}
+    stage => VARIANT;
     from => {|pvar| {
         let pnn = match Ptr::with_checked(pvar) {
             Some(nn) => nn, 
             None => return Err(FromSafeArrElemError::VariantPtrNull)
         };
-        match Variant::<String>::from_variant(pnn) {
-            Ok(var) => Ok(var.unwrap()), 
+        match String::from_variant(pnn) {
+            Ok(s) => Ok(s), 
+            Err(FromVariantError::LimitExceeded(le)) => return Err(FromSafeArrElemError::from(le)), 
             Err(_) => return Err(FromSafeArrElemError::FromVariantFailed)
         }
     }}
     into => {|slf: String|{
-        let slf = Variant::new(slf);
         match slf.into_variant() {
-            Ok(slf) => {
-                let mut s = slf.as_ptr();
-                Ok(s)
-            }, 
+            Ok(slf) => Ok(slf.as_ptr()), 
             Err(ive) => Err(IntoSafeArrElemError::from(ive))
         }
     }}
 }}
 safe_arr_impl!{impl SafeArrayElement for Ptr<IDispatch>{
     SFTYPE = VT_DISPATCH; 
-    ptr
-    def => {{
-        let mut var: IDispatch = unsafe {mem::zeroed()};
-        &mut var as *mut IDispatch
-    }}
-    from => { |ptr: *mut IDispatch| {
-        match Ptr::with_checked(ptr) {
+    stage => *mut IDispatch;
+    from => { |pptr: *mut *mut IDispatch| {
+        match Ptr::with_checked(unsafe { *pptr }) {
             Some(pnn) => Ok(pnn), 
             None => Err(FromSafeArrElemError::DispatchPtrNull)
         }
@@ -355,41 +1238,50 @@ safe_arr_impl!{impl SafeArrayElement for bool {
 }}
 safe_arr_impl!{impl <T: VariantExt> SafeArrayElement for Variant<T> {
     SFTYPE = VT_VARIANT;
-    ptr
-    def => {{
-        let mut var: VARIANT = unsafe {mem::zeroed()};
-        &mut var as *mut VARIANT
+    stage => VARIANT;
+    from => {|pvar| {
+        let pnn = match Ptr::with_checked(pvar) {
+            Some(nn) => nn, 
+            None => return Err(FromSafeArrElemError::VariantPtrNull)
+        };
+        match T::from_variant(pnn) {
+            Ok(t) => Ok(Variant::new(t)), 
+            Err(_) => Err(FromSafeArrElemError::FromVariantFailed)
+        }
+    }}
+    into => {|slf: Variant<T>| -> Result<*mut VARIANT, IntoSafeArrElemError>{
+        match slf.unwrap().into_variant() {
+            Ok(slf) => Ok(slf.as_ptr()), 
+            Err(ive) => Err(IntoSafeArrElemError::from(ive))
+        }
     }}
+}}
+safe_arr_impl!{impl SafeArrayElement for Variants {
+    SFTYPE = VT_VARIANT;
+    stage => VARIANT;
     from => {|pvar| {
         let pnn = match Ptr::with_checked(pvar) {
             Some(nn) => nn, 
             None => return Err(FromSafeArrElemError::VariantPtrNull)
         };
-        match Variant::<T>::from_variant(pnn) {
+        match Variants::from_variant(pnn) {
             Ok(var) => Ok(var), 
             Err(_) => Err(FromSafeArrElemError::FromVariantFailed)
         }
     }}
-    into => {|slf: Variant<T>| -> Result<*mut VARIANT, IntoSafeArrElemError>{
+    into => {|slf: Variants| -> Result<*mut VARIANT, IntoSafeArrElemError>{
         match slf.into_variant() {
-            Ok(slf) => {
-                let mut s = slf.as_ptr();
-                Ok(s)
-            }, 
+            Ok(slf) => Ok(slf.as_ptr()), 
             Err(ive) => Err(IntoSafeArrElemError::from(ive))
         }
     }}
 }}
 safe_arr_impl!{impl SafeArrayElement for Ptr<IUnknown> {
     SFTYPE = VT_UNKNOWN; 
-    ptr
-    def => {{
-        let mut var: IUnknown = unsafe {mem::zeroed()};
-        &mut var as *mut IUnknown
-    }}
+    stage => *mut IUnknown;
     from => {
-        |ptr| {
-            match Ptr::with_checked(ptr) {
+        |pptr: *mut *mut IUnknown| {
+            match Ptr::with_checked(unsafe { *pptr }) {
                 Some(ptr) => Ok(ptr), 
                 None => Err(FromSafeArrElemError::UnknownPtrNull)
             }
@@ -401,6 +1293,30 @@ safe_arr_impl!{impl SafeArrayElement for Ptr<IUnknown> {
         }
     }
 }}
+
+// The reference `SafeArrayGetElement` AddRef'd for the `Ptr` read is adopted by the `OwnedPtr`. 
+// On the way in `SafeArrayPutElement` AddRefs for the array, and dropping `self` releases ours.
+impl<I: Interface> SafeArrayElement for OwnedPtr<I, ComDestructor> 
+    where Ptr<I>: SafeArrayElement
+{
+    const SFTYPE: u32 = <Ptr<I> as SafeArrayElement>::SFTYPE;
+
+    fn into_safearray(self, psa: *mut SAFEARRAY, ix: i32) -> Result<(), IntoSafeArrElemError> {
+        self.into_safearray_at(psa, &[ix])
+    }
+
+    fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError> {
+        Self::from_safearray_at(psa, &[ix])
+    }
+
+    fn into_safearray_at(self, psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<(), IntoSafeArrElemError> {
+        Ptr::with_checked(self.as_ptr()).unwrap().into_safearray_at(psa, ixs)
+    }
+
+    fn from_safearray_at(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<Self, FromSafeArrElemError> {
+        Ptr::<I>::from_safearray_at(psa, ixs).map(OwnedPtr::new)
+    }
+}
 safe_arr_impl!{impl SafeArrayElement for Decimal {
     SFTYPE = VT_DECIMAL; 
     def => {DECIMAL::from(DecWrapper::from(Decimal::new(0, 0)))}
@@ -456,23 +1372,35 @@ safe_arr_impl!{impl SafeArrayElement for UInt {
 #[allow(dead_code)]
 #[link(name="OleAut32")]
 extern "system" {
-     fn SafeArrayCreate(vt: VARTYPE, cDims: UINT, rgsabound: LPSAFEARRAYBOUND) -> LPSAFEARRAY;
-	 fn SafeArrayDestroy(safe: LPSAFEARRAY)->HRESULT;
+     pub(crate) fn SafeArrayAccessData(psa: LPSAFEARRAY, ppvData: *mut *mut c_void) -> HRESULT;
+     pub(crate) fn SafeArrayUnaccessData(psa: LPSAFEARRAY) -> HRESULT;
+
+     fn SafeArrayCopy(psa: LPSAFEARRAY, ppsaOut: *mut LPSAFEARRAY) -> HRESULT;
+
+     pub(crate) fn SafeArrayCreate(vt: VARTYPE, cDims: UINT, rgsabound: LPSAFEARRAYBOUND) -> LPSAFEARRAY;
+     pub(crate) fn SafeArrayCreateEx(vt: VARTYPE, cDims: UINT, rgsabound: LPSAFEARRAYBOUND, pvExtra: *mut c_void) -> LPSAFEARRAY;
+     pub(crate) fn SafeArrayCreateVector(vt: VARTYPE, lLbound: c_long, cElements: ULONG) -> LPSAFEARRAY;
+     pub(crate) fn SafeArrayCreateVectorEx(vt: VARTYPE, lLbound: c_long, cElements: ULONG, pvExtra: *mut c_void) -> LPSAFEARRAY;
+	 pub(crate) fn SafeArrayDestroy(safe: LPSAFEARRAY)->HRESULT;
     
-     fn SafeArrayGetDim(psa: LPSAFEARRAY) -> UINT;
+     pub(crate) fn SafeArrayGetDim(psa: LPSAFEARRAY) -> UINT;
 	
-     fn SafeArrayGetElement(psa: LPSAFEARRAY, rgIndices: *const c_long, pv: *mut c_void) -> HRESULT;
-     fn SafeArrayGetElemSize(psa: LPSAFEARRAY) -> UINT;
+     pub(crate) fn SafeArrayGetElement(psa: LPSAFEARRAY, rgIndices: *const c_long, pv: *mut c_void) -> HRESULT;
+     pub(crate) fn SafeArrayGetElemSize(psa: LPSAFEARRAY) -> UINT;
+     pub(crate) fn SafeArrayGetRecordInfo(psa: LPSAFEARRAY, prinfo: *mut *mut IRecordInfo) -> HRESULT;
     
      fn SafeArrayGetLBound(psa: LPSAFEARRAY, nDim: UINT, plLbound: *mut c_long)->HRESULT;
      fn SafeArrayGetUBound(psa: LPSAFEARRAY, nDim: UINT, plUbound: *mut c_long)->HRESULT;
     
      fn SafeArrayGetVartype(psa: LPSAFEARRAY, pvt: *mut VARTYPE) -> HRESULT;
 
-     fn SafeArrayLock(psa: LPSAFEARRAY) -> HRESULT;
-	 fn SafeArrayUnlock(psa: LPSAFEARRAY) -> HRESULT;
+     pub(crate) fn SafeArrayLock(psa: LPSAFEARRAY) -> HRESULT;
+	 pub(crate) fn SafeArrayUnlock(psa: LPSAFEARRAY) -> HRESULT;
     
-     fn SafeArrayPutElement(psa: LPSAFEARRAY, rgIndices: *const c_long, pv: *mut c_void) -> HRESULT;
+     pub(crate) fn SafeArrayPtrOfIndex(psa: LPSAFEARRAY, rgIndices: *const c_long, ppvData: *mut *mut c_void) -> HRESULT;
+     pub(crate) fn SafeArrayPutElement(psa: LPSAFEARRAY, rgIndices: *const c_long, pv: *mut c_void) -> HRESULT;
+
+     pub(crate) fn SafeArrayRedim(psa: LPSAFEARRAY, psaboundNew: LPSAFEARRAYBOUND) -> HRESULT;
 }
 
 #[cfg(test)]
@@ -489,6 +1417,282 @@ mod test {
             assert_eq!(r, $vals);
         };
     }
+    #[test]
+    fn test_limits() {
+        use super::super::limits::DecodeLimits;
+        use super::super::errors::LimitError;
+        let limits = DecodeLimits { max_elements: Some(2), max_bstr_len: Some(3), ..DecodeLimits::default() };
+        let p = vec![1i32, 2, 3].into_iter().into_safearray().unwrap();
+        match limits.scoped(|| ExactSizeIterator::<Item=i32>::from_safearray(p.as_ptr())) {
+            Err(FromSafeArrayError::LimitExceeded(LimitError::TooManyElements{len: 3, max: 2})) => {}, 
+            _ => panic!("expected element limit"),
+        };
+        let p = vec![String::from("long string")].into_iter().into_safearray().unwrap();
+        match limits.scoped(|| ExactSizeIterator::<Item=String>::from_safearray(p.as_ptr())) {
+            Err(FromSafeArrayError::ElementConversionFailed{..}) => {}, 
+            _ => panic!("expected bstr limit"),
+        };
+    }
+
+    #[test]
+    fn test_2d() {
+        let rows = vec![vec![1i32, 2, 3], vec![4, 5, 6]];
+        let psa = into_safearray_2d(rows.clone()).unwrap();
+        assert_eq!(unsafe { SafeArrayGetDim(psa.as_ptr()) }, 2);
+        assert_eq!(get_bounds(psa.as_ptr(), 1).unwrap(), (0, 1));
+        assert_eq!(get_bounds(psa.as_ptr(), 2).unwrap(), (0, 2));
+        // dimension 1 varies fastest in memory: row 1 of column 0 comes straight after row 0
+        let data = unsafe { (*psa.as_ptr()).pvData as *const i32 };
+        assert_eq!(unsafe { [*data, *data.add(1), *data.add(2)] }, [1, 4, 2]);
+        let back: Vec<Vec<i32>> = from_safearray_2d(psa.as_ptr()).unwrap();
+        assert_eq!(back, rows);
+
+        let strs = vec![vec![String::from("a"), String::from("b")], vec![String::from("c"), String::from("d")]];
+        let psa = into_safearray_2d(strs.clone()).unwrap();
+        assert_eq!(from_safearray_2d::<String>(psa.as_ptr()).unwrap(), strs);
+
+        match into_safearray_2d(vec![vec![1i32, 2], vec![3]]) {
+            Err(IntoSafeArrayError::JaggedRows{row, expected, found}) => assert_eq!((row, expected, found), (1, 2, 1)), 
+            _ => panic!("Error")
+        }
+    }
+
+    #[test]
+    fn test_create_vector() {
+        let psa = create_vector(VT_UNKNOWN, 0, 2);
+        let _sad = SafeArrayDestructor::new(psa);
+        assert!(unsafe { (*psa).fFeatures } as u32 & FADF_HAVEIID != 0);
+        assert_eq!(get_vartype(psa).unwrap(), VT_UNKNOWN);
+
+        let psa = create_vector(VT_I4, 0, 3);
+        let _sad2 = SafeArrayDestructor::new(psa);
+        assert_eq!(get_bounds(psa, 1).unwrap(), (0, 2));
+    }
+
+    #[test]
+    fn test_elem_size() {
+        let psa = vec![1.5f64].into_iter().into_safearray().unwrap();
+        let _sad = SafeArrayDestructor::new(psa.as_ptr());
+        assert!(check_elem_size(psa.as_ptr(), 8).is_ok());
+        match check_elem_size(psa.as_ptr(), 4) {
+            Err(FromSafeArrayError::ElementSizeMismatch{expected: 4, found: 8}) => {}, 
+            _ => panic!("expected a size mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        use super::super::leak_tracking;
+        leak_tracking::assert_balanced(|| {
+            let mut builder = SafeArrayBuilder::<i32>::with_capacity(2).unwrap();
+            for i in 0..5 {
+                builder.push(i).unwrap();
+            }
+            assert_eq!(builder.len(), 5);
+            let psa = builder.finish().unwrap();
+            assert_eq!(get_bounds(psa.as_ptr(), 1).unwrap(), (0, 4));
+            drop(SafeArrayDestructor::new(psa.as_ptr()));
+
+            let mut builder = SafeArrayBuilder::<String>::new().unwrap();
+            builder.push(String::from("dropped")).unwrap();
+        });
+    }
+
+    #[test]
+    fn test_safearray_n() {
+        let mut sa = SafeArrayN::<i32>::new(&[(1, 2), (0, 2), (-1, 0)]).unwrap();
+        assert_eq!(sa.dims(), 3);
+        assert_eq!(sa.bounds().unwrap(), vec![(1, 2), (0, 2), (-1, 0)]);
+        assert_eq!(sa.vartype().unwrap(), VT_I4);
+        assert_eq!(sa.len().unwrap(), 12);
+        sa.set(&[2, 1, -1], 42).unwrap();
+        assert_eq!(sa.get(&[2, 1, -1]).unwrap(), 42);
+        // dimension 1 varies fastest in memory, so [2, 1, -1] is 1 + 1 * 2 elements in
+        let data = unsafe { (*sa.as_ptr()).pvData as *const i32 };
+        assert_eq!(unsafe { *data.add(3) }, 42);
+        assert!(sa.get(&[-1, 1, 2]).is_err());
+        assert_eq!(sa.get(&[1, 0, 0]).unwrap(), 0);
+        match sa.get(&[1, 0]) {
+            Err(FromSafeArrElemError::IndexDimsMismatch{expected: 3, found: 2}) => {}, 
+            _ => panic!("Error")
+        }
+        assert!(sa.get(&[3, 0, 0]).is_err());
+
+        let psa = sa.into_ptr();
+        let sa = SafeArrayN::<i32>::from_ptr(psa.as_ptr()).unwrap();
+        assert_eq!(sa.get(&[2, 1, -1]).unwrap(), 42);
+        drop(sa);
+
+        let psa = vec![String::from("a")].into_iter().into_safearray().unwrap();
+        match SafeArrayN::<i32>::from_ptr(psa.as_ptr()) {
+            Err(FromSafeArrayError::VarTypeDoesNotMatch{..}) => {}, 
+            _ => panic!("Error")
+        }
+    }
+
+    #[test]
+    fn test_recovery() {
+        let sparse = || {
+            let mut sab = SAFEARRAYBOUND { cElements: 3, lLbound: 0 };
+            let psa = unsafe { SafeArrayCreate(VT_VARIANT as u16, 1, &mut sab) };
+            String::from("a").into_safearray(psa, 0).unwrap();
+            String::from("c").into_safearray(psa, 2).unwrap();
+            psa
+        };
+        let r = ExactSizeIterator::<Item=String>::from_safearray_recover(sparse(), |ix, _| {
+            assert_eq!(ix, 1);
+            ElementRecovery::Skip
+        });
+        assert_eq!(r.unwrap(), vec![String::from("a"), String::from("c")]);
+        let r = ExactSizeIterator::<Item=String>::from_safearray_recover(sparse(), |_, _| ElementRecovery::default_value());
+        assert_eq!(r.unwrap(), vec![String::from("a"), String::new(), String::from("c")]);
+        match ExactSizeIterator::<Item=String>::from_safearray(sparse()) {
+            Err(FromSafeArrayError::ElementConversionFailed{index: 1, ..}) => {}, 
+            _ => panic!("expected element failure"),
+        }
+    }
+
+    #[test]
+    fn test_stage_balanced() {
+        use super::super::leak_tracking;
+        leak_tracking::assert_balanced(|| {
+            let strs = vec![String::from("a"), String::from("b")];
+            let psa = strs.clone().into_iter().into_safearray().unwrap();
+            let r = ExactSizeIterator::<Item=String>::from_safearray(psa.as_ptr()).unwrap();
+            assert_eq!(r, strs);
+        });
+
+        let mut staged = ElementStage::<VARIANT>::new();
+        assert!(!staged.handed_on());
+        unsafe { (*staged.as_mut_ptr()).n1.n2_mut().vt = VT_UNKNOWN as u16 };
+        assert!(staged.handed_on());
+        staged.take();
+    }
+
+    #[test]
+    fn test_lbound() {
+        let psa = vec![1i32, 2, 3].into_iter().into_safearray_with_lbound(1).unwrap();
+        assert_eq!(safearray_shape(psa.as_ptr()).unwrap(), vec![(1, 3)]);
+        let (l_bound, v) = ExactSizeIterator::<Item=i32>::from_safearray_with_lbound(psa.as_ptr()).unwrap();
+        assert_eq!(l_bound, 1);
+        assert_eq!(v, vec![1, 2, 3]);
+
+        let psa = v.into_iter().into_safearray_with_lbound(l_bound).unwrap();
+        let _sad = SafeArrayDestructor::new(psa.as_ptr());
+        assert_eq!(i32::from_safearray(psa.as_ptr(), 3).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_info() {
+        let psa = vec![Variant::new(1i32)].into_iter().into_safearray().unwrap();
+        let _sad = SafeArrayDestructor::new(psa.as_ptr());
+        let info = safearray_info(psa.as_ptr()).unwrap();
+        assert!(info.is_variant() && !info.is_bstr());
+        assert_eq!(info.dims, 1);
+        assert_eq!(info.element_size as usize, mem::size_of::<VARIANT>());
+        assert_eq!(info.vartype, Some(VT_VARIANT));
+        match safearray_info(null_mut()) {
+            Err(FromSafeArrayError::SafeArrayPtrNull) => {}, 
+            _ => panic!("expected null pointer error"),
+        }
+    }
+
+    #[test]
+    fn test_copy() {
+        leak_tracking::assert_balanced(|| {
+            let psa = vec![String::from("a"), String::from("b")].into_iter().into_safearray_with_lbound(1).unwrap();
+            let copy = copy_safearray(psa.as_ptr()).unwrap();
+            assert_ne!(copy.as_ptr(), psa.as_ptr());
+            assert_eq!(safearray_shape(copy.as_ptr()).unwrap(), vec![(1, 2)]);
+            let a = ExactSizeIterator::<Item=String>::from_safearray(psa.as_ptr()).unwrap();
+            let b = ExactSizeIterator::<Item=String>::from_safearray(copy.as_ptr()).unwrap();
+            assert_eq!(a, b);
+        });
+    }
+
+    #[test]
+    fn test_vec_ext() {
+        let v = vec![String::from("x"), String::from("y")];
+        let psa = v[1..].to_safearray().unwrap();
+        assert_eq!(Vec::<String>::from_safearray(psa.as_ptr()).unwrap(), vec![String::from("y")]);
+        let psa = v.into_safearray().unwrap();
+        assert_eq!(Vec::<String>::from_safearray(psa.as_ptr()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_collect() {
+        let words = "one two three four";
+        let psa = collect_safearray(words.split(' ').filter(|w| w.len() > 3).map(String::from)).unwrap();
+        let v = ExactSizeIterator::<Item=String>::from_safearray(psa.as_ptr()).unwrap();
+        assert_eq!(v, vec![String::from("three"), String::from("four")]);
+
+        let psa = collect_safearray(Vec::<i32>::new()).unwrap();
+        assert_eq!(safearray_shape(psa.as_ptr()).unwrap(), vec![(0, 0)]);
+        drop(SafeArrayDestructor::new(psa.as_ptr()));
+    }
+
+    #[test]
+    fn test_presized() {
+        let psa = (0..100i32).into_iter().into_safearray().unwrap();
+        let v = ExactSizeIterator::<Item=i32>::from_safearray(psa.as_ptr()).unwrap();
+        assert_eq!(v.len(), 100);
+        //push growth would have left 128
+        assert_eq!(v.capacity(), 100);
+    }
+
+    #[test]
+    fn test_owned_interface() {
+        use super::super::stream::{bytes_to_stream_variant, stream_variant_to_bytes, StreamVarType};
+        let pvar = bytes_to_stream_variant(b"x", StreamVarType::Unknown).unwrap();
+        let punk = unsafe { *(*pvar.as_ptr()).n1.n2().n3.punkVal() };
+        unsafe { (*punk).AddRef() };
+        let owned = OwnedPtr::<IUnknown, ComDestructor>::new(Ptr::with_checked(punk).unwrap());
+
+        let psa = vec![owned].into_iter().into_safearray().unwrap();
+        let back = ExactSizeIterator::<Item=OwnedPtr<IUnknown, ComDestructor>>::from_safearray(psa.as_ptr()).unwrap();
+        assert_eq!(back[0].as_ptr(), punk);
+        drop(back);
+
+        // only the VARIANT's reference is left
+        assert_eq!(unsafe { (*punk).AddRef() }, 2);
+        unsafe { (*punk).Release() };
+        assert_eq!(stream_variant_to_bytes(pvar).unwrap(), b"x".to_vec());
+    }
+
+    #[test]
+    fn test_resize() {
+        let psa = vec![1i32, 2, 3].into_iter().into_safearray_with_lbound(1).unwrap();
+        resize_safearray(psa.as_ptr(), 2).unwrap();
+        assert_eq!(safearray_shape(psa.as_ptr()).unwrap(), vec![(1, 2)]);
+        extend_safearray(psa.as_ptr(), vec![7i32, 8]).unwrap();
+        match extend_safearray(psa.as_ptr(), vec![1.0f64]) {
+            Err(SafeArrayError::From(_)) => {}, 
+            _ => panic!("expected a vartype mismatch"),
+        }
+        let v = ExactSizeIterator::<Item=i32>::from_safearray(psa.as_ptr()).unwrap();
+        assert_eq!(v, vec![1, 2, 7, 8]);
+    }
+
+    #[test]
+    fn test_null_input() {
+        match ExactSizeIterator::<Item=i32>::from_safearray(null_mut()) {
+            Err(FromSafeArrayError::SafeArrayPtrNull) => {}, 
+            _ => panic!("expected null pointer error"),
+        }
+    }
+
+    #[test]
+    fn test_shape() {
+        let psa = vec![1u8, 2, 3, 4].into_iter().into_safearray().unwrap();
+        let _sad = SafeArrayDestructor::new(psa.as_ptr());
+        assert_eq!(safearray_shape(psa.as_ptr()).unwrap(), vec![(0, 4)]);
+        assert_eq!(safearray_len(psa.as_ptr()).unwrap(), 4);
+        match safearray_len(null_mut()) {
+            Err(FromSafeArrayError::SafeArrayPtrNull) => {}, 
+            _ => panic!("expected null pointer error"),
+        }
+    }
+
     #[test]
     fn test_i16() {
         validate_safe_arr!(i16, vec![0,1,2,3,4], VT_I2 );
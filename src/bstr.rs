@@ -4,7 +4,9 @@ use winapi::um::oleauto::{SysAllocStringLen, SysFreeString, SysStringLen};
 use widestring::U16String;
 
 use super::errors::BStringError;
+use super::leak_tracking;
 use super::ptr::Ptr;
+use super::stats::ConversionStats;
 
 // pub type wchar_t = u16;
 // pub type WCHAR = wchar_t;
@@ -34,7 +36,9 @@ pub trait BStringExt {
     fn allocate_managed_bstr(&mut self) -> Result<DroppableBString, BStringError>;
     /// Manually and correct free the memory allocated via Sys* methods
     fn deallocate_bstr(bstr: Ptr<u16>);
-    /// Convenience method for conversion to a good intermediary type
+    /// Convenience method for conversion to a good intermediary type. 
+    /// The length comes from the BSTR prefix (`SysStringLen`), never from a 
+    /// NUL scan, so interior NULs are preserved.
     fn from_bstr(bstr: *mut u16) -> U16String;
     /// Convenience method for conversion to a good intermediary type
     fn from_pbstr(bstr: Ptr<u16>) -> U16String;
@@ -49,7 +53,10 @@ impl BStringExt for U16String {
         let rw = cln.as_ptr();
         let bstr: BSTR = unsafe {SysAllocStringLen(rw, sz as u32)};
         match Ptr::with_checked(bstr) {
-            Some(pbstr) => Ok(pbstr), 
+            Some(pbstr) => {
+                ConversionStats::record_bstr(sz);
+                Ok(pbstr)
+            }, 
             None => Err(BStringError::AllocateFailed{len: sz})
         }
     }
@@ -60,6 +67,7 @@ impl BStringExt for U16String {
 
     fn deallocate_bstr(bstr: Ptr<u16>) {
         let bstr: BSTR = bstr.as_ptr();
+        leak_tracking::bstr_freed();
         unsafe { SysFreeString(bstr) }
     }
 
@@ -106,6 +114,7 @@ impl Drop for DroppableBString {
     fn drop(&mut self) {
         match self.inner {
             Some(ptr) => {
+                leak_tracking::bstr_freed();
                 unsafe { SysFreeString(ptr.as_ptr())}
             }, 
             None => {}
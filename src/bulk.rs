@@ -0,0 +1,433 @@
+//! # Bulk
+//! Whole-array copies for fixed-size element types. 
+//! 
+//! `SafeArrayExt` moves elements one at a time through `SafeArrayPutElement` / 
+//! `SafeArrayGetElement`. For element types whose SAFEARRAY storage is a plain 
+//! array of fixed-size values - the numeric types, but also `CY`, `DATE` and 
+//! `DECIMAL` - the data can be copied in one go under `SafeArrayAccessData`. 
+//! `bool` goes the same way, mapped to and from `VARIANT_BOOL` in the copy loop, 
+//! which makes boolean mask columns as cheap as numeric ones.
+//! 
+//! [`concat_safearrays`] and [`append_safearray`] use the same copies to batch 
+//! the results of several COM calls into one array.
+//! 
+//! [`concat_safearrays`]: fn.concat_safearrays.html
+//! [`append_safearray`]: fn.append_safearray.html
+use std::mem;
+use std::ptr;
+
+use rust_decimal::Decimal;
+
+use winapi::ctypes::c_void;
+use winapi::shared::wtypes::{CY, DATE, DECIMAL, VARIANT_BOOL};
+use winapi::um::oaidl::SAFEARRAY;
+
+use super::array::{
+    check_elem_size, 
+    get_bounds, 
+    get_vartype, 
+    redim_vector, 
+    SafeArrayAccessData, 
+    SafeArrayCreateVector, 
+    SafeArrayDestructor, 
+    SafeArrayElement, 
+    SafeArrayGetDim, 
+    SafeArrayUnaccessData,
+};
+use super::errors::{FromSafeArrayError, FromSafeArrElemError, IntoSafeArrayError, IntoSafeArrElemError, SafeArrayError};
+use super::limits::DecodeLimits;
+use super::options::{ConvertOptions, FloatPolicy};
+use super::ptr::Ptr;
+use super::stats::ConversionStats;
+use super::types::{Currency, Date, DecWrapper, VariantBool};
+
+/// Number of elements `bulk_from_safearray_cancellable` copies between cancellation checks
+pub const BULK_CHUNK: usize = 64 * 1024;
+
+/// Element types stored in a SAFEARRAY as a plain array of `Raw` values
+/// 
+/// # Safety
+/// 
+/// The bulk functions reinterpret the data of a `SFTYPE` array as `[Raw]` without checking 
+/// more than its element size. `Raw` must have exactly the layout `SFTYPE` elements are stored 
+/// with, and any value such an array can hold must be a valid `Raw`. `PLAIN` may only be 
+/// `true` when `Self` and `Raw` are the same type, since elements are then copied as `Self` 
+/// without going through `from_raw`.
+pub unsafe trait BulkElement: SafeArrayElement + Copy {
+    /// The in-memory representation of one element
+    type Raw: Copy;
+    /// `Self` and `Raw` are the same type, so whole runs are moved with one `copy_nonoverlapping` 
+    /// instead of converting element by element. See the safety section above.
+    const PLAIN: bool = false;
+    /// Converts to the in-memory representation
+    fn into_raw(self) -> Self::Raw;
+    /// Converts from the in-memory representation
+    fn from_raw(raw: Self::Raw) -> Self;
+    /// The value as a float, checked against the `FloatPolicy` in force. `None` for non-float types.
+    fn as_float(self) -> Option<f64> {
+        None
+    }
+}
+
+macro_rules! bulk_impl {
+    ($t:ty) => {
+        bulk_impl!($t, $t, |v| v, |r| r, true);
+    };
+    ($t:ty, float) => {
+        unsafe impl BulkElement for $t {
+            type Raw = $t;
+            const PLAIN: bool = true;
+            fn into_raw(self) -> $t {
+                self
+            }
+            fn from_raw(raw: $t) -> $t {
+                raw
+            }
+            fn as_float(self) -> Option<f64> {
+                Some(f64::from(self))
+            }
+        }
+    };
+    ($t:ty, $raw:ty, $into:expr, $from:expr) => {
+        bulk_impl!($t, $raw, $into, $from, false);
+    };
+    ($t:ty, $raw:ty, $into:expr, $from:expr, $plain:expr) => {
+        unsafe impl BulkElement for $t {
+            type Raw = $raw;
+            const PLAIN: bool = $plain;
+            fn into_raw(self) -> $raw {
+                $into(self)
+            }
+            fn from_raw(raw: $raw) -> $t {
+                $from(raw)
+            }
+        }
+    };
+}
+
+bulk_impl!(i8);
+bulk_impl!(i16);
+bulk_impl!(i32);
+bulk_impl!(u8);
+bulk_impl!(u16);
+bulk_impl!(u32);
+bulk_impl!(f32, float);
+bulk_impl!(f64, float);
+bulk_impl!(bool, VARIANT_BOOL, |b| VARIANT_BOOL::from(VariantBool::from(b)), |r| bool::from(VariantBool::from(r)));
+bulk_impl!(Currency, CY, CY::from, Currency::from);
+bulk_impl!(Date, DATE, f64::from, Date::from);
+bulk_impl!(DecWrapper, DECIMAL, DECIMAL::from, DecWrapper::from);
+bulk_impl!(Decimal, DECIMAL, |d| DECIMAL::from(DecWrapper::new(d)), |r| Decimal::from(DecWrapper::from(r)));
+
+/// Creates a 1-D SAFEARRAY holding a copy of `items`, copied under a single `SafeArrayAccessData`
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{bulk_from_safearray, bulk_into_safearray, Currency};
+/// 
+/// fn main() {
+///     let prices = vec![Currency::from(12500), Currency::from(-300)];
+///     let psa = bulk_into_safearray(&prices).unwrap();
+///     assert_eq!(bulk_from_safearray::<Currency>(psa.as_ptr()).unwrap(), prices);
+/// }
+/// ```
+pub fn bulk_into_safearray<T: BulkElement>(items: &[T]) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+    if let Some((ix, value)) = non_finite(items) {
+        return Err(IntoSafeArrayError::from_element_err(IntoSafeArrElemError::NonFiniteFloat{value: value}, ix));
+    }
+    let psa = unsafe { SafeArrayCreateVector(T::SFTYPE as u16, 0, items.len() as u32) };
+    if psa.is_null() {
+        return Err(IntoSafeArrayError::SafeArrayCreateFailed);
+    }
+    let mut sad = SafeArrayDestructor::new(psa);
+    ConversionStats::record_safearray(items.len() * mem::size_of::<T::Raw>());
+
+    let mut data: *mut c_void = ptr::null_mut();
+    let hr = unsafe { SafeArrayAccessData(psa, &mut data) };
+    if hr != 0 {
+        return Err(IntoSafeArrayError::AccessDataFailed{hr: hr});
+    }
+    let data = data as *mut T::Raw;
+    if T::PLAIN {
+        unsafe { ptr::copy_nonoverlapping(items.as_ptr() as *const T::Raw, data, items.len()) };
+    } else {
+        for (ix, item) in items.iter().enumerate() {
+            unsafe { ptr::write(data.add(ix), item.into_raw()) };
+        }
+    }
+    unsafe { SafeArrayUnaccessData(psa) };
+    sad.inner = ptr::null_mut();
+    Ok(Ptr::with_checked(psa).unwrap())
+}
+
+/// Copies the contents of a 1-D SAFEARRAY of `T` under a single `SafeArrayAccessData`. 
+/// The array is released afterwards.
+pub fn bulk_from_safearray<T: BulkElement>(psa: *mut SAFEARRAY) -> Result<Vec<T>, FromSafeArrayError> {
+    bulk_from_safearray_cancellable(psa, |_, _| false)
+}
+
+/// Like `bulk_from_safearray`, but copies in chunks of `BULK_CHUNK` elements and calls 
+/// `cancelled(done, total)` before each one. When it returns `true` the copy stops with 
+/// `FromSafeArrayError::Cancelled`; the array is still released. 
+/// 
+/// `cancelled` can poll an `AtomicBool` set by a UI thread, or report progress.
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use oaidl::{bulk_from_safearray_cancellable, bulk_into_safearray, FromSafeArrayError};
+/// 
+/// fn main() {
+///     let stop = AtomicBool::new(true);
+///     let psa = bulk_into_safearray(&vec![0f64; 100_000]).unwrap();
+///     let res = bulk_from_safearray_cancellable::<f64, _>(psa.as_ptr(), |_, _| stop.load(Ordering::Relaxed));
+///     match res {
+///         Err(FromSafeArrayError::Cancelled{done, ..}) => assert_eq!(done, 0),
+///         _ => panic!("expected cancellation"),
+///     }
+/// }
+/// ```
+pub fn bulk_from_safearray_cancellable<T, F>(psa: *mut SAFEARRAY, mut cancelled: F) -> Result<Vec<T>, FromSafeArrayError> 
+    where T: BulkElement, 
+          F: FnMut(usize, usize) -> bool
+{
+    if psa.is_null() {
+        return Err(FromSafeArrayError::SafeArrayPtrNull);
+    }
+    let _sad = SafeArrayDestructor::new(psa);
+    let (_, len) = vector_bounds::<T>(psa)?;
+    DecodeLimits::check_elements(len)?;
+
+    let mut data: *mut c_void = ptr::null_mut();
+    let hr = unsafe { SafeArrayAccessData(psa, &mut data) };
+    if hr != 0 {
+        return Err(FromSafeArrayError::AccessDataFailed{hr: hr});
+    }
+    let data = data as *const T::Raw;
+    let mut items: Vec<T> = Vec::with_capacity(len);
+    while items.len() < len {
+        let done = items.len();
+        if cancelled(done, len) {
+            unsafe { SafeArrayUnaccessData(psa) };
+            return Err(FromSafeArrayError::Cancelled{done: done, total: len});
+        }
+        let end = (done + BULK_CHUNK).min(len);
+        if T::PLAIN {
+            unsafe {
+                ptr::copy_nonoverlapping(data.add(done) as *const T, items.as_mut_ptr().add(done), end - done);
+                items.set_len(end);
+            }
+        } else {
+            items.extend((done..end).map(|ix| T::from_raw(unsafe { ptr::read(data.add(ix)) })));
+        }
+        if let Some((ix, value)) = non_finite(&items[done..]) {
+            unsafe { SafeArrayUnaccessData(psa) };
+            return Err(FromSafeArrayError::from_element_err(FromSafeArrElemError::NonFiniteFloat{value: value}, done + ix));
+        }
+    }
+    unsafe { SafeArrayUnaccessData(psa) };
+    Ok(items)
+}
+
+/// The first float in `items` the `FloatPolicy` in force rejects, with its index
+fn non_finite<T: BulkElement>(items: &[T]) -> Option<(usize, f64)> {
+    if ConvertOptions::current().float_policy == FloatPolicy::Allow {
+        return None;
+    }
+    items.iter().enumerate()
+         .filter_map(|(ix, item)| item.as_float().map(|value| (ix, value)))
+         .find(|&(_, value)| !value.is_finite())
+}
+
+/// Creates a new 1-D SAFEARRAY holding the elements of `a` followed by those of `b`. 
+/// Both must be 1-D arrays of `T`. Neither is modified or released.
+pub fn concat_safearrays<T: BulkElement>(a: *mut SAFEARRAY, b: *mut SAFEARRAY) -> Result<Ptr<SAFEARRAY>, SafeArrayError> {
+    for &psa in &[a, b] {
+        if psa.is_null() {
+            return Err(FromSafeArrayError::SafeArrayPtrNull.into());
+        }
+    }
+    let (_, a_len) = vector_bounds::<T>(a)?;
+    let (_, b_len) = vector_bounds::<T>(b)?;
+
+    let psa = unsafe { SafeArrayCreateVector(T::SFTYPE as u16, 0, (a_len + b_len) as u32) };
+    if psa.is_null() {
+        return Err(IntoSafeArrayError::SafeArrayCreateFailed.into());
+    }
+    let mut sad = SafeArrayDestructor::new(psa);
+    ConversionStats::record_safearray((a_len + b_len) * mem::size_of::<T::Raw>());
+
+    copy_raw::<T>(a, psa, 0, a_len)?;
+    copy_raw::<T>(b, psa, a_len, b_len)?;
+    sad.inner = ptr::null_mut();
+    Ok(Ptr::with_checked(psa).unwrap())
+}
+
+/// Extends the 1-D SAFEARRAY of `T` at `psa` in place with `items`, keeping its lower bound. 
+/// The array must not be fixed-size or locked, as `SafeArrayRedim` requires.
+pub fn append_safearray<T, I>(psa: *mut SAFEARRAY, items: I) -> Result<(), SafeArrayError> 
+    where T: BulkElement, 
+          I: IntoIterator<Item=T>
+{
+    if psa.is_null() {
+        return Err(FromSafeArrayError::SafeArrayPtrNull.into());
+    }
+    let (l_bound, len) = vector_bounds::<T>(psa)?;
+    let raw: Vec<T::Raw> = items.into_iter().map(T::into_raw).collect();
+    if raw.is_empty() {
+        return Ok(());
+    }
+
+    redim_vector(psa, l_bound, len + raw.len())?;
+
+    let mut data: *mut c_void = ptr::null_mut();
+    let hr = unsafe { SafeArrayAccessData(psa, &mut data) };
+    if hr != 0 {
+        redim_vector(psa, l_bound, len)?;
+        return Err(IntoSafeArrayError::AccessDataFailed{hr: hr}.into());
+    }
+    unsafe {
+        ptr::copy_nonoverlapping(raw.as_ptr(), (data as *mut T::Raw).add(len), raw.len());
+        SafeArrayUnaccessData(psa);
+    }
+    Ok(())
+}
+
+/// Checks that `psa` is a 1-D array of `T` with `T::Raw`-sized elements and returns its lower bound and length
+fn vector_bounds<T: BulkElement>(psa: *mut SAFEARRAY) -> Result<(i32, usize), FromSafeArrayError> {
+    let sa_dims = unsafe { SafeArrayGetDim(psa) };
+    if sa_dims != 1 {
+        return Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims});
+    }
+    let vt = get_vartype(psa)?;
+    if vt != T::SFTYPE {
+        return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: T::SFTYPE, found: vt});
+    }
+    check_elem_size(psa, mem::size_of::<T::Raw>())?;
+    let (l_bound, r_bound) = get_bounds(psa, 1)?;
+    Ok((l_bound, (r_bound - l_bound + 1).max(0) as usize))
+}
+
+/// Copies `len` raw elements from the start of `src` to `dest` at `offset`
+fn copy_raw<T: BulkElement>(src: *mut SAFEARRAY, dest: *mut SAFEARRAY, offset: usize, len: usize) -> Result<(), SafeArrayError> {
+    let mut src_data: *mut c_void = ptr::null_mut();
+    let hr = unsafe { SafeArrayAccessData(src, &mut src_data) };
+    if hr != 0 {
+        return Err(FromSafeArrayError::AccessDataFailed{hr: hr}.into());
+    }
+    let mut dest_data: *mut c_void = ptr::null_mut();
+    let hr = unsafe { SafeArrayAccessData(dest, &mut dest_data) };
+    if hr != 0 {
+        unsafe { SafeArrayUnaccessData(src) };
+        return Err(IntoSafeArrayError::AccessDataFailed{hr: hr}.into());
+    }
+    unsafe {
+        ptr::copy_nonoverlapping(src_data as *const T::Raw, (dest_data as *mut T::Raw).add(offset), len);
+        SafeArrayUnaccessData(dest);
+        SafeArrayUnaccessData(src);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::SafeArrayExt;
+    #[test]
+    fn test_bulk_date() {
+        let dates = vec![Date::from(43000.25), Date::from(-1.5)];
+        let psa = bulk_into_safearray(&dates).unwrap();
+        assert_eq!(bulk_from_safearray::<Date>(psa.as_ptr()).unwrap(), dates);
+    }
+
+    #[test]
+    fn test_bulk_decimal_matches_element_path() {
+        let decs = vec![Decimal::new(12345, 2), Decimal::new(-1, 0)];
+        let psa = bulk_into_safearray(&decs).unwrap();
+        let v = ExactSizeIterator::<Item=Decimal>::from_safearray(psa.as_ptr()).unwrap();
+        assert_eq!(v, decs);
+
+        let psa = decs.clone().into_iter().into_safearray().unwrap();
+        assert_eq!(bulk_from_safearray::<Decimal>(psa.as_ptr()).unwrap(), decs);
+    }
+
+    #[test]
+    fn test_bulk_plain() {
+        let v: Vec<u16> = (0..1000).collect();
+        let psa = bulk_into_safearray(&v).unwrap();
+        let back = ExactSizeIterator::<Item=u16>::from_safearray(psa.as_ptr()).unwrap();
+        assert_eq!(back, v);
+
+        let f = vec![1.5f32, -0.25, 3e10];
+        let psa = f.clone().into_iter().into_safearray().unwrap();
+        assert_eq!(bulk_from_safearray::<f32>(psa.as_ptr()).unwrap(), f);
+    }
+
+    #[test]
+    fn test_bulk_bool() {
+        let mask = vec![true, false, false, true];
+        let psa = bulk_into_safearray(&mask).unwrap();
+        let v = ExactSizeIterator::<Item=bool>::from_safearray(psa.as_ptr()).unwrap();
+        assert_eq!(v, mask);
+
+        let psa = mask.clone().into_iter().into_safearray().unwrap();
+        assert_eq!(bulk_from_safearray::<bool>(psa.as_ptr()).unwrap(), mask);
+    }
+
+    #[test]
+    fn test_concat_append() {
+        let a = bulk_into_safearray(&[1i32, 2]).unwrap();
+        let b = bulk_into_safearray(&[3i32]).unwrap();
+        let c = concat_safearrays::<i32>(a.as_ptr(), b.as_ptr()).unwrap();
+        append_safearray(c.as_ptr(), vec![4i32, 5]).unwrap();
+        assert_eq!(bulk_from_safearray::<i32>(c.as_ptr()).unwrap(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(bulk_from_safearray::<i32>(a.as_ptr()).unwrap(), vec![1, 2]);
+
+        let d = bulk_into_safearray(&[1.0f64]).unwrap();
+        match concat_safearrays::<i32>(b.as_ptr(), d.as_ptr()) {
+            Err(SafeArrayError::From(ref e)) => match **e {
+                FromSafeArrayError::VarTypeDoesNotMatch{..} => {}, 
+                _ => panic!("expected a vartype mismatch"),
+            }, 
+            _ => panic!("expected a vartype mismatch"),
+        }
+        bulk_from_safearray::<i32>(b.as_ptr()).unwrap();
+        bulk_from_safearray::<f64>(d.as_ptr()).unwrap();
+    }
+
+    #[test]
+    fn test_bulk_vartype_mismatch() {
+        let psa = bulk_into_safearray(&[1i32]).unwrap();
+        match bulk_from_safearray::<Currency>(psa.as_ptr()) {
+            Err(FromSafeArrayError::VarTypeDoesNotMatch{..}) => {}, 
+            _ => panic!("expected a vartype mismatch"),
+        };
+    }
+
+    #[test]
+    fn test_cancel() {
+        let len = BULK_CHUNK * 2 + 5;
+        let psa = bulk_into_safearray(&vec![3i32; len]).unwrap();
+        let mut calls = Vec::new();
+        let res = bulk_from_safearray_cancellable::<i32, _>(psa.as_ptr(), |done, total| {
+            calls.push(done);
+            assert_eq!(total, len);
+            done > 0
+        });
+        match res {
+            Err(FromSafeArrayError::Cancelled{done, total}) => assert_eq!((done, total), (BULK_CHUNK, len)),
+            _ => panic!("expected cancellation"),
+        }
+        assert_eq!(calls, vec![0, BULK_CHUNK]);
+
+        let psa = bulk_into_safearray(&vec![3i32; len]).unwrap();
+        let out = bulk_from_safearray_cancellable::<i32, _>(psa.as_ptr(), |_, _| false).unwrap();
+        assert_eq!(out.len(), len);
+    }
+}
@@ -0,0 +1,282 @@
+//! # ByRef
+//! Borrowed `VT_BYREF` variants for out-parameters. 
+//! 
+//! The `Box<T>` impls of `VariantExt` allocate a fresh heap cell for every 
+//! byref VARIANT. [`ByRefExt::byref_variant`] instead points the VARIANT at an 
+//! existing `&mut T`, so the callee writes straight into the caller's value. 
+//! The returned [`ByRefVariant`] borrows the target, so the VARIANT cannot 
+//! outlive it. 
+//! 
+//! Types whose Rust layout differs from the OLE one (`bool`, `String`, 
+//! `U16String`) go through a staging slot that is written back to the target 
+//! when the `ByRefVariant` is dropped. 
+//! 
+//! [`ByRefExt::byref_variant`]: trait.ByRefExt.html#tymethod.byref_variant
+//! [`ByRefVariant`]: struct.ByRefVariant.html
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr::null_mut;
+
+use widestring::U16String;
+
+use winapi::shared::wtypes::{
+    VARIANT_BOOL, 
+    VT_BOOL, 
+    VT_BSTR, 
+    VT_BYREF, 
+    VT_I1, 
+    VT_I2, 
+    VT_I4, 
+    VT_I8, 
+    VT_R4, 
+    VT_R8, 
+    VT_UI1, 
+    VT_UI2, 
+    VT_UI4, 
+    VT_UI8,
+};
+use winapi::um::oaidl::VARIANT;
+use winapi::um::oleauto::SysFreeString;
+
+use super::bstr::BStringExt;
+use super::errors::{FromVariantError, IntoVariantError};
+use super::limits::DecodeLimits;
+use super::options::ConvertOptions;
+use super::ptr::Ptr;
+use super::types::VariantBool;
+
+/// A `VT_BYREF` VARIANT pointing into a borrowed value
+pub struct ByRefVariant<'a> {
+    var: Box<VARIANT>, 
+    stage: Stage<'a>, 
+    _marker: PhantomData<&'a mut ()>,
+}
+
+enum Stage<'a> {
+    Direct, 
+    Bool(&'a mut bool, Box<VARIANT_BOOL>), 
+    BStr(&'a mut U16String, Box<*mut u16>), 
+    String(&'a mut String, Box<*mut u16>),
+}
+
+impl<'a> ByRefVariant<'a> {
+    fn new(vt: u32, stage: Stage<'a>, set: &dyn Fn(&mut VARIANT)) -> ByRefVariant<'a> {
+        let mut var: Box<VARIANT> = Box::new(unsafe { mem::zeroed() });
+        unsafe { var.n1.n2_mut().vt = (VT_BYREF | vt) as u16 };
+        set(&mut var);
+        ByRefVariant { var: var, stage: stage, _marker: PhantomData }
+    }
+
+    /// Pointer to hand to the callee. Valid for as long as `self` is alive.
+    pub fn as_ptr(&mut self) -> *mut VARIANT {
+        &mut *self.var
+    }
+
+    /// The vartype of the VARIANT, including `VT_BYREF`
+    pub fn vartype(&self) -> u32 {
+        unsafe { self.var.n1.n2().vt as u32 }
+    }
+}
+
+impl<'a> Drop for ByRefVariant<'a> {
+    fn drop(&mut self) {
+        match self.stage {
+            Stage::Direct => {}, 
+            Stage::Bool(ref mut target, ref slot) => {
+                **target = bool::from(VariantBool::from(**slot));
+            }, 
+            Stage::BStr(ref mut target, ref mut slot) => {
+                if !slot.is_null() {
+                    **target = U16String::from_bstr(**slot);
+                    unsafe { SysFreeString(**slot) };
+                    **slot = null_mut();
+                }
+            }, 
+            Stage::String(ref mut target, ref mut slot) => {
+                if !slot.is_null() {
+                    **target = U16String::from_bstr(**slot).to_string_lossy();
+                    unsafe { SysFreeString(**slot) };
+                    **slot = null_mut();
+                }
+            },
+        }
+    }
+}
+
+/// Implemented on values that can be passed by reference through a `VT_BYREF` VARIANT
+pub trait ByRefExt {
+    /// Creates a byref VARIANT pointing at `self`
+    fn byref_variant<'a>(&'a mut self) -> Result<ByRefVariant<'a>, IntoVariantError>;
+
+    /// Copies the pointee of a byref VARIANT - such as one built from `Box<T>` with 
+    /// `into_variant` and passed to an FFI call - back into `self`. The VARIANT is 
+    /// left untouched and still owned by the caller.
+    fn read_back(&mut self, var: Ptr<VARIANT>) -> Result<(), FromVariantError>;
+}
+
+/// Checks `var` is `VT_BYREF | vt` and returns its pointee pointer
+fn byref_target<T>(var: Ptr<VARIANT>, vt: u32, get: &dyn Fn(&VARIANT) -> *mut T) -> Result<*mut T, FromVariantError> {
+    let var = unsafe { var.as_ref() };
+    let found = unsafe { var.n1.n2().vt } as u32;
+    if found != VT_BYREF | vt {
+        return Err(FromVariantError::VarTypeDoesNotMatch{expected: VT_BYREF | vt, found: found});
+    }
+    let p = get(var);
+    if p.is_null() {
+        return Err(FromVariantError::ByRefPtrNull);
+    }
+    Ok(p)
+}
+
+macro_rules! byref_impl {
+    (@check float, $val:expr) => {
+        ConvertOptions::check_float(f64::from($val)).map_err(|v| FromVariantError::NonFiniteFloat{value: v})?;
+    };
+    (@check, $val:expr) => {};
+    ($t:ty, $vt:expr, $field:ident, $field_mut:ident $(, $kind:ident)*) => {
+        impl ByRefExt for $t {
+            fn byref_variant<'a>(&'a mut self) -> Result<ByRefVariant<'a>, IntoVariantError> {
+                let p = self as *mut $t as *mut _;
+                Ok(ByRefVariant::new($vt, Stage::Direct, &|var: &mut VARIANT| unsafe {
+                    *var.n1.n2_mut().n3.$field_mut() = p;
+                }))
+            }
+
+            fn read_back(&mut self, var: Ptr<VARIANT>) -> Result<(), FromVariantError> {
+                let p = byref_target(var, $vt, &|var: &VARIANT| unsafe { *var.n1.n2().n3.$field() })?;
+                let val = unsafe { *p };
+                byref_impl!(@check $($kind)*, val);
+                *self = val;
+                Ok(())
+            }
+        }
+    };
+}
+
+byref_impl!(i8, VT_I1, pcVal, pcVal_mut);
+byref_impl!(i16, VT_I2, piVal, piVal_mut);
+byref_impl!(i32, VT_I4, plVal, plVal_mut);
+byref_impl!(i64, VT_I8, pllVal, pllVal_mut);
+byref_impl!(u8, VT_UI1, pbVal, pbVal_mut);
+byref_impl!(u16, VT_UI2, puiVal, puiVal_mut);
+byref_impl!(u32, VT_UI4, pulVal, pulVal_mut);
+byref_impl!(u64, VT_UI8, pullVal, pullVal_mut);
+byref_impl!(f32, VT_R4, pfltVal, pfltVal_mut, float);
+byref_impl!(f64, VT_R8, pdblVal, pdblVal_mut, float);
+
+impl ByRefExt for bool {
+    fn byref_variant<'a>(&'a mut self) -> Result<ByRefVariant<'a>, IntoVariantError> {
+        let mut slot = Box::new(VARIANT_BOOL::from(VariantBool::from(*self)));
+        let p: *mut VARIANT_BOOL = &mut *slot;
+        Ok(ByRefVariant::new(VT_BOOL, Stage::Bool(self, slot), &|var: &mut VARIANT| unsafe {
+            *var.n1.n2_mut().n3.pboolVal_mut() = p;
+        }))
+    }
+
+    fn read_back(&mut self, var: Ptr<VARIANT>) -> Result<(), FromVariantError> {
+        let p = byref_target(var, VT_BOOL, &|var: &VARIANT| unsafe { *var.n1.n2().n3.pboolVal() })?;
+        *self = bool::from(VariantBool::from(unsafe { *p }));
+        Ok(())
+    }
+}
+
+impl ByRefExt for U16String {
+    fn byref_variant<'a>(&'a mut self) -> Result<ByRefVariant<'a>, IntoVariantError> {
+        let bstr = self.clone().allocate_bstr()?;
+        let mut slot = Box::new(bstr.as_ptr());
+        let p: *mut *mut u16 = &mut *slot;
+        Ok(ByRefVariant::new(VT_BSTR, Stage::BStr(self, slot), &|var: &mut VARIANT| unsafe {
+            *var.n1.n2_mut().n3.pbstrVal_mut() = p;
+        }))
+    }
+
+    fn read_back(&mut self, var: Ptr<VARIANT>) -> Result<(), FromVariantError> {
+        *self = read_back_bstr(var)?;
+        Ok(())
+    }
+}
+
+impl ByRefExt for String {
+    fn byref_variant<'a>(&'a mut self) -> Result<ByRefVariant<'a>, IntoVariantError> {
+        let bstr = U16String::from_str(self).allocate_bstr()?;
+        let mut slot = Box::new(bstr.as_ptr());
+        let p: *mut *mut u16 = &mut *slot;
+        Ok(ByRefVariant::new(VT_BSTR, Stage::String(self, slot), &|var: &mut VARIANT| unsafe {
+            *var.n1.n2_mut().n3.pbstrVal_mut() = p;
+        }))
+    }
+
+    fn read_back(&mut self, var: Ptr<VARIANT>) -> Result<(), FromVariantError> {
+        *self = read_back_bstr(var)?.to_string_lossy();
+        Ok(())
+    }
+}
+
+// A null BSTR is the empty string
+fn read_back_bstr(var: Ptr<VARIANT>) -> Result<U16String, FromVariantError> {
+    let p = byref_target(var, VT_BSTR, &|var: &VARIANT| unsafe { *var.n1.n2().n3.pbstrVal() })?;
+    let bstr = unsafe { *p };
+    if bstr.is_null() {
+        return Ok(U16String::new());
+    }
+    DecodeLimits::check_bstr(bstr)?;
+    Ok(U16String::from_bstr(bstr))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_direct() {
+        let mut n = 5i32;
+        {
+            let mut var = n.byref_variant().unwrap();
+            assert_eq!(var.vartype(), VT_BYREF | VT_I4);
+            let pvar = var.as_ptr();
+            unsafe { **(*pvar).n1.n2_mut().n3.plVal_mut() = 42 };
+        }
+        assert_eq!(n, 42);
+    }
+
+    #[test]
+    fn test_staged() {
+        let mut b = false;
+        let mut s = String::from("in");
+        {
+            let mut bvar = b.byref_variant().unwrap();
+            unsafe { **(*bvar.as_ptr()).n1.n2_mut().n3.pboolVal_mut() = -1 };
+            let mut svar = s.byref_variant().unwrap();
+            let pvar = svar.as_ptr();
+            unsafe {
+                let slot = *(*pvar).n1.n2_mut().n3.pbstrVal_mut();
+                SysFreeString(*slot);
+                *slot = U16String::from_str("out").allocate_bstr().unwrap().as_ptr();
+            }
+        }
+        assert_eq!(b, true);
+        assert_eq!(s, "out");
+    }
+
+    #[test]
+    fn test_read_back() {
+        use super::super::variant::VariantExt;
+        let pvar = Box::new(7i32).into_variant().unwrap();
+        unsafe { **(*pvar.as_ptr()).n1.n2_mut().n3.plVal_mut() = 99 };
+        let mut n = 7i32;
+        n.read_back(pvar).unwrap();
+        assert_eq!(n, 99);
+        let mut b = false;
+        match b.read_back(pvar) {
+            Err(FromVariantError::VarTypeDoesNotMatch{expected, found}) => {
+                assert_eq!((expected, found), (VT_BYREF | VT_BOOL, VT_BYREF | VT_I4));
+            }, 
+            _ => panic!("expected vartype mismatch"),
+        }
+        drop(Box::<i32>::from_variant(pvar).unwrap());
+
+        let pvar = Box::new(String::from("in")).into_variant().unwrap();
+        let mut s = String::new();
+        s.read_back(pvar).unwrap();
+        assert_eq!(s, "in");
+    }
+}
@@ -0,0 +1,111 @@
+//! # Bytes
+//! Binary payloads as `VT_ARRAY | VT_UI1` variants. 
+//! 
+//! A byte SAFEARRAY is the standard way to move binary data through automation. 
+//! These helpers copy the whole buffer in one go through `SafeArrayAccessData` 
+//! rather than putting/getting one element at a time.
+use std::mem;
+
+use winapi::shared::wtypes::{VT_ARRAY, VT_EMPTY, VT_UI1};
+use winapi::um::oaidl::{SAFEARRAY, VARIANT, VARIANT_n3};
+
+use super::bulk::{bulk_from_safearray, bulk_into_safearray};
+use super::errors::{FromSafeArrayError, FromVariantError, IntoSafeArrayError, IntoVariantError};
+use super::ptr::Ptr;
+use super::variant::new_variant;
+
+const VT_ARRAY_UI1: u32 = VT_ARRAY | VT_UI1;
+
+/// Converts `bytes` into a `VT_ARRAY | VT_UI1` VARIANT
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{bytes_to_variant, variant_to_bytes};
+/// 
+/// fn main() {
+///     let pvar = bytes_to_variant(b"\x89PNG").unwrap();
+///     assert_eq!(variant_to_bytes(pvar).unwrap(), b"\x89PNG".to_vec());
+/// }
+/// ```
+pub fn bytes_to_variant(bytes: &[u8]) -> Result<Ptr<VARIANT>, IntoVariantError> {
+    let psa = bytes_into_safearray(bytes)?;
+    let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
+    unsafe {
+        let n_ptr = n3.parray_mut();
+        *n_ptr = psa.as_ptr();
+    }
+    Ok(new_variant(VT_ARRAY_UI1, n3))
+}
+
+/// Copies the bytes out of a `VT_ARRAY | VT_UI1` VARIANT. The array is released afterwards.
+pub fn variant_to_bytes(var: Ptr<VARIANT>) -> Result<Vec<u8>, FromVariantError> {
+    let pvar = var.as_ptr();
+    let mut n1 = unsafe {(*pvar).n1};
+    let vt = unsafe {n1.n2()}.vt as u32;
+    if vt != VT_ARRAY_UI1 {
+        return Err(FromVariantError::VarTypeDoesNotMatch{expected: VT_ARRAY_UI1, found: vt});
+    }
+    let psa = unsafe { *n1.n2_mut().n3.parray() };
+    if psa.is_null() {
+        return Err(FromVariantError::ArrayPtrNull);
+    }
+    let bytes = bytes_from_safearray(psa)?;
+    //the array is gone, don't leave a dangling parray behind
+    unsafe { (*pvar).n1.n2_mut().vt = VT_EMPTY as u16 };
+    Ok(bytes)
+}
+
+/// Creates a 1-D `VT_UI1` SAFEARRAY holding a copy of `bytes`. 
+/// 
+/// The buffer is copied in with one `memcpy` under `SafeArrayAccessData`, where 
+/// `into_safearray` on a `Vec<u8>` would put each byte separately. 
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{bytes_from_safearray, bytes_into_safearray};
+/// 
+/// fn main() {
+///     let blob = vec![0xffu8; 1 << 20];
+///     let psa = bytes_into_safearray(&blob).unwrap();
+///     assert_eq!(bytes_from_safearray(psa.as_ptr()).unwrap(), blob);
+/// }
+/// ```
+pub fn bytes_into_safearray(bytes: &[u8]) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+    bulk_into_safearray(bytes)
+}
+
+/// Copies the contents of a 1-D `VT_UI1` SAFEARRAY with one `memcpy`. The array is released afterwards.
+pub fn bytes_from_safearray(psa: *mut SAFEARRAY) -> Result<Vec<u8>, FromSafeArrayError> {
+    bulk_from_safearray(psa)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::SafeArrayExt;
+    #[test]
+    fn test_roundtrip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let pvar = bytes_to_variant(&bytes).unwrap();
+        assert_eq!(variant_to_bytes(pvar).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_empty() {
+        let pvar = bytes_to_variant(&[]).unwrap();
+        assert_eq!(variant_to_bytes(pvar).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_matches_element_path() {
+        let psa = bytes_into_safearray(&[1, 2, 3]).unwrap();
+        let v = ExactSizeIterator::<Item=u8>::from_safearray(psa.as_ptr()).unwrap();
+        assert_eq!(v, vec![1, 2, 3]);
+    }
+}
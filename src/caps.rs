@@ -0,0 +1,172 @@
+//! # Capabilities
+//! Runtime detection of optional OleAut32 entry points. 
+//! 
+//! Some OleAut32 functions only exist on newer versions of Windows. Rather than 
+//! linking them statically (and failing to load on older servers), they are 
+//! looked up with `GetProcAddress` the first time they're needed. 
+//! [`capabilities`] reports what was found; functions built on them fall back 
+//! to older equivalents when an entry point is missing.
+//! 
+//! [`capabilities`]: fn.capabilities.html
+use std::mem;
+use std::sync::Once;
+
+use winapi::ctypes::c_void;
+use winapi::shared::minwindef::HMODULE;
+use winapi::shared::ntdef::HRESULT;
+use winapi::um::libloaderapi::{GetModuleHandleW, GetProcAddress, LoadLibraryW};
+use winapi::um::oaidl::SAFEARRAY;
+
+use super::array::{SafeArrayLock, SafeArrayUnlock};
+use super::errors::FromSafeArrayError;
+use super::ptr::Ptr;
+
+type SafeArrayAddRefFn = unsafe extern "system" fn(psa: *mut SAFEARRAY, data_to_release: *mut *mut c_void) -> HRESULT;
+type SafeArrayReleaseDataFn = unsafe extern "system" fn(data: *mut c_void);
+type SafeArrayReleaseDescriptorFn = unsafe extern "system" fn(psa: *mut SAFEARRAY);
+
+/// Which optional OleAut32 entry points are available in this process
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct OleAutCapabilities {
+    /// `SafeArrayAddRef` (Windows 8 and later)
+    pub safearray_add_ref: bool, 
+    /// `SafeArrayReleaseData` (Windows 8 and later)
+    pub safearray_release_data: bool, 
+    /// `SafeArrayReleaseDescriptor` (Windows 8 and later)
+    pub safearray_release_descriptor: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Entries {
+    safearray_add_ref: Option<SafeArrayAddRefFn>, 
+    safearray_release_data: Option<SafeArrayReleaseDataFn>, 
+    safearray_release_descriptor: Option<SafeArrayReleaseDescriptorFn>,
+}
+
+static INIT: Once = Once::new();
+static mut ENTRIES: Entries = Entries {
+    safearray_add_ref: None, 
+    safearray_release_data: None, 
+    safearray_release_descriptor: None,
+};
+
+fn entries() -> Entries {
+    INIT.call_once(|| {
+        let module = oleaut32();
+        if module.is_null() {
+            return;
+        }
+        unsafe {
+            ENTRIES = Entries {
+                safearray_add_ref: lookup(module, b"SafeArrayAddRef\0").map(|p| mem::transmute(p)), 
+                safearray_release_data: lookup(module, b"SafeArrayReleaseData\0").map(|p| mem::transmute(p)), 
+                safearray_release_descriptor: lookup(module, b"SafeArrayReleaseDescriptor\0").map(|p| mem::transmute(p)),
+            };
+        }
+    });
+    unsafe { ENTRIES }
+}
+
+fn oleaut32() -> HMODULE {
+    let name: Vec<u16> = "oleaut32.dll\0".encode_utf16().collect();
+    unsafe {
+        let module = GetModuleHandleW(name.as_ptr());
+        if module.is_null() {
+            LoadLibraryW(name.as_ptr())
+        } else {
+            module
+        }
+    }
+}
+
+fn lookup(module: HMODULE, name: &[u8]) -> Option<*mut c_void> {
+    let p = unsafe { GetProcAddress(module, name.as_ptr() as *const i8) };
+    if p.is_null() {
+        None
+    } else {
+        Some(p as *mut c_void)
+    }
+}
+
+/// Reports the optional OleAut32 entry points found in this process. 
+/// The lookup happens once; later calls are cheap.
+pub fn capabilities() -> OleAutCapabilities {
+    let e = entries();
+    OleAutCapabilities {
+        safearray_add_ref: e.safearray_add_ref.is_some(), 
+        safearray_release_data: e.safearray_release_data.is_some(), 
+        safearray_release_descriptor: e.safearray_release_descriptor.is_some(),
+    }
+}
+
+/// Keeps the data of a SAFEARRAY alive until dropped. 
+/// 
+/// Uses `SafeArrayAddRef` where available, so the array may still be destroyed by 
+/// its owner while pinned, and releases the data and descriptor references it took 
+/// when dropped. On older systems it falls back to `SafeArrayLock`/`SafeArrayUnlock`.
+#[derive(Debug)]
+pub struct SafeArrayPin {
+    psa: *mut SAFEARRAY, 
+    data: Option<*mut c_void>,
+}
+
+impl SafeArrayPin {
+    /// Pins `psa`
+    pub fn new(psa: Ptr<SAFEARRAY>) -> Result<SafeArrayPin, FromSafeArrayError> {
+        let psa = psa.as_ptr();
+        let e = entries();
+        if let (Some(add_ref), Some(_), Some(_)) = (e.safearray_add_ref, e.safearray_release_data, e.safearray_release_descriptor) {
+            let mut data: *mut c_void = ::std::ptr::null_mut();
+            let hr = unsafe { add_ref(psa, &mut data) };
+            if hr != 0 {
+                return Err(FromSafeArrayError::LockFailed{hr: hr});
+            }
+            return Ok(SafeArrayPin { psa: psa, data: Some(data) });
+        }
+        let hr = unsafe { SafeArrayLock(psa) };
+        if hr != 0 {
+            return Err(FromSafeArrayError::LockFailed{hr: hr});
+        }
+        Ok(SafeArrayPin { psa: psa, data: None })
+    }
+}
+
+impl Drop for SafeArrayPin {
+    fn drop(&mut self) {
+        match self.data {
+            Some(data) => {
+                let e = entries();
+                //data that isn't reference counted comes back null, only the descriptor was AddRef'd
+                if !data.is_null() {
+                    if let Some(release) = e.safearray_release_data {
+                        unsafe { release(data) };
+                    }
+                }
+                if let Some(release) = e.safearray_release_descriptor {
+                    unsafe { release(self.psa) };
+                }
+            }, 
+            None => {
+                unsafe { SafeArrayUnlock(self.psa) };
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::{SafeArrayDestructor, SafeArrayExt};
+    #[test]
+    fn test_capabilities_stable() {
+        assert_eq!(capabilities(), capabilities());
+    }
+
+    #[test]
+    fn test_pin() {
+        let psa = vec![1i32, 2].into_iter().into_safearray().unwrap();
+        let _sad = SafeArrayDestructor::new(psa.as_ptr());
+        let pin = SafeArrayPin::new(Ptr::with_checked(psa.as_ptr()).unwrap()).unwrap();
+        drop(pin);
+    }
+}
@@ -0,0 +1,180 @@
+//! # Classify
+//! Reverse lookup from a VARIANT to a description of what it holds.
+//!
+//! [`classify`] reads only the tag and, for arrays and records, the descriptor
+//! behind it - it never decodes or frees the payload. The result is meant for
+//! logging and for routing payloads of unknown shape to the typed decoder that
+//! matches them.
+//!
+//! [`classify`]: fn.classify.html
+use std::ptr::null_mut;
+
+use winapi::shared::guiddef::GUID;
+use winapi::shared::wtypes::{
+    VT_ARRAY,
+    VT_BYREF,
+    VT_DISPATCH,
+    VT_EMPTY,
+    VT_NULL,
+    VT_RECORD,
+    VT_TYPEMASK,
+    VT_UNKNOWN,
+    VT_VARIANT,
+};
+use winapi::um::oaidl::{IRecordInfo, VARIANT};
+
+use super::array::{get_vartype, SafeArrayGetDim, SafeArrayGetRecordInfo};
+use super::meta::VT_TABLE;
+use super::ptr::Ptr;
+
+/// Broad category of the value a VARIANT holds
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum VariantKind {
+    /// `VT_EMPTY`
+    Empty,
+    /// `VT_NULL`
+    Null,
+    /// A number, string, date, currency, boolean, error code or decimal
+    Scalar,
+    /// `VT_UNKNOWN` or `VT_DISPATCH`
+    Interface,
+    /// `VT_RECORD`, a user-defined type
+    Record,
+    /// `VT_VARIANT` - only valid by reference or as an array element type
+    Variant,
+    /// Any other `VARTYPE`
+    Other,
+}
+
+/// Structured description of a VARIANT, as returned by [`classify`](fn.classify.html)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct VariantClass {
+    /// The full `vt` tag, including the `VT_BYREF` and `VT_ARRAY` flags
+    pub vartype: u32,
+    /// The tag without flags
+    pub base: u32,
+    /// Category of the base type
+    pub kind: VariantKind,
+    /// `VT_BYREF` is set
+    pub byref: bool,
+    /// `VT_ARRAY` is set
+    pub array: bool,
+    /// For arrays, the element type recorded in the SAFEARRAY itself
+    pub element_vt: Option<u32>,
+    /// For arrays, the number of dimensions
+    pub dims: Option<u32>,
+    /// For records and record arrays, the GUID of the UDT in registry format
+    pub record_guid: Option<String>,
+    /// The Rust type the base type decodes into, from [`VT_TABLE`](constant.VT_TABLE.html)
+    pub rust_type: Option<&'static str>,
+}
+
+/// Describes the value `ptr` holds without decoding or releasing it
+pub fn classify(ptr: &Ptr<VARIANT>) -> VariantClass {
+    let var = ptr.as_ptr();
+    let vt = unsafe { (*var).n1.n2().vt } as u32;
+    let base = vt & VT_TYPEMASK;
+    let byref = vt & VT_BYREF != 0;
+    let array = vt & VT_ARRAY != 0;
+    let mut class = VariantClass {
+        vartype: vt,
+        base: base,
+        kind: kind_of(base),
+        byref: byref,
+        array: array,
+        element_vt: None,
+        dims: None,
+        record_guid: None,
+        rust_type: VT_TABLE.iter().find(|row| row.vartype == base).map(|row| row.rust_type),
+    };
+
+    if array {
+        let psa = unsafe {
+            let n3 = &(*var).n1.n2().n3;
+            if byref {
+                let ppsa = *n3.pparray();
+                if ppsa.is_null() { null_mut() } else { *ppsa }
+            } else {
+                *n3.parray()
+            }
+        };
+        if !psa.is_null() {
+            class.element_vt = get_vartype(psa).ok();
+            class.dims = Some(unsafe { SafeArrayGetDim(psa) });
+            if base == VT_RECORD {
+                let mut rec: *mut IRecordInfo = null_mut();
+                if unsafe { SafeArrayGetRecordInfo(psa, &mut rec) } == 0 && !rec.is_null() {
+                    class.record_guid = record_guid(rec);
+                    unsafe { (*rec).Release() };
+                }
+            }
+        }
+    } else if base == VT_RECORD {
+        let rec = unsafe { (*var).n1.n2().n3.n4().pRecInfo };
+        class.record_guid = record_guid(rec);
+    }
+    class
+}
+
+fn kind_of(base: u32) -> VariantKind {
+    match base {
+        VT_EMPTY => VariantKind::Empty,
+        VT_NULL => VariantKind::Null,
+        VT_UNKNOWN | VT_DISPATCH => VariantKind::Interface,
+        VT_RECORD => VariantKind::Record,
+        VT_VARIANT => VariantKind::Variant,
+        _ if VT_TABLE.iter().any(|row| row.vartype == base) => VariantKind::Scalar,
+        _ => VariantKind::Other,
+    }
+}
+
+fn record_guid(rec: *mut IRecordInfo) -> Option<String> {
+    if rec.is_null() {
+        return None;
+    }
+    let mut guid: GUID = unsafe { ::std::mem::zeroed() };
+    if unsafe { (*rec).GetGuid(&mut guid) } != 0 {
+        return None;
+    }
+    let d4 = guid.Data4;
+    Some(format!("{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+        guid.Data1, guid.Data2, guid.Data3, d4[0], d4[1], d4[2], d4[3], d4[4], d4[5], d4[6], d4[7]))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use winapi::shared::wtypes::VT_I4;
+    use super::super::variant::{VariantExt, VtNull};
+    #[test]
+    fn test_scalar() {
+        let pvar = 5i32.into_variant().unwrap();
+        let class = classify(&pvar);
+        assert_eq!(class.vartype, VT_I4);
+        assert_eq!(class.kind, VariantKind::Scalar);
+        assert_eq!(class.rust_type, Some("i32"));
+        assert!(!class.byref && !class.array);
+        i32::from_variant(pvar).unwrap();
+    }
+
+    #[test]
+    fn test_array() {
+        let pvar = vec![String::from("a"), String::from("b")].into_variant().unwrap();
+        let class = classify(&pvar);
+        assert!(class.array);
+        //String elements are stored as VT_VARIANT
+        assert_eq!(class.base, VT_VARIANT);
+        assert_eq!(class.element_vt, Some(VT_VARIANT));
+        assert_eq!(class.dims, Some(1));
+        Vec::<String>::from_variant(pvar).unwrap();
+    }
+
+    #[test]
+    fn test_null() {
+        let pvar = VtNull{}.into_variant().unwrap();
+        assert_eq!(classify(&pvar).kind, VariantKind::Null);
+        assert_eq!(classify(&pvar).rust_type, None);
+    }
+}
+
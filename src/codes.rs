@@ -0,0 +1,118 @@
+//! # Codes
+//! Named `SCode` values for the HRESULTs automation code most often matches on. 
+//! 
+//! ```
+//! extern crate oaidl;
+//! 
+//! use oaidl::{codes, SCode};
+//! 
+//! fn main() {
+//!     let sc = SCode::from(0x8002_0003u32 as i32);
+//!     assert_eq!(sc, codes::DISP_E_MEMBERNOTFOUND);
+//! }
+//! ```
+use super::types::SCode;
+
+macro_rules! scodes {
+    ($($(#[$attr:meta])* $name:ident = $val:expr;)*) => {
+        $(
+            $(#[$attr])*
+            pub const $name: SCode = SCode($val as i32);
+        )*
+    };
+}
+
+scodes! {
+    /// Success
+    S_OK = 0u32;
+    /// Success, with a false or partial result
+    S_FALSE = 1u32;
+
+    /// Not implemented
+    E_NOTIMPL = 0x8000_4001u32;
+    /// The interface is not supported
+    E_NOINTERFACE = 0x8000_4002u32;
+    /// Invalid pointer
+    E_POINTER = 0x8000_4003u32;
+    /// The operation was aborted
+    E_ABORT = 0x8000_4004u32;
+    /// Unspecified failure
+    E_FAIL = 0x8000_4005u32;
+    /// Unexpected failure
+    E_UNEXPECTED = 0x8000_FFFFu32;
+    /// Access denied
+    E_ACCESSDENIED = 0x8007_0005u32;
+    /// Out of memory
+    E_OUTOFMEMORY = 0x8007_000Eu32;
+    /// One or more arguments are invalid
+    E_INVALIDARG = 0x8007_0057u32;
+
+    /// Unknown interface
+    DISP_E_UNKNOWNINTERFACE = 0x8002_0001u32;
+    /// Member not found
+    DISP_E_MEMBERNOTFOUND = 0x8002_0003u32;
+    /// Parameter not found
+    DISP_E_PARAMNOTFOUND = 0x8002_0004u32;
+    /// Type mismatch
+    DISP_E_TYPEMISMATCH = 0x8002_0005u32;
+    /// Unknown name
+    DISP_E_UNKNOWNNAME = 0x8002_0006u32;
+    /// No named arguments
+    DISP_E_NONAMEDARGS = 0x8002_0007u32;
+    /// Bad variable type
+    DISP_E_BADVARTYPE = 0x8002_0008u32;
+    /// The member raised an exception, see the `EXCEPINFO`
+    DISP_E_EXCEPTION = 0x8002_0009u32;
+    /// Out of present range
+    DISP_E_OVERFLOW = 0x8002_000Au32;
+    /// Invalid index
+    DISP_E_BADINDEX = 0x8002_000Bu32;
+    /// Unknown language
+    DISP_E_UNKNOWNLCID = 0x8002_000Cu32;
+    /// Memory is locked
+    DISP_E_ARRAYISLOCKED = 0x8002_000Du32;
+    /// Invalid number of parameters
+    DISP_E_BADPARAMCOUNT = 0x8002_000Eu32;
+    /// Parameter not optional
+    DISP_E_PARAMNOTOPTIONAL = 0x8002_000Fu32;
+    /// Invalid callee
+    DISP_E_BADCALLEE = 0x8002_0010u32;
+    /// Does not support a collection
+    DISP_E_NOTACOLLECTION = 0x8002_0011u32;
+    /// Division by zero
+    DISP_E_DIVBYZERO = 0x8002_0012u32;
+    /// Buffer too small
+    DISP_E_BUFFERTOOSMALL = 0x8002_0013u32;
+
+    /// Element not found in the type library
+    TYPE_E_ELEMENTNOTFOUND = 0x8002_802Bu32;
+
+    /// Excel's `#NULL!` cell error, as a `VT_ERROR` cell value
+    XL_ERR_NULL = 0x800A_07D0u32;
+    /// Excel's `#DIV/0!` cell error
+    XL_ERR_DIV0 = 0x800A_07D7u32;
+    /// Excel's `#VALUE!` cell error
+    XL_ERR_VALUE = 0x800A_07DFu32;
+    /// Excel's `#REF!` cell error
+    XL_ERR_REF = 0x800A_07E7u32;
+    /// Excel's `#NAME?` cell error
+    XL_ERR_NAME = 0x800A_07EDu32;
+    /// Excel's `#NUM!` cell error
+    XL_ERR_NUM = 0x800A_07F4u32;
+    /// Excel's `#N/A` cell error
+    XL_ERR_NA = 0x800A_07FAu32;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_codes() {
+        assert!(S_FALSE.is_success());
+        assert!(!DISP_E_TYPEMISMATCH.is_success());
+        assert_eq!(DISP_E_TYPEMISMATCH.facility(), 2);
+        assert_eq!(E_INVALIDARG.facility(), 7);
+        assert_eq!(E_INVALIDARG.code(), 0x57);
+        assert_eq!(XL_ERR_NA.code(), 2042);
+    }
+}
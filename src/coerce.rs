@@ -0,0 +1,157 @@
+//! # Coerce
+//! Conversions that let OLE Automation change the vartype first. 
+//! 
+//! [`coerce`] hands the `VARIANT` to `VariantChangeTypeEx`, so a `VT_BSTR` 
+//! holding `"42"` decodes as an `i32` and a `VT_I2` as a `String`. The 
+//! plain `from_variant` conversions only accept the exact vartype. 
+//! 
+//! Parsing and producing text depends on the locale: `coerce` uses the one 
+//! in the `ConvertOptions` in force, [`coerce_in`] takes it explicitly. 
+//! 
+//! [`coerce`]: fn.coerce.html
+//! [`coerce_in`]: fn.coerce_in.html
+use winapi::um::oaidl::VARIANT;
+use winapi::um::oleauto::VariantChangeTypeEx;
+
+use super::codes;
+use super::compare::VariantGuard;
+use super::dtor::{ClearVariant, FreeVariant, PtrDestructor};
+use super::errors::CoercionError;
+use super::options::{ConvertOptions, Locale};
+use super::ptr::Ptr;
+use super::types::SCode;
+use super::variant::{VariantExt, Variants};
+
+/// Converts the `VARIANT` to `T`, coercing it to `T::VARTYPE` with `VariantChangeTypeEx` when it holds another vartype. 
+/// 
+/// The locale is the one in the `ConvertOptions` in force - the user's default unless changed. 
+/// The `VARIANT` is cleared either way. Byref variants are coerced from their pointees. 
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{coerce, VariantExt};
+/// 
+/// fn main() {
+///     let pvar = String::from("42").into_variant().unwrap();
+///     assert_eq!(coerce::<i32>(pvar).unwrap(), 42);
+///     let pvar = 7i16.into_variant().unwrap();
+///     assert_eq!(coerce::<String>(pvar).unwrap(), "7");
+/// }
+/// ```
+pub fn coerce<T: VariantExt>(var: Ptr<VARIANT>) -> Result<T, CoercionError> {
+    coerce_in(var, Locale::new(ConvertOptions::current().lcid))
+}
+
+/// Converts the `VARIANT` to `T` like [`coerce`], parsing and formatting text for `locale`. 
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{coerce_in, Locale, VariantExt};
+/// 
+/// fn main() {
+///     let de = Locale::from_langid(0x07, 0x01);
+///     let pvar = String::from("1,5").into_variant().unwrap();
+///     assert_eq!(coerce_in::<f64>(pvar, de).unwrap(), 1.5);
+///     let pvar = String::from("1.5").into_variant().unwrap();
+///     assert_eq!(coerce_in::<f64>(pvar, Locale::INVARIANT).unwrap(), 1.5);
+/// }
+/// ```
+/// 
+/// [`coerce`]: fn.coerce.html
+pub fn coerce_in<T: VariantExt>(var: Ptr<VARIANT>, locale: Locale) -> Result<T, CoercionError> {
+    let from = unsafe {(*var.as_ptr()).n1.n2()}.vt as u32;
+    if from == T::VARTYPE {
+        return T::from_variant(var).map_err(CoercionError::from);
+    }
+
+    let mut dest = VariantGuard::empty();
+    let hr = unsafe { VariantChangeTypeEx(dest.as_mut_ptr(), var.as_ptr(), locale.lcid(), 0, T::VARTYPE as u16) };
+    ClearVariant::destroy(var);
+    let sc = SCode::from(hr);
+    if sc == codes::DISP_E_TYPEMISMATCH {
+        return Err(CoercionError::TypeMismatch{from: from, to: T::VARTYPE});
+    }
+    if sc == codes::DISP_E_OVERFLOW {
+        return Err(CoercionError::Overflow{from: from, to: T::VARTYPE});
+    }
+    if hr != 0 {
+        return Err(CoercionError::ChangeTypeFailed{from: from, to: T::VARTYPE, hr: hr});
+    }
+    //the guard clears the coerced copy, read_raw AddRefs any interface it keeps
+    let ret = unsafe { T::read_raw(&dest.0) }?;
+    Ok(ret)
+}
+
+impl Variants {
+    /// The text OLE Automation gives the value in `locale` - `1.5` is `"1,5"` in German. 
+    /// 
+    /// Unlike `to_string_lossy` this formats dates, and every number the way VB's `CStr` would. 
+    pub fn to_string_in(&self, locale: Locale) -> Result<String, CoercionError> {
+        let pvar = self.clone().into_variant()?;
+        let ret = coerce_in::<String>(pvar, locale);
+        FreeVariant::destroy(pvar);
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::leak_tracking;
+    #[test]
+    fn test_coerce() {
+        let pvar = String::from("42").into_variant().unwrap();
+        assert_eq!(coerce::<i32>(pvar).unwrap(), 42);
+        FreeVariant::destroy(pvar);
+        let pvar = (-3i16).into_variant().unwrap();
+        assert_eq!(coerce::<i32>(pvar).unwrap(), -3);
+        FreeVariant::destroy(pvar);
+        let pvar = 1.5f64.into_variant().unwrap();
+        assert_eq!(coerce::<f64>(pvar).unwrap(), 1.5);
+        FreeVariant::destroy(pvar);
+    }
+
+    #[test]
+    fn test_coerce_errors() {
+        let pvar = String::from("not a number").into_variant().unwrap();
+        match coerce::<i32>(pvar) {
+            Err(CoercionError::TypeMismatch{..}) => {}, 
+            _ => panic!("Error")
+        }
+        FreeVariant::destroy(pvar);
+        let pvar = 70000i32.into_variant().unwrap();
+        match coerce::<i16>(pvar) {
+            Err(CoercionError::Overflow{from, to}) => assert_eq!((from, to), (i32::VARTYPE, i16::VARTYPE)), 
+            _ => panic!("Error")
+        }
+        FreeVariant::destroy(pvar);
+    }
+
+    #[test]
+    fn test_coerce_in() {
+        let de = Locale::from_langid(0x07, 0x01);
+        let pvar = String::from("2,25").into_variant().unwrap();
+        assert_eq!(coerce_in::<f64>(pvar, de).unwrap(), 2.25);
+        FreeVariant::destroy(pvar);
+        let pvar = String::from("2.25").into_variant().unwrap();
+        assert_eq!(coerce_in::<f64>(pvar, Locale::INVARIANT).unwrap(), 2.25);
+        FreeVariant::destroy(pvar);
+        assert_eq!(Variants::F64(2.25).to_string_in(de).unwrap(), "2,25");
+        assert_eq!(Variants::F64(2.25).to_string_in(Locale::INVARIANT).unwrap(), "2.25");
+    }
+
+    #[test]
+    fn test_coerce_balanced() {
+        leak_tracking::assert_balanced(|| {
+            let pvar = String::from("12").into_variant().unwrap();
+            assert_eq!(coerce::<u8>(pvar).unwrap(), 12);
+            FreeVariant::destroy(pvar);
+        });
+    }
+}
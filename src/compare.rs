@@ -0,0 +1,232 @@
+//! # Compare
+//! Deep, value based comparison of `VARIANT`s. 
+//! 
+//! Neither variant is consumed or modified - every value is read from a copy. 
+use std::mem;
+
+use winapi::ctypes::c_void;
+use winapi::shared::wtypes::{
+    VT_ARRAY, 
+    VT_DECIMAL, 
+    VT_DISPATCH, 
+    VT_EMPTY, 
+    VT_NULL, 
+    VT_RECORD, 
+    VT_UNKNOWN, 
+    VT_VARIANT,
+};
+use winapi::um::oaidl::{SAFEARRAY, VARIANT, VARIANT_n3};
+use winapi::um::oleauto::{VariantClear, VariantCopyInd};
+
+use super::array::{get_bounds, get_vartype, SafeArrayGetDim, SafeArrayGetElemSize, SafeArrayGetElement};
+use super::errors::{FromSafeArrayError, FromSafeArrElemError, FromVariantError};
+use super::ptr::Ptr;
+use super::types::DecWrapper;
+use super::variant::Variants;
+
+/// Compares two `VARIANT`s by the values they hold. 
+/// 
+/// Byref variants are compared by their pointees and arrays element by element 
+/// (recursing into `VT_VARIANT` elements), so a `VT_BYREF | VT_I4` pointing at 
+/// `5` equals a `VT_I4` holding `5`. Interface pointers compare by identity. 
+/// Vartypes must match otherwise - `VT_I2` 5 does not equal `VT_I4` 5. 
+/// 
+/// Returns an error if either variant holds a vartype that can't be decoded, 
+/// including arrays of `VT_RECORD`, whose elements have no VARIANT form.
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{variant_eq, VariantExt};
+/// 
+/// fn main() {
+///     let a = String::from("same").into_variant().unwrap();
+///     let b = String::from("same").into_variant().unwrap();
+///     assert!(variant_eq(&a, &b).unwrap());
+/// }
+/// ```
+pub fn variant_eq(a: &Ptr<VARIANT>, b: &Ptr<VARIANT>) -> Result<bool, FromVariantError> {
+    unsafe { raw_eq(a.as_ptr(), b.as_ptr()) }
+}
+
+/// Stack `VARIANT` that is cleared when dropped
+pub(crate) struct VariantGuard(pub(crate) VARIANT);
+
+impl VariantGuard {
+    pub(crate) fn empty() -> VariantGuard {
+        VariantGuard(unsafe {mem::zeroed()})
+    }
+
+    /// Copies `src`, dereferencing it if it is a byref variant
+    unsafe fn copy_ind(src: *mut VARIANT) -> Result<VariantGuard, FromVariantError> {
+        let mut dest = VariantGuard::empty();
+        let hr = VariantCopyInd(dest.as_mut_ptr(), src);
+        match hr {
+            0 => Ok(dest), 
+            _ => Err(FromVariantError::VariantCopyFailed{hr: hr})
+        }
+    }
+
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut VARIANT {
+        &mut self.0 as *mut VARIANT
+    }
+
+    fn vt(&self) -> u32 {
+        unsafe {self.0.n1.n2()}.vt as u32
+    }
+}
+
+impl Drop for VariantGuard {
+    fn drop(&mut self) {
+        unsafe { VariantClear(self.as_mut_ptr()) };
+    }
+}
+
+unsafe fn raw_eq(a: *mut VARIANT, b: *mut VARIANT) -> Result<bool, FromVariantError> {
+    if a.is_null() || b.is_null() {
+        return Err(FromVariantError::VariantPtrNull);
+    }
+    let mut a = VariantGuard::copy_ind(a)?;
+    let mut b = VariantGuard::copy_ind(b)?;
+    let vt = a.vt();
+    if vt != b.vt() {
+        return Ok(false);
+    }
+
+    if vt & VT_ARRAY != 0 {
+        let psa_a = *a.0.n1.n2().n3.parray();
+        let psa_b = *b.0.n1.n2().n3.parray();
+        return array_eq(psa_a, psa_b);
+    }
+
+    match vt {
+        VT_EMPTY | VT_NULL => Ok(true), 
+        VT_UNKNOWN => Ok(*a.0.n1.n2().n3.punkVal() == *b.0.n1.n2().n3.punkVal()), 
+        VT_DISPATCH => Ok(*a.0.n1.n2().n3.pdispVal() == *b.0.n1.n2().n3.pdispVal()), 
+        VT_DECIMAL => Ok(DecWrapper::from(*a.0.n1.decVal()) == DecWrapper::from(*b.0.n1.decVal())), 
+        _ => {
+            //the guards still clear whatever from_variant leaves behind
+            let va = Variants::from_variant(Ptr::with_checked(a.as_mut_ptr()).unwrap())?;
+            let vb = Variants::from_variant(Ptr::with_checked(b.as_mut_ptr()).unwrap())?;
+            Ok(va == vb)
+        }
+    }
+}
+
+unsafe fn array_eq(a: *mut SAFEARRAY, b: *mut SAFEARRAY) -> Result<bool, FromVariantError> {
+    if a.is_null() || b.is_null() {
+        return Ok(a.is_null() && b.is_null());
+    }
+    let dims = SafeArrayGetDim(a);
+    if dims != SafeArrayGetDim(b) {
+        return Ok(false);
+    }
+    let vt = get_vartype(a)?;
+    if vt != get_vartype(b)? {
+        return Ok(false);
+    }
+    //elements are copied into a VARIANT: records, or anything wider than its value slot, won't fit
+    let wide = SafeArrayGetElemSize(a) as usize > mem::size_of::<VARIANT_n3>();
+    if vt == VT_RECORD || (wide && vt != VT_VARIANT && vt != VT_DECIMAL) {
+        return Err(FromVariantError::UnknownVarType{vartype: VT_ARRAY | vt});
+    }
+    let mut bounds = Vec::with_capacity(dims as usize);
+    for dim in 1..=dims {
+        let bound = get_bounds(a, dim)?;
+        if bound != get_bounds(b, dim)? {
+            return Ok(false);
+        }
+        if bound.1 < bound.0 {
+            //no elements
+            return Ok(true);
+        }
+        bounds.push(bound);
+    }
+
+    let mut ixs: Vec<i32> = bounds.iter().map(|b| b.0).collect();
+    let mut index = 0;
+    loop {
+        let mut ea = element_variant(a, vt, &ixs, index)?;
+        let mut eb = element_variant(b, vt, &ixs, index)?;
+        if !raw_eq(ea.as_mut_ptr(), eb.as_mut_ptr())? {
+            return Ok(false);
+        }
+        index += 1;
+
+        //advance the last dimension first, carrying into the ones before it
+        let mut dim = bounds.len();
+        loop {
+            if dim == 0 {
+                return Ok(true);
+            }
+            dim -= 1;
+            if ixs[dim] < bounds[dim].1 {
+                ixs[dim] += 1;
+                break;
+            }
+            ixs[dim] = bounds[dim].0;
+        }
+    }
+}
+
+/// Copies one element of the array into a `VARIANT` of the element's vartype. 
+/// `array_eq` has checked the element fits the slot it is copied into.
+unsafe fn element_variant(psa: *mut SAFEARRAY, vt: u32, ixs: &[i32], index: usize) -> Result<VariantGuard, FromVariantError> {
+    let mut var = VariantGuard::empty();
+    let pv: *mut c_void = match vt {
+        VT_VARIANT => var.as_mut_ptr() as *mut c_void, 
+        VT_DECIMAL => var.0.n1.decVal_mut() as *mut _ as *mut c_void, 
+        _ => &mut var.0.n1.n2_mut().n3 as *mut _ as *mut c_void,
+    };
+    let hr = SafeArrayGetElement(psa, ixs.as_ptr(), pv);
+    if hr != 0 {
+        let fsae = FromSafeArrayError::from_element_err(FromSafeArrElemError::GetElementFailed{hr: hr}, index);
+        return Err(FromVariantError::from(fsae));
+    }
+    if vt != VT_VARIANT {
+        var.0.n1.n2_mut().vt = vt as u16;
+    }
+    Ok(var)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::variant::VariantExt;
+    #[test]
+    fn test_scalars() {
+        let a = 1337i32.into_variant().unwrap();
+        let b = 1337i32.into_variant().unwrap();
+        let c = 1337i16.into_variant().unwrap();
+        assert!(variant_eq(&a, &b).unwrap());
+        assert!(!variant_eq(&a, &c).unwrap());
+    }
+
+    #[test]
+    fn test_byref() {
+        let a = Box::new(7i32).into_variant().unwrap();
+        let b = 7i32.into_variant().unwrap();
+        assert!(variant_eq(&a, &b).unwrap());
+    }
+
+    #[test]
+    fn test_arrays() {
+        let a = vec![String::from("a"), String::from("b")].into_variant().unwrap();
+        let b = vec![String::from("a"), String::from("b")].into_variant().unwrap();
+        let c = vec![String::from("a"), String::from("c")].into_variant().unwrap();
+        assert!(variant_eq(&a, &b).unwrap());
+        assert!(!variant_eq(&a, &c).unwrap());
+    }
+
+    #[test]
+    fn test_decimal_arrays() {
+        use rust_decimal::Decimal;
+        let a = vec![Decimal::new(15, 1), Decimal::new(-2, 0)].into_variant().unwrap();
+        let b = vec![Decimal::new(15, 1), Decimal::new(-2, 0)].into_variant().unwrap();
+        let c = vec![Decimal::new(15, 1), Decimal::new(2, 0)].into_variant().unwrap();
+        assert!(variant_eq(&a, &b).unwrap());
+        assert!(!variant_eq(&a, &c).unwrap());
+    }
+}
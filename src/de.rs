@@ -0,0 +1,319 @@
+//! # De
+//! A serde `Deserializer` that reads VARIANTs, behind the `serde` feature.
+//!
+//! Reads the shapes [`to_variant`](fn.to_variant.html) writes, and is lenient where
+//! automation servers are: structs read from 1-D arrays by position or from N x 2
+//! keyed arrays by name, `VT_EMPTY` and `VT_NULL` both read as `None`, byref variants
+//! read as their pointees, and other multi-dimensional arrays read as nested sequences,
+//! the last dimension innermost. Sequences and tuples always read an array by rows,
+//! even one that is keyed. `VT_CY`, `VT_DATE` and `VT_DECIMAL` read as floats, and
+//! whole `VT_DECIMAL`s also as 128-bit integers.
+use std::vec;
+
+use rust_decimal::Decimal;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use winapi::shared::wtypes::{DECIMAL, VT_UI1};
+use winapi::um::oaidl::VARIANT;
+
+use super::errors::{FromVariantError, SerdeError};
+use super::ptr::Ptr;
+use super::types::CY_SCALE;
+use super::value::{ValueArray, VariantValue};
+use super::variant::decimal_to_parts;
+
+/// Deserializes any `DeserializeOwned` value from a `Ptr<VARIANT>`. The VARIANT is cleared afterwards.
+pub fn from_variant_de<T: DeserializeOwned>(var: Ptr<VARIANT>) -> Result<T, SerdeError> {
+    let value = VariantValue::from_variant(var)?;
+    T::deserialize(VariantDeserializer::new(value))
+}
+
+/// The serde `Deserializer` behind `from_variant_de`, reading a `VariantValue`
+#[derive(Debug)]
+pub struct VariantDeserializer {
+    value: VariantValue,
+}
+
+impl VariantDeserializer {
+    /// A deserializer that reads `value`
+    pub fn new(value: VariantValue) -> VariantDeserializer {
+        VariantDeserializer { value: value }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for VariantDeserializer {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            VariantValue::Empty | VariantValue::Null => visitor.visit_unit(),
+            VariantValue::Bool(v) => visitor.visit_bool(v),
+            VariantValue::I8(v) => visitor.visit_i8(v),
+            VariantValue::I16(v) => visitor.visit_i16(v),
+            VariantValue::I32(v) => visitor.visit_i32(v),
+            VariantValue::I64(v) => visitor.visit_i64(v),
+            VariantValue::U8(v) => visitor.visit_u8(v),
+            VariantValue::U16(v) => visitor.visit_u16(v),
+            VariantValue::U32(v) => visitor.visit_u32(v),
+            VariantValue::U64(v) => visitor.visit_u64(v),
+            VariantValue::Int(v) => visitor.visit_i32(i32::from(v)),
+            VariantValue::UInt(v) => visitor.visit_u32(u32::from(v)),
+            VariantValue::SCode(sc) => visitor.visit_i32(i32::from(sc)),
+            VariantValue::F32(v) => visitor.visit_f32(v),
+            VariantValue::F64(v) => visitor.visit_f64(v),
+            VariantValue::Currency(cy) => visitor.visit_f64(i64::from(cy) as f64 / CY_SCALE as f64),
+            VariantValue::Date(d) => visitor.visit_f64(f64::from(d)),
+            VariantValue::Decimal(dec) => {
+                let text = Decimal::from(dec).to_string();
+                visitor.visit_f64(text.parse().unwrap_or(0.0))
+            },
+            VariantValue::String(s) => visitor.visit_string(s.to_string_lossy()),
+            VariantValue::ByRef(inner) => VariantDeserializer::new(*inner).deserialize_any(visitor),
+            VariantValue::Array(arr) => {
+                if arr.is_keyed() {
+                    visitor.visit_map(MapAccess::new(arr))
+                } else {
+                    visitor.visit_seq(SeqAccess { elements: rows(arr).into_iter() })
+                }
+            },
+            other => Err(SerdeError::NoSerdeForm{vartype: other.vartype()}),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            VariantValue::Empty | VariantValue::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            //a keyed array is still rows when a sequence is asked for, e.g. Vec<(String, i32)>
+            VariantValue::Array(arr) => visitor.visit_seq(SeqAccess { elements: rows(arr).into_iter() }),
+            other => VariantDeserializer::new(other).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            VariantValue::Decimal(dec) => match decimal_to_parts(&DECIMAL::from(dec)) {
+                Some((magnitude, negative)) => visitor.visit_i128(if negative {-(magnitude as i128)} else {magnitude as i128}),
+                None => Err(SerdeError::from(FromVariantError::DecimalOutOfRange{target: "i128"})),
+            },
+            other => VariantDeserializer::new(other).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            VariantValue::Decimal(dec) => match decimal_to_parts(&DECIMAL::from(dec)) {
+                Some((magnitude, false)) => visitor.visit_u128(magnitude),
+                _ => Err(SerdeError::from(FromVariantError::DecimalOutOfRange{target: "u128"})),
+            },
+            other => VariantDeserializer::new(other).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            VariantValue::Array(ref arr) if arr.vartype == VT_UI1 && arr.bounds.len() == 1 => {
+                let bytes = arr.elements.iter().map(|elem| match *elem {
+                    VariantValue::U8(b) => b,
+                    _ => 0,
+                }).collect();
+                visitor.visit_byte_buf(bytes)
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        let vartype = self.value.vartype();
+        match self.value {
+            VariantValue::String(s) => visitor.visit_enum(s.to_string_lossy().into_deserializer()),
+            //a variant with contents is a one entry map from its name
+            VariantValue::Array(arr) if arr.is_keyed() && arr.bounds[0].1 == 1 => {
+                let mut pair = arr.elements.into_iter();
+                let variant = pair.next().unwrap_or(VariantValue::Empty);
+                let value = pair.next().unwrap_or(VariantValue::Empty);
+                visitor.visit_enum(EnumAccess { variant: variant, value: value })
+            },
+            _ => Err(SerdeError::NoSerdeForm{vartype: vartype}),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string unit unit_struct
+        map struct identifier
+    }
+}
+
+/// Splits an array into its rows: the elements of a 1-D array, or arrays of one dimension fewer
+fn rows(arr: ValueArray) -> Vec<VariantValue> {
+    if arr.bounds.len() <= 1 {
+        return arr.elements;
+    }
+    let inner: Vec<(i32, u32)> = arr.bounds[1..].to_vec();
+    let row_len = inner.iter().fold(1usize, |n, &(_, count)| n.saturating_mul(count as usize));
+    let mut rows = Vec::with_capacity(arr.bounds[0].1 as usize);
+    let mut elements = arr.elements.into_iter();
+    for _ in 0..arr.bounds[0].1 {
+        let row: Vec<VariantValue> = elements.by_ref().take(row_len).collect();
+        rows.push(VariantValue::Array(ValueArray { vartype: arr.vartype, bounds: inner.clone(), elements: row }));
+    }
+    rows
+}
+
+struct SeqAccess {
+    elements: vec::IntoIter<VariantValue>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeError> {
+        match self.elements.next() {
+            Some(elem) => seed.deserialize(VariantDeserializer::new(elem)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+struct MapAccess {
+    elements: vec::IntoIter<VariantValue>,
+}
+
+impl MapAccess {
+    fn new(arr: ValueArray) -> MapAccess {
+        MapAccess { elements: arr.elements.into_iter() }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError> {
+        match self.elements.next() {
+            Some(key) => seed.deserialize(VariantDeserializer::new(key)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, SerdeError> {
+        let value = self.elements.next().unwrap_or(VariantValue::Empty);
+        seed.deserialize(VariantDeserializer::new(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len() / 2)
+    }
+}
+
+struct EnumAccess {
+    variant: VariantValue,
+    value: VariantValue,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = SerdeError;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, VariantDeserializer), SerdeError> {
+        let variant = seed.deserialize(VariantDeserializer::new(self.variant))?;
+        Ok((variant, VariantDeserializer::new(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Deserialize;
+    use widestring::U16String;
+    use winapi::shared::wtypes::VT_VARIANT;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    fn de<T: DeserializeOwned>(value: VariantValue) -> T {
+        T::deserialize(VariantDeserializer::new(value)).unwrap()
+    }
+
+    #[test]
+    fn test_de_lenient() {
+        //fields by name from a keyed array, in any order
+        let keyed = ValueArray::keyed(vec![
+            (U16String::from_str("y"), VariantValue::I16(2)),
+            (U16String::from_str("x"), VariantValue::I32(1)),
+        ]);
+        assert_eq!(de::<Point>(VariantValue::Array(keyed)), Point { x: 1, y: 2 });
+        assert_eq!(de::<Option<i32>>(VariantValue::Empty), None);
+        assert_eq!(de::<i32>(VariantValue::ByRef(Box::new(VariantValue::I32(5)))), 5);
+
+        let matrix = ValueArray {
+            vartype: VT_VARIANT,
+            bounds: vec![(1, 2), (1, 3)],
+            elements: (1..=6).map(VariantValue::I32).collect(),
+        };
+        assert_eq!(de::<Vec<Vec<i32>>>(VariantValue::Array(matrix)), vec![vec![1, 2, 3], vec![4, 5, 6]]);
+
+        //asked for as a sequence, a keyed array is its rows
+        let keyed = ValueArray::keyed(vec![(U16String::from_str("a"), VariantValue::I32(1))]);
+        assert_eq!(de::<Vec<(String, i32)>>(VariantValue::Array(keyed)), vec![(String::from("a"), 1)]);
+    }
+
+    #[test]
+    fn test_de_errors() {
+        match Point::deserialize(VariantDeserializer::new(VariantValue::Unknown(None))) {
+            Err(SerdeError::NoSerdeForm{..}) => {},
+            _ => panic!("Error")
+        }
+        assert!(Point::deserialize(VariantDeserializer::new(VariantValue::String(U16String::from_str("x")))).is_err());
+    }
+}
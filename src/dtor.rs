@@ -0,0 +1,373 @@
+//! # Destructors
+//! Ownership policies for pointers allocated by conversions. 
+//! 
+//! A [`PtrDestructor`] says how a kind of pointer is released; [`OwnedPtr`] 
+//! holds a pointer and applies its policy when dropped. COM interface pointers 
+//! use `ComDestructor`, which calls `Release`. Wrapping a policy in 
+//! [`CountingDestructor`] tallies every acquire, release and destroy in counters 
+//! kept per wrapped policy, so tests can assert that conversion-heavy code paths 
+//! did not leak. 
+//! 
+//! [`PtrDestructor`]: trait.PtrDestructor.html
+//! [`OwnedPtr`]: struct.OwnedPtr.html
+//! [`CountingDestructor`]: struct.CountingDestructor.html
+use std::any::TypeId;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::Mutex;
+
+use winapi::ctypes::c_void;
+use winapi::um::combaseapi::CoTaskMemFree;
+use winapi::um::oaidl::{SAFEARRAY, VARIANT};
+use winapi::um::oleauto::{SysFreeString, VariantClear};
+use winapi::um::propidl::{PropVariantClear, PROPVARIANT};
+use winapi::um::unknwnbase::IUnknown;
+use winapi::Interface;
+
+use super::array::{copy_safearray, SafeArrayDestroy};
+use super::errors::IntoSafeArrayError;
+use super::leak_tracking;
+use super::ptr::Ptr;
+
+/// Policy for releasing a pointer of type `*mut T`
+pub trait PtrDestructor<T> {
+    /// Frees the memory behind `p`
+    fn destroy(p: Ptr<T>);
+
+    /// Called when an `OwnedPtr` takes ownership of `p`
+    fn acquired(_p: Ptr<T>) {}
+
+    /// Called when an `OwnedPtr` hands `p` off without destroying it
+    fn released(_p: Ptr<T>) {}
+}
+
+/// Clears and frees a boxed `VARIANT` as returned by `into_variant`
+#[derive(Clone, Copy, Debug)]
+pub struct FreeVariant;
+
+impl PtrDestructor<VARIANT> for FreeVariant {
+    fn destroy(p: Ptr<VARIANT>) {
+        leak_tracking::variant_freed(p.as_ptr());
+        let mut var = unsafe { Box::from_raw(p.as_ptr()) };
+        unsafe { VariantClear(&mut *var) };
+    }
+}
+
+/// Clears a `VARIANT` allocated by COM with `CoTaskMemAlloc` and frees it with `CoTaskMemFree`
+#[derive(Clone, Copy, Debug)]
+pub struct FreeCoTaskMemVariant;
+
+impl PtrDestructor<VARIANT> for FreeCoTaskMemVariant {
+    fn destroy(p: Ptr<VARIANT>) {
+        leak_tracking::variant_cleared(p.as_ptr());
+        unsafe { 
+            VariantClear(p.as_ptr());
+            CoTaskMemFree(p.as_ptr() as *mut c_void);
+        }
+    }
+}
+
+/// Clears a `VARIANT` with `VariantClear` but leaves its memory alone. For variants 
+/// whose storage belongs to someone else - the caller's stack, a `DISPPARAMS` array 
+/// or an allocator this crate doesn't know.
+#[derive(Clone, Copy, Debug)]
+pub struct ClearVariant;
+
+impl PtrDestructor<VARIANT> for ClearVariant {
+    fn destroy(p: Ptr<VARIANT>) {
+        leak_tracking::variant_cleared(p.as_ptr());
+        unsafe { VariantClear(p.as_ptr()) };
+    }
+}
+
+/// Clears and frees a boxed `PROPVARIANT` as returned by `into_propvariant`
+#[derive(Clone, Copy, Debug)]
+pub struct FreePropVariant;
+
+impl PtrDestructor<PROPVARIANT> for FreePropVariant {
+    fn destroy(p: Ptr<PROPVARIANT>) {
+        let mut pv = unsafe { Box::from_raw(p.as_ptr()) };
+        unsafe { PropVariantClear(&mut *pv) };
+    }
+}
+
+/// Clears a `PROPVARIANT` with `PropVariantClear` but leaves its memory alone
+#[derive(Clone, Copy, Debug)]
+pub struct ClearPropVariant;
+
+impl PtrDestructor<PROPVARIANT> for ClearPropVariant {
+    fn destroy(p: Ptr<PROPVARIANT>) {
+        unsafe { PropVariantClear(p.as_ptr()) };
+    }
+}
+
+/// Destroys a `SAFEARRAY` with `SafeArrayDestroy`
+#[derive(Clone, Copy, Debug)]
+pub struct FreeSafeArray;
+
+impl PtrDestructor<SAFEARRAY> for FreeSafeArray {
+    fn destroy(p: Ptr<SAFEARRAY>) {
+        leak_tracking::safearray_freed();
+        unsafe { SafeArrayDestroy(p.as_ptr()) };
+    }
+}
+
+/// Frees a BSTR with `SysFreeString`
+#[derive(Clone, Copy, Debug)]
+pub struct FreeBStr;
+
+impl PtrDestructor<u16> for FreeBStr {
+    fn destroy(p: Ptr<u16>) {
+        leak_tracking::bstr_freed();
+        unsafe { SysFreeString(p.as_ptr()) };
+    }
+}
+
+/// Releases one reference on a COM interface pointer with `IUnknown::Release`
+#[derive(Clone, Copy, Debug)]
+pub struct ComDestructor;
+
+impl<T: Interface> PtrDestructor<T> for ComDestructor {
+    fn destroy(p: Ptr<T>) {
+        let punk = p.as_ptr() as *mut IUnknown;
+        unsafe { (*punk).Release() };
+    }
+}
+
+// `CountingDestructor` counters, one entry per wrapped policy
+static COUNTS: Mutex<Vec<(TypeId, DestructorCounts)>> = Mutex::new(Vec::new());
+
+/// Snapshot of the `CountingDestructor` counters of one policy
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DestructorCounts {
+    /// Pointers taken into ownership
+    pub acquired: usize, 
+    /// Pointers handed off without being destroyed
+    pub released: usize, 
+    /// Pointers destroyed
+    pub destroyed: usize,
+}
+
+impl DestructorCounts {
+    /// Pointers that were acquired but neither released nor destroyed
+    pub fn leaked(&self) -> usize {
+        self.acquired.saturating_sub(self.released + self.destroyed)
+    }
+
+    /// Difference between two snapshots
+    pub fn since(&self, earlier: &DestructorCounts) -> DestructorCounts {
+        DestructorCounts {
+            acquired: self.acquired.saturating_sub(earlier.acquired), 
+            released: self.released.saturating_sub(earlier.released), 
+            destroyed: self.destroyed.saturating_sub(earlier.destroyed),
+        }
+    }
+}
+
+/// Wraps the policy `D`, recording every acquire, release and destroy in counters shared 
+/// by every `CountingDestructor<D>` and kept apart from those of other policies.
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{CountingDestructor, FreeVariant, OwnedPtr, VariantExt};
+/// 
+/// fn main() {
+///     let before = CountingDestructor::<FreeVariant>::counts();
+///     {
+///         let pvar = 1i32.into_variant().unwrap();
+///         let _owned = OwnedPtr::<_, CountingDestructor<FreeVariant>>::new(pvar);
+///     }
+///     let delta = CountingDestructor::<FreeVariant>::counts().since(&before);
+///     assert_eq!(delta.leaked(), 0);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct CountingDestructor<D> {
+    _marker: PhantomData<D>,
+}
+
+impl<D: 'static> CountingDestructor<D> {
+    /// Current values of the counters for `D`
+    pub fn counts() -> DestructorCounts {
+        let counts = COUNTS.lock().unwrap_or_else(|e| e.into_inner());
+        counts.iter()
+              .find(|&&(id, _)| id == TypeId::of::<D>())
+              .map_or(DestructorCounts::default(), |&(_, c)| c)
+    }
+
+    fn update<F: FnOnce(&mut DestructorCounts)>(f: F) {
+        let mut counts = COUNTS.lock().unwrap_or_else(|e| e.into_inner());
+        let id = TypeId::of::<D>();
+        match counts.iter().position(|&(other, _)| other == id) {
+            Some(ix) => f(&mut counts[ix].1), 
+            None => {
+                let mut c = DestructorCounts::default();
+                f(&mut c);
+                counts.push((id, c));
+            },
+        }
+    }
+}
+
+impl<T, D: PtrDestructor<T> + 'static> PtrDestructor<T> for CountingDestructor<D> {
+    fn destroy(p: Ptr<T>) {
+        D::destroy(p);
+        CountingDestructor::<D>::update(|c| c.destroyed += 1);
+    }
+
+    fn acquired(p: Ptr<T>) {
+        D::acquired(p);
+        CountingDestructor::<D>::update(|c| c.acquired += 1);
+    }
+
+    fn released(p: Ptr<T>) {
+        D::released(p);
+        CountingDestructor::<D>::update(|c| c.released += 1);
+    }
+}
+
+/// Owns a `*mut T` and releases it with the policy `D` when dropped
+pub struct OwnedPtr<T, D: PtrDestructor<T>> {
+    inner: Ptr<T>, 
+    _marker: PhantomData<D>,
+}
+
+impl<T, D: PtrDestructor<T>> OwnedPtr<T, D> {
+    /// Takes ownership of `p`
+    pub fn new(p: Ptr<T>) -> OwnedPtr<T, D> {
+        D::acquired(copy_ptr(&p));
+        OwnedPtr {
+            inner: p, 
+            _marker: PhantomData,
+        }
+    }
+
+    /// The owned pointer; ownership is kept
+    pub fn as_ptr(&self) -> *mut T {
+        self.inner.as_ptr()
+    }
+
+    /// Gives up ownership without destroying, e.g. when passing the pointer to a callee that frees it
+    pub fn into_inner(self) -> Ptr<T> {
+        let p = copy_ptr(&self.inner);
+        mem::forget(self);
+        D::released(copy_ptr(&p));
+        p
+    }
+
+    /// Like `into_inner`, but returns the bare pointer for FFI out-parameters
+    pub fn into_raw(self) -> *mut T {
+        self.into_inner().as_ptr()
+    }
+}
+
+// Prints the address only, so interface and VARIANT pointers can be debugged too
+impl<T, D: PtrDestructor<T>> fmt::Debug for OwnedPtr<T, D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("OwnedPtr").field(&self.inner.as_ptr()).finish()
+    }
+}
+
+/// A SAFEARRAY destroyed with `SafeArrayDestroy` when dropped. 
+/// 
+/// `into_safearray` returns a bare `Ptr<SAFEARRAY>`, which is never freed on its own. 
+/// Wrap it in this to keep it owned until it is handed to COM with `into_raw`. 
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{OwnedSafeArray, SafeArrayExt};
+/// 
+/// fn main() {
+///     let owned = OwnedSafeArray::new(vec![1i32, 2, 3].into_iter().into_safearray().unwrap());
+///     assert!(!owned.as_ptr().is_null());
+///     // dropped here, destroying the array
+/// }
+/// ```
+pub type OwnedSafeArray = OwnedPtr<SAFEARRAY, FreeSafeArray>;
+
+impl OwnedPtr<SAFEARRAY, FreeSafeArray> {
+    /// Deep-copies the array with `SafeArrayCopy`, see [`copy_safearray`](fn.copy_safearray.html)
+    pub fn try_clone(&self) -> Result<OwnedSafeArray, IntoSafeArrayError> {
+        copy_safearray(self.as_ptr()).map(OwnedSafeArray::new)
+    }
+}
+
+impl<T: Interface> OwnedPtr<T, ComDestructor> {
+    /// Takes a new reference on `p` with `IUnknown::AddRef`, leaving the caller's reference alone
+    pub fn add_ref(p: Ptr<T>) -> OwnedPtr<T, ComDestructor> {
+        let punk = p.as_ptr() as *mut IUnknown;
+        unsafe { (*punk).AddRef() };
+        OwnedPtr::new(p)
+    }
+}
+
+/// Cloning an interface pointer takes another reference with `AddRef`
+impl<T: Interface> Clone for OwnedPtr<T, ComDestructor> {
+    fn clone(&self) -> Self {
+        OwnedPtr::add_ref(copy_ptr(&self.inner))
+    }
+}
+
+/// Owned pointers are equal when they point at the same thing
+impl<T, D: PtrDestructor<T>> PartialEq for OwnedPtr<T, D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_ptr() == other.as_ptr()
+    }
+}
+
+impl<T, D: PtrDestructor<T>> Eq for OwnedPtr<T, D> {}
+
+impl<T, D: PtrDestructor<T>> Drop for OwnedPtr<T, D> {
+    fn drop(&mut self) {
+        D::destroy(copy_ptr(&self.inner));
+    }
+}
+
+fn copy_ptr<T>(p: &Ptr<T>) -> Ptr<T> {
+    Ptr::with_checked(p.as_ptr()).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::SafeArrayExt;
+    use super::super::variant::VariantExt;
+    #[test]
+    fn test_counting() {
+        let before = CountingDestructor::<FreeVariant>::counts();
+        let before_sa = CountingDestructor::<FreeSafeArray>::counts();
+        {
+            let pvar = String::from("counted").into_variant().unwrap();
+            let _owned = OwnedPtr::<_, CountingDestructor<FreeVariant>>::new(pvar);
+            let psa = vec![1i32, 2].into_iter().into_safearray().unwrap();
+            let owned = OwnedPtr::<_, CountingDestructor<FreeSafeArray>>::new(psa);
+            let psa = owned.into_inner();
+            let _owned = OwnedPtr::<_, FreeSafeArray>::new(psa);
+        }
+        //each policy keeps its own counts
+        let delta = CountingDestructor::<FreeVariant>::counts().since(&before);
+        assert_eq!(delta, DestructorCounts { acquired: 1, released: 0, destroyed: 1 });
+        let delta = CountingDestructor::<FreeSafeArray>::counts().since(&before_sa);
+        assert_eq!(delta, DestructorCounts { acquired: 1, released: 1, destroyed: 0 });
+        assert_eq!(delta.leaked(), 0);
+    }
+
+    #[test]
+    fn test_owned_safearray() {
+        leak_tracking::assert_balanced(|| {
+            let _owned = OwnedSafeArray::new(vec![1i32, 2].into_iter().into_safearray().unwrap());
+        });
+        let owned = OwnedSafeArray::new(vec![3i32].into_iter().into_safearray().unwrap());
+        let copy = owned.try_clone().unwrap();
+        let psa = owned.into_raw();
+        assert_eq!(ExactSizeIterator::<Item=i32>::from_safearray(psa).unwrap(), vec![3]);
+        assert_eq!(ExactSizeIterator::<Item=i32>::from_safearray(copy.into_raw()).unwrap(), vec![3]);
+    }
+}
@@ -0,0 +1,221 @@
+//! # Enums
+//! Fieldless Rust enums marshalled as their integer discriminant.
+//!
+//! Automation enums travel through `IDispatch` as plain `VT_I4` (or `VT_UI4`)
+//! values. Implementing [`ComEnum`] - by hand or with [`com_enum!`] - maps an enum
+//! to that representation and rejects discriminants the enum does not declare,
+//! instead of transmuting them into an invalid value. Some servers send enum
+//! values as `VT_INT`/`VT_UINT` instead; those are accepted when decoding too.
+//!
+//! [`ComEnum`]: trait.ComEnum.html
+//! [`com_enum!`]: ../macro.com_enum.html
+use std::convert::TryFrom;
+
+use winapi::shared::wtypes::{VT_I4, VT_INT, VT_UI4, VT_UINT};
+use winapi::um::oaidl::VARIANT;
+
+use super::errors::{FromVariantError, IntoVariantError};
+use super::ptr::Ptr;
+use super::types::{Int, UInt};
+use super::variant::VariantExt;
+
+/// A fieldless enum with explicit discriminants, carried in a VARIANT as its `Repr`
+///
+/// `Repr` is `i32` for `VT_I4` enums and `u32` for `VT_UI4` enums.
+pub trait ComEnum: Copy {
+    /// Integer type the enum travels as
+    type Repr: VariantExt + Copy + Into<i64> + TryFrom<i64>;
+    /// Name of the enum, used in errors
+    const NAME: &'static str;
+
+    /// The discriminant of this value
+    fn discriminant(self) -> Self::Repr;
+
+    /// The value with discriminant `d`, if the enum declares one
+    fn from_discriminant(d: Self::Repr) -> Option<Self>;
+}
+
+/// Converts a `ComEnum` into a VARIANT tagged with the `VARTYPE` of its `Repr`
+pub fn enum_into_variant<E: ComEnum>(e: E) -> Result<Ptr<VARIANT>, IntoVariantError> {
+    e.discriminant().into_variant()
+}
+
+/// Reads a `ComEnum` from a VARIANT, failing with `UnknownDiscriminant` for undeclared values. 
+/// 
+/// A `VT_INT` VARIANT is read as a `VT_I4` enum, and a `VT_UINT` one as a `VT_UI4` enum.
+pub fn enum_from_variant<E: ComEnum>(var: Ptr<VARIANT>) -> Result<E, FromVariantError> {
+    let vt = unsafe { (*var.as_ptr()).n1.n2().vt } as u32;
+    let value: i64 = match (E::Repr::VARTYPE, vt) {
+        (VT_I4, VT_INT) => i32::from(Int::from_variant(var)?).into(), 
+        (VT_UI4, VT_UINT) => u32::from(UInt::from_variant(var)?).into(), 
+        _ => E::Repr::from_variant(var)?.into(),
+    };
+    E::Repr::try_from(value).ok()
+        .and_then(E::from_discriminant)
+        .ok_or_else(|| FromVariantError::UnknownDiscriminant{value: value, target: E::NAME})
+}
+
+/// Declares a fieldless enum with explicit discriminants and implements `ComEnum` and `VariantExt` for it
+///
+/// The type after the name is the representation: `i32` marshals as `VT_I4`, `u32` as `VT_UI4`.
+/// 
+/// For an enum declared elsewhere, `com_enum!(impl Name: repr { Variant, ... })` implements 
+/// the traits only. The enum must be `Copy` and list every variant to be accepted on decode.
+///
+/// ## Example
+///
+/// ```
+/// #[macro_use] extern crate oaidl;
+///
+/// use oaidl::VariantExt;
+///
+/// com_enum! {
+///     #[derive(Debug, PartialEq)]
+///     pub enum XlDirection: i32 {
+///         Up = -4162,
+///         Down = -4121,
+///     }
+/// }
+///
+/// #[derive(Clone, Copy, Debug, PartialEq)]
+/// enum XlSheetVisibility {
+///     Visible = -1,
+///     Hidden = 0,
+///     VeryHidden = 2,
+/// }
+///
+/// com_enum!(impl XlSheetVisibility: i32 { Visible, Hidden, VeryHidden });
+///
+/// fn main() {
+///     let pvar = XlDirection::Down.into_variant().unwrap();
+///     assert_eq!(XlDirection::from_variant(pvar).unwrap(), XlDirection::Down);
+///
+///     let pvar = XlSheetVisibility::VeryHidden.into_variant().unwrap();
+///     assert_eq!(XlSheetVisibility::from_variant(pvar).unwrap(), XlSheetVisibility::VeryHidden);
+/// }
+/// ```
+#[macro_export]
+macro_rules! com_enum {
+    (impl $name:ident : $repr:ident { $($var:ident),* $(,)* }) => {
+        impl $crate::ComEnum for $name {
+            type Repr = $repr;
+            const NAME: &'static str = stringify!($name);
+
+            fn discriminant(self) -> $repr {
+                self as $repr
+            }
+
+            fn from_discriminant(d: $repr) -> Option<$name> {
+                match d {
+                    $(x if x == $name::$var as $repr => Some($name::$var),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl $crate::VariantExt for $name {
+            const VARTYPE: u32 = <$repr as $crate::VariantExt>::VARTYPE;
+
+            fn from_variant(var: $crate::Ptr<$crate::__VARIANT>) -> Result<$name, $crate::FromVariantError> {
+                $crate::enum_from_variant(var)
+            }
+
+            fn into_variant(self) -> Result<$crate::Ptr<$crate::__VARIANT>, $crate::IntoVariantError> {
+                $crate::enum_into_variant(self)
+            }
+        }
+    };
+    (
+        $(#[$m:meta])*
+        $vis:vis enum $name:ident : $repr:ident {
+            $($(#[$vm:meta])* $var:ident = $d:expr),* $(,)*
+        }
+    ) => {
+        $(#[$m])*
+        #[derive(Clone, Copy)]
+        #[repr($repr)]
+        $vis enum $name {
+            $($(#[$vm])* $var = $d),*
+        }
+
+        $crate::com_enum!(impl $name: $repr { $($var),* });
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use winapi::shared::wtypes::{VT_I4, VT_UI4};
+    use super::super::errors::FromVariantError;
+    use super::super::types::{Int, UInt};
+    use super::super::variant::VariantExt;
+
+    com_enum! {
+        #[derive(Debug, PartialEq)]
+        enum Direction: i32 {
+            Up = -4162,
+            Down = -4121,
+        }
+    }
+
+    com_enum! {
+        #[derive(Debug, PartialEq)]
+        enum Flags: u32 {
+            Low = 1,
+            High = 0x8000_0000,
+        }
+    }
+
+    #[test]
+    fn test_i4() {
+        assert_eq!(Direction::VARTYPE, VT_I4);
+        let pvar = Direction::Down.into_variant().unwrap();
+        assert_eq!(unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32, VT_I4);
+        assert_eq!(Direction::from_variant(pvar).unwrap(), Direction::Down);
+    }
+
+    #[test]
+    fn test_ui4() {
+        assert_eq!(Flags::VARTYPE, VT_UI4);
+        let pvar = Flags::High.into_variant().unwrap();
+        assert_eq!(Flags::from_variant(pvar).unwrap(), Flags::High);
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Visibility {
+        Visible = -1,
+        Hidden = 0,
+    }
+    com_enum!(impl Visibility: i32 { Visible, Hidden });
+
+    #[test]
+    fn test_existing_enum() {
+        assert_eq!(Visibility::VARTYPE, VT_I4);
+        let pvar = Visibility::Visible.into_variant().unwrap();
+        assert_eq!(Visibility::from_variant(pvar).unwrap(), Visibility::Visible);
+    }
+
+    #[test]
+    fn test_int_vartypes() {
+        let pvar = Int::from(-4121).into_variant().unwrap();
+        assert_eq!(Direction::from_variant(pvar).unwrap(), Direction::Down);
+        let pvar = UInt::from(0x8000_0000).into_variant().unwrap();
+        assert_eq!(Flags::from_variant(pvar).unwrap(), Flags::High);
+        let pvar = Int::from(1).into_variant().unwrap();
+        match Flags::from_variant(pvar) {
+            Err(FromVariantError::VarTypeDoesNotMatch{..}) => {},
+            _ => panic!("expected a vartype mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_unknown_discriminant() {
+        let pvar = 7i32.into_variant().unwrap();
+        match Direction::from_variant(pvar) {
+            Err(FromVariantError::UnknownDiscriminant{value, target}) => {
+                assert_eq!(value, 7);
+                assert_eq!(target, "Direction");
+            },
+            _ => panic!("Error")
+        }
+    }
+}
@@ -1,3 +1,9 @@
+#[cfg(feature = "serde")]
+use std::fmt;
+use std::io;
+
+use rust_decimal::Decimal;
+
 /// Supererror type SafeArray element conversion errors
 #[derive(Debug, Fail)]
 pub enum ElementError {
@@ -7,6 +13,9 @@ pub enum ElementError {
     /// Holds IntoSafeArrElemErrors
     #[fail(display = "{}", _0)]
     Into(Box<IntoSafeArrElemError>), 
+    /// Holds the FromVariantError an element conversion failed with
+    #[fail(display = "{}", _0)]
+    FromVariant(Box<FromVariantError>),
 }
 
 /// Errors for converting from C/C++ data structure to Rust types
@@ -30,6 +39,32 @@ pub enum FromSafeArrElemError {
     /// IDispatch pointer during conversion was null
     #[fail(display = "IDispatch pointer is null")]
     DispatchPtrNull,
+    /// A BSTR was not valid UTF-16 and strict decoding was requested
+    #[fail(display = "BSTR is not valid UTF-16")]
+    InvalidUtf16,
+    /// A `DecodeLimits` limit was exceeded
+    #[fail(display = "{}", _0)]
+    LimitExceeded(LimitError),
+    /// The element type only supports one-dimensional arrays
+    #[fail(display = "element type does not support {}-dimensional indices", dims)]
+    DimsUnsupported {
+        /// number of indices given
+        dims: usize
+    },
+    /// The number of indices given does not match the number of dimensions of the array
+    #[fail(display = "{} indices given for a {}-dimensional array", found, expected)]
+    IndexDimsMismatch {
+        /// dimensions of the array
+        expected: u32, 
+        /// number of indices given
+        found: usize
+    },
+    /// A NaN or infinity was decoded under `FloatPolicy::RejectNonFinite`
+    #[fail(display = "non-finite float {} rejected by FloatPolicy", value)]
+    NonFiniteFloat {
+        /// the rejected value
+        value: f64
+    },
 }
 
 /// Errors for converting into C/C++ data structures from Rust types
@@ -56,6 +91,42 @@ pub enum IntoSafeArrElemError {
     /// Encapsulates a `IntoVariantError`
     #[fail(display = "IntoVariantError: {}", _0)]
     IntoVariantError(Box<IntoVariantError>),
+    /// The element type only supports one-dimensional arrays
+    #[fail(display = "element type does not support {}-dimensional indices", dims)]
+    DimsUnsupported {
+        /// number of indices given
+        dims: usize
+    },
+    /// The number of indices given does not match the number of dimensions of the array
+    #[fail(display = "{} indices given for a {}-dimensional array", found, expected)]
+    IndexDimsMismatch {
+        /// dimensions of the array
+        expected: u32, 
+        /// number of indices given
+        found: usize
+    },
+    /// A NaN or infinity was encoded under `FloatPolicy::RejectNonFinite`
+    #[fail(display = "non-finite float {} rejected by FloatPolicy", value)]
+    NonFiniteFloat {
+        /// the rejected value
+        value: f64
+    },
+    /// `IRecordInfo::PutField` failed for a field of a `VT_RECORD` element
+    #[fail(display = "IRecordInfo::PutField failed for field {} with HRESULT = 0x{:x}", field, hr)]
+    RecordPutFieldFailed {
+        /// name of the field
+        field: &'static str, 
+        /// HRESULT returned
+        hr: i32
+    },
+    /// The element's vartype does not match the array's element type
+    #[fail(display = "element vartype {} does not match array vartype {}", found, expected)]
+    VarTypeDoesNotMatch {
+        /// the array's element vartype
+        expected: u32, 
+        /// the element's vartype
+        found: u32
+    },
 }
 
 impl From<FromSafeArrElemError> for ElementError {
@@ -70,6 +141,12 @@ impl From<IntoSafeArrElemError> for ElementError {
     }
 }
 
+impl From<FromVariantError> for ElementError {
+    fn from(fve: FromVariantError) -> ElementError {
+        ElementError::FromVariant(Box::new(fve))
+    }
+}
+
 /// Supererror for SafeArray errors
 #[derive(Debug, Fail)]
 pub enum SafeArrayError {
@@ -124,7 +201,49 @@ pub enum FromSafeArrayError{
         index: usize, 
         /// The element error encapsulating the failure
         element: Box<ElementError>
-    }
+    },
+    /// Call to SafeArrayAccessData failed
+    #[fail(display = "SafeArrayAccessData failed with HRESULT = 0x{:x}", hr)]
+    AccessDataFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// The array's `cbElements` is not the size of the element type it is read as
+    #[fail(display = "SAFEARRAY elements are {} bytes but {} were expected", found, expected)]
+    ElementSizeMismatch {
+        /// size of the element type in bytes
+        expected: usize, 
+        /// `cbElements` of the array
+        found: usize
+    },
+    /// `SAFEARRAY` pointer was null
+    #[fail(display = "SAFEARRAY pointer is null")]
+    SafeArrayPtrNull,
+    /// Pinning the array with `SafeArrayAddRef`/`SafeArrayLock` failed
+    #[fail(display = "SAFEARRAY lock failed with HRESULT = 0x{:x}", hr)]
+    LockFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// The caller's cancellation check asked for the conversion to stop
+    #[fail(display = "conversion cancelled after {} of {} elements", done, total)]
+    Cancelled {
+        /// elements copied before cancellation
+        done: usize, 
+        /// elements in the array
+        total: usize
+    },
+    /// A `DecodeLimits` limit was exceeded
+    #[fail(display = "{}", _0)]
+    LimitExceeded(LimitError),
+    /// A row array did not have one element per field
+    #[fail(display = "row has {} elements but {} fields were expected", found, expected)]
+    RowLengthMismatch {
+        /// number of fields
+        expected: usize, 
+        /// number of elements in the array
+        found: usize
+    },
 }
 
 /// Represents the different ways converting into `SAFEARRAY` can fail
@@ -141,6 +260,52 @@ pub enum IntoSafeArrayError {
     /// The called to `SafeArrayCreate` failed
     #[fail(display = "safe array creation failed")]
     SafeArrayCreateFailed,
+    /// `IRecordInfo::PutField` failed for a field of a record
+    #[fail(display = "IRecordInfo::PutField failed for field {} at index {} with HRESULT = 0x{:x}", field, index, hr)]
+    RecordPutFieldFailed {
+        /// index of the record in the array
+        index: usize, 
+        /// the field being written
+        field: &'static str, 
+        /// HRESULT returned
+        hr: i32
+    },
+    /// Call to SafeArrayAccessData failed
+    #[fail(display = "SafeArrayAccessData failed with HRESULT = 0x{:x}", hr)]
+    AccessDataFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// Call to SafeArrayRedim failed
+    #[fail(display = "SafeArrayRedim failed with HRESULT = 0x{:x}", hr)]
+    RedimFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// Call to SafeArrayCopy failed
+    #[fail(display = "SafeArrayCopy failed with HRESULT = 0x{:x}", hr)]
+    CopyFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// The number of elements given does not match the number the bounds describe
+    #[fail(display = "bounds describe {} elements but {} were given", expected, found)]
+    ElementCountMismatch {
+        /// element count the bounds describe
+        expected: usize, 
+        /// number of elements given
+        found: usize
+    },
+    /// The rows of a 2-D conversion were not all the same length
+    #[fail(display = "row {} has {} elements but the first row has {}", row, found, expected)]
+    JaggedRows {
+        /// index of the offending row
+        row: usize, 
+        /// length of the first row
+        expected: usize, 
+        /// length of the offending row
+        found: usize
+    },
 }
 
 impl From<FromSafeArrayError> for SafeArrayError {
@@ -210,6 +375,37 @@ pub enum FromVariantError {
     /// Encapsulates BString errors
     #[fail(display = "{}", _0)]
     AllocBStr(BStringError),
+    /// `VariantExt` for references is encode-only; decode into the owned type instead
+    #[fail(display = "cannot decode vartype {} into a reference", vartype)]
+    BorrowedTarget {
+        /// the vartype of the reference impl
+        vartype: u32
+    },
+    /// A string VARIANT could not be parsed as the requested type
+    #[fail(display = "could not parse {:?} as {}", input, target)]
+    ParseFailed {
+        /// the string that failed to parse
+        input: String, 
+        /// name of the type being parsed
+        target: &'static str
+    },
+    /// The pointer held by a `VT_BYREF` VARIANT was null
+    #[fail(display = "VT_BYREF pointer is null")]
+    ByRefPtrNull,
+    /// An integer VARIANT held a value the target enum does not declare
+    #[fail(display = "{} is not a valid discriminant of {}", value, target)]
+    UnknownDiscriminant {
+        /// the discriminant found
+        value: i64, 
+        /// name of the enum being decoded
+        target: &'static str
+    },
+    /// A NaN or infinity was decoded under `FloatPolicy::RejectNonFinite`
+    #[fail(display = "non-finite float {} rejected by FloatPolicy", value)]
+    NonFiniteFloat {
+        /// the rejected value
+        value: f64
+    },
     /// `IUnknown` pointer during conversion was null
     #[fail(display = "IUnknown pointer is null")]
     UnknownPtrNull,
@@ -228,6 +424,70 @@ pub enum FromVariantError {
     /// Conversion into `SAFEARRAY` failed.
     #[fail(display = "Safe array conversion failed: {}", _0)]
     SafeArrConvFailed(Box<SafeArrayError>),
+    /// `VariantCopy`/`VariantCopyInd` failed with the `HRESULT` stored within
+    #[fail(display = "VariantCopy failed with HRESULT = 0x{:x}", hr)]
+    VariantCopyFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// `CLIPDATA` used a clipboard format other than a Windows clipboard format (`CF_*`)
+    #[fail(display = "clipboard format is not supported: {}", format)]
+    ClipFormatUnsupported {
+        /// the `ulClipFmt` value that was found
+        format: i32
+    },
+    /// The vartype found in the `VARIANT` has no dynamic representation
+    #[fail(display = "vartype is not supported by dynamic conversion: {}", vartype)]
+    UnknownVarType {
+        /// the vartype that was found
+        vartype: u32
+    },
+    /// `QueryInterface` for `IStream` failed with the `HRESULT` stored within
+    #[fail(display = "QueryInterface for IStream failed with HRESULT = 0x{:x}", hr)]
+    StreamQueryFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// `IStream::Read` failed with the `HRESULT` stored within
+    #[fail(display = "IStream::Read failed with HRESULT = 0x{:x}", hr)]
+    StreamReadFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// A `DecodeLimits` limit was exceeded
+    #[fail(display = "{}", _0)]
+    LimitExceeded(LimitError),
+    /// A `VT_RECORD` VARIANT had a null `pvRecord` or `pRecInfo`
+    #[fail(display = "VT_RECORD VARIANT has a null record or IRecordInfo")]
+    RecordPtrNull,
+    /// `IRecordInfo::GetField` failed for a field of a record
+    #[fail(display = "IRecordInfo::GetField failed for field {} with HRESULT = 0x{:x}", field, hr)]
+    RecordGetFieldFailed {
+        /// name of the field
+        field: String, 
+        /// HRESULT returned
+        hr: i32
+    },
+    /// A fixed-length array held the wrong number of elements
+    #[fail(display = "array has {} elements but {} were expected", found, expected)]
+    ArrayLengthMismatch {
+        /// the fixed length
+        expected: usize, 
+        /// number of elements found
+        found: usize
+    },
+    /// A `VT_DATE` held a value outside the years 100 to 9999, or NaN
+    #[fail(display = "DATE {} is out of range", value)]
+    DateOutOfRange {
+        /// the raw DATE value
+        value: f64
+    },
+    /// A `VT_DECIMAL` held a fraction, or a negative value for an unsigned target
+    #[fail(display = "VT_DECIMAL value is not a whole number in the range of {}", target)]
+    DecimalOutOfRange {
+        /// name of the integer type being decoded
+        target: &'static str
+    },
 }
 
 /// Encapsulates errors that can occur during conversion into VARIANT
@@ -236,9 +496,59 @@ pub enum IntoVariantError {
     /// Encapsulates a `BStringError`
     #[fail(display = "{}", _0)]
     AllocBStrFailed(BStringError),
+    /// `CoTaskMemAlloc` failed to allocate the payload
+    #[fail(display = "CoTaskMemAlloc failed for len: {}", len)]
+    CoTaskMemAllocFailed {
+        /// the number of bytes requested
+        len: usize
+    },
     /// Encapsulates a `SafeArrayError`
     #[fail(display = "SafeArray conversion failed: {}", _0)]
     SafeArrConvFailed(Box<SafeArrayError>),
+    /// `SHCreateMemStream` failed to create the stream
+    #[fail(display = "SHCreateMemStream failed for len: {}", len)]
+    StreamCreateFailed {
+        /// the number of bytes requested
+        len: usize
+    },
+    /// Reading the source data failed
+    #[fail(display = "reading the source failed: {}", _0)]
+    SourceReadFailed(#[cause] io::Error),
+    /// A NaN or infinity was encoded under `FloatPolicy::RejectNonFinite`
+    #[fail(display = "non-finite float {} rejected by FloatPolicy", value)]
+    NonFiniteFloat {
+        /// the rejected value
+        value: f64
+    },
+    /// `IRecordInfo::RecordCreate` returned null
+    #[fail(display = "IRecordInfo::RecordCreate failed")]
+    RecordCreateFailed,
+    /// `Record::into_fields` failed to convert a field
+    #[fail(display = "record field conversion failed with {}", _0)]
+    RecordFieldConvFailed(Box<ElementError>),
+    /// `IRecordInfo::PutField` failed for a field of a record
+    #[fail(display = "IRecordInfo::PutField failed for field {} with HRESULT = 0x{:x}", field, hr)]
+    RecordPutFieldFailed {
+        /// name of the field
+        field: &'static str, 
+        /// HRESULT returned
+        hr: i32
+    },
+    /// A string holds a NUL, which a NUL-terminated `VT_LPWSTR` cannot carry
+    #[fail(display = "string has an interior NUL at UTF-16 index {}", pos)]
+    InteriorNul {
+        /// index of the NUL in UTF-16 code units
+        pos: usize
+    },
+    /// A date falls outside the years 100 to 9999 that a `VT_DATE` represents
+    #[fail(display = "date out of range for VT_DATE")]
+    DateOutOfRange,
+    /// A 128-bit integer does not fit the 96-bit mantissa of a `VT_DECIMAL`
+    #[fail(display = "integer of magnitude {} does not fit in a VT_DECIMAL", magnitude)]
+    DecimalOverflow {
+        /// the absolute value of the integer
+        magnitude: u128
+    },
 }
 
 impl From<IntoVariantError> for IntoSafeArrElemError {
@@ -257,4 +567,531 @@ impl<I: Into<SafeArrayError>> From<I> for IntoVariantError {
     fn from(i: I) -> IntoVariantError {
         IntoVariantError::SafeArrConvFailed(Box::new(i.into()))
     }
-}
\ No newline at end of file
+}
+
+/// Ways building or converting a `VariantTable` can fail
+#[derive(Debug, Fail)]
+pub enum TableError {
+    /// A row did not hold exactly one cell per column
+    #[fail(display = "row has {} cells but the table has {} columns", found, expected)]
+    RowWidthMismatch {
+        /// number of columns in the table
+        expected: usize, 
+        /// number of cells in the offending row
+        found: usize
+    },
+    /// A cell of the header row did not hold a string
+    #[fail(display = "header cell for column {} is not a string", column)]
+    HeaderNotString {
+        /// index of the column
+        column: usize
+    },
+    /// Expected vartype did not match found vartype - runtime consistency check
+    #[fail(display = "expected vartype was not found - expected: {} - found: {}", expected, found)]
+    VarTypeDoesNotMatch {
+        /// The expected vartype
+        expected: u32, 
+        /// the found vartype
+        found: u32
+    },
+    /// A map key cell did not hold a string
+    #[fail(display = "key in row {} is not a string", row)]
+    KeyNotString {
+        /// index of the row
+        row: usize
+    },
+    /// A map key appeared in more than one row
+    #[fail(display = "key {:?} appears more than once", key)]
+    DuplicateKey {
+        /// the repeated key
+        key: String
+    },
+    /// Pointer to the `SAFEARRAY` was null
+    #[fail(display = "SAFEARRAY pointer is null")]
+    ArrayPtrNull, 
+    /// Conversion to or from `SAFEARRAY` failed.
+    #[fail(display = "Safe array conversion failed: {}", _0)]
+    SafeArrConvFailed(Box<SafeArrayError>),
+}
+
+impl<I: Into<SafeArrayError>> From<I> for TableError {
+    fn from(i: I) -> TableError {
+        TableError::SafeArrConvFailed(Box::new(i.into()))
+    }
+}
+
+/// Ways a decode can exceed the `DecodeLimits` in force
+#[derive(Clone, Copy, Debug, Fail)]
+pub enum LimitError {
+    /// The array holds more elements than allowed
+    #[fail(display = "array has {} elements, limit is {}", len, max)]
+    TooManyElements {
+        /// element count of the array
+        len: usize, 
+        /// the limit in force
+        max: usize
+    },
+    /// Arrays are nested deeper than allowed
+    #[fail(display = "arrays nested deeper than limit of {}", max)]
+    TooDeep {
+        /// the limit in force
+        max: usize
+    },
+    /// A BSTR is longer than allowed
+    #[fail(display = "BSTR has length {}, limit is {}", len, max)]
+    BStrTooLong {
+        /// length of the BSTR
+        len: usize, 
+        /// the limit in force
+        max: usize
+    },
+}
+
+impl From<LimitError> for FromSafeArrElemError {
+    fn from(le: LimitError) -> FromSafeArrElemError {
+        FromSafeArrElemError::LimitExceeded(le)
+    }
+}
+
+impl From<LimitError> for FromSafeArrayError {
+    fn from(le: LimitError) -> FromSafeArrayError {
+        FromSafeArrayError::LimitExceeded(le)
+    }
+}
+
+impl From<LimitError> for FromVariantError {
+    fn from(le: LimitError) -> FromVariantError {
+        FromVariantError::LimitExceeded(le)
+    }
+}
+
+/// Ways building a value from loosely typed input can fail
+#[derive(Debug, Fail)]
+pub enum ValueError {
+    /// The string is not a decimal number with at most four fractional digits
+    #[fail(display = "invalid currency value: {:?}", input)]
+    CurrencyParse {
+        /// the string that failed to parse
+        input: String
+    },
+    /// The value does not fit the 64-bit, four decimal place CY range
+    #[fail(display = "currency value out of range: {}", value)]
+    CurrencyOutOfRange {
+        /// the value that was out of range
+        value: f64
+    },
+    /// The date is outside the range a DATE can represent
+    #[fail(display = "date out of range")]
+    DateOutOfRange,
+    /// Excel serial 60, the 1900-02-29 that Excel's 1900 date system counts but that never happened
+    #[fail(display = "Excel serial 60 is the nonexistent 1900-02-29")]
+    PhantomLeapDay,
+    /// A currency calculation overflowed the 64-bit CY range
+    #[fail(display = "currency arithmetic overflowed")]
+    CurrencyOverflow,
+    /// A ratio had a zero denominator
+    #[fail(display = "ratio denominator was zero")]
+    ZeroDenominator,
+    /// The time is before 1601 or too far in the future for a FILETIME, or for `SystemTime`
+    #[fail(display = "time out of FILETIME range")]
+    FileTimeOutOfRange,
+    /// The string is not a GUID in registry format
+    #[fail(display = "invalid GUID: {:?}", input)]
+    GuidParse {
+        /// the string that failed to parse
+        input: String
+    },
+}
+
+/// Ways moving a VARIANT through the Global Interface Table can fail
+#[derive(Debug, Fail)]
+pub enum MarshalError {
+    /// The Global Interface Table could not be created - usually COM isn't initialised on this thread
+    #[fail(display = "Global Interface Table unavailable, HRESULT = 0x{:x}", hr)]
+    GitUnavailable {
+        /// HRESULT returned by CoCreateInstance
+        hr: i32
+    },
+    /// `RegisterInterfaceInGlobal` failed
+    #[fail(display = "RegisterInterfaceInGlobal failed with HRESULT = 0x{:x}", hr)]
+    RegisterFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// `GetInterfaceFromGlobal` failed
+    #[fail(display = "GetInterfaceFromGlobal failed with HRESULT = 0x{:x}", hr)]
+    GetFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// Decoding a by-value payload failed
+    #[fail(display = "{}", _0)]
+    Decode(Box<FromVariantError>),
+    /// Re-encoding a by-value payload failed
+    #[fail(display = "{}", _0)]
+    Encode(Box<IntoVariantError>),
+    /// An array payload holds interface pointers, which only the top level of a VARIANT can carry through the GIT
+    #[fail(display = "interface pointers nested in an array cannot be made agile")]
+    NestedInterface,
+}
+
+/// Ways a `Record` can fail to match the UDT an `IRecordInfo` describes
+#[derive(Clone, Copy, Debug, Fail)]
+pub enum RecordLayoutError {
+    /// `IRecordInfo::GetFieldNames` failed
+    #[fail(display = "IRecordInfo::GetFieldNames failed with HRESULT = 0x{:x}", hr)]
+    GetFieldNamesFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// A field of the `Record` is missing from the UDT, or comes before a field it follows in the `Record`
+    #[fail(display = "field {} at position {} is missing from the UDT or out of order", field, index)]
+    FieldMismatch {
+        /// position of the field in `Record::FIELDS`
+        index: usize, 
+        /// name of the field
+        field: &'static str
+    },
+    /// The UDT's `ITypeInfo` couldn't be read
+    #[fail(display = "reading the UDT's type info failed with HRESULT = 0x{:x}", hr)]
+    TypeInfoFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// A field of the `Record` sits at a different byte offset than the UDT declares for it
+    #[fail(display = "field {} at position {} is at offset {}, the UDT has it at {}", field, index, found, expected)]
+    OffsetMismatch {
+        /// position of the field in `Record::FIELDS`
+        index: usize, 
+        /// name of the field
+        field: &'static str, 
+        /// offset the UDT declares
+        expected: usize, 
+        /// offset in the `Record`
+        found: usize
+    },
+}
+
+/// Ways coercing a VARIANT with `VariantChangeType` can fail
+#[derive(Debug, Fail)]
+pub enum CoercionError {
+    /// The value can't be represented as the requested type at all - `DISP_E_TYPEMISMATCH`
+    #[fail(display = "can't coerce vartype {} to vartype {}", from, to)]
+    TypeMismatch {
+        /// vartype of the source VARIANT
+        from: u32, 
+        /// vartype requested
+        to: u32
+    },
+    /// The value is out of range for the requested type - `DISP_E_OVERFLOW`
+    #[fail(display = "value of vartype {} overflows vartype {}", from, to)]
+    Overflow {
+        /// vartype of the source VARIANT
+        from: u32, 
+        /// vartype requested
+        to: u32
+    },
+    /// `VariantChangeType` failed for any other reason
+    #[fail(display = "VariantChangeType from vartype {} to vartype {} failed with HRESULT = 0x{:x}", from, to, hr)]
+    ChangeTypeFailed {
+        /// vartype of the source VARIANT
+        from: u32, 
+        /// vartype requested
+        to: u32, 
+        /// HRESULT returned
+        hr: i32
+    },
+    /// The coerced VARIANT could not be decoded
+    #[fail(display = "{}", _0)]
+    Decode(Box<FromVariantError>),
+    /// The value could not be encoded as a VARIANT to coerce
+    #[fail(display = "{}", _0)]
+    Encode(Box<IntoVariantError>),
+}
+
+impl From<FromVariantError> for CoercionError {
+    fn from(fve: FromVariantError) -> CoercionError {
+        CoercionError::Decode(Box::new(fve))
+    }
+}
+
+impl From<IntoVariantError> for CoercionError {
+    fn from(ive: IntoVariantError) -> CoercionError {
+        CoercionError::Encode(Box::new(ive))
+    }
+}
+
+/// Ways VARIANT arithmetic can fail
+#[derive(Debug, Fail)]
+pub enum ArithmeticError {
+    /// The result doesn't fit any vartype the operation can produce - `DISP_E_OVERFLOW`
+    #[fail(display = "arithmetic overflowed")]
+    Overflow,
+    /// The operands can't be combined, e.g. a non-numeric string - `DISP_E_TYPEMISMATCH`
+    #[fail(display = "can't combine vartype {} with vartype {}", left, right)]
+    TypeMismatch {
+        /// vartype of the left operand
+        left: u32, 
+        /// vartype of the right operand
+        right: u32
+    },
+    /// Division by zero - `DISP_E_DIVBYZERO`
+    #[fail(display = "division by zero")]
+    DivisionByZero,
+    /// The `Var*` function failed for any other reason
+    #[fail(display = "VARIANT arithmetic failed with HRESULT = 0x{:x}", hr)]
+    OperationFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// The result could not be decoded
+    #[fail(display = "{}", _0)]
+    Decode(Box<FromVariantError>),
+    /// An operand could not be encoded as a VARIANT
+    #[fail(display = "{}", _0)]
+    Encode(Box<IntoVariantError>),
+}
+
+impl From<FromVariantError> for ArithmeticError {
+    fn from(fve: FromVariantError) -> ArithmeticError {
+        ArithmeticError::Decode(Box::new(fve))
+    }
+}
+
+impl From<IntoVariantError> for ArithmeticError {
+    fn from(ive: IntoVariantError) -> ArithmeticError {
+        ArithmeticError::Encode(Box::new(ive))
+    }
+}
+
+/// Ways formatting a VARIANT as text can fail
+#[derive(Debug, Fail)]
+pub enum FormatError {
+    /// The vartype can't be formatted this way - `DISP_E_TYPEMISMATCH`
+    #[fail(display = "can't format vartype {}", found)]
+    TypeMismatch {
+        /// vartype of the VARIANT
+        found: u32
+    },
+    /// The `VarFormat*` function failed for any other reason, e.g. an invalid format string
+    #[fail(display = "formatting failed with HRESULT = 0x{:x}", hr)]
+    FormatFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// The value could not be encoded as a VARIANT to format
+    #[fail(display = "{}", _0)]
+    Encode(Box<IntoVariantError>),
+}
+
+impl From<IntoVariantError> for FormatError {
+    fn from(ive: IntoVariantError) -> FormatError {
+        FormatError::Encode(Box::new(ive))
+    }
+}
+
+/// Ways converting a `Decimal` to a `Currency` can fail
+#[derive(Clone, Copy, Debug, Fail)]
+pub enum CurrencyError {
+    /// The value has nonzero digits past the four decimal places CY keeps
+    #[fail(display = "{} has more than four decimal places", value)]
+    TooPrecise {
+        /// the value converted
+        value: Decimal
+    },
+    /// The value is outside the 64-bit CY range
+    #[fail(display = "{} is out of the currency range", value)]
+    Overflow {
+        /// the value converted
+        value: Decimal
+    },
+}
+
+/// Ways calls on COM interface pointers can fail
+#[derive(Clone, Copy, Debug, Fail)]
+pub enum ComError {
+    /// `QueryInterface` failed - usually `E_NOINTERFACE`
+    #[fail(display = "QueryInterface failed with HRESULT = 0x{:x}", hr)]
+    QueryInterfaceFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// `GetRecordInfoFromGuids` failed - usually because the type library isn't registered
+    #[fail(display = "GetRecordInfoFromGuids failed with HRESULT = 0x{:x}", hr)]
+    RecordInfoFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// `VariantToPropVariant` failed - usually a VARTYPE PROPVARIANT can't hold
+    #[fail(display = "VariantToPropVariant failed with HRESULT = 0x{:x}", hr)]
+    VariantToPropVariantFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// `PropVariantToVariant` failed - usually a PROPVARIANT-only VARTYPE with no VARIANT form
+    #[fail(display = "PropVariantToVariant failed with HRESULT = 0x{:x}", hr)]
+    PropVariantToVariantFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+}
+
+impl ComError {
+    /// The HRESULT the failed call returned
+    pub fn hr(&self) -> i32 {
+        match *self {
+            ComError::QueryInterfaceFailed{hr} 
+            | ComError::RecordInfoFailed{hr} 
+            | ComError::VariantToPropVariantFailed{hr} 
+            | ComError::PropVariantToVariantFailed{hr} => hr,
+        }
+    }
+}
+
+/// Ways serializing to or deserializing from VARIANTs can fail
+/// 
+/// serde needs a `std::error::Error`, so this implements `Fail` through that rather than deriving it.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum SerdeError {
+    /// A message from a `Serialize` or `Deserialize` impl, or from serde itself
+    Custom(String),
+    /// A map key that doesn't serialize as a string
+    KeyNotString {
+        /// vartype the key serialized as
+        vartype: u32
+    },
+    /// The vartype has no serde form, e.g. an interface pointer
+    NoSerdeForm {
+        /// vartype of the value
+        vartype: u32
+    },
+    /// The serialized value could not be encoded as a VARIANT
+    Encode(Box<IntoVariantError>),
+    /// The VARIANT could not be decoded
+    Decode(Box<FromVariantError>),
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SerdeError::Custom(ref msg) => write!(f, "{}", msg),
+            SerdeError::KeyNotString{vartype} => write!(f, "map key of vartype {} is not a string", vartype),
+            SerdeError::NoSerdeForm{vartype} => write!(f, "vartype {} has no serde form", vartype),
+            SerdeError::Encode(ref ive) => write!(f, "{}", ive),
+            SerdeError::Decode(ref fve) => write!(f, "{}", fve),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::std::error::Error for SerdeError {}
+
+#[cfg(feature = "serde")]
+impl ::serde::ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> SerdeError {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::de::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> SerdeError {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<IntoVariantError> for SerdeError {
+    fn from(ive: IntoVariantError) -> SerdeError {
+        SerdeError::Encode(Box::new(ive))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<FromVariantError> for SerdeError {
+    fn from(fve: FromVariantError) -> SerdeError {
+        SerdeError::Decode(Box::new(fve))
+    }
+}
+
+/// Ways converting VARIANTs to JSON can fail
+#[cfg(feature = "json")]
+#[derive(Debug, Fail)]
+pub enum JsonError {
+    /// The vartype has no JSON form, e.g. an interface pointer
+    #[fail(display = "vartype {} has no JSON form", vartype)]
+    NoJsonForm {
+        /// vartype of the value
+        vartype: u32
+    },
+    /// JSON numbers can't be NaN or infinite
+    #[fail(display = "{} has no JSON form", value)]
+    NonFiniteFloat {
+        /// the value
+        value: f64
+    },
+    /// A JSON object needs a 2-D array, which `Variants` can't hold
+    #[fail(display = "JSON objects have no Variants form")]
+    ObjectNotVariants,
+    /// The VARIANT could not be decoded
+    #[fail(display = "{}", _0)]
+    Decode(Box<FromVariantError>),
+}
+
+#[cfg(feature = "json")]
+impl From<FromVariantError> for JsonError {
+    fn from(fve: FromVariantError) -> JsonError {
+        JsonError::Decode(Box::new(fve))
+    }
+}
+
+/// Ways converting to and from WinRT `PropertyValue` boxes can fail
+#[cfg(feature = "winrt")]
+#[derive(Clone, Copy, Debug, Fail)]
+pub enum WinRtError {
+    /// `RoGetActivationFactory` for `Windows.Foundation.PropertyValue` failed - 
+    /// usually because the Windows Runtime isn't initialized on this thread
+    #[fail(display = "PropertyValue activation failed with HRESULT = 0x{:x}", hr)]
+    ActivationFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// An `IPropertyValueStatics::Create*` call failed
+    #[fail(display = "PropertyValue creation failed with HRESULT = 0x{:x}", hr)]
+    CreateFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// An `IPropertyValue` getter failed
+    #[fail(display = "IPropertyValue call failed with HRESULT = 0x{:x}", hr)]
+    CallFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// `WindowsCreateString` failed
+    #[fail(display = "HSTRING creation failed with HRESULT = 0x{:x}", hr)]
+    HStringFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// The object does not implement `IPropertyValue`
+    #[fail(display = "object is not a PropertyValue, QueryInterface returned 0x{:x}", hr)]
+    NotAPropertyValue {
+        /// HRESULT returned
+        hr: i32
+    },
+    /// The value's vartype has no WinRT `PropertyType`
+    #[fail(display = "vartype {} has no PropertyType equivalent", vartype)]
+    NoEquivalent {
+        /// the vartype of the value
+        vartype: u32
+    },
+    /// The `PropertyType` has no `Variants` arm
+    #[fail(display = "PropertyType {} is not supported", property_type)]
+    UnsupportedPropertyType {
+        /// the `Windows.Foundation.PropertyType` value
+        property_type: i32
+    },
+}
@@ -0,0 +1,236 @@
+//! # Format
+//! VB style text rendering of `VARIANT`s through `VarFormat` and friends. 
+//! 
+//! [`format_variant`] takes the format strings of VBA's `Format` - `"#,##0.00"`, 
+//! `"yyyy-mm-dd"` or named formats such as `"Short Date"` and `"Percent"`. 
+//! [`format_number`] and [`format_date_time`] mirror `FormatNumber` and 
+//! `FormatDateTime`. The value is never modified. 
+//! 
+//! [`format_variant`]: fn.format_variant.html
+//! [`format_number`]: fn.format_number.html
+//! [`format_date_time`]: fn.format_date_time.html
+use std::ptr::null_mut;
+
+use widestring::U16String;
+
+use winapi::ctypes::c_int;
+use winapi::shared::minwindef::ULONG;
+use winapi::shared::ntdef::{HRESULT, LCID};
+use winapi::shared::wtypes::BSTR;
+use winapi::um::oaidl::{LPVARIANT, VARIANT};
+use winapi::um::oleauto::SysFreeString;
+
+use super::bstr::BStringExt;
+use super::codes;
+use super::dtor::{FreeVariant, PtrDestructor};
+use super::errors::FormatError;
+use super::options::Locale;
+use super::ptr::Ptr;
+use super::types::SCode;
+use super::variant::Variants;
+
+/// The named formats of `FormatDateTime`
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DateFormat {
+    /// The date and/or time, whichever parts are non-zero - `vbGeneralDate`
+    General = 0,
+    /// The long date format of the locale - `vbLongDate`
+    LongDate = 1,
+    /// The short date format of the locale - `vbShortDate`
+    ShortDate = 2,
+    /// The long time format of the locale - `vbLongTime`
+    LongTime = 3,
+    /// Hours and minutes, 24 hour clock - `vbShortTime`
+    ShortTime = 4,
+}
+
+/// The options of `FormatNumber`. `None` uses the locale's setting. 
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{format_number, NumberFormat, VariantExt};
+/// 
+/// fn main() {
+///     let pvar = (-5f64).into_variant().unwrap();
+///     let nf = NumberFormat { digits: Some(0), negative_parens: Some(true), ..NumberFormat::default() };
+///     assert_eq!(format_number(&pvar, &nf).unwrap(), "(5)");
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct NumberFormat {
+    /// Digits after the decimal separator
+    pub digits: Option<u8>,
+    /// Whether values between -1 and 1 get a leading zero
+    pub leading_zero: Option<bool>,
+    /// Whether negative values are put in parentheses instead of getting a minus sign
+    pub negative_parens: Option<bool>,
+    /// Whether digits are grouped with the locale's thousands separator
+    pub group_digits: Option<bool>,
+}
+
+/// Formats the value with a VBA `Format` format string in the user's default locale. 
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{format_variant, VariantExt};
+/// 
+/// fn main() {
+///     let pvar = 42i32.into_variant().unwrap();
+///     assert_eq!(format_variant(&pvar, "0000").unwrap(), "0042");
+/// }
+/// ```
+pub fn format_variant(var: &Ptr<VARIANT>, format: &str) -> Result<String, FormatError> {
+    let mut wide = to_wide(format);
+    let mut out: BSTR = null_mut();
+    let hr = unsafe { VarFormat(var.as_ptr(), wide.as_mut_ptr(), 0, 0, 0, &mut out) };
+    bstr_result(hr, out, var)
+}
+
+/// Formats the value with a VBA `Format` format string in `locale`. 
+pub fn format_variant_in(var: &Ptr<VARIANT>, format: &str, locale: Locale) -> Result<String, FormatError> {
+    let mut wide = to_wide(format);
+    let mut tokens: Vec<u8> = vec![0; 256];
+    loop {
+        let mut used: c_int = 0;
+        let hr = unsafe {
+            VarTokenizeFormatString(wide.as_mut_ptr(), tokens.as_mut_ptr(), tokens.len() as c_int, 0, 0, locale.lcid(), &mut used)
+        };
+        if SCode::from(hr) == codes::DISP_E_BUFFERTOOSMALL && tokens.len() < 0x1_0000 {
+            let len = tokens.len() * 2;
+            tokens.resize(len, 0);
+            continue;
+        }
+        if hr != 0 {
+            return Err(FormatError::FormatFailed{hr: hr});
+        }
+        break;
+    }
+    let mut out: BSTR = null_mut();
+    let hr = unsafe { VarFormatFromTokens(var.as_ptr(), wide.as_mut_ptr(), tokens.as_mut_ptr(), 0, &mut out, locale.lcid()) };
+    bstr_result(hr, out, var)
+}
+
+/// Formats a number like VBA's `FormatNumber`. 
+pub fn format_number(var: &Ptr<VARIANT>, options: &NumberFormat) -> Result<String, FormatError> {
+    let digits = options.digits.map_or(-1, c_int::from);
+    let mut out: BSTR = null_mut();
+    let hr = unsafe {
+        VarFormatNumber(var.as_ptr(), digits, tristate(options.leading_zero), tristate(options.negative_parens), tristate(options.group_digits), 0, &mut out)
+    };
+    bstr_result(hr, out, var)
+}
+
+/// Formats a date like VBA's `FormatDateTime`. 
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{format_date_time, Date, DateFormat, VariantExt};
+/// 
+/// fn main() {
+///     let pvar = Date::from(0.75f64).into_variant().unwrap();
+///     assert_eq!(format_date_time(&pvar, DateFormat::ShortTime).unwrap(), "18:00");
+/// }
+/// ```
+pub fn format_date_time(var: &Ptr<VARIANT>, format: DateFormat) -> Result<String, FormatError> {
+    let mut out: BSTR = null_mut();
+    let hr = unsafe { VarFormatDateTime(var.as_ptr(), format as c_int, 0, &mut out) };
+    bstr_result(hr, out, var)
+}
+
+impl Variants {
+    /// Formats the value with a VBA `Format` format string, see `format_variant`. 
+    pub fn format(&self, format: &str) -> Result<String, FormatError> {
+        let pvar = self.clone().into_variant()?;
+        let ret = format_variant(&pvar, format);
+        FreeVariant::destroy(pvar);
+        ret
+    }
+}
+
+//VB's TriState - vbUseDefault, vbTrue and vbFalse
+fn tristate(opt: Option<bool>) -> c_int {
+    match opt {
+        None => -2, 
+        Some(true) => -1, 
+        Some(false) => 0,
+    }
+}
+
+fn to_wide(s: &str) -> Vec<u16> {
+    let mut wide: Vec<u16> = s.encode_utf16().collect();
+    wide.push(0);
+    wide
+}
+
+fn bstr_result(hr: HRESULT, out: BSTR, var: &Ptr<VARIANT>) -> Result<String, FormatError> {
+    if SCode::from(hr) == codes::DISP_E_TYPEMISMATCH {
+        let vt = unsafe {(*var.as_ptr()).n1.n2()}.vt as u32;
+        return Err(FormatError::TypeMismatch{found: vt});
+    }
+    if hr != 0 {
+        return Err(FormatError::FormatFailed{hr: hr});
+    }
+    if out.is_null() {
+        return Ok(String::new());
+    }
+    //allocated by oleaut32, so not counted by leak_tracking
+    let s = U16String::from_bstr(out).to_string_lossy();
+    unsafe { SysFreeString(out) };
+    Ok(s)
+}
+
+#[link(name="OleAut32")]
+extern "system" {
+    fn VarFormat(pvarIn: LPVARIANT, pstrFormat: *mut u16, iFirstDay: c_int, iFirstWeek: c_int, dwFlags: ULONG, pbstrOut: *mut BSTR) -> HRESULT;
+    fn VarFormatNumber(pvarIn: LPVARIANT, iNumDig: c_int, iIncLead: c_int, iUseParens: c_int, iGroup: c_int, dwFlags: ULONG, pbstrOut: *mut BSTR) -> HRESULT;
+    fn VarFormatDateTime(pvarIn: LPVARIANT, iNamedFormat: c_int, dwFlags: ULONG, pbstrOut: *mut BSTR) -> HRESULT;
+    fn VarTokenizeFormatString(pstrFormat: *mut u16, rgbTok: *mut u8, cbTok: c_int, iFirstDay: c_int, iFirstWeek: c_int, lcid: LCID, pcbActual: *mut c_int) -> HRESULT;
+    fn VarFormatFromTokens(pvarIn: LPVARIANT, pstrFormat: *mut u16, pbTokCur: *mut u8, dwFlags: ULONG, pbstrOut: *mut BSTR, lcid: LCID) -> HRESULT;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::leak_tracking;
+    use super::super::types::Date;
+    use super::super::variant::VariantExt;
+    #[test]
+    fn test_format_variant() {
+        leak_tracking::assert_balanced(|| {
+            let pvar = 1234.5f64.into_variant().unwrap();
+            assert_eq!(format_variant_in(&pvar, "#,##0.00", Locale::INVARIANT).unwrap(), "1,234.50");
+            assert_eq!(format_variant_in(&pvar, "#,##0.00", Locale::from_langid(0x07, 0x01)).unwrap(), "1.234,50");
+            FreeVariant::destroy(pvar);
+        });
+        assert_eq!(Variants::I32(7).format("000").unwrap(), "007");
+    }
+
+    #[test]
+    fn test_format_number_date() {
+        //separators come from the user's locale, so only digits are checked
+        let pvar = 1234f64.into_variant().unwrap();
+        let nf = NumberFormat { digits: Some(0), group_digits: Some(false), ..NumberFormat::default() };
+        assert_eq!(format_number(&pvar, &nf).unwrap(), "1234");
+        FreeVariant::destroy(pvar);
+        let pvar = Date::from(0.5f64).into_variant().unwrap();
+        let time = format_date_time(&pvar, DateFormat::ShortTime).unwrap();
+        assert!(time.starts_with("12") && time.ends_with("00"), "{}", time);
+        FreeVariant::destroy(pvar);
+    }
+
+    #[test]
+    fn test_format_mismatch() {
+        let pvar = Variants::Unknown(None).into_variant().unwrap();
+        assert!(format_number(&pvar, &NumberFormat::default()).is_err());
+        FreeVariant::destroy(pvar);
+    }
+}
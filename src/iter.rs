@@ -0,0 +1,132 @@
+//! # Iter
+//! Streaming reads of 1-D SAFEARRAYs.
+//!
+//! [`SafeArrayIter`] reads one element per `next()` instead of collecting the
+//! whole array into a `Vec` first, so scanning or filtering a large array from
+//! a COM server needs no more memory than the element in hand.
+//!
+//! [`SafeArrayIter`]: struct.SafeArrayIter.html
+use std::marker::PhantomData;
+use std::ptr::null_mut;
+
+use winapi::um::oaidl::SAFEARRAY;
+
+use super::array::{get_bounds, get_vartype, SafeArrayDestructor, SafeArrayElement, SafeArrayGetDim, SafeArrayLock, SafeArrayUnlock};
+use super::errors::{ElementError, FromSafeArrayError};
+
+/// Iterator over the elements of a 1-D SAFEARRAY, read on demand.
+///
+/// The array is locked while the iterator lives, so it cannot be resized or destroyed
+/// underneath it, and is unlocked and destroyed when the iterator is dropped.
+/// A failed element is yielded as an `Err` and iteration carries on with the next one.
+///
+/// ## Example
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::{SafeArrayExt, SafeArrayIter};
+///
+/// fn main() {
+///     let psa = (0..1000i32).into_iter().into_safearray().unwrap();
+///     let iter = SafeArrayIter::<i32>::new(psa.as_ptr()).unwrap();
+///     let big = iter.filter_map(Result::ok).filter(|&i| i > 990).count();
+///     assert_eq!(big, 9);
+/// }
+/// ```
+pub struct SafeArrayIter<T: SafeArrayElement> {
+    psa: *mut SAFEARRAY,
+    ix: i32,
+    remaining: usize,
+    _marker: PhantomData<T>
+}
+
+impl<T: SafeArrayElement> SafeArrayIter<T> {
+    /// Takes ownership of `psa`, which must be a 1-D array of `T::SFTYPE`.
+    /// The array is destroyed on error, as the other decoders do.
+    pub fn new(psa: *mut SAFEARRAY) -> Result<SafeArrayIter<T>, FromSafeArrayError> {
+        if psa.is_null() {
+            return Err(FromSafeArrayError::SafeArrayPtrNull);
+        }
+        let mut sad = SafeArrayDestructor::new(psa);
+        let sa_dims = unsafe { SafeArrayGetDim(psa) };
+        if sa_dims != 1 {
+            return Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims});
+        }
+        let vt = get_vartype(psa)?;
+        if vt != T::SFTYPE {
+            return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: T::SFTYPE, found: vt});
+        }
+        let (l_bound, r_bound) = get_bounds(psa, 1)?;
+        let hr = unsafe { SafeArrayLock(psa) };
+        if hr != 0 {
+            return Err(FromSafeArrayError::LockFailed{hr: hr});
+        }
+        sad.inner = null_mut();
+        //counted rather than bounded, so an upper bound of i32::MAX is still read
+        let remaining = (r_bound as i64 - l_bound as i64 + 1).max(0) as usize;
+        Ok(SafeArrayIter { psa: psa, ix: l_bound, remaining: remaining, _marker: PhantomData })
+    }
+}
+
+impl<T: SafeArrayElement> Iterator for SafeArrayIter<T> {
+    type Item = Result<T, ElementError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let ix = self.ix;
+        self.ix = self.ix.wrapping_add(1);
+        self.remaining -= 1;
+        Some(T::from_safearray(self.psa, ix).map_err(ElementError::from))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T: SafeArrayElement> ExactSizeIterator for SafeArrayIter<T> {}
+
+impl<T: SafeArrayElement> Drop for SafeArrayIter<T> {
+    fn drop(&mut self) {
+        unsafe { SafeArrayUnlock(self.psa) };
+        drop(SafeArrayDestructor::new(self.psa));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::SafeArrayExt;
+    use super::super::leak_tracking;
+    #[test]
+    fn test_iter() {
+        leak_tracking::assert_balanced(|| {
+            let psa = vec![String::from("a"), String::from("b"), String::from("c")].into_iter().into_safearray().unwrap();
+            let mut iter = SafeArrayIter::<String>::new(psa.as_ptr()).unwrap();
+            assert_eq!(iter.len(), 3);
+            assert_eq!(iter.next().unwrap().unwrap(), "a");
+            assert_eq!(iter.len(), 2);
+        });
+    }
+
+    #[test]
+    fn test_iter_max_bound() {
+        let psa = vec![7i32].into_iter().into_safearray_with_lbound(i32::max_value()).unwrap();
+        let iter = SafeArrayIter::<i32>::new(psa.as_ptr()).unwrap();
+        assert_eq!(iter.len(), 1);
+        let v: Vec<i32> = iter.map(Result::unwrap).collect();
+        assert_eq!(v, vec![7]);
+    }
+
+    #[test]
+    fn test_iter_mismatch() {
+        let psa = vec![1i32].into_iter().into_safearray().unwrap();
+        match SafeArrayIter::<f64>::new(psa.as_ptr()) {
+            Err(FromSafeArrayError::VarTypeDoesNotMatch{..}) => {},
+            _ => panic!("expected a vartype mismatch"),
+        }
+    }
+}
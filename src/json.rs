@@ -0,0 +1,204 @@
+//! # Json
+//! Conversions between `serde_json::Value` and VARIANTs, behind the `json` feature. 
+//! 
+//! | JSON | VARIANT |
+//! |------|---------|
+//! | `null` | `VT_NULL` (`VT_EMPTY` reads as `null` too) |
+//! | `true`/`false` | `VT_BOOL` |
+//! | integer | `VT_I4` when it fits, else `VT_I8`, or `VT_UI8` above `i64::MAX` |
+//! | other number | `VT_R8` |
+//! | string | `VT_BSTR` |
+//! | array | `VT_ARRAY | VT_VARIANT`, 1-D |
+//! | object | `VT_ARRAY | VT_VARIANT`, N x 2 - key in the first column, value in the second |
+//! 
+//! The object form is the one [`map_into_safearray`](fn.map_into_safearray.html) writes, 
+//! so any N x 2 `VT_VARIANT` array with string keys reads back as an object. Other 
+//! multi-dimensional arrays read as nested arrays, the last dimension innermost. 
+//! 
+//! Reading also accepts the remaining numeric vartypes; `VT_CY`, `VT_DECIMAL` and `VT_DATE` 
+//! (as its OLE day count) become floats and `VT_ERROR` its SCODE. Interface pointers have 
+//! no JSON form.
+use std::convert::TryFrom;
+
+use serde_json::{Map, Number, Value};
+use widestring::U16String;
+use winapi::shared::wtypes::VT_VARIANT;
+use winapi::um::oaidl::VARIANT;
+
+use rust_decimal::Decimal;
+
+use super::errors::{IntoVariantError, JsonError};
+use super::ptr::Ptr;
+use super::types::CY_SCALE;
+use super::value::{ValueArray, VariantValue};
+use super::variant::Variants;
+
+/// Builds the `VariantValue` for a JSON value
+pub fn json_to_value(json: &Value) -> VariantValue {
+    match *json {
+        Value::Null => VariantValue::Null, 
+        Value::Bool(b) => VariantValue::Bool(b), 
+        Value::Number(ref n) => number_to_value(n), 
+        Value::String(ref s) => VariantValue::String(U16String::from_str(s)), 
+        Value::Array(ref items) => {
+            VariantValue::Array(ValueArray::vector(VT_VARIANT, items.iter().map(json_to_value).collect()))
+        }, 
+        Value::Object(ref map) => {
+            let entries = map.iter().map(|(key, value)| (U16String::from_str(key), json_to_value(value))).collect();
+            VariantValue::Array(ValueArray::keyed(entries))
+        },
+    }
+}
+
+/// Converts a JSON value into a `Ptr<VARIANT>`
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// #[macro_use] extern crate serde_json;
+/// 
+/// use oaidl::{json_from_variant, json_into_variant};
+/// 
+/// fn main() {
+///     let json = json!({"name": "Ada", "tags": [1, 2.5, null]});
+///     let pvar = json_into_variant(&json).unwrap();
+///     assert_eq!(json_from_variant(pvar).unwrap(), json);
+/// }
+/// ```
+pub fn json_into_variant(json: &Value) -> Result<Ptr<VARIANT>, IntoVariantError> {
+    json_to_value(json).into_variant()
+}
+
+/// Converts a `Ptr<VARIANT>` into a JSON value. The VARIANT is cleared afterwards.
+pub fn json_from_variant(var: Ptr<VARIANT>) -> Result<Value, JsonError> {
+    let value = VariantValue::from_variant(var)?;
+    value_to_json(&value)
+}
+
+/// Builds the JSON value for a `VariantValue`
+pub fn value_to_json(value: &VariantValue) -> Result<Value, JsonError> {
+    Ok(match *value {
+        VariantValue::Empty | VariantValue::Null => Value::Null, 
+        VariantValue::Bool(b) => Value::Bool(b), 
+        VariantValue::I8(v) => Value::from(v), 
+        VariantValue::I16(v) => Value::from(v), 
+        VariantValue::I32(v) => Value::from(v), 
+        VariantValue::I64(v) => Value::from(v), 
+        VariantValue::U8(v) => Value::from(v), 
+        VariantValue::U16(v) => Value::from(v), 
+        VariantValue::U32(v) => Value::from(v), 
+        VariantValue::U64(v) => Value::from(v), 
+        VariantValue::Int(v) => Value::from(i32::from(v)), 
+        VariantValue::UInt(v) => Value::from(u32::from(v)), 
+        VariantValue::SCode(sc) => Value::from(i32::from(sc)), 
+        VariantValue::F32(v) => float_to_json(f64::from(v))?, 
+        VariantValue::F64(v) => float_to_json(v)?, 
+        VariantValue::Currency(cy) => float_to_json(i64::from(cy) as f64 / CY_SCALE as f64)?, 
+        VariantValue::Date(d) => float_to_json(f64::from(d))?, 
+        VariantValue::Decimal(dec) => {
+            let text = Decimal::from(dec).to_string();
+            float_to_json(text.parse().unwrap_or(0.0))?
+        }, 
+        VariantValue::String(ref s) => Value::String(s.to_string_lossy()), 
+        VariantValue::Array(ref arr) => array_to_json(arr)?, 
+        VariantValue::ByRef(ref inner) => value_to_json(inner)?, 
+        _ => return Err(JsonError::NoJsonForm{vartype: value.vartype()}),
+    })
+}
+
+impl Variants {
+    /// The JSON form of the value, mapped as for `json_from_variant`
+    pub fn to_json(&self) -> Result<Value, JsonError> {
+        value_to_json(&VariantValue::from(self.clone()))
+    }
+
+    /// The `Variants` for a JSON value. Objects have no `Variants` form since they need a 2-D array; 
+    /// use `json_into_variant` for those.
+    pub fn from_json(json: &Value) -> Result<Variants, JsonError> {
+        Variants::try_from(json_to_value(json)).map_err(|_| JsonError::ObjectNotVariants)
+    }
+}
+
+fn number_to_value(n: &Number) -> VariantValue {
+    if let Some(v) = n.as_i64() {
+        if v >= i64::from(i32::min_value()) && v <= i64::from(i32::max_value()) {
+            return VariantValue::I32(v as i32);
+        }
+        return VariantValue::I64(v);
+    }
+    if let Some(v) = n.as_u64() {
+        return VariantValue::U64(v);
+    }
+    VariantValue::F64(n.as_f64().unwrap_or(0.0))
+}
+
+fn float_to_json(v: f64) -> Result<Value, JsonError> {
+    Number::from_f64(v).map(Value::Number).ok_or(JsonError::NonFiniteFloat{value: v})
+}
+
+fn array_to_json(arr: &ValueArray) -> Result<Value, JsonError> {
+    if arr.is_keyed() {
+        let mut map = Map::with_capacity(arr.elements.len() / 2);
+        for pair in arr.elements.chunks(2) {
+            if let VariantValue::String(ref key) = pair[0] {
+                map.insert(key.to_string_lossy(), value_to_json(&pair[1])?);
+            }
+        }
+        return Ok(Value::Object(map));
+    }
+    let counts: Vec<usize> = arr.bounds.iter().map(|&(_, count)| count as usize).collect();
+    let mut elements = arr.elements.iter();
+    nested_to_json(&counts, &mut elements)
+}
+
+fn nested_to_json<'a, I: Iterator<Item=&'a VariantValue>>(counts: &[usize], elements: &mut I) -> Result<Value, JsonError> {
+    let mut items = Vec::with_capacity(counts[0]);
+    for _ in 0..counts[0] {
+        if counts.len() == 1 {
+            match elements.next() {
+                Some(elem) => items.push(value_to_json(elem)?), 
+                None => break,
+            }
+        } else {
+            items.push(nested_to_json(&counts[1..], elements)?);
+        }
+    }
+    Ok(Value::Array(items))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::leak_tracking;
+    use super::super::dtor::{FreeVariant, PtrDestructor};
+    #[test]
+    fn test_json_roundtrip() {
+        leak_tracking::assert_balanced(|| {
+            let json = json!({"id": 7, "big": 5_000_000_000i64, "ratio": 0.5, "ok": true, "none": null, "list": ["a", [1, 2]]});
+            let pvar = json_into_variant(&json).unwrap();
+            assert_eq!(json_from_variant(pvar).unwrap(), json);
+            FreeVariant::destroy(pvar);
+        });
+    }
+
+    #[test]
+    fn test_json_variants() {
+        let v = Variants::from_json(&json!([1, "two", null])).unwrap();
+        assert_eq!(v, Variants::Array(vec![Variants::I32(1), Variants::from("two"), Variants::Null]));
+        assert_eq!(v.to_json().unwrap(), json!([1, "two", null]));
+        assert!(Variants::from_json(&json!({"a": 1})).is_err());
+        assert_eq!(Variants::currency_str("1.25").unwrap().to_json().unwrap(), json!(1.25));
+        assert!(Variants::F64(::std::f64::NAN).to_json().is_err());
+    }
+
+    #[test]
+    fn test_json_matrix() {
+        let arr = ValueArray {
+            vartype: VT_VARIANT, 
+            bounds: vec![(1, 2), (1, 3)], 
+            elements: (1..=6).map(VariantValue::I32).collect(),
+        };
+        assert_eq!(value_to_json(&VariantValue::Array(arr)).unwrap(), json!([[1, 2, 3], [4, 5, 6]]));
+    }
+}
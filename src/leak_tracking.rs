@@ -0,0 +1,176 @@
+//! # Leak tracking
+//! Allocation balance checks for test suites.
+//!
+//! Every BSTR, boxed VARIANT and SAFEARRAY this crate allocates is counted on
+//! the calling thread, and so is every one it frees - through the destructor
+//! policies in this crate, `DroppableBString`, the SAFEARRAY guards used while
+//! decoding, and the containers (argument buffers, `VT_VARIANT` arrays, `Invoke`
+//! results) that a VARIANT's contents are moved into. Freeing a VARIANT counts
+//! the BSTR or SAFEARRAY it held as freed too.
+//!
+//! [`scope`] runs a closure and reports allocations minus frees for each kind.
+//! A positive balance is a leak; a negative one means more was freed than was
+//! allocated inside the scope, e.g. arrays received from a COM server and
+//! released by a decode.
+//!
+//! The balance is kept with the thread's `ConversionStats` and only counted
+//! while collection is on; `scope` switches it on for the closure. Pointers
+//! held in an `OwnedPtr` can instead be counted per destructor policy by
+//! wrapping the policy in `CountingDestructor`.
+//!
+//! ```
+//! extern crate oaidl;
+//!
+//! use oaidl::{FreeVariant, PtrDestructor, VariantExt};
+//! use oaidl::leak_tracking;
+//!
+//! fn main() {
+//!     let ((), report) = leak_tracking::scope(|| {
+//!         let pvar = String::from("tracked").into_variant().unwrap();
+//!         FreeVariant::destroy(pvar);
+//!     });
+//!     assert!(report.is_balanced(), "{:?}", report);
+//! }
+//! ```
+//!
+//! [`scope`]: fn.scope.html
+use winapi::shared::wtypes::{VT_ARRAY, VT_BSTR, VT_BYREF};
+use winapi::um::oaidl::VARIANT;
+
+use super::stats::ConversionStats;
+
+/// Allocations minus frees within a [`scope`](fn.scope.html), per kind of allocation
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct LeakReport {
+    /// BSTRs allocated but not freed
+    pub bstrs: i64,
+    /// Boxed VARIANTs allocated but not freed
+    pub variants: i64,
+    /// SAFEARRAYs created but not destroyed
+    pub safearrays: i64,
+}
+
+impl LeakReport {
+    /// Every allocation in the scope was matched by a free
+    pub fn is_balanced(&self) -> bool {
+        self.bstrs == 0 && self.variants == 0 && self.safearrays == 0
+    }
+}
+
+/// Runs `f`, returning its result and the allocation balance of the current thread while it ran.
+/// Scopes nest; each reports only what happened inside it.
+pub fn scope<F: FnOnce() -> R, R>(f: F) -> (R, LeakReport) {
+    let _restore = Restore(ConversionStats::set_enabled(true));
+    let before = ConversionStats::live();
+    let ret = f();
+    let after = ConversionStats::live();
+    let report = LeakReport {
+        bstrs: after.bstrs - before.bstrs,
+        variants: after.variants - before.variants,
+        safearrays: after.safearrays - before.safearrays,
+    };
+    (ret, report)
+}
+
+/// Runs `f` and panics if it left any allocation of this crate unfreed
+pub fn assert_balanced<F: FnOnce() -> R, R>(f: F) -> R {
+    let (ret, report) = scope(f);
+    assert!(report.is_balanced(), "unbalanced allocations: {:?}", report);
+    ret
+}
+
+struct Restore(bool);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        ConversionStats::set_enabled(self.0);
+    }
+}
+
+fn update<F: FnOnce(&mut LeakReport)>(f: F) {
+    ConversionStats::update_live(f);
+}
+
+pub(crate) fn bstr_allocated() {
+    update(|c| c.bstrs += 1);
+}
+
+pub(crate) fn bstr_freed() {
+    update(|c| c.bstrs -= 1);
+}
+
+pub(crate) fn variant_allocated() {
+    update(|c| c.variants += 1);
+}
+
+pub(crate) fn safearray_allocated() {
+    update(|c| c.safearrays += 1);
+}
+
+pub(crate) fn safearray_freed() {
+    update(|c| c.safearrays -= 1);
+}
+
+/// Counts what `VariantClear` on `var` is about to free. Call before clearing.
+pub(crate) fn variant_cleared(var: *const VARIANT) {
+    let vt = unsafe { (*var).n1.n2().vt } as u32;
+    if vt & VT_BYREF != 0 {
+        return;
+    }
+    if vt & VT_ARRAY != 0 {
+        safearray_freed();
+    } else if vt == VT_BSTR {
+        bstr_freed();
+    }
+}
+
+/// Counts a boxed VARIANT, and what it holds, as freed
+pub(crate) fn variant_freed(var: *const VARIANT) {
+    variant_cleared(var);
+    update(|c| c.variants -= 1);
+}
+
+/// Counts what `var` holds as allocated - for contents this crate did not allocate 
+/// itself, such as a `VariantCopy`
+pub(crate) fn contents_adopted(var: *const VARIANT) {
+    let vt = unsafe { (*var).n1.n2().vt } as u32;
+    if vt & VT_BYREF == 0 {
+        if vt & VT_ARRAY != 0 {
+            safearray_allocated();
+        } else if vt == VT_BSTR {
+            bstr_allocated();
+        }
+    }
+}
+
+/// Counts a boxed VARIANT, and what it holds, as allocated - for variants built 
+/// around contents this crate did not allocate itself
+pub(crate) fn variant_adopted(var: *const VARIANT) {
+    contents_adopted(var);
+    variant_allocated();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::SafeArrayExt;
+    use super::super::dtor::{FreeVariant, PtrDestructor};
+    use super::super::variant::VariantExt;
+    #[test]
+    fn test_balanced() {
+        assert_balanced(|| {
+            let pvar = String::from("tracked").into_variant().unwrap();
+            FreeVariant::destroy(pvar);
+            let psa = vec![1i32, 2, 3].into_iter().into_safearray().unwrap();
+            ExactSizeIterator::<Item=i32>::from_safearray(psa.as_ptr()).unwrap();
+        });
+    }
+
+    #[test]
+    fn test_leak() {
+        let (pvar, report) = scope(|| String::from("leaked").into_variant().unwrap());
+        assert_eq!(report, LeakReport { bstrs: 1, variants: 1, safearrays: 0 });
+        let ((), report) = scope(|| FreeVariant::destroy(pvar));
+        assert_eq!(report, LeakReport { bstrs: -1, variants: -1, safearrays: 0 });
+    }
+}
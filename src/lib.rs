@@ -72,32 +72,151 @@
 //!     unsafe {c_masq(s.as_ptr(), p.as_ptr())};
 //! } 
 
+#[cfg(feature="chrono")]
+extern crate chrono;
+
 #[macro_use] extern crate failure;
 
+#[cfg(feature = "derive")]
+#[cfg_attr(test, macro_use)]
+extern crate oaidl_derive;
+
+//the derives name the crate as ::oaidl, as users see it
+#[cfg(all(test, feature = "derive"))]
+extern crate self as oaidl;
+
+#[cfg(feature="indexmap")]
+extern crate indexmap;
+
+#[cfg(feature="ndarray")]
+extern crate ndarray;
+
 extern crate rust_decimal;
 
 #[cfg(feature="serde")]
 #[macro_use]
 extern crate serde;
 
+#[cfg(feature="json")]
+#[cfg_attr(test, macro_use)]
+extern crate serde_json;
+
+#[cfg(feature="small_strings")]
+extern crate smallstr;
+
+#[cfg(feature="smallvec")]
+extern crate smallvec;
+
+#[cfg(feature="uuid")]
+extern crate uuid;
+
 extern crate widestring;
 
 
 extern crate winapi;
 
+mod agile;
+mod args;
+mod arith;
 mod array;
 mod bstr;
+mod bulk;
+mod byref;
+mod bytes;
+mod caps;
+mod classify;
+mod coerce;
+pub mod codes;
+mod compare;
+#[cfg(feature = "serde")]
+mod de;
+mod dtor;
+mod enums;
 mod errors;
+mod format;
+mod iter;
+#[cfg(feature = "json")]
+mod json;
+pub mod leak_tracking;
+mod limits;
+mod map;
+mod meta;
+#[cfg(feature = "ndarray")]
+mod nd;
+mod options;
+mod propvariant;
 mod ptr;
+pub mod raw;
+mod record;
+mod row;
+#[cfg(feature = "serde")]
+mod ser;
+mod stats;
+mod stream;
+mod strings;
+mod table;
 mod types;
+mod value;
 mod variant;
+mod varray;
+mod view;
+#[cfg(feature = "winrt")]
+mod winrt;
 
-// Types = Ptr, Currency, Date, DecWrapper, Int, SCode, UInt, VariantBool, 
-//  Variant, VtEmpty, VtNull
-// Traits = BStringExt, SafeArrayElement, SafeArrayExt, VariantExt
-pub use self::array::{SafeArrayElement, SafeArrayExt};
+// Types = Ptr, AgileVariant, ByRefVariant, ClearPropVariant, ClearVariant, ClipData, ComDestructor, ConversionStats, ConvertOptions, CountingDestructor, DateFormat, DecodeLimits, DestructorCounts, ElementRecovery, FloatPolicy, FreeBStr, FreeCoTaskMemVariant, FreePropVariant, FreeSafeArray, FreeVariant, Locale, NonePolicy, NullPolicy, NumberFormat, OleAutCapabilities, OwnedPtr, OwnedSafeArray, RawByRef, RecordElement, RecordFields, SafeArrayBuilder, SafeArrayDataGuard, SafeArrayInfo, SafeArrayIter, SafeArrayN, SafeArrayPin, SafeArrayViewMut, StreamVarType, Utf16Mode, VariantClass, VariantKind, VtMeta, Currency, Date, DecWrapper, ExcelDateSystem, FileTime, Guid, Int, SCode, UInt, VariantBool, 
+//  ValueArray, Variant, VariantArgs, Variants, VariantTable, VariantValue, VtEmpty, VtNull
+// Traits = ArrayData, BStringExt, BulkElement, ByRefExt, ComEnum, PropVariantExt, PtrDestructor, Record, SafeArrayElement, SafeArrayExt, SafeArrayRow, SafeArraySliceExt, SafeArrayVecExt, VariantExt
+pub use self::agile::AgileVariant;
+pub use self::args::{put_retval, variants_from_args, VariantArgs};
+pub use self::arith::{variant_add, variant_div, variant_mul, variant_sub};
+pub use self::array::{collect_safearray, copy_safearray, extend_safearray, from_safearray_2d, into_safearray_2d, resize_safearray, safearray_info, safearray_len, safearray_shape, ElementRecovery, SafeArrayBuilder, SafeArrayElement, SafeArrayExt, SafeArrayInfo, SafeArrayN, SafeArraySliceExt, SafeArrayVecExt};
 pub use self::bstr::{BStringExt, DroppableBString};
+pub use self::bulk::{append_safearray, bulk_from_safearray, bulk_from_safearray_cancellable, bulk_into_safearray, concat_safearrays, BulkElement, BULK_CHUNK};
+pub use self::byref::{ByRefExt, ByRefVariant};
+pub use self::bytes::{bytes_from_safearray, bytes_into_safearray, bytes_to_variant, variant_to_bytes};
+pub use self::caps::{capabilities, OleAutCapabilities, SafeArrayPin};
+pub use self::classify::{classify, VariantClass, VariantKind};
+pub use self::coerce::{coerce, coerce_in};
+pub use self::compare::variant_eq;
+#[cfg(feature = "serde")]
+pub use self::de::{from_variant_de, VariantDeserializer};
+pub use self::dtor::{ClearPropVariant, ClearVariant, ComDestructor, CountingDestructor, DestructorCounts, FreeBStr, FreeCoTaskMemVariant, FreePropVariant, FreeSafeArray, FreeVariant, OwnedPtr, OwnedSafeArray, PtrDestructor};
+pub use self::enums::{enum_from_variant, enum_into_variant, ComEnum};
 pub use self::errors::*;
+pub use self::format::{format_date_time, format_number, format_variant, format_variant_in, DateFormat, NumberFormat};
+pub use self::iter::SafeArrayIter;
+#[cfg(feature = "json")]
+pub use self::json::{json_from_variant, json_into_variant, json_to_value, value_to_json};
+pub use self::limits::DecodeLimits;
+pub use self::map::{map_from_safearray, map_into_safearray};
+#[cfg(feature="indexmap")]
+pub use self::map::{ordered_map_from_safearray, ordered_map_into_safearray};
+pub use self::meta::{VtMeta, VT_TABLE};
+#[cfg(feature = "ndarray")]
+pub use self::nd::{array2_from_safearray, array2_into_safearray, arrayd_from_safearray, arrayd_into_safearray};
+pub use self::options::{ConvertOptions, FloatPolicy, Locale, NonePolicy, NullPolicy, LOCALE_INVARIANT, LOCALE_USER_DEFAULT};
+pub use self::propvariant::{propvariant_to_variant, variant_to_propvariant, PropVariantExt};
 pub use self::ptr::Ptr;
-pub use self::types::{Currency, Date, DecWrapper,Int, SCode, UInt, VariantBool};
-pub use self::variant::{Variant, VariantExt, VtEmpty, VtNull};
\ No newline at end of file
+pub use self::record::{__push_field, check_record_fields, check_record_layout, record_field_names, record_from_variant, record_info_from_guids, record_into_variant, records_from_safearray, records_into_safearray, Record, RecordElement, RecordFields};
+#[cfg(feature = "derive")]
+pub use oaidl_derive::{SafeArrayRow, VariantRecord};
+pub use self::row::{__RowReader, __RowWriter, SafeArrayRow};
+#[cfg(feature = "serde")]
+pub use self::ser::{to_variant, VariantSerializer};
+pub use self::stats::ConversionStats;
+pub use self::stream::{bytes_to_stream_variant, read_to_stream_variant, stream_variant_to_bytes, StreamVarType};
+pub use self::strings::{safearray_to_strings, strings_to_safearray, Utf16Mode};
+pub use self::table::{TableLayout, VariantTable};
+pub use self::types::{ClipData, Currency, Date, DecWrapper, ExcelDateSystem, FileTime, Guid, Int, SCode, UInt, VariantBool};
+pub use self::value::{ValueArray, VariantValue};
+pub use self::variant::{RawByRef, Variant, VariantExt, Variants, VtEmpty, VtNull};
+pub use self::varray::{variant_to_variants, variants_to_variant};
+pub use self::view::{ArrayData, SafeArrayDataGuard, SafeArrayViewMut};
+#[cfg(feature = "winrt")]
+pub use self::winrt::{property_value_from_variants, variants_from_property_value};
+
+// Used by com_enum! and com_row! so callers do not need winapi in scope
+#[doc(hidden)]
+pub use winapi::um::oaidl::SAFEARRAY as __SAFEARRAY;
+#[doc(hidden)]
+pub use winapi::um::oaidl::VARIANT as __VARIANT;
\ No newline at end of file
@@ -0,0 +1,143 @@
+//! # Limits
+//! Per-thread bounds on what decoding will allocate. 
+//! 
+//! A `SAFEARRAY` or `BSTR` received from an untrusted client can claim an 
+//! arbitrary size, and `VT_VARIANT` arrays can nest arrays inside their 
+//! elements. [`DecodeLimits`] caps the element count, nesting depth and BSTR 
+//! length that `from_safearray`/`from_variant` will accept; exceeding a limit 
+//! fails the conversion with a [`LimitError`] before the memory is allocated. 
+//! 
+//! No limits are set by default. The limits are part of the thread's 
+//! `ConvertOptions`, so setting either changes both. 
+//! 
+//! [`DecodeLimits`]: struct.DecodeLimits.html
+//! [`LimitError`]: enum.LimitError.html
+use std::cell::Cell;
+
+use winapi::um::oleauto::SysStringLen;
+
+use super::errors::LimitError;
+use super::options::ConvertOptions;
+
+thread_local! {
+    static DEPTH: Cell<usize> = Cell::new(0);
+}
+
+/// Limits enforced while decoding on the current thread. `None` means unlimited.
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{DecodeLimits, SafeArrayExt};
+/// 
+/// fn main() {
+///     let limits = DecodeLimits { max_elements: Some(2), ..DecodeLimits::default() };
+///     let psa = vec![1i32, 2, 3].into_iter().into_safearray().unwrap();
+///     let r = limits.scoped(|| ExactSizeIterator::<Item=i32>::from_safearray(psa.as_ptr()));
+///     assert!(r.is_err());
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DecodeLimits {
+    /// Maximum number of elements in a single SAFEARRAY (across all dimensions)
+    pub max_elements: Option<usize>, 
+    /// Maximum number of SAFEARRAYs nested inside each other through `VT_VARIANT` elements, 
+    /// counting the outermost array
+    pub max_depth: Option<usize>, 
+    /// Maximum length of a BSTR, in UTF-16 code units
+    pub max_bstr_len: Option<usize>,
+}
+
+impl DecodeLimits {
+    /// Returns the limits in force on the current thread.
+    pub fn current() -> DecodeLimits {
+        ConvertOptions::current().limits
+    }
+
+    /// Sets the limits for the current thread, returning the previous ones.
+    pub fn set(limits: DecodeLimits) -> DecodeLimits {
+        ConvertOptions::replace_limits(limits)
+    }
+
+    /// Runs `f` with these limits in force, restoring the previous limits afterwards.
+    pub fn scoped<F: FnOnce() -> R, R>(self, f: F) -> R {
+        let _restore = Restore(DecodeLimits::set(self));
+        f()
+    }
+
+    pub(crate) fn check_elements(len: usize) -> Result<(), LimitError> {
+        match DecodeLimits::current().max_elements {
+            Some(max) if len > max => Err(LimitError::TooManyElements{len: len, max: max}), 
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_bstr(bstr: *mut u16) -> Result<(), LimitError> {
+        if let Some(max) = DecodeLimits::current().max_bstr_len {
+            let len = unsafe { SysStringLen(bstr) } as usize;
+            if len > max {
+                return Err(LimitError::BStrTooLong{len: len, max: max});
+            }
+        }
+        Ok(())
+    }
+
+    /// Records entry into an array decode; the depth is released when the guard drops.
+    pub(crate) fn enter_array() -> Result<DepthGuard, LimitError> {
+        let depth = DEPTH.with(|d| d.get()) + 1;
+        if let Some(max) = DecodeLimits::current().max_depth {
+            if depth > max {
+                return Err(LimitError::TooDeep{max: max});
+            }
+        }
+        DEPTH.with(|d| d.set(depth));
+        Ok(DepthGuard)
+    }
+}
+
+struct Restore(DecodeLimits);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        DecodeLimits::set(self.0);
+    }
+}
+
+pub(crate) struct DepthGuard;
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|d| d.set(d.get() - 1));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_scoped() {
+        let limits = DecodeLimits { max_elements: Some(4), ..DecodeLimits::default() };
+        limits.scoped(|| {
+            assert_eq!(DecodeLimits::current(), limits);
+            assert!(DecodeLimits::check_elements(4).is_ok());
+            assert!(DecodeLimits::check_elements(5).is_err());
+        });
+        assert_eq!(DecodeLimits::current(), DecodeLimits::default());
+    }
+
+    #[test]
+    fn test_depth() {
+        let limits = DecodeLimits { max_depth: Some(1), ..DecodeLimits::default() };
+        limits.scoped(|| {
+            let outer = DecodeLimits::enter_array().unwrap();
+            match DecodeLimits::enter_array() {
+                Err(LimitError::TooDeep{max}) => assert_eq!(max, 1), 
+                _ => panic!("expected depth limit"),
+            }
+            drop(outer);
+            assert!(DecodeLimits::enter_array().is_ok());
+        });
+    }
+}
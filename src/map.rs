@@ -0,0 +1,120 @@
+//! # Map
+//! Conversions between string-keyed maps and 2-column `VT_VARIANT` SAFEARRAYs.
+//!
+//! Each entry becomes one row, key in the first column and value in the second -
+//! the shape `Scripting.Dictionary` contents take when passed through `Range.Value`
+//! or returned from VBA helpers. Rows are written in the map's iteration order
+//! and read back in array order, so an ordered map (like `IndexMap` behind the
+//! `indexmap` feature) survives the round trip with its key order intact.
+use std::collections::HashSet;
+use std::iter::FromIterator;
+
+#[cfg(feature="indexmap")]
+use indexmap::IndexMap;
+use winapi::um::oaidl::SAFEARRAY;
+
+use super::errors::TableError;
+use super::ptr::Ptr;
+use super::table::{TableLayout, VariantTable};
+use super::variant::Variants;
+
+/// Converts map entries into an N x 2 `VT_VARIANT` SAFEARRAY, one row per entry in iteration order
+///
+/// Works with anything that yields key/value pairs, e.g. `HashMap<String, Variants>` or `BTreeMap<&str, i32>`.
+pub fn map_into_safearray<I, K, V>(entries: I) -> Result<Ptr<SAFEARRAY>, TableError>
+    where I: IntoIterator<Item=(K, V)>, K: Into<String>, V: Into<Variants>
+{
+    let mut table = VariantTable::new(vec![String::from("Key"), String::from("Value")]);
+    for (key, value) in entries {
+        table.push_row(vec![Variants::String(key.into()), value.into()])?;
+    }
+    table.into_safearray(TableLayout::RowsOnly)
+}
+
+/// Reads an N x 2 `VT_VARIANT` SAFEARRAY into a map, in array row order. The array is released afterwards.
+///
+/// The first column must hold string keys and no key may appear twice.
+pub fn map_from_safearray<M>(psa: *mut SAFEARRAY) -> Result<M, TableError>
+    where M: FromIterator<(String, Variants)>
+{
+    let table = VariantTable::from_safearray(psa, TableLayout::RowsOnly)?;
+    if !table.is_empty() && table.columns().len() != 2 {
+        return Err(TableError::RowWidthMismatch{expected: 2, found: table.columns().len()});
+    }
+
+    let mut seen = HashSet::with_capacity(table.len());
+    let mut entries = Vec::with_capacity(table.len());
+    for (ix, mut row) in table.into_rows().into_iter().enumerate() {
+        let value = row.pop().unwrap();
+        let key = match row.pop().unwrap() {
+            Variants::String(key) => key,
+            _ => return Err(TableError::KeyNotString{row: ix}),
+        };
+        if !seen.insert(key.clone()) {
+            return Err(TableError::DuplicateKey{key: key});
+        }
+        entries.push((key, value));
+    }
+    Ok(entries.into_iter().collect())
+}
+
+/// Converts an `IndexMap` into an N x 2 `VT_VARIANT` SAFEARRAY, keeping insertion order
+#[cfg(feature="indexmap")]
+pub fn ordered_map_into_safearray(map: IndexMap<String, Variants>) -> Result<Ptr<SAFEARRAY>, TableError> {
+    map_into_safearray(map)
+}
+
+/// Reads an N x 2 `VT_VARIANT` SAFEARRAY into an `IndexMap`, keeping the row order as insertion order
+#[cfg(feature="indexmap")]
+pub fn ordered_map_from_safearray(psa: *mut SAFEARRAY) -> Result<IndexMap<String, Variants>, TableError> {
+    map_from_safearray(psa)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::{BTreeMap, HashMap};
+    #[test]
+    fn test_btreemap() {
+        let mut map = BTreeMap::new();
+        map.insert(String::from("b"), Variants::from(2i32));
+        map.insert(String::from("a"), Variants::from("one"));
+        let psa = map_into_safearray(map.clone()).unwrap();
+        let back: BTreeMap<String, Variants> = map_from_safearray(psa.as_ptr()).unwrap();
+        assert_eq!(map, back);
+    }
+
+    #[test]
+    fn test_key_not_string() {
+        let mut table = VariantTable::new(vec![String::from("Key"), String::from("Value")]);
+        table.push_row(vec![Variants::from("a"), Variants::from(1i32)]).unwrap();
+        table.push_row(vec![Variants::from(2i32), Variants::from(2i32)]).unwrap();
+        let psa = table.into_safearray(TableLayout::RowsOnly).unwrap();
+        match map_from_safearray::<HashMap<String, Variants>>(psa.as_ptr()) {
+            Err(TableError::KeyNotString{row}) => assert_eq!(row, 1),
+            _ => panic!("Error")
+        }
+    }
+
+    #[test]
+    fn test_duplicate_key() {
+        let psa = map_into_safearray(vec![("a", 1i32), ("a", 2i32)]).unwrap();
+        match map_from_safearray::<HashMap<String, Variants>>(psa.as_ptr()) {
+            Err(TableError::DuplicateKey{key}) => assert_eq!(key, "a"),
+            _ => panic!("Error")
+        }
+    }
+
+    #[cfg(feature="indexmap")]
+    #[test]
+    fn test_indexmap_order() {
+        let mut map = IndexMap::new();
+        for key in &["zeta", "alpha", "mu", "beta"] {
+            map.insert(key.to_string(), Variants::from(key.len() as i32));
+        }
+        let psa = ordered_map_into_safearray(map.clone()).unwrap();
+        let back = ordered_map_from_safearray(psa.as_ptr()).unwrap();
+        assert_eq!(back.keys().collect::<Vec<_>>(), vec!["zeta", "alpha", "mu", "beta"]);
+        assert_eq!(map, back);
+    }
+}
@@ -0,0 +1,189 @@
+//! # Meta
+//! Compile-time table of the scalar types this crate converts.
+//!
+//! Each row names a Rust type, the `VARTYPE` it is tagged with, the `VARIANT`
+//! union member holding it, and which of the array (`SafeArrayElement`), byref
+//! (`Box<T>`) and dynamic (`Variants`) paths support it. The same table drives a
+//! round-trip test per row, so a type added to one path but not the table - or
+//! listed in the table but missing a path - fails the tests.
+use winapi::shared::wtypes::{
+    VT_BOOL,
+    VT_BSTR,
+    VT_CF,
+    VT_CY,
+    VT_DATE,
+    VT_DECIMAL,
+    VT_ERROR,
+    VT_I1,
+    VT_I2,
+    VT_I4,
+    VT_I8,
+    VT_INT,
+    VT_R4,
+    VT_R8,
+    VT_UI1,
+    VT_UI2,
+    VT_UI4,
+    VT_UI8,
+    VT_UINT,
+};
+
+/// One row of [`VT_TABLE`](constant.VT_TABLE.html)
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct VtMeta {
+    /// The Rust type, as written in source
+    pub rust_type: &'static str,
+    /// The `VARTYPE` a VARIANT holding it is tagged with
+    pub vartype: u32,
+    /// The `VARIANT` union member holding the value
+    pub member: &'static str,
+    /// `Vec<T>` converts to and from a SAFEARRAY of `vartype`
+    pub array: bool,
+    /// `Box<T>` converts to and from a `VT_BYREF | vartype` VARIANT
+    pub byref: bool,
+    /// `Variants` has an arm for the type
+    pub dynamic: bool,
+}
+
+// Expands a matrix test step only for the paths a row supports
+#[cfg(test)]
+macro_rules! matrix_path {
+    (true, $e:expr) => { $e };
+    (false, $e:expr) => { () };
+}
+
+macro_rules! vt_table {
+    ($($test:ident: $t:ty = $vt:ident, $member:ident, $sample:expr,
+        array: $array:tt, byref: $byref:tt, dynamic: $dynamic:tt;)*) => {
+        /// One row per scalar `VARTYPE` with a `VariantExt` impl, in `VARTYPE` order
+        pub const VT_TABLE: &'static [VtMeta] = &[
+            $(VtMeta {
+                rust_type: stringify!($t),
+                vartype: $vt,
+                member: stringify!($member),
+                array: $array,
+                byref: $byref,
+                dynamic: $dynamic,
+            },)*
+        ];
+
+        #[cfg(test)]
+        mod matrix {
+            use super::*;
+            use std::fmt::Debug;
+            use winapi::shared::wtypes::VT_BYREF;
+            use winapi::um::oaidl::VARIANT;
+            use super::super::array::{SafeArrayElement, SafeArrayExt};
+            use super::super::ptr::Ptr;
+            use super::super::types::*;
+            use super::super::variant::{VariantExt, Variants};
+
+            fn vt_of(pvar: Ptr<VARIANT>) -> u32 {
+                unsafe { (*pvar.as_ptr()).n1.n2().vt as u32 }
+            }
+
+            fn scalar<T: VariantExt + Clone + PartialEq + Debug>(val: T, vt: u32) {
+                assert_eq!(T::VARTYPE, vt);
+                let pvar = val.clone().into_variant().unwrap();
+                assert_eq!(vt_of(pvar), vt);
+                assert_eq!(T::from_variant(pvar).unwrap(), val);
+            }
+
+            fn array<T: SafeArrayElement + Clone + PartialEq + Debug>(val: T, vt: u32) {
+                assert_eq!(T::SFTYPE, vt);
+                let psa = vec![val.clone(), val.clone()].into_iter().into_safearray().unwrap();
+                let out = ExactSizeIterator::<Item=T>::from_safearray(psa.as_ptr()).unwrap();
+                assert_eq!(out, vec![val.clone(), val]);
+            }
+
+            fn byref<T>(val: T, vt: u32) where T: Clone + PartialEq + Debug, Box<T>: VariantExt {
+                assert_eq!(<Box<T>>::VARTYPE, VT_BYREF | vt);
+                let pvar = Box::new(val.clone()).into_variant().unwrap();
+                assert_eq!(vt_of(pvar), VT_BYREF | vt);
+                assert_eq!(*<Box<T>>::from_variant(pvar).unwrap(), val);
+            }
+
+            fn dynamic<T: Into<Variants>>(val: T, vt: u32) {
+                let dv = val.into();
+                let pvar = dv.clone().into_variant().unwrap();
+                assert_eq!(vt_of(pvar), vt);
+                assert_eq!(Variants::from_variant(pvar).unwrap(), dv);
+            }
+
+            $(
+                #[test]
+                fn $test() {
+                    scalar::<$t>($sample, $vt);
+                    matrix_path!($array, array::<$t>($sample, $vt));
+                    matrix_path!($byref, byref::<$t>($sample, $vt));
+                    matrix_path!($dynamic, dynamic::<$t>($sample, $vt));
+                }
+            )*
+
+            #[test]
+            fn test_unique() {
+                for (ix, row) in VT_TABLE.iter().enumerate() {
+                    assert!(VT_TABLE[ix + 1..].iter().all(|other| other.vartype != row.vartype), "{:?}", row);
+                }
+            }
+        }
+    };
+}
+
+vt_table! {
+    test_i2: i16 = VT_I2, iVal, -300i16, array: true, byref: true, dynamic: true;
+    test_i4: i32 = VT_I4, lVal, -70_000i32, array: true, byref: true, dynamic: true;
+    test_r4: f32 = VT_R4, fltVal, 1.5f32, array: true, byref: true, dynamic: true;
+    test_r8: f64 = VT_R8, dblVal, -2.25f64, array: true, byref: true, dynamic: true;
+    test_cy: Currency = VT_CY, cyVal, Currency::from(123_400), array: true, byref: true, dynamic: true;
+    test_date: Date = VT_DATE, date, Date::from(43_000.5), array: true, byref: true, dynamic: true;
+    test_bstr: String = VT_BSTR, bstrVal, String::from("matrix"), array: false, byref: true, dynamic: true;
+    test_error: SCode = VT_ERROR, scode, SCode::from(-2_147_352_573), array: true, byref: true, dynamic: true;
+    test_bool: bool = VT_BOOL, boolVal, true, array: true, byref: true, dynamic: true;
+    test_decimal: DecWrapper = VT_DECIMAL, decVal, DecWrapper::new(::rust_decimal::Decimal::new(-1234, 2)), array: true, byref: true, dynamic: true;
+    test_i1: i8 = VT_I1, cVal, -5i8, array: true, byref: true, dynamic: true;
+    test_ui1: u8 = VT_UI1, bVal, 200u8, array: true, byref: true, dynamic: true;
+    test_ui2: u16 = VT_UI2, uiVal, 60_000u16, array: true, byref: true, dynamic: true;
+    test_ui4: u32 = VT_UI4, ulVal, 4_000_000_000u32, array: true, byref: true, dynamic: true;
+    test_i8: i64 = VT_I8, llVal, -1i64 << 40, array: false, byref: true, dynamic: true;
+    test_ui8: u64 = VT_UI8, ullVal, 1u64 << 60, array: false, byref: true, dynamic: true;
+    test_int: Int = VT_INT, intVal, Int::from(-9), array: true, byref: true, dynamic: true;
+    test_uint: UInt = VT_UINT, uintVal, UInt::from(9), array: true, byref: true, dynamic: true;
+    test_cf: ClipData = VT_CF, byref, ClipData::new(13, vec![0x41, 0, 0, 0]), array: false, byref: false, dynamic: false;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rust_decimal::Decimal;
+    use super::super::types::{Currency, Date, DecWrapper, Int, SCode, UInt};
+    use super::super::variant::Variants;
+
+    // Exhaustive, so a new `Variants` arm has to be sorted into scalar or not here
+    fn is_scalar(v: &Variants) -> bool {
+        match *v {
+            Variants::Empty | Variants::Null | Variants::Unknown(_) | Variants::Dispatch(_) | Variants::Array(_) => false,
+            Variants::I64(_) | Variants::I32(_) | Variants::U8(_) | Variants::I16(_) | Variants::F32(_) | 
+            Variants::F64(_) | Variants::Bool(_) | Variants::SCode(_) | Variants::Currency(_) | Variants::Date(_) | 
+            Variants::I8(_) | Variants::U16(_) | Variants::U32(_) | Variants::U64(_) | Variants::Int(_) | 
+            Variants::UInt(_) | Variants::Decimal(_) | Variants::String(_) | Variants::WideString(_) => true,
+        }
+    }
+
+    #[test]
+    fn test_covers_variants() {
+        let samples = vec![
+            Variants::I64(1), Variants::I32(1), Variants::U8(1), Variants::I16(1), Variants::F32(1.0), 
+            Variants::F64(1.0), Variants::Bool(true), Variants::SCode(SCode::from(0)), Variants::Currency(Currency::from(1)), 
+            Variants::Date(Date::from(1.0)), Variants::I8(1), Variants::U16(1), Variants::U32(1), Variants::U64(1), 
+            Variants::Int(Int::from(1)), Variants::UInt(UInt::from(1)), Variants::Decimal(DecWrapper::new(Decimal::new(1, 0))), 
+            Variants::String(String::from("s")),
+        ];
+        for v in samples.iter().filter(|v| is_scalar(v)) {
+            assert!(VT_TABLE.iter().any(|row| row.vartype == v.vartype() && row.dynamic), "{:?} has no dynamic row", v);
+        }
+        for row in VT_TABLE.iter().filter(|row| row.dynamic) {
+            assert!(samples.iter().any(|v| v.vartype() == row.vartype), "{:?} has no Variants arm", row);
+        }
+    }
+}
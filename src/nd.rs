@@ -0,0 +1,99 @@
+//! # ndarray
+//! Conversions between `ndarray` arrays and SAFEARRAYs, behind the `ndarray` feature.
+//!
+//! Axis 0 maps to SAFEARRAY dimension 1, axis 1 to dimension 2 and so on, the
+//! same way [`into_safearray_2d`] treats rows and columns. Encoded arrays get a
+//! lower bound of 0 in every dimension. Decoding accepts any lower bounds, as
+//! Excel's 1-based `Range.Value` arrays have, and shifts them to 0.
+//!
+//! [`into_safearray_2d`]: fn.into_safearray_2d.html
+use ndarray::{indices, Array2, ArrayD, Dimension, Ix2, IxDyn};
+use winapi::um::oaidl::SAFEARRAY;
+
+use super::array::{SafeArrayElement, SafeArrayN};
+use super::errors::{FromSafeArrayError, IntoSafeArrayError};
+use super::limits::DecodeLimits;
+use super::ptr::Ptr;
+
+/// Converts an array of any dimensionality into a SAFEARRAY with the same shape
+pub fn arrayd_into_safearray<T: SafeArrayElement>(arr: ArrayD<T>) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+    let shape = arr.shape().to_vec();
+    let bounds: Vec<(i32, i32)> = shape.iter().map(|&len| (0, len as i32 - 1)).collect();
+    let mut sa = SafeArrayN::<T>::new(&bounds)?;
+    for (index, (ix, elem)) in indices(IxDyn(&shape)).into_iter().zip(arr.into_iter()).enumerate() {
+        let ixs: Vec<i32> = ix.slice().iter().map(|&i| i as i32).collect();
+        if let Err(e) = sa.set(&ixs, elem) {
+            return Err(IntoSafeArrayError::from_element_err(e, index));
+        }
+    }
+    Ok(sa.into_ptr())
+}
+
+/// Reads a SAFEARRAY of any dimensionality into an array with the same shape. The array is released afterwards.
+pub fn arrayd_from_safearray<T: SafeArrayElement>(psa: *mut SAFEARRAY) -> Result<ArrayD<T>, FromSafeArrayError> {
+    let sa = SafeArrayN::<T>::from_ptr(psa)?;
+    let _depth = DecodeLimits::enter_array()?;
+    let len = sa.len()?;
+    DecodeLimits::check_elements(len)?;
+
+    let bounds = sa.bounds()?;
+    let shape: Vec<usize> = bounds.iter().map(|&(lo, hi)| (hi as i64 - lo as i64 + 1).max(0) as usize).collect();
+    let mut elems = Vec::with_capacity(len);
+    for (index, ix) in indices(IxDyn(&shape)).into_iter().enumerate() {
+        let ixs: Vec<i32> = ix.slice().iter().zip(bounds.iter()).map(|(&i, &(lo, _))| lo + i as i32).collect();
+        match sa.get(&ixs) {
+            Ok(val) => elems.push(val),
+            Err(e) => return Err(FromSafeArrayError::from_element_err(e, index)),
+        }
+    }
+    Ok(ArrayD::from_shape_vec(IxDyn(&shape), elems).expect("one element per index of the shape"))
+}
+
+/// Converts a 2-D array into a 2-D SAFEARRAY: dimension 1 is the row, dimension 2 the column
+pub fn array2_into_safearray<T: SafeArrayElement>(arr: Array2<T>) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+    arrayd_into_safearray(arr.into_dyn())
+}
+
+/// Reads a 2-D SAFEARRAY into a 2-D array. The array is released afterwards.
+pub fn array2_from_safearray<T: SafeArrayElement>(psa: *mut SAFEARRAY) -> Result<Array2<T>, FromSafeArrayError> {
+    let arr = arrayd_from_safearray(psa)?;
+    let sa_dims = arr.ndim() as u32;
+    arr.into_dimensionality::<Ix2>().map_err(|_| FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims})
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ndarray::arr2;
+    use super::super::array::{get_bounds, SafeArrayExt};
+    #[test]
+    fn test_array2() {
+        let arr = arr2(&[[1.5f64, 2.5, 3.5], [4.5, 5.5, 6.5]]);
+        let psa = array2_into_safearray(arr.clone()).unwrap();
+        assert_eq!(get_bounds(psa.as_ptr(), 1).unwrap(), (0, 1));
+        assert_eq!(get_bounds(psa.as_ptr(), 2).unwrap(), (0, 2));
+        assert_eq!(f64::from_safearray_at(psa.as_ptr(), &[1, 0]).unwrap(), 4.5);
+        assert_eq!(array2_from_safearray::<f64>(psa.as_ptr()).unwrap(), arr);
+    }
+
+    #[test]
+    fn test_arrayd() {
+        let arr = ArrayD::from_shape_vec(IxDyn(&[2, 2, 3]), (0..12i32).collect()).unwrap();
+        let psa = arrayd_into_safearray(arr.clone()).unwrap();
+        let sa = SafeArrayN::<i32>::from_ptr(psa.as_ptr()).unwrap();
+        assert_eq!(sa.bounds().unwrap(), vec![(0, 1), (0, 1), (0, 2)]);
+        assert_eq!(sa.get(&[1, 0, 2]).unwrap(), arr[[1, 0, 2]]);
+        assert_eq!(arrayd_from_safearray::<i32>(sa.into_ptr().as_ptr()).unwrap(), arr);
+
+        let mut sa = SafeArrayN::<i32>::new(&[(1, 2), (1, 2)]).unwrap();
+        sa.set(&[2, 1], 7).unwrap();
+        let arr = array2_from_safearray::<i32>(sa.into_ptr().as_ptr()).unwrap();
+        assert_eq!(arr[[1, 0]], 7);
+
+        let psa = vec![1i32, 2].into_iter().into_safearray().unwrap();
+        match array2_from_safearray::<i32>(psa.as_ptr()) {
+            Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: 1}) => {},
+            _ => panic!("Error")
+        }
+    }
+}
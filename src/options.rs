@@ -0,0 +1,396 @@
+//! # Options
+//! Conversion policy in one place.
+//!
+//! [`ConvertOptions`] bundles the knobs that change how values are converted:
+//! the locale used when OLE Automation coerces a value, whether narrower
+//! integers and floats are widened to the requested type, what `VT_EMPTY` and
+//! `VT_NULL` decode to, what `None` encodes as, whether NaN and infinities are accepted, and the
+//! [`DecodeLimits`] in force. The options apply per thread, and their `limits`
+//! are the ones `DecodeLimits::current` reports; the `*_with` methods of
+//! `VariantExt` and `SafeArrayExt` run one conversion with a given set of
+//! options and restore the previous ones afterwards.
+//!
+//! They apply to the `VariantExt` and `SafeArrayExt` conversions, byref boxes
+//! and the `bulk_*` functions. `SafeArrayViewMut` and `SafeArrayDataGuard`
+//! expose the stored elements without converting them, so no options apply there.
+//!
+//! The defaults are the strict behaviour of the plain conversions.
+//!
+//! [`Locale`] names the LCID handed to `VariantChangeTypeEx`, which decides the 
+//! decimal separator, date order and currency format used when text is parsed 
+//! or produced.
+//!
+//! [`ConvertOptions`]: struct.ConvertOptions.html
+//! [`DecodeLimits`]: struct.DecodeLimits.html
+//! [`Locale`]: struct.Locale.html
+use std::cell::Cell;
+use std::mem;
+
+use winapi::shared::wtypes::{
+    VT_BOOL,
+    VT_BSTR,
+    VT_CY,
+    VT_DATE,
+    VT_EMPTY,
+    VT_I1,
+    VT_I2,
+    VT_I4,
+    VT_I8,
+    VT_INT,
+    VT_NULL,
+    VT_R4,
+    VT_R8,
+    VT_UI1,
+    VT_UI2,
+    VT_UI4,
+    VT_UI8,
+    VT_UINT,
+};
+use winapi::um::oaidl::VARIANT;
+use winapi::um::oleauto::{VariantChangeTypeEx, VariantClear};
+
+use super::errors::FromVariantError;
+use super::limits::DecodeLimits;
+
+/// The user's default locale, `LOCALE_USER_DEFAULT`
+pub const LOCALE_USER_DEFAULT: u32 = 0x0400;
+/// The invariant locale, `LOCALE_INVARIANT`
+pub const LOCALE_INVARIANT: u32 = 0x007f;
+/// The system's default locale, `LOCALE_SYSTEM_DEFAULT`
+const LOCALE_SYSTEM_DEFAULT: u32 = 0x0800;
+
+/// A Windows locale identifier (LCID) for locale-aware coercions
+///
+/// ## Example
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::Locale;
+///
+/// fn main() {
+///     //German (Germany)
+///     let de = Locale::from_langid(0x07, 0x01);
+///     assert_eq!(de.lcid(), 0x0407);
+///     assert_eq!(Locale::from(0x0407u32), de);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Locale(u32);
+
+impl Locale {
+    /// The user's default locale
+    pub const USER_DEFAULT: Locale = Locale(LOCALE_USER_DEFAULT);
+    /// The system's default locale
+    pub const SYSTEM_DEFAULT: Locale = Locale(LOCALE_SYSTEM_DEFAULT);
+    /// The invariant locale - culture independent, English-like formats
+    pub const INVARIANT: Locale = Locale(LOCALE_INVARIANT);
+
+    /// Wraps a raw LCID
+    pub fn new(lcid: u32) -> Locale {
+        Locale(lcid)
+    }
+
+    /// Builds the LCID for a primary and sub language id with the default sort order, like `MAKELCID(MAKELANGID(..), SORT_DEFAULT)`
+    pub fn from_langid(primary: u16, sub: u16) -> Locale {
+        Locale(u32::from(sub) << 10 | u32::from(primary & 0x3ff))
+    }
+
+    /// The raw LCID
+    pub fn lcid(self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Locale {
+        Locale::USER_DEFAULT
+    }
+}
+
+impl From<u32> for Locale {
+    fn from(lcid: u32) -> Locale {
+        Locale(lcid)
+    }
+}
+
+impl From<Locale> for u32 {
+    fn from(locale: Locale) -> u32 {
+        locale.0
+    }
+}
+
+thread_local! {
+    static OPTIONS: Cell<ConvertOptions> = Cell::new(ConvertOptions::new());
+}
+
+/// What decoding a `VT_EMPTY` or `VT_NULL` VARIANT into a scalar type does
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NullPolicy {
+    /// Fail with `VarTypeDoesNotMatch`
+    Reject,
+    /// Decode as the zero value of the type - `0`, `false`, an empty string
+    Zero,
+}
+
+/// What an `Option<T>` of `None` encodes as
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NonePolicy {
+    /// `VT_NULL`, as a database NULL
+    Null,
+    /// `VT_EMPTY`, as an unset value
+    Empty,
+}
+
+/// Which floating point values convert
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FloatPolicy {
+    /// Any value, including NaN and infinities
+    Allow,
+    /// Only finite values; NaN and infinities fail with `NonFiniteFloat`
+    RejectNonFinite,
+}
+
+/// Conversion policies, set directly or built up with the setter methods
+///
+/// ## Example
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::{ConvertOptions, NullPolicy, VariantExt, VtNull};
+///
+/// fn main() {
+///     let opts = ConvertOptions::new().lenient_widening(true).null_policy(NullPolicy::Zero);
+///     let pvar = 7i16.into_variant().unwrap();
+///     assert_eq!(i64::from_variant_with(pvar, &opts).unwrap(), 7);
+///     let pvar = VtNull{}.into_variant().unwrap();
+///     assert_eq!(String::from_variant_with(pvar, &opts).unwrap(), "");
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ConvertOptions {
+    /// Locale used when OLE Automation coerces a value
+    pub lcid: u32,
+    /// Accept narrower integers and floats for a wider requested type
+    pub lenient_widening: bool,
+    /// What `VT_EMPTY` and `VT_NULL` decode to
+    pub null_policy: NullPolicy,
+    /// What an `Option<T>` of `None` encodes as
+    pub none_policy: NonePolicy,
+    /// Which floating point values convert
+    pub float_policy: FloatPolicy,
+    /// Decode limits in force while converting
+    pub limits: DecodeLimits,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> ConvertOptions {
+        ConvertOptions::new()
+    }
+}
+
+impl ConvertOptions {
+    /// Strict options: user default locale, no widening, `VT_EMPTY`/`VT_NULL` rejected, `None` as `VT_NULL`, any float allowed, no limits
+    pub fn new() -> ConvertOptions {
+        ConvertOptions {
+            lcid: LOCALE_USER_DEFAULT,
+            lenient_widening: false,
+            null_policy: NullPolicy::Reject,
+            none_policy: NonePolicy::Null,
+            float_policy: FloatPolicy::Allow,
+            limits: DecodeLimits::default(),
+        }
+    }
+
+    /// Locale used when OLE Automation coerces a value, a `Locale` or a raw LCID
+    pub fn locale<L: Into<Locale>>(mut self, locale: L) -> ConvertOptions {
+        self.lcid = locale.into().lcid();
+        self
+    }
+
+    /// Accept narrower integers and floats for a wider requested type, e.g. a `VT_I2` as `i64`
+    pub fn lenient_widening(mut self, lenient: bool) -> ConvertOptions {
+        self.lenient_widening = lenient;
+        self
+    }
+
+    /// What `VT_EMPTY` and `VT_NULL` decode to
+    pub fn null_policy(mut self, policy: NullPolicy) -> ConvertOptions {
+        self.null_policy = policy;
+        self
+    }
+
+    /// What an `Option<T>` of `None` encodes as
+    pub fn none_policy(mut self, policy: NonePolicy) -> ConvertOptions {
+        self.none_policy = policy;
+        self
+    }
+
+    /// Which floating point values convert
+    pub fn float_policy(mut self, policy: FloatPolicy) -> ConvertOptions {
+        self.float_policy = policy;
+        self
+    }
+
+    /// Decode limits in force while converting
+    pub fn limits(mut self, limits: DecodeLimits) -> ConvertOptions {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the options in force on the current thread.
+    pub fn current() -> ConvertOptions {
+        OPTIONS.with(|o| o.get())
+    }
+
+    /// Runs `f` with these options (and their limits) in force, restoring the previous ones afterwards.
+    pub fn scoped<F: FnOnce() -> R, R>(self, f: F) -> R {
+        let _restore = Restore(OPTIONS.with(|o| o.replace(self)));
+        f()
+    }
+
+    /// Swaps the limits of the current thread's options, returning the previous ones
+    pub(crate) fn replace_limits(limits: DecodeLimits) -> DecodeLimits {
+        OPTIONS.with(|o| {
+            let mut opts = o.get();
+            let prev = mem::replace(&mut opts.limits, limits);
+            o.set(opts);
+            prev
+        })
+    }
+
+    pub(crate) fn check_float(value: f64) -> Result<(), f64> {
+        match ConvertOptions::current().float_policy {
+            FloatPolicy::RejectNonFinite if !value.is_finite() => Err(value),
+            _ => Ok(()),
+        }
+    }
+
+    /// Coerces `var` (tagged `found`) into a new VARIANT of `target` when the options allow it.
+    /// Returns `None` when they don't. The caller clears the returned VARIANT.
+    pub(crate) fn coerce(var: *const VARIANT, found: u32, target: u32) -> Result<Option<VARIANT>, FromVariantError> {
+        let opts = ConvertOptions::current();
+        let nullish = found == VT_EMPTY || found == VT_NULL;
+        let allowed = if nullish {
+            opts.null_policy == NullPolicy::Zero && has_zero(target)
+        } else {
+            opts.lenient_widening && widens(found, target)
+        };
+        if !allowed {
+            return Ok(None);
+        }
+
+        //VT_NULL does not coerce to anything, VT_EMPTY coerces to the zero value
+        let empty: VARIANT = unsafe { mem::zeroed() };
+        let src = if nullish { &empty as *const VARIANT } else { var };
+        let mut dest: VARIANT = unsafe { mem::zeroed() };
+        let hr = unsafe { VariantChangeTypeEx(&mut dest, src, opts.lcid, 0, target as u16) };
+        if hr != 0 {
+            unsafe { VariantClear(&mut dest) };
+            return Err(FromVariantError::VarTypeDoesNotMatch{expected: target, found: found});
+        }
+        Ok(Some(dest))
+    }
+}
+
+struct Restore(ConvertOptions);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        OPTIONS.with(|o| o.set(self.0));
+    }
+}
+
+// Types a `VT_EMPTY` coerces to without owning anything beyond a BSTR
+fn has_zero(vt: u32) -> bool {
+    match vt {
+        VT_I1 | VT_I2 | VT_I4 | VT_I8 | VT_INT | VT_UI1 | VT_UI2 | VT_UI4 | VT_UI8 | VT_UINT |
+        VT_R4 | VT_R8 | VT_CY | VT_DATE | VT_BSTR | VT_BOOL => true,
+        _ => false,
+    }
+}
+
+// Lossless widenings: every value of `from` is exactly representable in `to`
+fn widens(from: u32, to: u32) -> bool {
+    match (from, to) {
+        (VT_I1, VT_I2) | (VT_I1, VT_I4) | (VT_I1, VT_INT) | (VT_I1, VT_I8) | (VT_I1, VT_R4) | (VT_I1, VT_R8) => true,
+        (VT_UI1, VT_I2) | (VT_UI1, VT_UI2) | (VT_UI1, VT_I4) | (VT_UI1, VT_INT) | (VT_UI1, VT_UI4) | (VT_UI1, VT_UINT) |
+        (VT_UI1, VT_I8) | (VT_UI1, VT_UI8) | (VT_UI1, VT_R4) | (VT_UI1, VT_R8) => true,
+        (VT_I2, VT_I4) | (VT_I2, VT_INT) | (VT_I2, VT_I8) | (VT_I2, VT_R4) | (VT_I2, VT_R8) => true,
+        (VT_UI2, VT_I4) | (VT_UI2, VT_INT) | (VT_UI2, VT_UI4) | (VT_UI2, VT_UINT) | (VT_UI2, VT_I8) |
+        (VT_UI2, VT_UI8) | (VT_UI2, VT_R4) | (VT_UI2, VT_R8) => true,
+        (VT_I4, VT_INT) | (VT_INT, VT_I4) | (VT_I4, VT_I8) | (VT_INT, VT_I8) | (VT_I4, VT_R8) | (VT_INT, VT_R8) => true,
+        (VT_UI4, VT_UINT) | (VT_UINT, VT_UI4) | (VT_UI4, VT_I8) | (VT_UINT, VT_I8) | (VT_UI4, VT_UI8) |
+        (VT_UINT, VT_UI8) | (VT_UI4, VT_R8) | (VT_UINT, VT_R8) => true,
+        (VT_R4, VT_R8) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::SafeArrayExt;
+    use super::super::bulk::{bulk_from_safearray, bulk_into_safearray};
+    use super::super::errors::{IntoSafeArrayError, IntoVariantError};
+    use super::super::variant::{VariantExt, VtEmpty};
+    #[test]
+    fn test_widening() {
+        let opts = ConvertOptions::new().lenient_widening(true);
+        let pvar = 200u8.into_variant().unwrap();
+        assert_eq!(i32::from_variant_with(pvar, &opts).unwrap(), 200);
+        let pvar = (-3i16).into_variant().unwrap();
+        assert!(i32::from_variant(pvar).is_err());
+        let pvar = 1.5f32.into_variant().unwrap();
+        assert_eq!(f64::from_variant_with(pvar, &opts).unwrap(), 1.5);
+        let pvar = 1i32.into_variant().unwrap();
+        assert!(i16::from_variant_with(pvar, &opts).is_err());
+    }
+
+    #[test]
+    fn test_null_policy() {
+        let opts = ConvertOptions::new().null_policy(NullPolicy::Zero);
+        let pvar = VtEmpty{}.into_variant().unwrap();
+        assert_eq!(i32::from_variant_with(pvar, &opts).unwrap(), 0);
+        let pvar = VtEmpty{}.into_variant().unwrap();
+        assert!(i32::from_variant(pvar).is_err());
+    }
+
+    #[test]
+    fn test_float_policy() {
+        let opts = ConvertOptions::new().float_policy(FloatPolicy::RejectNonFinite);
+        match ::std::f64::NAN.into_variant_with(&opts) {
+            Err(IntoVariantError::NonFiniteFloat{..}) => {},
+            _ => panic!("Error")
+        }
+        assert!(::std::f64::NAN.into_variant().is_ok());
+        let mut v = vec![1.0f64, ::std::f64::INFINITY].into_iter();
+        assert!(v.into_safearray_with(&opts).is_err());
+        assert!(Box::new(::std::f64::NAN).into_variant_with(&opts).is_err());
+        match opts.scoped(|| bulk_into_safearray(&[1.0f32, ::std::f32::NAN])) {
+            Err(IntoSafeArrayError::ElementConversionFailed{index, ..}) => assert_eq!(index, 1),
+            _ => panic!("expected the bulk path to reject NaN"),
+        }
+        let psa = bulk_into_safearray(&[::std::f64::INFINITY]).unwrap();
+        assert!(opts.scoped(|| bulk_from_safearray::<f64>(psa.as_ptr())).is_err());
+    }
+
+    #[test]
+    fn test_locale() {
+        assert_eq!(ConvertOptions::new().locale(Locale::INVARIANT).lcid, LOCALE_INVARIANT);
+        assert_eq!(ConvertOptions::new().locale(0x0407u32).lcid, Locale::from_langid(0x07, 0x01).lcid());
+        assert_eq!(Locale::default(), Locale::USER_DEFAULT);
+    }
+
+    #[test]
+    fn test_limits() {
+        let opts = ConvertOptions::new().limits(DecodeLimits { max_elements: Some(2), ..DecodeLimits::default() });
+        let psa = vec![1i32, 2, 3].into_iter().into_safearray().unwrap();
+        assert!(ExactSizeIterator::<Item=i32>::from_safearray_with(psa.as_ptr(), &opts).is_err());
+        assert_eq!(ConvertOptions::current(), ConvertOptions::new());
+        //the thread has one set of limits, whichever way they were set
+        opts.scoped(|| assert_eq!(DecodeLimits::current(), opts.limits));
+        opts.limits.scoped(|| assert_eq!(ConvertOptions::current().limits, opts.limits));
+    }
+}
@@ -0,0 +1,460 @@
+//! # PropVariant
+//! Conversions to and from `PROPVARIANT`, the VARIANT of the Shell, WMI and the Windows Property System.
+//!
+//! [`PropVariantExt`] mirrors [`VariantExt`]: the same error types, a boxed `into_propvariant`
+//! released with [`FreePropVariant`], and raw reads and writes for storage the caller manages.
+//! Besides the scalars the two share, it covers the PROPVARIANT-only forms property stores use:
+//! `VT_LPWSTR` strings allocated with `CoTaskMemAlloc`, and `VT_VECTOR` counted arrays.
+//!
+//! [`variant_to_propvariant`] and [`propvariant_to_variant`] carry any value across the
+//! boundary between automation and property-store interfaces through the Property System's
+//! own `VariantToPropVariant`/`PropVariantToVariant`, so nothing is decoded on the way.
+//!
+//! [`PropVariantExt`]: trait.PropVariantExt.html
+//! [`VariantExt`]: trait.VariantExt.html
+//! [`FreePropVariant`]: struct.FreePropVariant.html
+//! [`variant_to_propvariant`]: fn.variant_to_propvariant.html
+//! [`propvariant_to_variant`]: fn.propvariant_to_variant.html
+use std::mem;
+use std::ptr;
+use std::slice;
+
+use widestring::{U16CStr, U16String};
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::GUID;
+use winapi::shared::minwindef::{FILETIME, ULONG};
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::wtypes::{
+    VARIANT_BOOL,
+    VARIANT_FALSE,
+    VARIANT_TRUE,
+    VT_BOOL,
+    VT_BSTR,
+    VT_CLSID,
+    VT_FILETIME,
+    VT_I1,
+    VT_I2,
+    VT_I4,
+    VT_I8,
+    VT_LPWSTR,
+    VT_R4,
+    VT_R8,
+    VT_UI1,
+    VT_UI2,
+    VT_UI4,
+    VT_UI8,
+    VT_VECTOR,
+};
+use winapi::um::combaseapi::{CoTaskMemAlloc, CoTaskMemFree};
+use winapi::um::oaidl::VARIANT;
+use winapi::um::propidl::{PROPVARIANT, PROPVARIANT_data};
+
+use super::bstr::BStringExt;
+use super::dtor::{ClearPropVariant, OwnedPtr};
+use super::errors::{ComError, FromVariantError, IntoVariantError};
+use super::limits::DecodeLimits;
+use super::ptr::Ptr;
+use super::stats::ConversionStats;
+use super::types::{FileTime, Guid};
+
+/// Conversions between Rust types and `PROPVARIANT`
+///
+/// ## Example
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::{FreePropVariant, PropVariantExt, PtrDestructor};
+///
+/// fn main() {
+///     let ppv = String::from("Quarterly report").into_propvariant().unwrap();
+///     assert_eq!(String::from_propvariant(ppv).unwrap(), "Quarterly report");
+///     FreePropVariant::destroy(ppv);
+/// }
+/// ```
+pub trait PropVariantExt: Sized {
+    /// VARTYPE the type is written as
+    const VARTYPE: u32;
+
+    /// Writes `self` into `out`, which must hold nothing that still needs `PropVariantClear`.
+    /// On success the caller owns the contents; on error `out` is untouched.
+    unsafe fn write_propvariant(self, out: *mut PROPVARIANT) -> Result<(), IntoVariantError>;
+
+    /// Reads a value out of `src` without taking its contents. Strings and vectors are copied.
+    unsafe fn read_propvariant(src: *const PROPVARIANT) -> Result<Self, FromVariantError>;
+
+    /// Converts into a boxed PROPVARIANT, to be released with `FreePropVariant`
+    fn into_propvariant(self) -> Result<Ptr<PROPVARIANT>, IntoVariantError> {
+        let mut pv: PROPVARIANT = unsafe { mem::zeroed() };
+        unsafe { self.write_propvariant(&mut pv)? };
+        Ok(Ptr::with_checked(Box::into_raw(Box::new(pv))).unwrap())
+    }
+
+    /// Reads a value out of `pvar`. Its contents are released afterwards, on success or error,
+    /// leaving it `VT_EMPTY`; the memory behind `pvar` still belongs to the caller.
+    fn from_propvariant(pvar: Ptr<PROPVARIANT>) -> Result<Self, FromVariantError> {
+        let _clear = OwnedPtr::<PROPVARIANT, ClearPropVariant>::new(pvar);
+        unsafe { Self::read_propvariant(pvar.as_ptr()) }
+    }
+}
+
+/// Copies a VARIANT into a new boxed PROPVARIANT, to be released with `FreePropVariant`. 
+/// `var` is left as it was. 
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{propvariant_to_variant, variant_to_propvariant, FreePropVariant, PtrDestructor, VariantExt};
+/// 
+/// fn main() {
+///     let pvar = String::from("Contoso").into_variant().unwrap();
+///     let ppv = variant_to_propvariant(pvar).unwrap();
+///     let back = propvariant_to_variant(ppv).unwrap();
+///     assert_eq!(String::from_variant(back).unwrap(), "Contoso");
+///     FreePropVariant::destroy(ppv);
+/// }
+/// ```
+pub fn variant_to_propvariant(var: Ptr<VARIANT>) -> Result<Ptr<PROPVARIANT>, ComError> {
+    let mut pv: Box<PROPVARIANT> = Box::new(unsafe { mem::zeroed() });
+    let hr = unsafe { VariantToPropVariant(var.as_ptr(), &mut *pv) };
+    if hr != 0 {
+        return Err(ComError::VariantToPropVariantFailed{hr: hr});
+    }
+    Ok(Ptr::with_checked(Box::into_raw(pv)).unwrap())
+}
+
+/// Copies a PROPVARIANT into a new boxed VARIANT, to be released with `FreeVariant`. 
+/// `pvar` is left as it was. PROPVARIANT-only forms are mapped to their closest VARIANT: 
+/// `VT_LPWSTR` becomes a `VT_BSTR`, and a `VT_VECTOR` becomes a `VT_ARRAY`.
+pub fn propvariant_to_variant(pvar: Ptr<PROPVARIANT>) -> Result<Ptr<VARIANT>, ComError> {
+    let mut var: Box<VARIANT> = Box::new(unsafe { mem::zeroed() });
+    let hr = unsafe { PropVariantToVariant(pvar.as_ptr(), &mut *var) };
+    if hr != 0 {
+        return Err(ComError::PropVariantToVariantFailed{hr: hr});
+    }
+    ConversionStats::record_variant();
+    Ok(Ptr::with_checked(Box::into_raw(var)).unwrap())
+}
+
+#[link(name="propsys")]
+extern "system" {
+    fn VariantToPropVariant(pVar: *const VARIANT, pPropVar: *mut PROPVARIANT) -> HRESULT;
+    fn PropVariantToVariant(pPropVar: *const PROPVARIANT, pVar: *mut VARIANT) -> HRESULT;
+}
+
+/// Layout shared by the `CA*` counted arrays of a `VT_VECTOR` PROPVARIANT
+#[repr(C)]
+struct Counted<T> {
+    c_elems: ULONG,
+    p_elems: *mut T,
+}
+
+/// The union payload of `p`, read as a `T`
+fn data_ptr<T>(p: *const PROPVARIANT) -> *mut T {
+    unsafe { &(*p).data as *const PROPVARIANT_data as *mut T }
+}
+
+fn check_vt(src: *const PROPVARIANT, expected: u32) -> Result<(), FromVariantError> {
+    let found = unsafe { (*src).vt } as u32;
+    if found != expected {
+        return Err(FromVariantError::VarTypeDoesNotMatch{expected: expected, found: found});
+    }
+    Ok(())
+}
+
+/// Copies `elems` into a `CoTaskMemAlloc` buffer and points `out` at it as a `vt` vector
+unsafe fn write_counted<T: Copy>(out: *mut PROPVARIANT, vt: u32, elems: &[T]) -> Result<(), IntoVariantError> {
+    let len = elems.len() * mem::size_of::<T>();
+    let p_elems = CoTaskMemAlloc(len) as *mut T;
+    if p_elems.is_null() && len != 0 {
+        return Err(IntoVariantError::CoTaskMemAllocFailed{len: len});
+    }
+    if len != 0 {
+        ptr::copy_nonoverlapping(elems.as_ptr(), p_elems, elems.len());
+    }
+    (*out).vt = vt as u16;
+    ptr::write(data_ptr::<Counted<T>>(out), Counted { c_elems: elems.len() as ULONG, p_elems: p_elems });
+    Ok(())
+}
+
+/// The elements of the vector in `src`, which stay owned by `src`
+unsafe fn read_counted<'a, T>(src: *const PROPVARIANT) -> Result<&'a [T], FromVariantError> {
+    let ca = &*data_ptr::<Counted<T>>(src);
+    if ca.c_elems == 0 {
+        return Ok(&[]);
+    }
+    if ca.p_elems.is_null() {
+        return Err(FromVariantError::CVoidPtrNull);
+    }
+    DecodeLimits::check_elements(ca.c_elems as usize)?;
+    Ok(slice::from_raw_parts(ca.p_elems, ca.c_elems as usize))
+}
+
+/// Allocates a NUL-terminated copy of `s` with `CoTaskMemAlloc`, as `VT_LPWSTR` expects
+fn alloc_lpwstr(s: &str) -> Result<*mut u16, IntoVariantError> {
+    let wide: Vec<u16> = s.encode_utf16().collect();
+    if let Some(pos) = wide.iter().position(|&c| c == 0) {
+        return Err(IntoVariantError::InteriorNul{pos: pos});
+    }
+    let len = (wide.len() + 1) * mem::size_of::<u16>();
+    let p = unsafe { CoTaskMemAlloc(len) } as *mut u16;
+    if p.is_null() {
+        return Err(IntoVariantError::CoTaskMemAllocFailed{len: len});
+    }
+    unsafe {
+        ptr::copy_nonoverlapping(wide.as_ptr(), p, wide.len());
+        *p.add(wide.len()) = 0;
+    }
+    Ok(p)
+}
+
+fn read_lpwstr(p: *const u16) -> String {
+    if p.is_null() {
+        return String::new();
+    }
+    unsafe { U16CStr::from_ptr_str(p) }.to_string_lossy()
+}
+
+macro_rules! prop_scalar_impl {
+    ($t:ty, $vt:expr, $raw:ty, $to_raw:expr, $from_raw:expr) => {
+        impl PropVariantExt for $t {
+            const VARTYPE: u32 = $vt;
+
+            unsafe fn write_propvariant(self, out: *mut PROPVARIANT) -> Result<(), IntoVariantError> {
+                let to_raw: fn($t) -> $raw = $to_raw;
+                (*out).vt = $vt as u16;
+                ptr::write(data_ptr::<$raw>(out), to_raw(self));
+                Ok(())
+            }
+
+            unsafe fn read_propvariant(src: *const PROPVARIANT) -> Result<Self, FromVariantError> {
+                check_vt(src, $vt)?;
+                let from_raw: fn($raw) -> $t = $from_raw;
+                Ok(from_raw(ptr::read(data_ptr::<$raw>(src))))
+            }
+        }
+
+        impl PropVariantExt for Vec<$t> {
+            const VARTYPE: u32 = VT_VECTOR | $vt;
+
+            unsafe fn write_propvariant(self, out: *mut PROPVARIANT) -> Result<(), IntoVariantError> {
+                let to_raw: fn($t) -> $raw = $to_raw;
+                let raw: Vec<$raw> = self.into_iter().map(to_raw).collect();
+                write_counted(out, VT_VECTOR | $vt, &raw)
+            }
+
+            unsafe fn read_propvariant(src: *const PROPVARIANT) -> Result<Self, FromVariantError> {
+                check_vt(src, VT_VECTOR | $vt)?;
+                let from_raw: fn($raw) -> $t = $from_raw;
+                Ok(read_counted::<$raw>(src)?.iter().map(|&r| from_raw(r)).collect())
+            }
+        }
+    };
+    ($t:ty, $vt:expr) => {
+        prop_scalar_impl!($t, $vt, $t, |v| v, |r| r);
+    };
+}
+
+prop_scalar_impl!(i8, VT_I1);
+prop_scalar_impl!(u8, VT_UI1);
+prop_scalar_impl!(i16, VT_I2);
+prop_scalar_impl!(u16, VT_UI2);
+prop_scalar_impl!(i32, VT_I4);
+prop_scalar_impl!(u32, VT_UI4);
+prop_scalar_impl!(i64, VT_I8);
+prop_scalar_impl!(u64, VT_UI8);
+prop_scalar_impl!(f32, VT_R4);
+prop_scalar_impl!(f64, VT_R8);
+prop_scalar_impl!(bool, VT_BOOL, VARIANT_BOOL, |b| if b { VARIANT_TRUE } else { VARIANT_FALSE }, |r| r != VARIANT_FALSE);
+prop_scalar_impl!(FileTime, VT_FILETIME, FILETIME, FILETIME::from, FileTime::from);
+
+/// Written as `VT_CLSID`, the GUID allocated with `CoTaskMemAlloc`
+impl PropVariantExt for Guid {
+    const VARTYPE: u32 = VT_CLSID;
+
+    unsafe fn write_propvariant(self, out: *mut PROPVARIANT) -> Result<(), IntoVariantError> {
+        let len = mem::size_of::<GUID>();
+        let p = CoTaskMemAlloc(len) as *mut GUID;
+        if p.is_null() {
+            return Err(IntoVariantError::CoTaskMemAllocFailed{len: len});
+        }
+        ptr::write(p, GUID::from(self));
+        (*out).vt = VT_CLSID as u16;
+        ptr::write(data_ptr::<*mut GUID>(out), p);
+        Ok(())
+    }
+
+    unsafe fn read_propvariant(src: *const PROPVARIANT) -> Result<Self, FromVariantError> {
+        check_vt(src, VT_CLSID)?;
+        let p = ptr::read(data_ptr::<*mut GUID>(src));
+        if p.is_null() {
+            return Err(FromVariantError::CVoidPtrNull);
+        }
+        Ok(Guid::from(*p))
+    }
+}
+
+/// Written as `VT_VECTOR | VT_CLSID`, the GUIDs stored inline
+impl PropVariantExt for Vec<Guid> {
+    const VARTYPE: u32 = VT_VECTOR | VT_CLSID;
+
+    unsafe fn write_propvariant(self, out: *mut PROPVARIANT) -> Result<(), IntoVariantError> {
+        let raw: Vec<GUID> = self.into_iter().map(GUID::from).collect();
+        write_counted(out, VT_VECTOR | VT_CLSID, &raw)
+    }
+
+    unsafe fn read_propvariant(src: *const PROPVARIANT) -> Result<Self, FromVariantError> {
+        check_vt(src, VT_VECTOR | VT_CLSID)?;
+        Ok(read_counted::<GUID>(src)?.iter().map(Guid::from).collect())
+    }
+}
+
+/// Written as `VT_LPWSTR`. Reads accept `VT_LPWSTR` and `VT_BSTR`; invalid UTF-16 is replaced.
+impl PropVariantExt for String {
+    const VARTYPE: u32 = VT_LPWSTR;
+
+    unsafe fn write_propvariant(self, out: *mut PROPVARIANT) -> Result<(), IntoVariantError> {
+        let p = alloc_lpwstr(&self)?;
+        (*out).vt = VT_LPWSTR as u16;
+        ptr::write(data_ptr::<*mut u16>(out), p);
+        Ok(())
+    }
+
+    unsafe fn read_propvariant(src: *const PROPVARIANT) -> Result<Self, FromVariantError> {
+        let p = ptr::read(data_ptr::<*mut u16>(src));
+        match (*src).vt as u32 {
+            VT_LPWSTR => Ok(read_lpwstr(p)),
+            VT_BSTR => {
+                DecodeLimits::check_bstr(p)?;
+                Ok(U16String::from_bstr(p).to_string_lossy())
+            },
+            found => Err(FromVariantError::VarTypeDoesNotMatch{expected: VT_LPWSTR, found: found}),
+        }
+    }
+}
+
+/// Written as `VT_VECTOR | VT_LPWSTR`, each string allocated with `CoTaskMemAlloc`
+impl PropVariantExt for Vec<String> {
+    const VARTYPE: u32 = VT_VECTOR | VT_LPWSTR;
+
+    unsafe fn write_propvariant(self, out: *mut PROPVARIANT) -> Result<(), IntoVariantError> {
+        let mut ptrs: Vec<*mut u16> = Vec::with_capacity(self.len());
+        for s in &self {
+            match alloc_lpwstr(s) {
+                Ok(p) => ptrs.push(p),
+                Err(ive) => {
+                    for p in ptrs {
+                        CoTaskMemFree(p as *mut c_void);
+                    }
+                    return Err(ive);
+                }
+            }
+        }
+        let ret = write_counted(out, VT_VECTOR | VT_LPWSTR, &ptrs);
+        if ret.is_err() {
+            for p in ptrs {
+                CoTaskMemFree(p as *mut c_void);
+            }
+        }
+        ret
+    }
+
+    unsafe fn read_propvariant(src: *const PROPVARIANT) -> Result<Self, FromVariantError> {
+        check_vt(src, VT_VECTOR | VT_LPWSTR)?;
+        Ok(read_counted::<*mut u16>(src)?.iter().map(|&p| read_lpwstr(p)).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::dtor::{FreePropVariant, PtrDestructor};
+    use super::super::strings::{safearray_to_strings, Utf16Mode};
+    use super::super::variant::VariantExt;
+
+    fn roundtrip<T: PropVariantExt>(val: T) -> T {
+        let ppv = val.into_propvariant().unwrap();
+        let ret = T::from_propvariant(ppv).unwrap();
+        assert_eq!(unsafe { (*ppv.as_ptr()).vt }, 0);
+        FreePropVariant::destroy(ppv);
+        ret
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(roundtrip(-5i8), -5);
+        assert_eq!(roundtrip(0xABCDu16), 0xABCD);
+        assert_eq!(roundtrip(-1_000_000i32), -1_000_000);
+        assert_eq!(roundtrip(u64::max_value()), u64::max_value());
+        assert_eq!(roundtrip(0.25f32), 0.25);
+        assert_eq!(roundtrip(true), true);
+        assert_eq!(roundtrip(false), false);
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(<i32 as PropVariantExt>::VARTYPE, VT_I4);
+        assert_eq!(<String as PropVariantExt>::VARTYPE, VT_LPWSTR);
+        assert_eq!(roundtrip(String::from("Author")), "Author");
+        assert_eq!(roundtrip(String::new()), "");
+        match String::from("a\0b").into_propvariant() {
+            Err(IntoVariantError::InteriorNul{pos: 1}) => {},
+            _ => panic!("expected an interior NUL error"),
+        }
+    }
+
+    #[test]
+    fn test_vectors() {
+        assert_eq!(roundtrip(vec![1.5f64, -2.5, 1e300]), vec![1.5, -2.5, 1e300]);
+        assert_eq!(roundtrip(vec![true, false, true]), vec![true, false, true]);
+        assert_eq!(roundtrip(Vec::<u8>::new()), Vec::<u8>::new());
+        let tags = vec![String::from("draft"), String::from("q3")];
+        assert_eq!(roundtrip(tags.clone()), tags);
+    }
+
+    #[test]
+    fn test_filetime() {
+        let ft = FileTime::from(131_000_000_000_000_000u64);
+        assert_eq!(roundtrip(ft), ft);
+        assert_eq!(<FileTime as PropVariantExt>::VARTYPE, VT_FILETIME);
+        assert_eq!(roundtrip(vec![ft, FileTime::from(0u64)]), vec![ft, FileTime::from(0u64)]);
+    }
+
+    #[test]
+    fn test_guid() {
+        let g = Guid::new(0x6B29_FC40, 0xCA47, 0x1067, [0xB3, 0x1D, 0x00, 0xDD, 0x01, 0x06, 0x62, 0xDA]);
+        assert_eq!(roundtrip(g), g);
+        assert_eq!(roundtrip(vec![g, g]), vec![g, g]);
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let ppv = 7u32.into_propvariant().unwrap();
+        match i32::from_propvariant(ppv) {
+            Err(FromVariantError::VarTypeDoesNotMatch{expected, found}) => {
+                assert_eq!(expected, VT_I4);
+                assert_eq!(found, VT_UI4);
+            },
+            _ => panic!("expected a vartype mismatch"),
+        }
+        FreePropVariant::destroy(ppv);
+    }
+    #[test]
+    fn test_variant_bridge() {
+        let pvar = 1.25f64.into_variant().unwrap();
+        let ppv = variant_to_propvariant(pvar).unwrap();
+        assert_eq!(f64::from_variant(pvar).unwrap(), 1.25);
+        assert_eq!(f64::from_propvariant(ppv).unwrap(), 1.25);
+        FreePropVariant::destroy(ppv);
+
+        let ppv = vec![String::from("a"), String::from("b")].into_propvariant().unwrap();
+        let pvar = propvariant_to_variant(ppv).unwrap();
+        FreePropVariant::destroy(ppv);
+        //a VT_ARRAY | VT_BSTR, which safearray_to_strings releases; only the box is left
+        let psa = unsafe { *(*pvar.as_ptr()).n1.n2().n3.parray() };
+        assert_eq!(safearray_to_strings(psa, Utf16Mode::Strict).unwrap(), vec!["a", "b"]);
+        drop(unsafe { Box::from_raw(pvar.as_ptr()) });
+    }
+}
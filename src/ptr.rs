@@ -1,5 +1,14 @@
 use std::fmt;
-use std::ptr::NonNull;
+use std::ptr::{null_mut, NonNull};
+
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::IID;
+use winapi::um::oaidl::IDispatch;
+use winapi::um::unknwnbase::IUnknown;
+use winapi::Interface;
+
+use super::dtor::{ComDestructor, OwnedPtr};
+use super::errors::ComError;
 
 /// Convenience type for holding value of `*mut T`
 /// Mostly just a projection of `NonNull<T>` functionality
@@ -54,6 +63,39 @@ impl<T> Ptr<T> {
     }
 }
 
+impl Ptr<IUnknown> {
+    /// Calls `QueryInterface` for the interface `T`, returning an owned reference
+    pub fn query<T: Interface>(&self) -> Result<OwnedPtr<T, ComDestructor>, ComError> {
+        unsafe { self.query_iid(&T::uuidof()) }
+    }
+
+    /// Calls `QueryInterface` with an explicit IID, returning the result as a `T`. 
+    /// 
+    /// For IIDs other than `T::uuidof()` that share `T`'s vtable, such as a dispinterface 
+    /// (`DIID_...`) queried as `IDispatch`. 
+    /// 
+    /// ## Safety
+    /// 
+    /// Whatever interface `iid` identifies must have the vtable layout of `T`. 
+    /// The returned pointer is used as a `T` without any further check.
+    pub unsafe fn query_iid<T: Interface>(&self, iid: &IID) -> Result<OwnedPtr<T, ComDestructor>, ComError> {
+        let mut p: *mut c_void = null_mut();
+        let hr = (*self.as_ptr()).QueryInterface(iid, &mut p);
+        if hr < 0 {
+            return Err(ComError::QueryInterfaceFailed{hr: hr});
+        }
+        match Ptr::with_checked(p as *mut T) {
+            Some(p) => Ok(OwnedPtr::new(p)), 
+            None => Err(ComError::QueryInterfaceFailed{hr: hr}),
+        }
+    }
+
+    /// `QueryInterface` for `IDispatch`, as needed before late-bound calls on a `VT_UNKNOWN` result
+    pub fn query_dispatch(&self) -> Result<OwnedPtr<IDispatch, ComDestructor>, ComError> {
+        self.query::<IDispatch>()
+    }
+}
+
 impl<T> fmt::Pointer for Ptr<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:p}", self.inner)
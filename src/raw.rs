@@ -0,0 +1,102 @@
+//! # Raw
+//! Conversions to and from VARIANT storage the caller manages.
+//!
+//! `into_variant` and `from_variant` hand VARIANTs around as boxed `Ptr<VARIANT>`s.
+//! Bindings that keep VARIANTs in their own buffers - `DISPPARAMS` arrays, Excel
+//! `XLOPER` shims, out-parameters - only need the type mapping, so these functions
+//! write into and read from a plain `*mut VARIANT`/`*const VARIANT` instead. For
+//! the built-in scalar and string types neither direction allocates a box.
+//!
+//! ```
+//! extern crate oaidl;
+//! extern crate winapi;
+//!
+//! use std::mem;
+//! use oaidl::raw;
+//! use winapi::um::oaidl::VARIANT;
+//! use winapi::um::oleauto::VariantClear;
+//!
+//! fn main() {
+//!     let mut slots: [VARIANT; 2] = unsafe { mem::zeroed() };
+//!     unsafe {
+//!         raw::write_variant(42i32, &mut slots[0]).unwrap();
+//!         raw::write_variant(String::from("forty-two"), &mut slots[1]).unwrap();
+//!         assert_eq!(raw::read_variant::<i32>(&slots[0]).unwrap(), 42);
+//!         assert_eq!(raw::read_variant::<String>(&slots[1]).unwrap(), "forty-two");
+//!         for slot in slots.iter_mut() {
+//!             VariantClear(slot);
+//!         }
+//!     }
+//! }
+//! ```
+use winapi::um::oaidl::VARIANT;
+
+use super::errors::{FromVariantError, IntoVariantError};
+use super::variant::VariantExt;
+
+/// Writes `value` into `out`.
+///
+/// `out` must point to writable VARIANT storage holding nothing that still needs
+/// `VariantClear` - it is overwritten, not cleared. On success the caller owns
+/// the contents and must clear them eventually; on error `out` is untouched.
+pub unsafe fn write_variant<T: VariantExt>(value: T, out: *mut VARIANT) -> Result<(), IntoVariantError> {
+    value.write_raw(out)
+}
+
+/// Reads a `T` out of `src`.
+///
+/// `src` is only read: it keeps ownership of its contents on success and on error,
+/// and the caller clears it as before. Strings are copied out; interface pointers
+/// come back `AddRef`'d, so the caller releases them independently of `src`.
+pub unsafe fn read_variant<T: VariantExt>(src: *const VARIANT) -> Result<T, FromVariantError> {
+    T::read_raw(src)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::mem;
+    use winapi::um::oleauto::VariantClear;
+    use super::super::leak_tracking;
+    use super::super::types::Currency;
+    #[test]
+    fn test_round_trip() {
+        let mut var: VARIANT = unsafe { mem::zeroed() };
+        unsafe {
+            write_variant(Currency::from(12500), &mut var).unwrap();
+            assert_eq!(read_variant::<Currency>(&var).unwrap(), Currency::from(12500));
+            match read_variant::<String>(&var) {
+                Err(FromVariantError::VarTypeDoesNotMatch{..}) => {},
+                _ => panic!("expected a vartype mismatch"),
+            }
+            assert_eq!(read_variant::<Currency>(&var).unwrap(), Currency::from(12500));
+        }
+    }
+
+    #[test]
+    fn test_read_leaves_source() {
+        let mut var: VARIANT = unsafe { mem::zeroed() };
+        leak_tracking::assert_balanced(|| unsafe {
+            write_variant(vec![1i32, 2, 3], &mut var).unwrap();
+            assert_eq!(read_variant::<Vec<i32>>(&var).unwrap(), vec![1, 2, 3]);
+            assert_eq!(read_variant::<Vec<i32>>(&var).unwrap(), vec![1, 2, 3]);
+        });
+        unsafe { VariantClear(&mut var) };
+    }
+
+    #[test]
+    fn test_read_interface() {
+        use super::super::stream::{bytes_to_stream_variant, StreamVarType};
+        use super::super::dtor::{ComDestructor, FreeVariant, OwnedPtr, PtrDestructor};
+        use winapi::um::unknwnbase::IUnknown;
+        let pvar = bytes_to_stream_variant(b"x", StreamVarType::Unknown).unwrap();
+        let owned = unsafe { read_variant::<Option<OwnedPtr<IUnknown, ComDestructor>>>(pvar.as_ptr()) }.unwrap().unwrap();
+        unsafe { VariantClear(pvar.as_ptr()) };
+        // the VARIANT's reference is gone, only ours is left
+        let punk = owned.as_ptr();
+        assert_eq!(unsafe { (*punk).AddRef() }, 2);
+        unsafe { (*punk).Release() };
+        drop(owned);
+        FreeVariant::destroy(pvar);
+    }
+}
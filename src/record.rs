@@ -0,0 +1,654 @@
+//! # Record
+//! SAFEARRAYs of user-defined types (`VT_RECORD`). 
+//! 
+//! A UDT is described at runtime by an `IRecordInfo`, usually obtained from the 
+//! type library that declares it (`GetRecordInfoFromTypeInfo`). Rust structs that 
+//! mirror a UDT implement [`Record`] by listing their fields as VARIANTs; 
+//! [`records_into_safearray`] then lays them out in a `VT_RECORD` SAFEARRAY in 
+//! one call - the shape WMI method parameters and similar APIs require. 
+//! [`records_from_safearray`] reads such an array back, and [`RecordElement`] 
+//! makes a record a `SafeArrayElement`, for element-wise access to record arrays. 
+//! 
+//! Single records travel as `VT_RECORD` VARIANTs, which carry the record buffer 
+//! (`pvRecord`) together with its `IRecordInfo` (`pRecInfo`): see 
+//! [`record_into_variant`] and [`record_from_variant`]. When no type info is at 
+//! hand, [`record_info_from_guids`] looks the UDT up in its registered type library.
+//! 
+//! [`Record`]: trait.Record.html
+//! [`records_into_safearray`]: fn.records_into_safearray.html
+//! [`records_from_safearray`]: fn.records_from_safearray.html
+//! [`RecordElement`]: struct.RecordElement.html
+//! [`record_into_variant`]: fn.record_into_variant.html
+//! [`record_from_variant`]: fn.record_from_variant.html
+//! [`record_info_from_guids`]: fn.record_info_from_guids.html
+use std::mem;
+use std::ptr::null_mut;
+use std::slice;
+
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{GUID, REFGUID};
+use winapi::shared::minwindef::ULONG;
+use winapi::shared::ntdef::{HRESULT, LCID};
+use winapi::shared::wtypes::{BSTR, VT_RECORD};
+use winapi::um::oaidl::{IRecordInfo, ITypeInfo, INVOKE_PROPERTYPUT, SAFEARRAY, TYPEATTR, VARDESC, VARIANT, VARIANT_n3, __tagBRECORD};
+use winapi::um::oleauto::{SysFreeString, SysStringLen, VariantClear};
+
+use super::array::{
+    SafeArrayAccessData, SafeArrayCreateVectorEx, SafeArrayDestructor, SafeArrayElement, SafeArrayGetDim, 
+    SafeArrayGetElemSize, SafeArrayGetRecordInfo, SafeArrayLock, SafeArrayPtrOfIndex, SafeArrayUnaccessData, SafeArrayUnlock, SafeArrayVecExt,
+};
+use super::dtor::{ClearVariant, ComDestructor, FreeVariant, OwnedPtr, PtrDestructor};
+use super::errors::{
+    ComError, ElementError, FromSafeArrayError, FromSafeArrElemError, FromVariantError, IntoSafeArrayError, IntoSafeArrElemError, IntoVariantError, 
+    RecordLayoutError,
+};
+use super::ptr::Ptr;
+use super::types::Guid;
+use super::stats::ConversionStats;
+use super::variant::{new_variant, VariantExt};
+
+/// A Rust struct mirroring a user-defined type described by an `IRecordInfo`
+/// 
+/// Field names must match the UDT's field names; fields not listed keep the 
+/// value `IRecordInfo` initialised them with.
+pub trait Record: Sized {
+    /// UDT field names this struct maps, in declaration order. 
+    /// Used by `check_record_fields`; empty means unchecked.
+    const FIELDS: &'static [&'static str] = &[];
+
+    /// Converts each field into a VARIANT, paired with the UDT field name
+    fn into_fields(self) -> Result<Vec<(&'static str, Ptr<VARIANT>)>, IntoSafeArrElemError>;
+
+    /// Rebuilds the struct from the fields of a record, read by name through `fields`
+    fn from_fields(fields: &RecordFields) -> Result<Self, FromVariantError>;
+
+    /// Byte offsets of the fields in `FIELDS` order, for a `#[repr(C)]` struct that 
+    /// mirrors the UDT's memory layout. Used by `check_record_layout`; `None` means 
+    /// only the names are checked.
+    fn field_offsets() -> Option<Vec<usize>> {
+        None
+    }
+}
+
+/// Read access to the fields of one record, handed to `Record::from_fields`
+#[derive(Debug)]
+pub struct RecordFields {
+    rec_info: *mut IRecordInfo, 
+    data: *mut c_void,
+}
+
+impl RecordFields {
+    /// Reads the field `name` as a `T`. The record is left as it was.
+    pub fn get<T: VariantExt>(&self, name: &str) -> Result<T, FromVariantError> {
+        let wide = to_wide(name);
+        let mut var: VARIANT = unsafe { mem::zeroed() };
+        let hr = unsafe { (*self.rec_info).GetField(self.data, wide.as_ptr(), &mut var) };
+        if hr != 0 {
+            return Err(FromVariantError::RecordGetFieldFailed{field: name.to_string(), hr: hr});
+        }
+        let ret = unsafe { T::read_raw(&var) };
+        unsafe { VariantClear(&mut var) };
+        ret
+    }
+}
+
+/// Implements `Record` for a struct, one UDT field per struct field
+///
+/// Every field of the struct is listed, and each field type implements `VariantExt`. 
+/// A field is matched to the UDT field of the same name, or to the name given after `=>`. 
+/// Fields are read and written by name, so the Rust layout need not match the UDT's; 
+/// [`check_record_fields`](fn.check_record_fields.html) verifies the names and order 
+/// against an `IRecordInfo`.
+///
+/// With the `derive` feature, `#[derive(VariantRecord)]` generates the same impl, and for 
+/// a `#[repr(C)]` struct also the field offsets [`check_record_layout`](fn.check_record_layout.html) compares.
+///
+/// ## Example
+///
+/// ```
+/// #[macro_use] extern crate oaidl;
+///
+/// use oaidl::Record;
+///
+/// struct Point {
+///     x: i32,
+///     y: i32,
+///     label: String,
+/// }
+///
+/// com_record!(Point { x => "X", y => "Y", label });
+///
+/// fn main() {
+///     assert_eq!(Point::FIELDS, &["X", "Y", "label"]);
+/// }
+/// ```
+#[macro_export]
+macro_rules! com_record {
+    (@name $field:ident) => { stringify!($field) };
+    (@name $field:ident $udt:expr) => { $udt };
+    ($name:ident { $($field:ident $(=> $udt:expr)*),* $(,)* }) => {
+        impl $crate::Record for $name {
+            const FIELDS: &'static [&'static str] = &[$($crate::com_record!(@name $field $($udt)*)),*];
+
+            fn into_fields(self) -> Result<Vec<(&'static str, $crate::Ptr<$crate::__VARIANT>)>, $crate::IntoSafeArrElemError> {
+                let mut fields = Vec::new();
+                $($crate::__push_field(&mut fields, $crate::com_record!(@name $field $($udt)*), self.$field)?;)*
+                Ok(fields)
+            }
+
+            fn from_fields(fields: &$crate::RecordFields) -> Result<$name, $crate::FromVariantError> {
+                Ok($name {
+                    $($field: fields.get($crate::com_record!(@name $field $($udt)*))?,)*
+                })
+            }
+        }
+    };
+}
+
+/// The `GUID` UDT of the `stdole` type library, as GUID fields of records are declared. 
+/// `Data4` travels as an 8-element `VT_UI1` array.
+impl Record for Guid {
+    const FIELDS: &'static [&'static str] = &["Data1", "Data2", "Data3", "Data4"];
+
+    fn into_fields(self) -> Result<Vec<(&'static str, Ptr<VARIANT>)>, IntoSafeArrElemError> {
+        let (data1, data2, data3, data4) = self.fields();
+        let mut fields = Vec::new();
+        __push_field(&mut fields, "Data1", data1)?;
+        __push_field(&mut fields, "Data2", data2)?;
+        __push_field(&mut fields, "Data3", data3)?;
+        __push_field(&mut fields, "Data4", data4.to_vec())?;
+        Ok(fields)
+    }
+
+    fn from_fields(fields: &RecordFields) -> Result<Guid, FromVariantError> {
+        let data4: Vec<u8> = fields.get("Data4")?;
+        if data4.len() != 8 {
+            return Err(FromVariantError::ArrayLengthMismatch{expected: 8, found: data4.len()});
+        }
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&data4);
+        Ok(Guid::new(fields.get("Data1")?, fields.get("Data2")?, fields.get("Data3")?, bytes))
+    }
+}
+
+/// Converts one field for `com_record!`, freeing the fields converted so far if it fails
+#[doc(hidden)]
+pub fn __push_field<T: VariantExt>(fields: &mut Vec<(&'static str, Ptr<VARIANT>)>, name: &'static str, value: T) -> Result<(), IntoSafeArrElemError> {
+    match value.into_variant() {
+        Ok(pvar) => {
+            fields.push((name, pvar));
+            Ok(())
+        }, 
+        Err(e) => {
+            for (_, pvar) in fields.drain(..) {
+                FreeVariant::destroy(pvar);
+            }
+            Err(IntoSafeArrElemError::from(e))
+        }
+    }
+}
+
+/// Checks that every name in `R::FIELDS` is a field of the UDT `rec_info` describes, 
+/// in the same relative order. The UDT may have fields the struct leaves out.
+pub fn check_record_fields<R: Record>(rec_info: Ptr<IRecordInfo>) -> Result<(), RecordLayoutError> {
+    let names = record_field_names(rec_info)?;
+    check_field_order::<R>(&names)
+}
+
+fn check_field_order<R: Record>(names: &[String]) -> Result<(), RecordLayoutError> {
+    let mut rest = names.iter();
+    for (index, &field) in R::FIELDS.iter().enumerate() {
+        if !rest.any(|name| name == field) {
+            return Err(RecordLayoutError::FieldMismatch{index: index, field: field});
+        }
+    }
+    Ok(())
+}
+
+/// Checks `R` against the UDT `rec_info` describes as `check_record_fields` does, 
+/// and, when `R::field_offsets` reports them, that each field sits at the byte offset 
+/// the UDT declares for it.
+pub fn check_record_layout<R: Record>(rec_info: Ptr<IRecordInfo>) -> Result<(), RecordLayoutError> {
+    let pri = rec_info.as_ptr();
+    let names = record_field_names(rec_info)?;
+    check_field_order::<R>(&names)?;
+    let offsets = match R::field_offsets() {
+        Some(offsets) => offsets, 
+        None => return Ok(()),
+    };
+    let udt_offsets = record_field_offsets(pri)?;
+    for (index, (&field, &found)) in R::FIELDS.iter().zip(offsets.iter()).enumerate() {
+        let expected = names.iter().zip(udt_offsets.iter())
+                            .find(|&(name, _)| name == field)
+                            .map(|(_, &offset)| offset);
+        match expected {
+            Some(expected) if expected != found => {
+                return Err(RecordLayoutError::OffsetMismatch{index: index, field: field, expected: expected, found: found});
+            }, 
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+/// The byte offsets of the UDT's fields, in declaration order, read from its `ITypeInfo`
+fn record_field_offsets(pri: *mut IRecordInfo) -> Result<Vec<usize>, RecordLayoutError> {
+    let mut pti: *mut ITypeInfo = null_mut();
+    let hr = unsafe { (*pri).GetTypeInfo(&mut pti) };
+    if hr != 0 || pti.is_null() {
+        return Err(RecordLayoutError::TypeInfoFailed{hr: hr});
+    }
+    let ret = unsafe { var_offsets(pti) };
+    unsafe { (*pti).Release() };
+    ret
+}
+
+unsafe fn var_offsets(pti: *mut ITypeInfo) -> Result<Vec<usize>, RecordLayoutError> {
+    let mut pta: *mut TYPEATTR = null_mut();
+    let hr = (*pti).GetTypeAttr(&mut pta);
+    if hr != 0 {
+        return Err(RecordLayoutError::TypeInfoFailed{hr: hr});
+    }
+    let count = (*pta).cVars as u32;
+    (*pti).ReleaseTypeAttr(pta);
+
+    let mut offsets = Vec::with_capacity(count as usize);
+    for ix in 0..count {
+        let mut pvd: *mut VARDESC = null_mut();
+        let hr = (*pti).GetVarDesc(ix, &mut pvd);
+        if hr != 0 {
+            return Err(RecordLayoutError::TypeInfoFailed{hr: hr});
+        }
+        offsets.push(*(*pvd).u.oInst() as usize);
+        (*pti).ReleaseVarDesc(pvd);
+    }
+    Ok(offsets)
+}
+
+/// The field names of the UDT `rec_info` describes, in declaration order
+pub fn record_field_names(rec_info: Ptr<IRecordInfo>) -> Result<Vec<String>, RecordLayoutError> {
+    let pri = rec_info.as_ptr();
+    let mut count: ULONG = 0;
+    let hr = unsafe { (*pri).GetFieldNames(&mut count, null_mut()) };
+    if hr != 0 {
+        return Err(RecordLayoutError::GetFieldNamesFailed{hr: hr});
+    }
+    let mut bstrs: Vec<BSTR> = vec![null_mut(); count as usize];
+    let hr = unsafe { (*pri).GetFieldNames(&mut count, bstrs.as_mut_ptr()) };
+    if hr != 0 {
+        return Err(RecordLayoutError::GetFieldNamesFailed{hr: hr});
+    }
+    let names = bstrs.iter().take(count as usize).map(|&bstr| {
+        if bstr.is_null() {
+            return String::new();
+        }
+        let name = unsafe { slice::from_raw_parts(bstr, SysStringLen(bstr) as usize) };
+        String::from_utf16_lossy(name)
+    }).collect();
+    for bstr in bstrs {
+        unsafe { SysFreeString(bstr) };
+    }
+    Ok(names)
+}
+
+/// Looks up the `IRecordInfo` of the UDT `type_guid` declared in the registered 
+/// type library `lib_guid`, version `major.minor`, through `GetRecordInfoFromGuids`.
+pub fn record_info_from_guids(lib_guid: &GUID, major: u32, minor: u32, lcid: u32, type_guid: &GUID) -> Result<OwnedPtr<IRecordInfo, ComDestructor>, ComError> {
+    let mut pri: *mut IRecordInfo = null_mut();
+    let hr = unsafe { GetRecordInfoFromGuids(lib_guid, major, minor, lcid, type_guid, &mut pri) };
+    if hr != 0 {
+        return Err(ComError::RecordInfoFailed{hr: hr});
+    }
+    match Ptr::with_checked(pri) {
+        Some(pri) => Ok(OwnedPtr::new(pri)), 
+        None => Err(ComError::RecordInfoFailed{hr: hr}),
+    }
+}
+
+/// Converts `record` into a `VT_RECORD` VARIANT, with the record buffer allocated by 
+/// `IRecordInfo::RecordCreate`. 
+/// 
+/// The VARIANT holds its own reference to `rec_info`, and `VariantClear` destroys the record.
+pub fn record_into_variant<R: Record>(record: R, rec_info: Ptr<IRecordInfo>) -> Result<Ptr<VARIANT>, IntoVariantError> {
+    let pri = rec_info.as_ptr();
+    let fields = match record.into_fields() {
+        Ok(fields) => fields, 
+        Err(e) => return Err(IntoVariantError::RecordFieldConvFailed(Box::new(ElementError::from(e)))),
+    };
+    let data = unsafe { (*pri).RecordCreate() };
+    if data.is_null() {
+        for (_, pvar) in fields {
+            FreeVariant::destroy(pvar);
+        }
+        return Err(IntoVariantError::RecordCreateFailed);
+    }
+    if let Err((field, hr)) = put_fields(pri, data, fields) {
+        unsafe { (*pri).RecordDestroy(data) };
+        return Err(IntoVariantError::RecordPutFieldFailed{field: field, hr: hr});
+    }
+
+    unsafe { (*pri).AddRef() };
+    let mut n3: VARIANT_n3 = unsafe { mem::zeroed() };
+    unsafe {
+        *n3.n4_mut() = __tagBRECORD { pvRecord: data, pRecInfo: pri };
+    }
+    Ok(new_variant(VT_RECORD, n3))
+}
+
+/// Reads a `VT_RECORD` VARIANT into `R`. The VARIANT is cleared afterwards, whether or 
+/// not it held a record, destroying the record and releasing its `IRecordInfo`.
+pub fn record_from_variant<R: Record>(var: Ptr<VARIANT>) -> Result<R, FromVariantError> {
+    let pvar = var.as_ptr();
+    let n2 = unsafe { (*pvar).n1.n2() };
+    if n2.vt as u32 != VT_RECORD {
+        let found = n2.vt as u32;
+        ClearVariant::destroy(var);
+        return Err(FromVariantError::VarTypeDoesNotMatch{expected: VT_RECORD, found: found});
+    }
+    let rec = unsafe { n2.n3.n4() };
+    let ret = if rec.pvRecord.is_null() || rec.pRecInfo.is_null() {
+        Err(FromVariantError::RecordPtrNull)
+    } else {
+        R::from_fields(&RecordFields { rec_info: rec.pRecInfo, data: rec.pvRecord })
+    };
+    ClearVariant::destroy(var);
+    ret
+}
+
+/// Builds a 1-D `VT_RECORD` SAFEARRAY from `records`, with the record buffers 
+/// allocated by `SafeArrayCreateVectorEx` from `rec_info`. 
+/// 
+/// The array holds its own reference to `rec_info`. The array is sized from `records.len()`, 
+/// and an iterator that yields a different number of records fails with `ElementCountMismatch`.
+pub fn records_into_safearray<I, R>(records: I, rec_info: Ptr<IRecordInfo>) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError>
+where I: ExactSizeIterator<Item=R>, 
+      R: Record
+{
+    let c_elements = records.len() as u32;
+    let psa = unsafe { SafeArrayCreateVectorEx(VT_RECORD as u16, 0, c_elements, rec_info.as_ptr() as *mut c_void) };
+    if psa.is_null() {
+        return Err(IntoSafeArrayError::SafeArrayCreateFailed);
+    }
+    let mut sad = SafeArrayDestructor::new(psa);
+    let elem_size = unsafe { SafeArrayGetElemSize(psa) } as usize;
+    ConversionStats::record_safearray(c_elements as usize * elem_size);
+
+    let mut data: *mut c_void = null_mut();
+    let hr = unsafe { SafeArrayAccessData(psa, &mut data) };
+    if hr != 0 {
+        return Err(IntoSafeArrayError::AccessDataFailed{hr: hr});
+    }
+    let result = put_records(records, rec_info, data as *mut u8, elem_size, c_elements as usize);
+    unsafe { SafeArrayUnaccessData(psa) };
+    result?;
+
+    sad.inner = null_mut();
+    Ok(Ptr::with_checked(psa).unwrap())
+}
+
+/// Reads a 1-D `VT_RECORD` SAFEARRAY into `R`s, through the `IRecordInfo` the array carries. 
+/// The array is released afterwards.
+pub fn records_from_safearray<R: Record>(psa: *mut SAFEARRAY) -> Result<Vec<R>, FromSafeArrayError> {
+    let records = Vec::<RecordElement<R>>::from_safearray(psa)?;
+    Ok(records.into_iter().map(|rec| rec.0).collect())
+}
+
+/// Makes a `Record` a `SafeArrayElement` of `VT_RECORD` arrays. 
+/// 
+/// Elements are read and written in place, through the `IRecordInfo` of the array 
+/// they live in. Creating a record array needs that `IRecordInfo` up front, so build 
+/// new arrays with `records_into_safearray`; this makes the rest of the array API - 
+/// `SafeArrayIter`, `SafeArrayN`, `extend_safearray` and friends - work on them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordElement<R: Record>(pub R);
+
+impl<R: Record> SafeArrayElement for RecordElement<R> {
+    const SFTYPE: u32 = VT_RECORD;
+
+    fn into_safearray(self, psa: *mut SAFEARRAY, ix: i32) -> Result<(), IntoSafeArrElemError> {
+        self.into_safearray_at(psa, &[ix])
+    }
+
+    fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError> {
+        Self::from_safearray_at(psa, &[ix])
+    }
+
+    fn into_safearray_at(self, psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<(), IntoSafeArrElemError> {
+        let slot = RecordSlot::new(psa, ixs).map_err(|hr| IntoSafeArrElemError::PutElementFailed{hr: hr})?;
+        let fields = self.0.into_fields()?;
+        put_fields(slot.rec_info.as_ptr(), slot.data, fields)
+            .map_err(|(field, hr)| IntoSafeArrElemError::RecordPutFieldFailed{field: field, hr: hr})
+    }
+
+    fn from_safearray_at(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<Self, FromSafeArrElemError> {
+        let slot = RecordSlot::new(psa, ixs).map_err(|hr| FromSafeArrElemError::GetElementFailed{hr: hr})?;
+        match R::from_fields(&RecordFields { rec_info: slot.rec_info.as_ptr(), data: slot.data }) {
+            Ok(record) => Ok(RecordElement(record)), 
+            Err(_) => Err(FromSafeArrElemError::FromVariantFailed),
+        }
+    }
+}
+
+/// The record buffer of one element, with the array locked and its `IRecordInfo` held
+struct RecordSlot {
+    psa: *mut SAFEARRAY, 
+    rec_info: OwnedPtr<IRecordInfo, ComDestructor>, 
+    data: *mut c_void,
+}
+
+impl RecordSlot {
+    fn new(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<RecordSlot, HRESULT> {
+        if ixs.len() != unsafe { SafeArrayGetDim(psa) } as usize {
+            return Err(E_INVALIDARG);
+        }
+        let mut pri: *mut IRecordInfo = null_mut();
+        let hr = unsafe { SafeArrayGetRecordInfo(psa, &mut pri) };
+        if hr != 0 {
+            return Err(hr);
+        }
+        let rec_info = match Ptr::with_checked(pri) {
+            Some(pri) => OwnedPtr::new(pri), 
+            None => return Err(E_INVALIDARG),
+        };
+        let hr = unsafe { SafeArrayLock(psa) };
+        if hr != 0 {
+            return Err(hr);
+        }
+        let mut slot = RecordSlot { psa: psa, rec_info: rec_info, data: null_mut() };
+        let hr = unsafe { SafeArrayPtrOfIndex(psa, ixs.as_ptr(), &mut slot.data) };
+        if hr != 0 {
+            return Err(hr);
+        }
+        Ok(slot)
+    }
+}
+
+impl Drop for RecordSlot {
+    fn drop(&mut self) {
+        unsafe { SafeArrayUnlock(self.psa) };
+    }
+}
+
+/// Writes exactly `count` records. `ExactSizeIterator` is a safe trait, so `len()` 
+/// can't be trusted to bound the writes into the buffer.
+fn put_records<I, R>(mut records: I, rec_info: Ptr<IRecordInfo>, data: *mut u8, elem_size: usize, count: usize) -> Result<(), IntoSafeArrayError>
+where I: ExactSizeIterator<Item=R>, 
+      R: Record
+{
+    let pri = rec_info.as_ptr();
+    for ix in 0..count {
+        let record = match records.next() {
+            Some(record) => record, 
+            None => return Err(IntoSafeArrayError::ElementCountMismatch{expected: count, found: ix}),
+        };
+        let fields = match record.into_fields() {
+            Ok(fields) => fields, 
+            Err(e) => return Err(IntoSafeArrayError::from_element_err(e, ix)),
+        };
+        let buf = unsafe { data.offset((ix * elem_size) as isize) } as *mut c_void;
+        if let Err((field, hr)) = put_fields(pri, buf, fields) {
+            return Err(IntoSafeArrayError::RecordPutFieldFailed{index: ix, field: field, hr: hr});
+        }
+    }
+    match records.count() {
+        0 => Ok(()), 
+        extra => Err(IntoSafeArrayError::ElementCountMismatch{expected: count, found: count + extra}),
+    }
+}
+
+/// Puts each field into the record at `buf`, returning the field and HRESULT that failed
+fn put_fields(pri: *mut IRecordInfo, buf: *mut c_void, fields: Vec<(&'static str, Ptr<VARIANT>)>) -> Result<(), (&'static str, HRESULT)> {
+    let mut fields = fields.into_iter();
+    while let Some((name, pvar)) = fields.next() {
+        let wide = to_wide(name);
+        //PutField copies the value, so our VARIANT is freed either way
+        let hr = unsafe { (*pri).PutField(INVOKE_PROPERTYPUT, buf, wide.as_ptr(), pvar.as_ptr()) };
+        FreeVariant::destroy(pvar);
+        if hr != 0 {
+            for (_, pvar) in fields {
+                FreeVariant::destroy(pvar);
+            }
+            return Err((name, hr));
+        }
+    }
+    Ok(())
+}
+
+const E_INVALIDARG: HRESULT = 0x8007_0057u32 as HRESULT;
+
+fn to_wide(name: &str) -> Vec<u16> {
+    let mut wide: Vec<u16> = name.encode_utf16().collect();
+    wide.push(0);
+    wide
+}
+
+extern "system" {
+    fn GetRecordInfoFromGuids(rGuidTypeLib: REFGUID, uVerMajor: ULONG, uVerMinor: ULONG, lcid: LCID, rGuidTypeInfo: REFGUID, ppRecInfo: *mut *mut IRecordInfo) -> HRESULT;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::SafeArrayExt;
+
+    struct Empty;
+    impl Record for Empty {
+        fn into_fields(self) -> Result<Vec<(&'static str, Ptr<VARIANT>)>, IntoSafeArrElemError> {
+            Ok(Vec::new())
+        }
+        fn from_fields(_fields: &RecordFields) -> Result<Empty, FromVariantError> {
+            Ok(Empty)
+        }
+    }
+
+    #[test]
+    fn test_not_a_record() {
+        let pvar = 1i32.into_variant().unwrap();
+        match record_from_variant::<Empty>(pvar) {
+            Err(FromVariantError::VarTypeDoesNotMatch{expected, ..}) => assert_eq!(expected, VT_RECORD), 
+            _ => panic!("expected a vartype mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_not_a_record_array() {
+        let psa = vec![1i32, 2].into_iter().into_safearray().unwrap();
+        match records_from_safearray::<Empty>(psa.as_ptr()) {
+            Err(FromSafeArrayError::VarTypeDoesNotMatch{expected, ..}) => assert_eq!(expected, VT_RECORD), 
+            _ => panic!("expected a vartype mismatch"),
+        }
+    }
+
+    struct Point {
+        x: i32, 
+        label: String,
+    }
+    com_record!(Point { x => "X", label });
+
+    /// Reports one record fewer than it yields
+    struct UnderReported(::std::ops::Range<usize>);
+    impl Iterator for UnderReported {
+        type Item = Empty;
+        fn next(&mut self) -> Option<Empty> {
+            self.0.next().map(|_| Empty)
+        }
+    }
+    impl ExactSizeIterator for UnderReported {
+        fn len(&self) -> usize {
+            self.0.len() - 1
+        }
+    }
+
+    #[test]
+    fn test_put_records_count() {
+        //field-less records never touch the IRecordInfo, only the buffer bounds matter
+        let buf = vec![0u8; 4];
+        let pri = || Ptr::with_checked(buf.as_ptr() as *mut IRecordInfo).unwrap();
+        let records = UnderReported(0..3);
+        let count = records.len();
+        match put_records(records, pri(), buf.as_ptr() as *mut u8, 2, count) {
+            Err(IntoSafeArrayError::ElementCountMismatch{expected, found}) => assert_eq!((expected, found), (2, 3)), 
+            _ => panic!("expected ElementCountMismatch"),
+        }
+        match put_records(vec![Empty].into_iter(), pri(), buf.as_ptr() as *mut u8, 2, 2) {
+            Err(IntoSafeArrayError::ElementCountMismatch{expected, found}) => assert_eq!((expected, found), (2, 1)), 
+            _ => panic!("expected ElementCountMismatch"),
+        }
+    }
+
+    #[test]
+    fn test_com_record() {
+        assert_eq!(Point::FIELDS, &["X", "label"]);
+        let fields = Point { x: 3, label: String::from("p") }.into_fields().unwrap();
+        let names: Vec<&str> = fields.iter().map(|&(name, _)| name).collect();
+        assert_eq!(names, vec!["X", "label"]);
+        for (_, pvar) in fields {
+            FreeVariant::destroy(pvar);
+        }
+    }
+
+    #[cfg(feature = "derive")]
+    #[derive(VariantRecord)]
+    #[repr(C)]
+    struct Derived {
+        #[record(name = "X")]
+        x: i32, 
+        y: f64, 
+        label: String,
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive_record() {
+        assert_eq!(Derived::FIELDS, &["X", "y", "label"]);
+        let offsets = Derived::field_offsets().unwrap();
+        assert_eq!(offsets.len(), 3);
+        assert_eq!(offsets[0], 0);
+        assert_eq!(offsets[1], 8);
+        let fields = Derived { x: 3, y: 1.5, label: String::from("p") }.into_fields().unwrap();
+        let names: Vec<&str> = fields.iter().map(|&(name, _)| name).collect();
+        assert_eq!(names, Derived::FIELDS);
+        for (_, pvar) in fields {
+            FreeVariant::destroy(pvar);
+        }
+        assert!(Point::field_offsets().is_none());
+    }
+
+    #[test]
+    fn test_guid_fields() {
+        let g = Guid::new(1, 2, 3, [4, 5, 6, 7, 8, 9, 10, 11]);
+        let fields = g.into_fields().unwrap();
+        let names: Vec<&str> = fields.iter().map(|&(name, _)| name).collect();
+        assert_eq!(names, Guid::FIELDS);
+        for (_, pvar) in fields {
+            FreeVariant::destroy(pvar);
+        }
+    }
+
+    #[test]
+    fn test_unregistered_typelib() {
+        let nil: GUID = unsafe { mem::zeroed() };
+        assert!(record_info_from_guids(&nil, 1, 0, 0, &nil).is_err());
+    }
+}
+
@@ -0,0 +1,310 @@
+//! # Row
+//! Structs as a `VT_VARIANT` SAFEARRAY with one element per field.
+//!
+//! Scripting clients often pass records as a plain variant array - `Array("Widget", 3, 9.5)` -
+//! rather than as a UDT. [`com_row!`] implements [`SafeArrayRow`] for a struct so it maps to
+//! and from such an array in field declaration order; with the `derive` feature,
+//! `#[derive(SafeArrayRow)]` does the same. A field that fails to convert is reported as
+//! an `ElementConversionFailed` error carrying the field's index and the conversion error.
+//!
+//! [`com_row!`]: macro.com_row.html
+//! [`SafeArrayRow`]: trait.SafeArrayRow.html
+use std::mem;
+use std::ptr::null_mut;
+
+use winapi::ctypes::c_void;
+use winapi::shared::wtypes::VT_VARIANT;
+use winapi::um::oaidl::{SAFEARRAY, VARIANT};
+
+use super::array::{
+    check_elem_size,
+    get_bounds,
+    get_vartype,
+    SafeArrayAccessData,
+    SafeArrayCreateVector,
+    SafeArrayDestructor,
+    SafeArrayGetDim,
+    SafeArrayUnaccessData,
+};
+use super::errors::{FromSafeArrayError, IntoSafeArrayError, IntoSafeArrElemError};
+use super::ptr::Ptr;
+use super::stats::ConversionStats;
+use super::variant::VariantExt;
+
+/// A struct that converts to and from a 1-D `VT_VARIANT` SAFEARRAY, one element per field.
+///
+/// Implement it with [`com_row!`](macro.com_row.html), or `#[derive(SafeArrayRow)]` with the `derive` feature.
+pub trait SafeArrayRow: Sized {
+    /// Field names in row order
+    const FIELDS: &'static [&'static str];
+
+    /// Converts the struct into a `VT_VARIANT` SAFEARRAY with a lower bound of 0
+    fn into_row(self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError>;
+
+    /// Reads the struct out of a 1-D `VT_VARIANT` SAFEARRAY with exactly `FIELDS.len()` elements.
+    /// The array is released afterwards.
+    fn from_row(psa: *mut SAFEARRAY) -> Result<Self, FromSafeArrayError>;
+}
+
+/// Implements `SafeArrayRow` for a struct, one array element per listed field
+///
+/// Every field of the struct is listed, in the order the elements appear in the array,
+/// and each field type implements `VariantExt`.
+///
+/// ## Example
+///
+/// ```
+/// #[macro_use] extern crate oaidl;
+///
+/// use oaidl::SafeArrayRow;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Item {
+///     name: String,
+///     qty: i32,
+///     price: f64,
+/// }
+///
+/// com_row!(Item { name, qty, price });
+///
+/// fn main() {
+///     let item = Item { name: String::from("Widget"), qty: 3, price: 9.5 };
+///     let psa = Item { name: String::from("Widget"), qty: 3, price: 9.5 }.into_row().unwrap();
+///     assert_eq!(Item::from_row(psa.as_ptr()).unwrap(), item);
+/// }
+/// ```
+#[macro_export]
+macro_rules! com_row {
+    ($name:ident { $($field:ident),* $(,)* }) => {
+        impl $crate::SafeArrayRow for $name {
+            const FIELDS: &'static [&'static str] = &[$(stringify!($field)),*];
+
+            fn into_row(self) -> Result<$crate::Ptr<$crate::__SAFEARRAY>, $crate::IntoSafeArrayError> {
+                let mut row = $crate::__RowWriter::new(<$name as $crate::SafeArrayRow>::FIELDS.len())?;
+                $(row.push(self.$field)?;)*
+                Ok(row.finish())
+            }
+
+            fn from_row(psa: *mut $crate::__SAFEARRAY) -> Result<$name, $crate::FromSafeArrayError> {
+                let mut row = $crate::__RowReader::new(psa, <$name as $crate::SafeArrayRow>::FIELDS.len())?;
+                Ok($name {
+                    $($field: row.next()?,)*
+                })
+            }
+        }
+    };
+}
+
+/// Fills a `VT_VARIANT` vector in place for `com_row!`. Dropping it unfinished destroys the array.
+#[doc(hidden)]
+pub struct __RowWriter {
+    psa: *mut SAFEARRAY,
+    data: *mut VARIANT,
+    len: usize,
+    ix: usize,
+}
+
+impl __RowWriter {
+    /// Creates the array and maps its data
+    pub fn new(len: usize) -> Result<__RowWriter, IntoSafeArrayError> {
+        let psa = unsafe { SafeArrayCreateVector(VT_VARIANT as u16, 0, len as u32) };
+        if psa.is_null() {
+            return Err(IntoSafeArrayError::SafeArrayCreateFailed);
+        }
+        let mut sad = SafeArrayDestructor::new(psa);
+        ConversionStats::record_safearray(len * mem::size_of::<VARIANT>());
+
+        let mut data: *mut c_void = null_mut();
+        let hr = unsafe { SafeArrayAccessData(psa, &mut data) };
+        if hr != 0 {
+            return Err(IntoSafeArrayError::AccessDataFailed{hr: hr});
+        }
+        sad.inner = null_mut();
+        Ok(__RowWriter { psa: psa, data: data as *mut VARIANT, len: len, ix: 0 })
+    }
+
+    /// Writes the next field into its slot
+    pub fn push<T: VariantExt>(&mut self, value: T) -> Result<(), IntoSafeArrayError> {
+        assert!(self.ix < self.len, "more fields pushed than the row was created with");
+        let ix = self.ix;
+        match unsafe { value.write_raw(self.data.add(ix)) } {
+            Ok(()) => {
+                self.ix += 1;
+                Ok(())
+            },
+            Err(ive) => Err(IntoSafeArrayError::from_element_err(IntoSafeArrElemError::from(ive), ix)),
+        }
+    }
+
+    /// Unmaps the data and hands over the array
+    pub fn finish(mut self) -> Ptr<SAFEARRAY> {
+        let psa = self.psa;
+        unsafe { SafeArrayUnaccessData(psa) };
+        self.psa = null_mut();
+        Ptr::with_checked(psa).unwrap()
+    }
+}
+
+impl Drop for __RowWriter {
+    fn drop(&mut self) {
+        if self.psa.is_null() {
+            return;
+        }
+        //destroying the array clears the slots already written
+        unsafe { SafeArrayUnaccessData(self.psa) };
+        drop(SafeArrayDestructor::new(self.psa));
+    }
+}
+
+/// Reads a `VT_VARIANT` vector in place for `com_row!`, and destroys it when dropped.
+#[doc(hidden)]
+pub struct __RowReader {
+    psa: *mut SAFEARRAY,
+    data: *const VARIANT,
+    len: usize,
+    ix: usize,
+}
+
+impl __RowReader {
+    /// Takes ownership of `psa` and checks it is a `VT_VARIANT` vector of `len` elements
+    pub fn new(psa: *mut SAFEARRAY, len: usize) -> Result<__RowReader, FromSafeArrayError> {
+        if psa.is_null() {
+            return Err(FromSafeArrayError::SafeArrayPtrNull);
+        }
+        let mut sad = SafeArrayDestructor::new(psa);
+        let sa_dims = unsafe { SafeArrayGetDim(psa) };
+        if sa_dims != 1 {
+            return Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims});
+        }
+        let vt = get_vartype(psa)?;
+        if vt != VT_VARIANT {
+            return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: VT_VARIANT, found: vt});
+        }
+        let (l_bound, r_bound) = get_bounds(psa, 1)?;
+        let found = (r_bound as i64 - l_bound as i64 + 1).max(0) as usize;
+        if found != len {
+            return Err(FromSafeArrayError::RowLengthMismatch{expected: len, found: found});
+        }
+        check_elem_size(psa, mem::size_of::<VARIANT>())?;
+
+        let mut data: *mut c_void = null_mut();
+        let hr = unsafe { SafeArrayAccessData(psa, &mut data) };
+        if hr != 0 {
+            return Err(FromSafeArrayError::AccessDataFailed{hr: hr});
+        }
+        sad.inner = null_mut();
+        Ok(__RowReader { psa: psa, data: data as *const VARIANT, len: len, ix: 0 })
+    }
+
+    /// Reads the next field out of its slot, leaving the slot owned by the array
+    pub fn next<T: VariantExt>(&mut self) -> Result<T, FromSafeArrayError> {
+        let ix = self.ix;
+        if ix >= self.len {
+            return Err(FromSafeArrayError::RowLengthMismatch{expected: ix + 1, found: self.len});
+        }
+        self.ix += 1;
+        unsafe { T::read_raw(self.data.add(ix)) }.map_err(|fve| FromSafeArrayError::from_element_err(fve, ix))
+    }
+}
+
+impl Drop for __RowReader {
+    fn drop(&mut self) {
+        unsafe { SafeArrayUnaccessData(self.psa) };
+        drop(SafeArrayDestructor::new(self.psa));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::SafeArrayExt;
+    use super::super::errors::{ElementError, FromVariantError};
+    use super::super::leak_tracking;
+
+    #[derive(Debug, PartialEq)]
+    struct Item {
+        name: String,
+        qty: i32,
+        price: f64,
+    }
+    com_row!(Item { name, qty, price });
+
+    #[derive(Debug)]
+    struct Flipped {
+        qty: i32,
+        name: String,
+        price: f64,
+    }
+    com_row!(Flipped { qty, name, price, });
+
+    #[test]
+    fn test_roundtrip() {
+        assert_eq!(Item::FIELDS, &["name", "qty", "price"]);
+        leak_tracking::assert_balanced(|| {
+            let psa = Item { name: String::from("Widget"), qty: 3, price: 9.5 }.into_row().unwrap();
+            let item = Item::from_row(psa.as_ptr()).unwrap();
+            assert_eq!(item, Item { name: String::from("Widget"), qty: 3, price: 9.5 });
+        });
+    }
+
+    #[test]
+    fn test_field_error() {
+        leak_tracking::assert_balanced(|| {
+            let psa = Item { name: String::from("Widget"), qty: 3, price: 9.5 }.into_row().unwrap();
+            match Flipped::from_row(psa.as_ptr()) {
+                Err(FromSafeArrayError::ElementConversionFailed{index: 0, element}) => match *element {
+                    ElementError::FromVariant(ref e) => match **e {
+                        FromVariantError::VarTypeDoesNotMatch{..} => {},
+                        ref other => panic!("expected a vartype mismatch, got {:?}", other),
+                    },
+                    _ => panic!("expected the field's FromVariantError"),
+                },
+                other => panic!("expected field 0 to fail, got {:?}", other),
+            }
+        });
+    }
+
+    #[cfg(feature = "derive")]
+    #[derive(Debug, PartialEq, SafeArrayRow)]
+    struct Derived {
+        name: String,
+        qty: i32,
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive_row() {
+        assert_eq!(Derived::FIELDS, &["name", "qty"]);
+        leak_tracking::assert_balanced(|| {
+            let psa = Derived { name: String::from("Widget"), qty: 3 }.into_row().unwrap();
+            assert_eq!(Derived::from_row(psa.as_ptr()).unwrap(), Derived { name: String::from("Widget"), qty: 3 });
+        });
+    }
+
+    #[test]
+    fn test_wrong_length() {
+        let psa = vec![1i32, 2].into_iter().into_safearray().unwrap();
+        match Item::from_row(psa.as_ptr()) {
+            Err(FromSafeArrayError::VarTypeDoesNotMatch{..}) => {},
+            _ => panic!("expected a vartype mismatch"),
+        }
+        let psa = Flipped { qty: 1, name: String::new(), price: 0.0 }.into_row().unwrap();
+        match __RowReader::new(psa.as_ptr(), 2) {
+            Err(FromSafeArrayError::RowLengthMismatch{expected: 2, found: 3}) => {},
+            _ => panic!("expected a length mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_read_past_end() {
+        let psa = Item { name: String::from("Widget"), qty: 3, price: 9.5 }.into_row().unwrap();
+        let mut row = __RowReader::new(psa.as_ptr(), 3).unwrap();
+        row.next::<String>().unwrap();
+        row.next::<i32>().unwrap();
+        row.next::<f64>().unwrap();
+        match row.next::<i32>() {
+            Err(FromSafeArrayError::RowLengthMismatch{expected: 4, found: 3}) => {},
+            other => panic!("expected a length mismatch, got {:?}", other),
+        }
+    }
+}
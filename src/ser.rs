@@ -0,0 +1,360 @@
+//! # Ser
+//! A serde `Serializer` that builds VARIANTs, behind the `serde` feature.
+//!
+//! | Rust | VARIANT |
+//! |------|---------|
+//! | `bool`, integers, floats | the matching scalar vartype, `VT_BOOL`, `VT_I4`, `VT_R8`... |
+//! | `char`, `str`, `String` | `VT_BSTR` |
+//! | byte slices marked with `serde_bytes` | `VT_ARRAY | VT_UI1` |
+//! | `None` | `VT_NULL` |
+//! | `()` and unit structs | `VT_EMPTY` |
+//! | `Some(v)`, newtype structs | `v` itself |
+//! | sequences, tuples, structs | `VT_ARRAY | VT_VARIANT`, 1-D - struct fields by position |
+//! | maps | `VT_ARRAY | VT_VARIANT`, N x 2 - key in the first column, value in the second |
+//! | unit enum variants | `VT_BSTR` holding the variant name |
+//! | other enum variants | a 1 x 2 map from the variant name to its contents |
+//!
+//! Map keys must serialize as strings. 128-bit integers become `VT_DECIMAL`, and fail
+//! to serialize when their magnitude is 2^96 or more.
+//! [`from_variant_de`](fn.from_variant_de.html) reads all of these back.
+use serde::ser::{self, Serialize};
+use widestring::U16String;
+use winapi::shared::wtypes::{VT_UI1, VT_VARIANT};
+use winapi::um::oaidl::VARIANT;
+
+use super::errors::SerdeError;
+use super::ptr::Ptr;
+use super::types::DecWrapper;
+use super::value::{ValueArray, VariantValue};
+use super::variant::decimal_from_parts;
+
+/// Serializes any `Serialize` value into a `Ptr<VARIANT>`
+///
+/// ## Example
+///
+/// ```
+/// extern crate oaidl;
+/// #[macro_use] extern crate serde;
+///
+/// use oaidl::{from_variant_de, to_variant};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Order {
+///     id: u32,
+///     item: String,
+///     quantity: Option<i16>,
+/// }
+///
+/// fn main() {
+///     let order = Order { id: 7, item: String::from("widget"), quantity: None };
+///     let pvar = to_variant(&order).unwrap();
+///     assert_eq!(from_variant_de::<Order>(pvar).unwrap(), order);
+/// }
+/// ```
+pub fn to_variant<T: Serialize + ?Sized>(value: &T) -> Result<Ptr<VARIANT>, SerdeError> {
+    let value = value.serialize(VariantSerializer)?;
+    Ok(value.into_variant()?)
+}
+
+/// The serde `Serializer` behind `to_variant`, producing a `VariantValue`
+#[derive(Clone, Copy, Debug)]
+pub struct VariantSerializer;
+
+impl ser::Serializer for VariantSerializer {
+    type Ok = VariantValue;
+    type Error = SerdeError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = SeqSerializer;
+    type SerializeStructVariant = SeqSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::I8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::I16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::I32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::I64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::U8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::U16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::U32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::U64(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<VariantValue, SerdeError> {
+        let magnitude = if v < 0 {(v as u128).wrapping_neg()} else {v as u128};
+        Ok(VariantValue::Decimal(DecWrapper::from(decimal_from_parts(magnitude, v < 0)?)))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::Decimal(DecWrapper::from(decimal_from_parts(v, false)?)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::F32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<VariantValue, SerdeError> {
+        let mut buf = [0u16; 2];
+        Ok(VariantValue::String(U16String::from_vec(v.encode_utf16(&mut buf).to_vec())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::String(U16String::from_str(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::Array(ValueArray::vector(VT_UI1, v.iter().cloned().map(VariantValue::U8).collect())))
+    }
+
+    fn serialize_none(self) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<VariantValue, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::Empty)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::Empty)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<VariantValue, SerdeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<VariantValue, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<VariantValue, SerdeError> {
+        Ok(tagged(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, SerdeError> {
+        Ok(SeqSerializer::new(len.unwrap_or(0), None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, SerdeError> {
+        Ok(SeqSerializer::new(len, None))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer, SerdeError> {
+        Ok(SeqSerializer::new(len, None))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<SeqSerializer, SerdeError> {
+        Ok(SeqSerializer::new(len, Some(variant)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, SerdeError> {
+        Ok(MapSerializer { entries: Vec::with_capacity(len.unwrap_or(0)), key: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer, SerdeError> {
+        Ok(SeqSerializer::new(len, None))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<SeqSerializer, SerdeError> {
+        Ok(SeqSerializer::new(len, Some(variant)))
+    }
+}
+
+// An enum variant with contents: a one entry map from its name
+fn tagged(variant: &str, value: VariantValue) -> VariantValue {
+    VariantValue::Array(ValueArray::keyed(vec![(U16String::from_str(variant), value)]))
+}
+
+/// Collects sequences, tuples and struct fields into a 1-D `VT_VARIANT` array
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct SeqSerializer {
+    elements: Vec<VariantValue>,
+    variant: Option<&'static str>,
+}
+
+impl SeqSerializer {
+    fn new(len: usize, variant: Option<&'static str>) -> SeqSerializer {
+        SeqSerializer { elements: Vec::with_capacity(len), variant: variant }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.elements.push(value.serialize(VariantSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<VariantValue, SerdeError> {
+        let arr = VariantValue::Array(ValueArray::vector(VT_VARIANT, self.elements));
+        Ok(match self.variant {
+            Some(variant) => tagged(variant, arr),
+            None => arr,
+        })
+    }
+}
+
+macro_rules! seq_serializer_impl {
+    ($($tr:ident, $method:ident $(, $key:ident)*;)*) => {$(
+        impl ser::$tr for SeqSerializer {
+            type Ok = VariantValue;
+            type Error = SerdeError;
+
+            fn $method<T: Serialize + ?Sized>(&mut self, $($key: &'static str,)* value: &T) -> Result<(), SerdeError> {
+                $(let _ = $key;)*
+                self.push(value)
+            }
+
+            fn end(self) -> Result<VariantValue, SerdeError> {
+                self.finish()
+            }
+        }
+    )*};
+}
+
+seq_serializer_impl! {
+    SerializeSeq, serialize_element;
+    SerializeTuple, serialize_element;
+    SerializeTupleStruct, serialize_field;
+    SerializeTupleVariant, serialize_field;
+    SerializeStruct, serialize_field, key;
+    SerializeStructVariant, serialize_field, key;
+}
+
+/// Collects map entries into an N x 2 `VT_VARIANT` array
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct MapSerializer {
+    entries: Vec<(U16String, VariantValue)>,
+    key: Option<U16String>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = VariantValue;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        match key.serialize(VariantSerializer)? {
+            VariantValue::String(key) => {
+                self.key = Some(key);
+                Ok(())
+            },
+            other => Err(SerdeError::KeyNotString{vartype: other.vartype()}),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self.key.take().unwrap_or_default();
+        self.entries.push((key, value.serialize(VariantSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<VariantValue, SerdeError> {
+        Ok(VariantValue::Array(ValueArray::keyed(self.entries)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use super::*;
+    use super::super::de::from_variant_de;
+    use super::super::dtor::{FreeVariant, PtrDestructor};
+    use super::super::leak_tracking;
+    use winapi::shared::wtypes::{VT_ARRAY, VT_BSTR};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Rect { w: i32, h: i32 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Drawing {
+        name: String,
+        shapes: Vec<Shape>,
+        tags: BTreeMap<String, u8>,
+        owner: Option<String>,
+        origin: (i16, i16),
+    }
+
+    fn roundtrip<T: Serialize + ::serde::de::DeserializeOwned>(value: &T) -> T {
+        let pvar = to_variant(value).unwrap();
+        let back = from_variant_de(pvar).unwrap();
+        FreeVariant::destroy(pvar);
+        back
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        leak_tracking::assert_balanced(|| {
+            let mut tags = BTreeMap::new();
+            tags.insert(String::from("layer"), 2);
+            let drawing = Drawing {
+                name: String::from("plan"),
+                shapes: vec![Shape::Point, Shape::Circle(1.5), Shape::Rect { w: 3, h: 4 }],
+                tags: tags,
+                owner: None,
+                origin: (-1, 1),
+            };
+            assert_eq!(roundtrip(&drawing), drawing);
+            assert_eq!(roundtrip(&Some('x')), Some('x'));
+            assert_eq!(roundtrip(&-(1i128 << 90)), -(1i128 << 90));
+            assert_eq!(roundtrip(&((1u128 << 96) - 1)), (1u128 << 96) - 1);
+        });
+    }
+
+    #[test]
+    fn test_serde_shapes() {
+        let pvar = to_variant(&vec![1i32, 2]).unwrap();
+        assert_eq!(VariantValue::from_variant(pvar).unwrap().vartype(), VT_ARRAY | VT_VARIANT);
+        FreeVariant::destroy(pvar);
+        let pvar = to_variant(&Shape::Point).unwrap();
+        assert_eq!(VariantValue::from_variant(pvar).unwrap().vartype(), VT_BSTR);
+        FreeVariant::destroy(pvar);
+
+        let mut bad = BTreeMap::new();
+        bad.insert(1, 2);
+        match to_variant(&bad) {
+            Err(SerdeError::KeyNotString{..}) => {},
+            _ => panic!("Error")
+        }
+        match to_variant(&(1u128 << 96)) {
+            Err(SerdeError::Encode(_)) => {},
+            _ => panic!("Error")
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! # Stats
+//! Per-thread counters for the allocations performed by conversions.
+//!
+//! With collection switched on for a thread, every `BSTR`, `VARIANT` and
+//! `SAFEARRAY` this crate allocates on behalf of a conversion is tallied on it.
+//! The counters can be queried or reset at any time with [`ConversionStats`],
+//! which is useful to find the source of memory growth in long-running
+//! automation services. Collection is off by default, so conversions pay
+//! nothing for it unless [`ConversionStats::set_enabled`] turns it on.
+//!
+//! The same per-thread record holds the allocation balance `leak_tracking`
+//! reports, and the same switch governs it; `leak_tracking::scope` switches
+//! collection on while its closure runs.
+//!
+//! [`ConversionStats::set_enabled`]: struct.ConversionStats.html#method.set_enabled
+//!
+//! [`ConversionStats`]: struct.ConversionStats.html
+use std::cell::Cell;
+use std::mem;
+
+use winapi::um::oaidl::VARIANT;
+
+use super::leak_tracking::LeakReport;
+
+thread_local! {
+    static STATE: Cell<State> = Cell::new(State::default());
+}
+
+// Everything the crate's instrumentation collects on a thread
+#[derive(Clone, Copy, Default)]
+struct State {
+    enabled: bool,
+    stats: ConversionStats,
+    live: LeakReport,
+}
+
+/// Snapshot of the allocation counters for the current thread.
+///
+/// ## Example
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::{ConversionStats, VariantExt};
+///
+/// fn main() {
+///     ConversionStats::set_enabled(true);
+///     ConversionStats::reset();
+///     let _pvar = String::from("counted").into_variant().unwrap();
+///     let stats = ConversionStats::current();
+///     assert_eq!(stats.bstrs_allocated, 1);
+///     assert_eq!(stats.variants_created, 1);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ConversionStats {
+    /// Number of BSTRs allocated via `SysAllocStringLen`
+    pub bstrs_allocated: u64,
+    /// Total bytes of character data requested for BSTRs (length prefix not included)
+    pub bstr_bytes: u64,
+    /// Number of VARIANTs created by `into_variant` calls
+    pub variants_created: u64,
+    /// Total bytes allocated for VARIANT structures
+    pub variant_bytes: u64,
+    /// Number of SAFEARRAYs created by `into_safearray` calls
+    pub safearrays_created: u64,
+    /// Total bytes of element storage requested for SAFEARRAYs
+    pub safearray_bytes: u64,
+}
+
+impl ConversionStats {
+    /// Switches collection on or off for the current thread, returning the previous setting. 
+    /// Off by default.
+    pub fn set_enabled(enabled: bool) -> bool {
+        STATE.with(|s| {
+            let mut state = s.get();
+            let prev = mem::replace(&mut state.enabled, enabled);
+            s.set(state);
+            prev
+        })
+    }
+
+    /// Whether collection is on for the current thread
+    pub fn is_enabled() -> bool {
+        STATE.with(|s| s.get().enabled)
+    }
+
+    /// Returns the counters accumulated on the current thread.
+    pub fn current() -> ConversionStats {
+        STATE.with(|s| s.get().stats)
+    }
+
+    /// Zeroes the counters of the current thread, returning the values they held.
+    pub fn reset() -> ConversionStats {
+        STATE.with(|s| {
+            let mut state = s.get();
+            let prev = mem::replace(&mut state.stats, ConversionStats::default());
+            s.set(state);
+            prev
+        })
+    }
+
+    /// Difference between two snapshots, for measuring a region of code.
+    pub fn since(&self, earlier: &ConversionStats) -> ConversionStats {
+        ConversionStats {
+            bstrs_allocated: self.bstrs_allocated.saturating_sub(earlier.bstrs_allocated),
+            bstr_bytes: self.bstr_bytes.saturating_sub(earlier.bstr_bytes),
+            variants_created: self.variants_created.saturating_sub(earlier.variants_created),
+            variant_bytes: self.variant_bytes.saturating_sub(earlier.variant_bytes),
+            safearrays_created: self.safearrays_created.saturating_sub(earlier.safearrays_created),
+            safearray_bytes: self.safearray_bytes.saturating_sub(earlier.safearray_bytes),
+        }
+    }
+
+    fn update<F: FnOnce(&mut ConversionStats, &mut LeakReport)>(f: F) {
+        STATE.with(|s| {
+            let mut state = s.get();
+            if state.enabled {
+                f(&mut state.stats, &mut state.live);
+                s.set(state);
+            }
+        });
+    }
+
+    /// Applies `f` to the allocation balance of the current thread, if collection is on
+    pub(crate) fn update_live<F: FnOnce(&mut LeakReport)>(f: F) {
+        ConversionStats::update(|_, live| f(live));
+    }
+
+    /// The allocation balance of the current thread
+    pub(crate) fn live() -> LeakReport {
+        STATE.with(|s| s.get().live)
+    }
+
+    pub(crate) fn record_bstr(len: usize) {
+        ConversionStats::update(|s, live| {
+            s.bstrs_allocated += 1;
+            s.bstr_bytes += (len * mem::size_of::<u16>()) as u64;
+            live.bstrs += 1;
+        });
+    }
+
+    pub(crate) fn record_variant() {
+        ConversionStats::update(|s, live| {
+            s.variants_created += 1;
+            s.variant_bytes += mem::size_of::<VARIANT>() as u64;
+            live.variants += 1;
+        });
+    }
+
+    pub(crate) fn record_safearray(bytes: usize) {
+        ConversionStats::update(|s, live| {
+            s.safearrays_created += 1;
+            s.safearray_bytes += bytes as u64;
+            live.safearrays += 1;
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::leak_tracking;
+    #[test]
+    fn test_record() {
+        ConversionStats::set_enabled(true);
+        ConversionStats::reset();
+        let before = ConversionStats::current();
+        ConversionStats::record_bstr(4);
+        ConversionStats::record_safearray(16);
+        let delta = ConversionStats::current().since(&before);
+        assert_eq!(delta.bstrs_allocated, 1);
+        assert_eq!(delta.bstr_bytes, 8);
+        assert_eq!(delta.safearrays_created, 1);
+        assert_eq!(delta.safearray_bytes, 16);
+        assert_eq!(delta.variants_created, 0);
+    }
+
+    #[test]
+    fn test_reset() {
+        ConversionStats::set_enabled(true);
+        ConversionStats::record_variant();
+        let prev = ConversionStats::reset();
+        assert!(prev.variants_created > 0);
+        assert_eq!(ConversionStats::current(), ConversionStats::default());
+    }
+
+    #[test]
+    fn test_leak_scope() {
+        let ((), report) = leak_tracking::scope(|| ConversionStats::record_variant());
+        assert_eq!(report.variants, 1);
+        //the scope switched collection on only while it ran
+        assert!(!ConversionStats::is_enabled());
+        assert_eq!(ConversionStats::current().variants_created, 1);
+    }
+
+    #[test]
+    fn test_disabled() {
+        assert!(!ConversionStats::is_enabled());
+        ConversionStats::record_bstr(4);
+        assert_eq!(ConversionStats::current(), ConversionStats::default());
+        assert!(!ConversionStats::set_enabled(true));
+        ConversionStats::record_bstr(4);
+        assert_eq!(ConversionStats::current().bstrs_allocated, 1);
+    }
+}
@@ -0,0 +1,160 @@
+//! # Stream
+//! Binary payloads as `IStream`-backed variants. 
+//! 
+//! Some automation models (WIA, parts of the Office object models) take and hand out 
+//! image and document data as an `IStream` rather than a byte array. These helpers 
+//! wrap a byte buffer or any `Read` source in a `SHCreateMemStream` stream and put 
+//! it in a `VT_UNKNOWN` or `VT_STREAM` variant, and read such a variant back out.
+use std::io::Read;
+use std::mem;
+use std::ptr;
+
+use winapi::ctypes::c_void;
+use winapi::shared::minwindef::{BYTE, UINT, ULONG};
+use winapi::shared::wtypes::{VT_EMPTY, VT_STREAM, VT_UNKNOWN};
+use winapi::um::objidlbase::{IStream, STREAM_SEEK_SET};
+use winapi::um::oaidl::{VARIANT, VARIANT_n3};
+use winapi::um::unknwnbase::IUnknown;
+
+use super::dtor::{ComDestructor, OwnedPtr};
+use super::errors::{FromVariantError, IntoVariantError};
+use super::ptr::Ptr;
+use super::variant::new_variant;
+
+const READ_CHUNK: usize = 4096;
+
+/// The vartype an `IStream`-backed variant is tagged with
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StreamVarType {
+    /// `VT_UNKNOWN` - what most automation servers expect
+    Unknown, 
+    /// `VT_STREAM`
+    Stream,
+}
+
+impl StreamVarType {
+    /// The `VARTYPE` value for this tag
+    pub fn vartype(self) -> u32 {
+        match self {
+            StreamVarType::Unknown => VT_UNKNOWN, 
+            StreamVarType::Stream => VT_STREAM,
+        }
+    }
+}
+
+/// Copies `bytes` into a new memory stream and wraps it in a VARIANT tagged as `tag`
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{bytes_to_stream_variant, stream_variant_to_bytes, StreamVarType};
+/// 
+/// fn main() {
+///     let pvar = bytes_to_stream_variant(b"GIF89a", StreamVarType::Unknown).unwrap();
+///     assert_eq!(stream_variant_to_bytes(pvar).unwrap(), b"GIF89a".to_vec());
+/// }
+/// ```
+pub fn bytes_to_stream_variant(bytes: &[u8], tag: StreamVarType) -> Result<Ptr<VARIANT>, IntoVariantError> {
+    let pstm = unsafe { SHCreateMemStream(bytes.as_ptr(), bytes.len() as UINT) };
+    if pstm.is_null() {
+        return Err(IntoVariantError::StreamCreateFailed{len: bytes.len()});
+    }
+    let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
+    unsafe {
+        let n_ptr = n3.punkVal_mut();
+        *n_ptr = pstm as *mut IUnknown;
+    }
+    Ok(new_variant(tag.vartype(), n3))
+}
+
+/// Reads `src` to the end and wraps the data in a memory stream VARIANT tagged as `tag`
+pub fn read_to_stream_variant<R: Read>(mut src: R, tag: StreamVarType) -> Result<Ptr<VARIANT>, IntoVariantError> {
+    let mut bytes = Vec::new();
+    src.read_to_end(&mut bytes).map_err(IntoVariantError::SourceReadFailed)?;
+    bytes_to_stream_variant(&bytes, tag)
+}
+
+/// Reads the whole stream held by a `VT_UNKNOWN` or `VT_STREAM` VARIANT. 
+/// 
+/// The stream is rewound first when it supports seeking. The VARIANT's 
+/// reference to the stream is released afterwards.
+pub fn stream_variant_to_bytes(var: Ptr<VARIANT>) -> Result<Vec<u8>, FromVariantError> {
+    let pvar = var.as_ptr();
+    let mut n1 = unsafe {(*pvar).n1};
+    let vt = unsafe {n1.n2()}.vt as u32;
+    if vt != VT_UNKNOWN && vt != VT_STREAM {
+        return Err(FromVariantError::VarTypeDoesNotMatch{expected: VT_UNKNOWN, found: vt});
+    }
+    let punk = unsafe { *n1.n2_mut().n3.punkVal() };
+    if punk.is_null() {
+        return Err(FromVariantError::UnknownPtrNull);
+    }
+    //the VARIANT owned this reference
+    let punk = OwnedPtr::<IUnknown, ComDestructor>::new(Ptr::with_checked(punk).unwrap());
+    unsafe { (*pvar).n1.n2_mut().vt = VT_EMPTY as u16 };
+
+    let stream = match Ptr::with_checked(punk.as_ptr()).unwrap().query::<IStream>() {
+        Ok(stream) => stream, 
+        Err(e) => return Err(FromVariantError::StreamQueryFailed{hr: e.hr()}),
+    };
+    let pstm = stream.as_ptr();
+
+    unsafe {
+        let origin = mem::zeroed();
+        //non-seekable streams are read from where they are
+        let _ = (*pstm).Seek(origin, STREAM_SEEK_SET, ptr::null_mut());
+    }
+    let mut bytes = Vec::new();
+    let mut chunk = [0u8; READ_CHUNK];
+    loop {
+        let mut read: ULONG = 0;
+        let hr = unsafe { (*pstm).Read(chunk.as_mut_ptr() as *mut c_void, READ_CHUNK as ULONG, &mut read) };
+        if hr < 0 {
+            return Err(FromVariantError::StreamReadFailed{hr: hr});
+        }
+        if read == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..read as usize]);
+    }
+    Ok(bytes)
+}
+
+#[link(name="Shlwapi")]
+extern "system" {
+    fn SHCreateMemStream(pInit: *const BYTE, cbInit: UINT) -> *mut IStream;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use super::super::variant::VariantExt;
+    #[test]
+    fn test_roundtrip() {
+        let bytes: Vec<u8> = (0..10000).map(|n| n as u8).collect();
+        for tag in &[StreamVarType::Unknown, StreamVarType::Stream] {
+            let pvar = bytes_to_stream_variant(&bytes, *tag).unwrap();
+            let vt = unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32;
+            assert_eq!(vt, tag.vartype());
+            assert_eq!(stream_variant_to_bytes(pvar).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_read_source() {
+        let pvar = read_to_stream_variant(Cursor::new(vec![7u8; 3]), StreamVarType::Unknown).unwrap();
+        assert_eq!(stream_variant_to_bytes(pvar).unwrap(), vec![7u8; 3]);
+    }
+
+    #[test]
+    fn test_wrong_vartype() {
+        let pvar = 1i32.into_variant().unwrap();
+        match stream_variant_to_bytes(pvar) {
+            Err(FromVariantError::VarTypeDoesNotMatch{found, ..}) => assert_eq!(found, 3),
+            _ => panic!("expected a vartype mismatch"),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! # Strings
+//! `Vec<String>` to and from BSTR SAFEARRAYs in one call. 
+//! 
+//! `strings_to_safearray` builds a `VT_BSTR` array, the form most automation 
+//! servers expect for string lists. `safearray_to_strings` reads either a `VT_BSTR` 
+//! array or a `VT_VARIANT` array of BSTRs (what `SafeArrayExt` produces for `String`), 
+//! and the `Utf16Mode` decides what happens to unpaired surrogates.
+use std::mem;
+use std::ptr;
+
+use widestring::U16String;
+
+use winapi::ctypes::c_void;
+use winapi::shared::wtypes::{BSTR, VT_BSTR, VT_VARIANT};
+use winapi::um::oaidl::{SAFEARRAY, VARIANT};
+
+use super::array::{
+    check_elem_size, 
+    get_bounds, 
+    get_vartype, 
+    SafeArrayAccessData, 
+    SafeArrayCreateVector, 
+    SafeArrayDestructor, 
+    SafeArrayGetDim, 
+    SafeArrayPutElement, 
+    SafeArrayUnaccessData,
+};
+use super::bstr::BStringExt;
+use super::errors::{FromSafeArrElemError, FromSafeArrayError, IntoSafeArrElemError, IntoSafeArrayError};
+use super::limits::DecodeLimits;
+use super::ptr::Ptr;
+
+/// How `safearray_to_strings` treats BSTRs that are not valid UTF-16
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Utf16Mode {
+    /// Fail with `FromSafeArrElemError::InvalidUtf16` at the offending index
+    Strict, 
+    /// Replace unpaired surrogates with U+FFFD, as `String` variants do
+    Lossy,
+}
+
+/// Creates a 1-D `VT_BSTR` SAFEARRAY holding a copy of `items`
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{safearray_to_strings, strings_to_safearray, Utf16Mode};
+/// 
+/// fn main() {
+///     let psa = strings_to_safearray(&["Sheet1", "Sheet2"]).unwrap();
+///     let names = safearray_to_strings(psa.as_ptr(), Utf16Mode::Strict).unwrap();
+///     assert_eq!(names, vec!["Sheet1", "Sheet2"]);
+/// }
+/// ```
+pub fn strings_to_safearray<S: AsRef<str>>(items: &[S]) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+    let psa = unsafe { SafeArrayCreateVector(VT_BSTR as u16, 0, items.len() as u32) };
+    if psa.is_null() {
+        return Err(IntoSafeArrayError::SafeArrayCreateFailed);
+    }
+    let mut sad = SafeArrayDestructor::new(psa);
+    for (ix, item) in items.iter().enumerate() {
+        let bstr = match U16String::from_str(item.as_ref()).allocate_bstr() {
+            Ok(bstr) => bstr, 
+            Err(bse) => return Err(IntoSafeArrayError::from_element_err(IntoSafeArrElemError::from(bse), ix)),
+        };
+        //SafeArrayPutElement copies the BSTR
+        let ix = ix as i32;
+        let hr = unsafe { SafeArrayPutElement(psa, &ix, bstr.as_ptr() as *mut c_void) };
+        U16String::deallocate_bstr(bstr);
+        if hr < 0 {
+            return Err(IntoSafeArrayError::from_element_err(IntoSafeArrElemError::PutElementFailed{hr: hr}, ix as usize));
+        }
+    }
+    sad.inner = ptr::null_mut();
+    Ok(Ptr::with_checked(psa).unwrap())
+}
+
+/// Reads a 1-D `VT_BSTR` or `VT_VARIANT` SAFEARRAY of strings. The array is released afterwards.
+pub fn safearray_to_strings(psa: *mut SAFEARRAY, mode: Utf16Mode) -> Result<Vec<String>, FromSafeArrayError> {
+    if psa.is_null() {
+        return Err(FromSafeArrayError::SafeArrayPtrNull);
+    }
+    let _sad = SafeArrayDestructor::new(psa);
+    let sa_dims = unsafe { SafeArrayGetDim(psa) };
+    if sa_dims != 1 {
+        return Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims});
+    }
+    let vt = get_vartype(psa)?;
+    if vt != VT_BSTR && vt != VT_VARIANT {
+        return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: VT_BSTR, found: vt});
+    }
+    check_elem_size(psa, if vt == VT_BSTR { mem::size_of::<*mut u16>() } else { mem::size_of::<VARIANT>() })?;
+    let (l_bound, r_bound) = get_bounds(psa, 1)?;
+    let len = (r_bound - l_bound + 1).max(0) as usize;
+    DecodeLimits::check_elements(len)?;
+
+    let mut data: *mut c_void = ptr::null_mut();
+    let hr = unsafe { SafeArrayAccessData(psa, &mut data) };
+    if hr != 0 {
+        return Err(FromSafeArrayError::AccessDataFailed{hr: hr});
+    }
+    let res = (0..len).map(|ix| {
+        let bstr = if vt == VT_BSTR {
+            unsafe { *(data as *const BSTR).add(ix) }
+        } else {
+            bstr_in_variant(unsafe { &*(data as *const VARIANT).add(ix) })
+                .map_err(|e| FromSafeArrayError::from_element_err(e, ix))?
+        };
+        decode_bstr(bstr, mode).map_err(|e| FromSafeArrayError::from_element_err(e, ix))
+    }).collect();
+    unsafe { SafeArrayUnaccessData(psa) };
+    res
+}
+
+fn bstr_in_variant(var: &VARIANT) -> Result<BSTR, FromSafeArrElemError> {
+    let mut n1 = var.n1;
+    let n2 = unsafe { n1.n2_mut() };
+    if n2.vt as u32 != VT_BSTR {
+        return Err(FromSafeArrElemError::FromVariantFailed);
+    }
+    Ok(unsafe { *n2.n3.bstrVal() })
+}
+
+fn decode_bstr(bstr: BSTR, mode: Utf16Mode) -> Result<String, FromSafeArrElemError> {
+    //a null BSTR is the empty string
+    if bstr.is_null() {
+        return Ok(String::new());
+    }
+    DecodeLimits::check_bstr(bstr)?;
+    let wide = U16String::from_bstr(bstr);
+    match mode {
+        Utf16Mode::Strict => wide.to_string().map_err(|_| FromSafeArrElemError::InvalidUtf16), 
+        Utf16Mode::Lossy => Ok(wide.to_string_lossy()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::SafeArrayExt;
+    use super::super::errors::ElementError;
+    #[test]
+    fn test_roundtrip() {
+        let psa = strings_to_safearray(&vec![String::from("a"), String::new(), String::from("ünï")]).unwrap();
+        assert_eq!(get_vartype(psa.as_ptr()).unwrap(), VT_BSTR);
+        let out = safearray_to_strings(psa.as_ptr(), Utf16Mode::Strict).unwrap();
+        assert_eq!(out, vec!["a", "", "ünï"]);
+    }
+
+    #[test]
+    fn test_variant_array() {
+        let psa = vec![String::from("x"), String::from("y")].into_iter().into_safearray().unwrap();
+        let out = safearray_to_strings(psa.as_ptr(), Utf16Mode::Lossy).unwrap();
+        assert_eq!(out, vec!["x", "y"]);
+    }
+
+    #[test]
+    fn test_strict() {
+        let mut bad = U16String::from_vec(vec![0x61u16, 0xD800]);
+        let bstr = bad.allocate_bstr().unwrap();
+        let psa = unsafe { SafeArrayCreateVector(VT_BSTR as u16, 0, 1) };
+        let ix = 0i32;
+        assert_eq!(unsafe { SafeArrayPutElement(psa, &ix, bstr.as_ptr() as *mut c_void) }, 0);
+        let psa2 = unsafe { SafeArrayCreateVector(VT_BSTR as u16, 0, 1) };
+        assert_eq!(unsafe { SafeArrayPutElement(psa2, &ix, bstr.as_ptr() as *mut c_void) }, 0);
+        U16String::deallocate_bstr(bstr);
+        match safearray_to_strings(psa, Utf16Mode::Strict) {
+            Err(FromSafeArrayError::ElementConversionFailed{index, element}) => {
+                assert_eq!(index, 0);
+                match *element {
+                    ElementError::From(ref e) => match **e {
+                        FromSafeArrElemError::InvalidUtf16 => {}, 
+                        _ => panic!("expected InvalidUtf16"),
+                    }, 
+                    _ => panic!("expected a from error"),
+                }
+            }, 
+            _ => panic!("expected strict decode to fail"),
+        }
+        assert_eq!(safearray_to_strings(psa2, Utf16Mode::Lossy).unwrap(), vec!["a\u{FFFD}"]);
+    }
+}
@@ -0,0 +1,371 @@
+//! # Table
+//! Two dimensional tables of [`Variants`], with conversions to and from 2-D 
+//! `VT_VARIANT` SAFEARRAYs. 
+//! 
+//! The same structure serves Excel ranges (`Range.Value`), ADO `Recordset.GetRows` 
+//! and clipboard table formats - they only differ in the [`TableLayout`] of the array. 
+//! 
+//! [`Variants`]: enum.Variants.html
+//! [`TableLayout`]: enum.TableLayout.html
+use std::mem;
+use std::ptr::null_mut;
+
+use winapi::ctypes::c_void;
+use winapi::shared::wtypes::{VT_ARRAY, VT_EMPTY, VT_VARIANT};
+use winapi::um::oaidl::{SAFEARRAY, SAFEARRAYBOUND, VARIANT, VARIANT_n3};
+use winapi::um::oleauto::VariantClear;
+
+use super::array::{
+    get_bounds, 
+    get_vartype, 
+    safearray_len, 
+    SafeArrayCreate, 
+    SafeArrayDestructor, 
+    SafeArrayGetDim, 
+    SafeArrayGetElement, 
+    SafeArrayGetElemSize, 
+    SafeArrayPutElement,
+};
+use super::dtor::{ClearVariant, PtrDestructor};
+use super::errors::{
+    FromSafeArrayError, 
+    FromSafeArrElemError, 
+    IntoSafeArrayError, 
+    IntoSafeArrElemError, 
+    TableError,
+};
+use super::leak_tracking;
+use super::limits::DecodeLimits;
+use super::ptr::Ptr;
+use super::stats::ConversionStats;
+use super::variant::{new_variant, Variants};
+
+const VT_ARRAY_VARIANT: u32 = VT_ARRAY | VT_VARIANT;
+
+/// How the rows and columns of a [`VariantTable`] are laid out in a 2-D SAFEARRAY
+/// 
+/// [`VariantTable`]: struct.VariantTable.html
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TableLayout {
+    /// First dimension is the row, second the column, and the first row holds the column names. 
+    /// This is what Excel ranges with a header line look like.
+    HeaderRow, 
+    /// First dimension is the row, second the column, no header. 
+    /// Columns are named `Column1`, `Column2`, ... when reading.
+    RowsOnly, 
+    /// First dimension is the field, second the record, no header. 
+    /// This is the shape returned by ADO's `Recordset.GetRows`.
+    FieldsByRecords,
+}
+
+/// Rows of [`Variants`] under named columns
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{TableLayout, Variants, VariantTable};
+/// 
+/// fn main() {
+///     let mut table = VariantTable::new(vec![String::from("Name"), String::from("Qty")]);
+///     table.push_row(vec![Variants::from("Widget"), Variants::from(12i32)]).unwrap();
+///     
+///     let psa = table.clone().into_safearray(TableLayout::HeaderRow).unwrap();
+///     let back = VariantTable::from_safearray(psa.as_ptr(), TableLayout::HeaderRow).unwrap();
+///     assert_eq!(table, back);
+/// }
+/// ```
+/// 
+/// [`Variants`]: enum.Variants.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariantTable {
+    columns: Vec<String>, 
+    rows: Vec<Vec<Variants>>,
+}
+
+impl VariantTable {
+    /// Creates an empty table with the given column names
+    pub fn new(columns: Vec<String>) -> VariantTable {
+        VariantTable {
+            columns: columns, 
+            rows: Vec::new(),
+        }
+    }
+
+    /// Column names, in order
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// All rows of the table
+    pub fn rows(&self) -> &[Vec<Variants>] {
+        &self.rows
+    }
+
+    pub(crate) fn into_rows(self) -> Vec<Vec<Variants>> {
+        self.rows
+    }
+
+    /// Number of rows (not counting a header)
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Whether the table has no rows
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Appends a row. The row must have exactly one cell per column.
+    pub fn push_row(&mut self, row: Vec<Variants>) -> Result<(), TableError> {
+        if row.len() != self.columns.len() {
+            return Err(TableError::RowWidthMismatch{expected: self.columns.len(), found: row.len()});
+        }
+        self.rows.push(row);
+        Ok(())
+    }
+
+    /// Position of the column with the given name
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c == name)
+    }
+
+    /// Cell at `row` in the column named `column`
+    pub fn get(&self, row: usize, column: &str) -> Option<&Variants> {
+        let col = self.column_index(column)?;
+        self.rows.get(row).and_then(|r| r.get(col))
+    }
+
+    /// Converts the table into a 2-D `VT_VARIANT` SAFEARRAY laid out as `layout`
+    pub fn into_safearray(self, layout: TableLayout) -> Result<Ptr<SAFEARRAY>, TableError> {
+        let n_cols = self.columns.len();
+        let header = match layout {
+            TableLayout::HeaderRow => Some(self.columns.into_iter().map(Variants::String).collect::<Vec<_>>()), 
+            _ => None,
+        };
+        let n_rows = self.rows.len() + if header.is_some() {1} else {0};
+        let (d1, d2) = match layout {
+            TableLayout::FieldsByRecords => (n_cols, n_rows), 
+            _ => (n_rows, n_cols),
+        };
+
+        let mut sab = [
+            SAFEARRAYBOUND { cElements: d1 as u32, lLbound: 0i32}, 
+            SAFEARRAYBOUND { cElements: d2 as u32, lLbound: 0i32},
+        ];
+        let psa = unsafe { SafeArrayCreate(VT_VARIANT as u16, 2, sab.as_mut_ptr()) };
+        if psa.is_null() {
+            return Err(TableError::from(IntoSafeArrayError::SafeArrayCreateFailed));
+        }
+        let mut sad = SafeArrayDestructor::new(psa);
+        ConversionStats::record_safearray(d1 * d2 * unsafe { SafeArrayGetElemSize(psa) } as usize);
+
+        for (r, row) in header.into_iter().chain(self.rows.into_iter()).enumerate() {
+            for (c, cell) in row.into_iter().enumerate() {
+                let ixs = match layout {
+                    TableLayout::FieldsByRecords => [c as i32, r as i32], 
+                    _ => [r as i32, c as i32],
+                };
+                if let Err(e) = put_cell(psa, &ixs, cell) {
+                    return Err(TableError::from(IntoSafeArrayError::from_element_err(e, r * n_cols + c)));
+                }
+            }
+        }
+        sad.inner = null_mut();
+
+        Ok(Ptr::with_checked(psa).unwrap())
+    }
+
+    /// Reads a 2-D `VT_VARIANT` SAFEARRAY laid out as `layout`. The array is released afterwards.
+    pub fn from_safearray(psa: *mut SAFEARRAY, layout: TableLayout) -> Result<VariantTable, TableError> {
+        if psa.is_null() {
+            return Err(TableError::ArrayPtrNull);
+        }
+        let _sad = SafeArrayDestructor::new(psa);
+        let sa_dims = unsafe { SafeArrayGetDim(psa) };
+        if sa_dims != 2 {
+            return Err(TableError::from(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims}));
+        }
+        let vt = get_vartype(psa)?;
+        if vt != VT_VARIANT {
+            return Err(TableError::from(FromSafeArrayError::VarTypeDoesNotMatch{expected: VT_VARIANT, found: vt}));
+        }
+
+        let (row_bounds, col_bounds) = match layout {
+            TableLayout::FieldsByRecords => (get_bounds(psa, 2)?, get_bounds(psa, 1)?), 
+            _ => (get_bounds(psa, 1)?, get_bounds(psa, 2)?),
+        };
+        let n_cols = (col_bounds.1 - col_bounds.0 + 1).max(0) as usize;
+        DecodeLimits::check_elements(safearray_len(psa)?).map_err(FromSafeArrayError::from)?;
+
+        let n_rows = (row_bounds.1 as i64 - row_bounds.0 as i64 + 1).max(0) as usize;
+        let mut rows: Vec<Vec<Variants>> = Vec::with_capacity(n_rows);
+        for r in row_bounds.0..=row_bounds.1 {
+            let mut row = Vec::with_capacity(n_cols);
+            for c in col_bounds.0..=col_bounds.1 {
+                let ixs = match layout {
+                    TableLayout::FieldsByRecords => [c, r], 
+                    _ => [r, c],
+                };
+                match get_cell(psa, &ixs) {
+                    Ok(cell) => row.push(cell), 
+                    Err(e) => {
+                        let index = (r - row_bounds.0) as usize * n_cols + (c - col_bounds.0) as usize;
+                        return Err(TableError::from(FromSafeArrayError::from_element_err(e, index)));
+                    }
+                }
+            }
+            rows.push(row);
+        }
+
+        let columns = match layout {
+            TableLayout::HeaderRow => {
+                let header = if rows.is_empty() { Vec::new() } else { rows.remove(0) };
+                let mut columns = Vec::with_capacity(header.len());
+                for (ix, cell) in header.into_iter().enumerate() {
+                    match cell {
+                        Variants::String(name) => columns.push(name), 
+                        _ => return Err(TableError::HeaderNotString{column: ix}),
+                    }
+                }
+                columns
+            }, 
+            _ => (1..=n_cols).map(|ix| format!("Column{}", ix)).collect(),
+        };
+
+        Ok(VariantTable {
+            columns: columns, 
+            rows: rows,
+        })
+    }
+
+    /// Converts the table into a `VT_ARRAY | VT_VARIANT` VARIANT, which is what `Range.Value` expects
+    pub fn into_variant(self, layout: TableLayout) -> Result<Ptr<VARIANT>, TableError> {
+        let psa = self.into_safearray(layout)?;
+        let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
+        unsafe {
+            let n_ptr = n3.parray_mut();
+            *n_ptr = psa.as_ptr();
+        }
+        Ok(new_variant(VT_ARRAY_VARIANT, n3))
+    }
+
+    /// Reads a `VT_ARRAY | VT_VARIANT` VARIANT holding a 2-D array. 
+    /// 
+    /// The VARIANT's contents are released afterwards, and it is left `VT_EMPTY`.
+    pub fn from_variant(var: Ptr<VARIANT>, layout: TableLayout) -> Result<VariantTable, TableError> {
+        let pvar = var.as_ptr();
+        let mut n1 = unsafe {(*pvar).n1};
+        let vt = unsafe {n1.n2()}.vt as u32;
+        if vt != VT_ARRAY_VARIANT {
+            ClearVariant::destroy(var);
+            return Err(TableError::VarTypeDoesNotMatch{expected: VT_ARRAY_VARIANT, found: vt});
+        }
+        let psa = unsafe { *n1.n2_mut().n3.parray() };
+        //from_safearray destroys the array either way, so the VARIANT must not keep pointing at it
+        unsafe { (*pvar).n1.n2_mut().vt = VT_EMPTY as u16 };
+        VariantTable::from_safearray(psa, layout)
+    }
+}
+
+fn put_cell(psa: *mut SAFEARRAY, ixs: &[i32], cell: Variants) -> Result<(), IntoSafeArrElemError> {
+    let pvar = cell.into_variant()?;
+    let hr = unsafe { SafeArrayPutElement(psa, ixs.as_ptr(), pvar.as_ptr() as *mut c_void) };
+    //SafeArrayPutElement copies the VARIANT, so the one we allocated is released here
+    leak_tracking::variant_freed(pvar.as_ptr());
+    unsafe {
+        VariantClear(pvar.as_ptr());
+        drop(Box::from_raw(pvar.as_ptr()));
+    }
+    match hr {
+        0 => Ok(()), 
+        _ => Err(IntoSafeArrElemError::PutElementFailed{hr: hr})
+    }
+}
+
+fn get_cell(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<Variants, FromSafeArrElemError> {
+    let mut var: VARIANT = unsafe {mem::zeroed()};
+    let hr = unsafe { SafeArrayGetElement(psa, ixs.as_ptr(), &mut var as *mut _ as *mut c_void) };
+    if hr != 0 {
+        return Err(FromSafeArrElemError::GetElementFailed{hr: hr});
+    }
+    match Variants::from_variant(Ptr::with_checked(&mut var as *mut VARIANT).unwrap()) {
+        Ok(cell) => Ok(cell), 
+        Err(_) => Err(FromSafeArrElemError::FromVariantFailed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::dtor::FreeVariant;
+    use super::super::variant::VariantExt;
+    fn sample() -> VariantTable {
+        let mut table = VariantTable::new(vec![String::from("Name"), String::from("Qty"), String::from("Price")]);
+        table.push_row(vec![Variants::from("Widget"), Variants::from(12i32), Variants::from(1.5f64)]).unwrap();
+        table.push_row(vec![Variants::from("Gadget"), Variants::from(3i32), Variants::from(20.25f64)]).unwrap();
+        table
+    }
+
+    #[test]
+    fn test_push_row_width() {
+        let mut table = sample();
+        match table.push_row(vec![Variants::from(1i32)]) {
+            Err(TableError::RowWidthMismatch{expected, found}) => {
+                assert_eq!(expected, 3);
+                assert_eq!(found, 1);
+            }, 
+            _ => panic!("Error")
+        }
+        assert_eq!(table.get(1, "Qty"), Some(&Variants::from(3i32)));
+    }
+
+    #[test]
+    fn test_header_row() {
+        let table = sample();
+        let psa = table.clone().into_safearray(TableLayout::HeaderRow).unwrap();
+        let back = VariantTable::from_safearray(psa.as_ptr(), TableLayout::HeaderRow).unwrap();
+        assert_eq!(table, back);
+    }
+
+    #[test]
+    fn test_fields_by_records() {
+        let table = sample();
+        let psa = table.clone().into_safearray(TableLayout::FieldsByRecords).unwrap();
+        let back = VariantTable::from_safearray(psa.as_ptr(), TableLayout::FieldsByRecords).unwrap();
+        assert_eq!(table.rows(), back.rows());
+        assert_eq!(back.columns()[2], "Column3");
+    }
+
+    #[test]
+    fn test_dimensions() {
+        let table = sample();
+        let psa = table.clone().into_safearray(TableLayout::RowsOnly).unwrap();
+        assert_eq!(get_bounds(psa.as_ptr(), 1).unwrap(), (0, 1));
+        assert_eq!(get_bounds(psa.as_ptr(), 2).unwrap(), (0, 2));
+        // the cells of a row are a column apart, two VARIANTs
+        let cells = unsafe { (*psa.as_ptr()).pvData as *const VARIANT };
+        assert_eq!(unsafe { *(*cells.add(2)).n1.n2().n3.lVal() }, 12);
+        let back = VariantTable::from_safearray(psa.as_ptr(), TableLayout::RowsOnly).unwrap();
+        assert_eq!(table.rows(), back.rows());
+    }
+
+    #[test]
+    fn test_variant() {
+        let table = sample();
+        let pvar = table.clone().into_variant(TableLayout::RowsOnly).unwrap();
+        let back = VariantTable::from_variant(pvar, TableLayout::RowsOnly).unwrap();
+        assert_eq!(table.rows(), back.rows());
+        assert_eq!(unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32, VT_EMPTY);
+        FreeVariant::destroy(pvar);
+
+        let pvar = 5i32.into_variant().unwrap();
+        match VariantTable::from_variant(pvar, TableLayout::RowsOnly) {
+            Err(TableError::VarTypeDoesNotMatch{..}) => {},
+            _ => panic!("expected a vartype mismatch"),
+        }
+        assert_eq!(unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32, VT_EMPTY);
+        FreeVariant::destroy(pvar);
+    }
+}
@@ -4,18 +4,37 @@
 //!   * CY
 //!   * DATE
 //!   * DECIMAL
+//!   * CLIPDATA
+//!   * FILETIME
+//!   * GUID
 //! 
 use std::fmt;
+use std::io;
+use std::mem;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[cfg(feature = "impl_tryfrom")]
 use std::convert::{TryFrom};
 
 #[cfg(feature = "impl_tryfrom")]
 use std::num::{TryFromIntError};
 
+#[cfg(feature = "chrono")]
+use chrono::{Datelike, DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
+
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
+
 use rust_decimal::Decimal;
 
+use winapi::shared::guiddef::GUID;
+use winapi::shared::minwindef::FILETIME;
 use winapi::shared::wtypes::{CY, DECIMAL, DECIMAL_NEG, VARIANT_BOOL, VARIANT_TRUE};
+use winapi::um::minwinbase::SYSTEMTIME;
+use winapi::um::oleauto::{SystemTimeToVariantTime, VariantTimeToSystemTime};
+
+use super::codes;
+use super::errors::{CurrencyError, ValueError};
 
 /// Pseudo-`From` trait because of orphan rules
 trait Conversion<T> {
@@ -190,6 +209,148 @@ impl AsRef<i64> for Currency {
 wrapper_conv_impl!(i64, Currency);
 conversions_impl!(Currency, CY);
 
+pub(crate) const CY_SCALE: i64 = 10_000;
+
+impl Currency {
+    /// Converts a `Decimal` exactly. Trailing zeros past the fourth decimal place are fine; 
+    /// any other digit there fails with `CurrencyError::TooPrecise`.
+    pub fn from_decimal(dec: Decimal) -> Result<Currency, CurrencyError> {
+        let raw = DECIMAL::from(DecWrapper::from(dec));
+        let mut magnitude = (u128::from(raw.Hi32) << 64) | u128::from(raw.Lo64);
+        let mut scale = u32::from(raw.scale);
+        while scale > 4 {
+            if magnitude % 10 != 0 {
+                return Err(CurrencyError::TooPrecise{value: dec});
+            }
+            magnitude /= 10;
+            scale -= 1;
+        }
+        //below 2^96 * 10^4, so no overflow
+        let scaled = (magnitude * 10u128.pow(4 - scale)) as i128;
+        let val = if raw.sign == DECIMAL_NEG { -scaled } else { scaled };
+        if val < i128::from(i64::min_value()) || val > i128::from(i64::max_value()) {
+            return Err(CurrencyError::Overflow{value: dec});
+        }
+        Ok(Currency(val as i64))
+    }
+
+    /// This amount as a `Decimal`, always with four decimal places
+    pub fn to_decimal(self) -> Decimal {
+        //built from the magnitude, which unlike `-self.0` exists for i64::MIN too
+        let raw = DECIMAL {
+            wReserved: 0, 
+            scale: 4, 
+            sign: if self.0 < 0 {DECIMAL_NEG} else {0}, 
+            Hi32: 0, 
+            Lo64: self.0.unsigned_abs(),
+        };
+        Decimal::from(DecWrapper::from(raw))
+    }
+
+    /// Converts a float, rounding to four decimal places
+    pub fn from_f64(val: f64) -> Result<Currency, ValueError> {
+        let scaled = (val * CY_SCALE as f64).round();
+        if !scaled.is_finite() || scaled < i64::min_value() as f64 || scaled >= i64::max_value() as f64 {
+            return Err(ValueError::CurrencyOutOfRange{value: val});
+        }
+        Ok(Currency(scaled as i64))
+    }
+
+    /// Multiplies by an integer, like `VarCyMulI4`. The product is exact, so no rounding happens.
+    pub fn mul_i32(self, rhs: i32) -> Result<Currency, ValueError> {
+        self.0.checked_mul(rhs as i64).map(Currency).ok_or(ValueError::CurrencyOverflow)
+    }
+
+    /// Computes `self * num / den`, rounding half to even at four decimal places 
+    /// the way `VarCyMulI4` followed by a division in VB does. 
+    pub fn mul_ratio(self, num: i64, den: i64) -> Result<Currency, ValueError> {
+        if den == 0 {
+            return Err(ValueError::ZeroDenominator);
+        }
+        let q = div_round_even(self.0 as i128 * num as i128, den as i128);
+        to_cy(q)
+    }
+
+    /// `percent` percent of this amount, e.g. 7.5% of 19.99 is `19.99.percent_of(7.5)` 
+    /// giving 1.4992 (1.499250 rounded half to even).
+    pub fn percent_of(self, percent: Currency) -> Result<Currency, ValueError> {
+        let q = div_round_even(self.0 as i128 * percent.0 as i128, 100 * CY_SCALE as i128);
+        to_cy(q)
+    }
+
+    /// Rounds to `decimals` decimal places with banker's rounding, like `VarCyRound`. 
+    /// `decimals` of 4 or more leaves the value unchanged.
+    pub fn round(self, decimals: u32) -> Result<Currency, ValueError> {
+        if decimals >= 4 {
+            return Ok(self);
+        }
+        let step = 10i128.pow(4 - decimals);
+        to_cy(div_round_even(self.0 as i128, step) * step)
+    }
+}
+
+impl TryFrom<Decimal> for Currency {
+    type Error = CurrencyError;
+    fn try_from(dec: Decimal) -> Result<Currency, CurrencyError> {
+        Currency::from_decimal(dec)
+    }
+}
+
+impl From<Currency> for Decimal {
+    fn from(cy: Currency) -> Decimal {
+        cy.to_decimal()
+    }
+}
+
+/// Integer division rounding half to even
+fn div_round_even(n: i128, d: i128) -> i128 {
+    let (n, d) = if d < 0 { (-n, -d) } else { (n, d) };
+    let q = n.div_euclid(d);
+    let r = n.rem_euclid(d);
+    let twice = 2 * r;
+    if twice > d || (twice == d && q % 2 != 0) { q + 1 } else { q }
+}
+
+fn to_cy(q: i128) -> Result<Currency, ValueError> {
+    if q < i64::min_value() as i128 || q > i64::max_value() as i128 {
+        Err(ValueError::CurrencyOverflow)
+    } else {
+        Ok(Currency(q as i64))
+    }
+}
+
+/// Parses a decimal string such as `"-12.34"` with at most four fractional digits
+impl FromStr for Currency {
+    type Err = ValueError;
+    fn from_str(s: &str) -> Result<Currency, ValueError> {
+        let err = || ValueError::CurrencyParse{input: String::from(s)};
+        let t = s.trim();
+        //at most one sign
+        let (neg, t) = match t.chars().next() {
+            Some('-') => (true, &t[1..]), 
+            Some('+') => (false, &t[1..]), 
+            _ => (false, t),
+        };
+        let (whole, frac) = match t.find('.') {
+            Some(ix) => (&t[..ix], &t[ix + 1..]), 
+            None => (t, ""),
+        };
+        if (whole.is_empty() && frac.is_empty()) || frac.len() > 4 
+            || !whole.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(err());
+        }
+        let whole: i128 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| err())? };
+        let mut frac_val: i128 = if frac.is_empty() { 0 } else { frac.parse().map_err(|_| err())? };
+        for _ in frac.len()..4 {
+            frac_val *= 10;
+        }
+        //the magnitude is built wider than i64, so i64::MIN's round-trips
+        let val = whole.checked_mul(i128::from(CY_SCALE)).and_then(|w| w.checked_add(frac_val)).ok_or_else(err)?;
+        let val = i64::try_from(if neg { -val } else { val }).map_err(|_| err())?;
+        Ok(Currency(val))
+    }
+}
+
 /// Helper type for the OLE/COM+ type DATE
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq)]
@@ -203,6 +364,201 @@ impl AsRef<f64> for Date {
 
 wrapper_conv_impl!(f64, Date);
 
+impl Date {
+    /// The calendar fields of this DATE, via `VariantTimeToSystemTime`. 
+    /// The time of day is rounded to the nearest second, so `wMilliseconds` is always 0.
+    pub fn to_systemtime(&self) -> Result<SYSTEMTIME, ValueError> {
+        let mut st: SYSTEMTIME = unsafe { mem::zeroed() };
+        if !self.0.is_finite() || unsafe { VariantTimeToSystemTime(self.0, &mut st) } == 0 {
+            return Err(ValueError::DateOutOfRange);
+        }
+        Ok(st)
+    }
+
+    /// The DATE for the calendar fields of `st`, via `SystemTimeToVariantTime`. 
+    /// `wMilliseconds` and `wDayOfWeek` are ignored; fields outside the years 100 to 9999 fail.
+    pub fn from_systemtime(st: SYSTEMTIME) -> Result<Date, ValueError> {
+        let mut st = st;
+        let mut out = 0.0;
+        if unsafe { SystemTimeToVariantTime(&mut st, &mut out) } == 0 {
+            return Err(ValueError::DateOutOfRange);
+        }
+        Ok(Date(out))
+    }
+
+    /// The FILETIME for this DATE, to the nearest 100ns tick. DATE carries no time zone, 
+    /// so the result is only UTC if this value was. Fails for dates before 1601, the FILETIME epoch.
+    pub fn to_filetime(&self) -> Result<FileTime, ValueError> {
+        if !(self.0 >= -DATE_EPOCH_DAYS as f64 && self.0 < DATE_END_DAYS as f64) {
+            return Err(ValueError::DateOutOfRange);
+        }
+        Ok(FileTime(date_to_ticks(*self) as u64))
+    }
+
+    /// The DATE for a FILETIME. Fails for times after the year 9999.
+    pub fn from_filetime(ft: FileTime) -> Result<Date, ValueError> {
+        if ft.0 >= ((DATE_EPOCH_DAYS + DATE_END_DAYS) * TICKS_PER_DAY) as u64 {
+            return Err(ValueError::DateOutOfRange);
+        }
+        Ok(ticks_to_date(ft.0 as i64))
+    }
+}
+
+/// Which day an Excel workbook counts its serial dates from
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ExcelDateSystem {
+    /// Windows workbooks: serial 1 is 1900-01-01, and serial 60 is a 1900-02-29 that 
+    /// Excel kept from Lotus 1-2-3, which took 1900 for a leap year
+    Excel1900,
+    /// Workbooks from early Mac Excel: serial 0 is 1904-01-01, with no phantom day
+    Excel1904,
+}
+
+/// The DATE of 1904-01-01, serial 0 in the 1904 date system
+const EXCEL_1904_DAYS: f64 = 1462.0;
+
+impl Date {
+    /// The DATE for an Excel serial date, such as a cell's `Value2`. 
+    /// 
+    /// In the 1900 system the serials from 61 (1900-03-01) on match DATE, and those from 1 
+    /// to 59 are one day behind, since Excel counts the phantom 1900-02-29 as serial 60 - 
+    /// which fails with `ValueError::PhantomLeapDay`. Serials below 1 are a time of day alone 
+    /// and keep the same fraction, as VB's bare times do.
+    pub fn from_excel_serial(serial: f64, system: ExcelDateSystem) -> Result<Date, ValueError> {
+        let d = match system {
+            ExcelDateSystem::Excel1900 if serial >= 60.0 && serial < 61.0 => return Err(ValueError::PhantomLeapDay), 
+            ExcelDateSystem::Excel1900 if serial >= 1.0 && serial < 60.0 => serial + 1.0, 
+            ExcelDateSystem::Excel1900 => serial, 
+            ExcelDateSystem::Excel1904 => serial + EXCEL_1904_DAYS,
+        };
+        if !(serial >= 0.0 && d < DATE_END_DAYS as f64) {
+            return Err(ValueError::DateOutOfRange);
+        }
+        Ok(Date(d))
+    }
+
+    /// The Excel serial date for this DATE, the reverse of `from_excel_serial`. 
+    /// Fails for days before the system's first: 1900-01-01, or 1904-01-01. 
+    pub fn to_excel_serial(&self, system: ExcelDateSystem) -> Result<f64, ValueError> {
+        let d = self.0;
+        let serial = match system {
+            //a bare time of day
+            ExcelDateSystem::Excel1900 if d >= 0.0 && d < 1.0 => d, 
+            ExcelDateSystem::Excel1900 if d >= 2.0 && d < 61.0 => d - 1.0, 
+            ExcelDateSystem::Excel1900 if d >= 61.0 => d, 
+            ExcelDateSystem::Excel1900 => return Err(ValueError::DateOutOfRange), 
+            ExcelDateSystem::Excel1904 => d - EXCEL_1904_DAYS,
+        };
+        if !(serial >= 0.0 && d < DATE_END_DAYS as f64) {
+            return Err(ValueError::DateOutOfRange);
+        }
+        Ok(serial)
+    }
+}
+
+/// 100ns ticks per day
+const TICKS_PER_DAY: i64 = 864_000_000_000;
+/// Days from 1601-01-01, the FILETIME epoch, to 1899-12-30, the DATE epoch
+const DATE_EPOCH_DAYS: i64 = 109_205;
+
+/// 100ns ticks since 1601-01-01 - how FILETIME and WinRT's `DateTime` count - for a DATE
+pub(crate) fn date_to_ticks(date: Date) -> i64 {
+    let d = date.0;
+    //OLE dates before the epoch count the time of day forward from midnight
+    let linear = d.trunc() + (d - d.trunc()).abs();
+    //days and time of day are split so the tick count stays exact beyond f64's 2^53
+    let day = linear.floor();
+    let time = ((linear - day) * TICKS_PER_DAY as f64).round() as i64;
+    (day as i64 + DATE_EPOCH_DAYS) * TICKS_PER_DAY + time
+}
+
+/// The reverse of `date_to_ticks`
+pub(crate) fn ticks_to_date(ticks: i64) -> Date {
+    let day = (ticks.div_euclid(TICKS_PER_DAY) - DATE_EPOCH_DAYS) as f64;
+    let frac = ticks.rem_euclid(TICKS_PER_DAY) as f64 / TICKS_PER_DAY as f64;
+    Date(if day < 0.0 { day - frac } else { day + frac })
+}
+
+/// The first day an OLE DATE represents, 100-01-01
+#[cfg(feature = "chrono")]
+const DATE_MIN_DAYS: i64 = -657_434;
+/// The day after the last one an OLE DATE represents, 9999-12-31
+const DATE_END_DAYS: i64 = 2_958_466;
+
+/// The OLE DATE epoch, 1899-12-30
+#[cfg(feature = "chrono")]
+fn date_epoch() -> Result<NaiveDate, ValueError> {
+    NaiveDate::from_ymd_opt(1899, 12, 30).ok_or(ValueError::DateOutOfRange)
+}
+
+/// Converts to an OLE DATE: days since 1899-12-30, with the time of day as the fraction. 
+/// Before the epoch the fraction still counts forward from midnight, so it is subtracted.
+#[cfg(feature = "chrono")]
+pub(crate) fn date_from_naive(dt: NaiveDateTime) -> Result<Date, ValueError> {
+    let epoch = date_epoch()?;
+    let days = dt.date().signed_duration_since(epoch).num_days();
+    if days < DATE_MIN_DAYS || days >= DATE_END_DAYS {
+        return Err(ValueError::DateOutOfRange);
+    }
+    let time = dt.time();
+    let secs = time.num_seconds_from_midnight() as f64 + time.nanosecond() as f64 / 1e9;
+    let frac = secs / 86_400.0;
+    Ok(Date(if days >= 0 { days as f64 + frac } else { days as f64 - frac }))
+}
+
+/// The reverse of `date_from_naive`, to the nearest millisecond
+#[cfg(feature = "chrono")]
+pub(crate) fn naive_from_date(date: Date) -> Result<NaiveDateTime, ValueError> {
+    if !(date.0 >= DATE_MIN_DAYS as f64 && date.0 < DATE_END_DAYS as f64) {
+        return Err(ValueError::DateOutOfRange);
+    }
+    let whole = date.0.trunc();
+    let mut millis = ((date.0 - whole).abs() * 86_400_000.0).round() as u32;
+    let mut days = whole as i64 + i64::from(date_epoch()?.num_days_from_ce());
+    //a fraction that rounds up to midnight belongs to the next day
+    if millis == 86_400_000 {
+        millis = 0;
+        days += 1;
+    }
+    let day = NaiveDate::from_num_days_from_ce_opt(days as i32).ok_or(ValueError::DateOutOfRange)?;
+    let time = NaiveTime::from_num_seconds_from_midnight_opt(millis / 1000, (millis % 1000) * 1_000_000)
+        .ok_or(ValueError::DateOutOfRange)?;
+    Ok(NaiveDateTime::new(day, time))
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<NaiveDateTime> for Date {
+    type Error = ValueError;
+    fn try_from(dt: NaiveDateTime) -> Result<Date, ValueError> {
+        date_from_naive(dt)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<DateTime<Utc>> for Date {
+    type Error = ValueError;
+    fn try_from(dt: DateTime<Utc>) -> Result<Date, ValueError> {
+        date_from_naive(dt.naive_utc())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<Date> for NaiveDateTime {
+    type Error = ValueError;
+    fn try_from(date: Date) -> Result<NaiveDateTime, ValueError> {
+        naive_from_date(date)
+    }
+}
+
+/// DATE carries no time zone, so the value is taken to be in UTC
+#[cfg(feature = "chrono")]
+impl TryFrom<Date> for DateTime<Utc> {
+    type Error = ValueError;
+    fn try_from(date: Date) -> Result<DateTime<Utc>, ValueError> {
+        Ok(Utc.from_utc_datetime(&naive_from_date(date)?))
+    }
+}
+
 /// Helper type for the OLE/COM+ type DECIMAL
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -350,6 +706,9 @@ conversions_impl!(Decimal, DecWrapper);
 conversions_impl!(DecWrapper, DECIMAL);
 
 /// Helper type for the OLE/COM+ type VARIANT_BOOL
+/// 
+/// `true` converts to `VARIANT_TRUE` (-1). Any non-zero VARIANT_BOOL reads as `true`, 
+/// as in VB, so servers that write 1 interoperate.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct VariantBool(bool);
@@ -372,17 +731,17 @@ impl<'v> From<&'v mut VariantBool> for VARIANT_BOOL {
 
 impl From<VARIANT_BOOL> for VariantBool {
     fn from(vb: VARIANT_BOOL) -> VariantBool {
-        VariantBool(vb < 0) 
+        VariantBool(vb != 0) 
     }
 }
 impl<'v> From<&'v VARIANT_BOOL> for VariantBool {
     fn from(vb: &VARIANT_BOOL) -> VariantBool {
-        VariantBool(*vb < 0) 
+        VariantBool(*vb != 0) 
     }
 }
 impl<'v> From<&'v mut VARIANT_BOOL> for VariantBool {
     fn from(vb: &mut VARIANT_BOOL) -> VariantBool {
-        VariantBool(*vb < 0) 
+        VariantBool(*vb != 0) 
     }
 }
 
@@ -567,10 +926,231 @@ impl TryFrom<u8> for UInt {
     }
 }
 
+/// Helper type for the OLE/COM+ type CLIPDATA, used by VT_CF
+/// 
+/// Only Windows clipboard formats are represented: `format` is the `CF_*` value 
+/// (or a registered format id) and `bytes` the data in that format.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ClipData {
+    /// Windows clipboard format of the data
+    pub format: u32, 
+    /// The clipboard data
+    pub bytes: Vec<u8>,
+}
+
+impl ClipData {
+    /// default constructor
+    pub fn new(format: u32, bytes: Vec<u8>) -> ClipData {
+        ClipData {
+            format: format, 
+            bytes: bytes,
+        }
+    }
+}
+
+/// Helper type for the Win32 type FILETIME: 100-nanosecond ticks since 1601-01-01 UTC. 
+/// 
+/// Property stores carry it as `VT_FILETIME` in a PROPVARIANT; a VARIANT has no 
+/// FILETIME form, so automation interfaces pass timestamps as `Date` instead.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct FileTime(u64);
+
+impl AsRef<u64> for FileTime {
+    fn as_ref(&self) -> &u64 {
+        &self.0
+    }
+}
+
+wrapper_conv_impl!(u64, FileTime);
+
+impl From<FILETIME> for FileTime {
+    fn from(ft: FILETIME) -> FileTime {
+        FileTime((u64::from(ft.dwHighDateTime) << 32) | u64::from(ft.dwLowDateTime))
+    }
+}
+
+impl<'f> From<&'f FILETIME> for FileTime {
+    fn from(ft: &FILETIME) -> FileTime {
+        FileTime::from(*ft)
+    }
+}
+
+impl<'f> From<&'f mut FILETIME> for FileTime {
+    fn from(ft: &mut FILETIME) -> FileTime {
+        FileTime::from(*ft)
+    }
+}
+
+impl From<FileTime> for FILETIME {
+    fn from(ft: FileTime) -> FILETIME {
+        FILETIME { dwLowDateTime: ft.0 as u32, dwHighDateTime: (ft.0 >> 32) as u32 }
+    }
+}
+
+impl<'f> From<&'f FileTime> for FILETIME {
+    fn from(ft: &FileTime) -> FILETIME {
+        FILETIME::from(*ft)
+    }
+}
+
+impl<'f> From<&'f mut FileTime> for FILETIME {
+    fn from(ft: &mut FileTime) -> FILETIME {
+        FILETIME::from(*ft)
+    }
+}
+
+conversions_impl!(FileTime, FILETIME);
+
+/// FILETIME ticks from 1601-01-01 to the Unix epoch
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+const TICKS_PER_SEC: u64 = 10_000_000;
+
+impl FileTime {
+    /// Converts a `SystemTime`, dropping anything finer than 100ns. 
+    /// Fails for times before 1601 or beyond the 64-bit tick range.
+    pub fn from_system_time(t: SystemTime) -> Result<FileTime, ValueError> {
+        let ticks = match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => duration_ticks(d).and_then(|ticks| FILETIME_UNIX_EPOCH.checked_add(ticks)), 
+            Err(e) => duration_ticks(e.duration()).and_then(|ticks| FILETIME_UNIX_EPOCH.checked_sub(ticks)),
+        };
+        ticks.map(FileTime).ok_or(ValueError::FileTimeOutOfRange)
+    }
+
+    /// Converts to a `SystemTime`. Fails when the platform's `SystemTime` can't hold the time.
+    pub fn to_system_time(self) -> Result<SystemTime, ValueError> {
+        let t = if self.0 >= FILETIME_UNIX_EPOCH {
+            UNIX_EPOCH.checked_add(ticks_duration(self.0 - FILETIME_UNIX_EPOCH))
+        } else {
+            UNIX_EPOCH.checked_sub(ticks_duration(FILETIME_UNIX_EPOCH - self.0))
+        };
+        t.ok_or(ValueError::FileTimeOutOfRange)
+    }
+}
+
+fn duration_ticks(d: Duration) -> Option<u64> {
+    d.as_secs().checked_mul(TICKS_PER_SEC)?.checked_add(u64::from(d.subsec_nanos() / 100))
+}
+
+fn ticks_duration(ticks: u64) -> Duration {
+    Duration::new(ticks / TICKS_PER_SEC, (ticks % TICKS_PER_SEC) as u32 * 100)
+}
+
+/// Helper type for the COM type GUID (also CLSID and IID)
+/// 
+/// Displays and parses in registry format, `{6B29FC40-CA47-1067-B31D-00DD010662DA}`; 
+/// parsing also takes the form without braces, in either case. 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Guid {
+    data1: u32, 
+    data2: u16, 
+    data3: u16, 
+    data4: [u8; 8],
+}
+
+impl Guid {
+    /// Builds a GUID from the four fields of the COM `GUID` struct
+    pub fn new(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Guid {
+        Guid { data1: data1, data2: data2, data3: data3, data4: data4 }
+    }
+
+    /// The four fields of the COM `GUID` struct
+    pub fn fields(&self) -> (u32, u16, u16, [u8; 8]) {
+        (self.data1, self.data2, self.data3, self.data4)
+    }
+}
+
+impl From<GUID> for Guid {
+    fn from(g: GUID) -> Guid {
+        Guid::new(g.Data1, g.Data2, g.Data3, g.Data4)
+    }
+}
+
+impl<'g> From<&'g GUID> for Guid {
+    fn from(g: &GUID) -> Guid {
+        Guid::from(*g)
+    }
+}
+
+impl<'g> From<&'g mut GUID> for Guid {
+    fn from(g: &mut GUID) -> Guid {
+        Guid::from(*g)
+    }
+}
+
+impl From<Guid> for GUID {
+    fn from(g: Guid) -> GUID {
+        GUID { Data1: g.data1, Data2: g.data2, Data3: g.data3, Data4: g.data4 }
+    }
+}
+
+impl<'g> From<&'g Guid> for GUID {
+    fn from(g: &Guid) -> GUID {
+        GUID::from(*g)
+    }
+}
+
+impl<'g> From<&'g mut Guid> for GUID {
+    fn from(g: &mut Guid) -> GUID {
+        GUID::from(*g)
+    }
+}
+
+conversions_impl!(Guid, GUID);
+
+#[cfg(feature = "uuid")]
+impl From<Uuid> for Guid {
+    fn from(u: Uuid) -> Guid {
+        let (data1, data2, data3, data4) = u.as_fields();
+        Guid::new(data1, data2, data3, *data4)
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<Guid> for Uuid {
+    fn from(g: Guid) -> Uuid {
+        Uuid::from_fields(g.data1, g.data2, g.data3, &g.data4)
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let d = &self.data4;
+        write!(f, "{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}", 
+            self.data1, self.data2, self.data3, d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7])
+    }
+}
+
+impl FromStr for Guid {
+    type Err = ValueError;
+    fn from_str(s: &str) -> Result<Guid, ValueError> {
+        let err = || ValueError::GuidParse{input: String::from(s)};
+        let t = s.trim();
+        let t = if t.starts_with('{') && t.ends_with('}') { &t[1..t.len() - 1] } else { t };
+        let groups: Vec<&str> = t.split('-').collect();
+        if groups.len() != 5 
+            || groups.iter().map(|g| g.len()).ne([8, 4, 4, 4, 12].iter().cloned()) 
+            || !groups.iter().all(|g| g.chars().all(|c| c.is_ascii_hexdigit())) {
+            return Err(err());
+        }
+        let data1 = u32::from_str_radix(groups[0], 16).map_err(|_| err())?;
+        let data2 = u16::from_str_radix(groups[1], 16).map_err(|_| err())?;
+        let data3 = u16::from_str_radix(groups[2], 16).map_err(|_| err())?;
+        let tail = format!("{}{}", groups[3], groups[4]);
+        let mut data4 = [0u8; 8];
+        for (ix, b) in data4.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&tail[ix * 2..ix * 2 + 2], 16).map_err(|_| err())?;
+        }
+        Ok(Guid::new(data1, data2, data3, data4))
+    }
+}
+
 /// Helper type for the OLE/COM+ type SCODE
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub struct SCode(i32);
+pub struct SCode(pub(crate) i32);
 
 impl AsRef<i32> for SCode {
     fn as_ref(&self) -> &i32 {
@@ -604,9 +1184,191 @@ impl fmt::Binary for SCode {
 
 wrapper_conv_impl!(i32, SCode);
 
+const FACILITY_WIN32: i32 = 7;
+
+impl SCode {
+    /// True when the severity bit is clear
+    pub fn is_success(self) -> bool {
+        self.0 >= 0
+    }
+
+    /// The facility field (bits 16-26)
+    pub fn facility(self) -> i32 {
+        (self.0 >> 16) & 0x1FFF
+    }
+
+    /// The code field (low 16 bits)
+    pub fn code(self) -> i32 {
+        self.0 & 0xFFFF
+    }
+}
+
+/// Failures in the `FACILITY_WIN32` facility map back to the Win32 error code, 
+/// anything else is passed through as the raw HRESULT.
+impl From<SCode> for io::Error {
+    fn from(sc: SCode) -> io::Error {
+        if !sc.is_success() && sc.facility() == FACILITY_WIN32 {
+            io::Error::from_raw_os_error(sc.code())
+        } else {
+            io::Error::from_raw_os_error(sc.0)
+        }
+    }
+}
+
+/// Win32 error codes are wrapped as `HRESULT_FROM_WIN32` does; errors 
+/// without an OS error code become `E_FAIL`.
+impl<'e> From<&'e io::Error> for SCode {
+    fn from(e: &io::Error) -> SCode {
+        match e.raw_os_error() {
+            Some(code) if code > 0 && code <= 0xFFFF => {
+                SCode(((code & 0xFFFF) | (FACILITY_WIN32 << 16)) | (0x8000_0000u32 as i32))
+            }, 
+            Some(code) => SCode(code), 
+            None => codes::E_FAIL,
+        }
+    }
+}
+
+impl From<io::Error> for SCode {
+    fn from(e: io::Error) -> SCode {
+        SCode::from(&e)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[test]
+    fn currency_parse() {
+        assert_eq!(i64::from("12.34".parse::<Currency>().unwrap()), 123400);
+        assert_eq!(i64::from("-0.0001".parse::<Currency>().unwrap()), -1);
+        assert_eq!(i64::from("7".parse::<Currency>().unwrap()), 70000);
+        assert!("1.23456".parse::<Currency>().is_err());
+        assert!("12,3".parse::<Currency>().is_err());
+        assert!(".".parse::<Currency>().is_err());
+        assert!("++5".parse::<Currency>().is_err());
+        assert!("+-5".parse::<Currency>().is_err());
+        assert!("-".parse::<Currency>().is_err());
+        assert_eq!(i64::from("+5".parse::<Currency>().unwrap()), 50000);
+        assert_eq!(i64::from("-922337203685477.5808".parse::<Currency>().unwrap()), i64::min_value());
+        assert!("922337203685477.5808".parse::<Currency>().is_err());
+        assert_eq!(i64::from(Currency::from_f64(1.5).unwrap()), 15000);
+        assert!(Currency::from_f64(::std::f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_currency_ratio() {
+        let cy = |v: i64| Currency::from(v);
+        assert_eq!(i64::from(cy(199900).percent_of(cy(75000)).unwrap()), 14992);
+        assert_eq!(i64::from(cy(10000).mul_ratio(1, 3).unwrap()), 3333);
+        assert_eq!(i64::from(cy(5).mul_ratio(1, 2).unwrap()), 2);
+        assert_eq!(i64::from(cy(7).mul_ratio(1, 2).unwrap()), 4);
+        assert_eq!(i64::from(cy(-5).mul_ratio(1, 2).unwrap()), -2);
+        assert_eq!(i64::from(cy(5).mul_ratio(1, -2).unwrap()), -2);
+        assert!(cy(1).mul_ratio(1, 0).is_err());
+        assert!(cy(i64::max_value()).mul_ratio(2, 1).is_err());
+        assert_eq!(i64::from(cy(25000).round(0).unwrap()), 20000);
+        assert_eq!(i64::from(cy(35000).round(0).unwrap()), 40000);
+        assert_eq!(i64::from(cy(-12350).round(2).unwrap()), -12400);
+        assert_eq!(i64::from(cy(3).mul_i32(-4).unwrap()), -12);
+    }
+
+    #[test]
+    fn scode_io_error() {
+        //HRESULT_FROM_WIN32(ERROR_FILE_NOT_FOUND)
+        let sc = SCode::from(0x8007_0002u32 as i32);
+        assert_eq!(sc.facility(), 7);
+        let e = io::Error::from(sc);
+        assert_eq!(e.raw_os_error(), Some(2));
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        assert_eq!(SCode::from(&e), sc);
+
+        //DISP_E_TYPEMISMATCH passes through untouched
+        let sc = SCode::from(0x8002_0005u32 as i32);
+        assert_eq!(SCode::from(io::Error::from(sc)), sc);
+
+        let e = io::Error::new(io::ErrorKind::Other, "no os code");
+        assert_eq!(i32::from(SCode::from(e)), 0x8000_4005u32 as i32);
+    }
+
+    #[test]
+    fn test_filetime() {
+        let ft = FileTime::from_system_time(UNIX_EPOCH).unwrap();
+        assert_eq!(u64::from(ft), FILETIME_UNIX_EPOCH);
+        assert_eq!(ft.to_system_time().unwrap(), UNIX_EPOCH);
+
+        let t = UNIX_EPOCH + Duration::new(1_500_000_000, 123_456_789);
+        let ft = FileTime::from_system_time(t).unwrap();
+        assert_eq!(ft.to_system_time().unwrap(), UNIX_EPOCH + Duration::new(1_500_000_000, 123_456_700));
+
+        let raw = FILETIME::from(FileTime::from(0x0123_4567_89AB_CDEFu64));
+        assert_eq!(raw.dwHighDateTime, 0x0123_4567);
+        assert_eq!(raw.dwLowDateTime, 0x89AB_CDEF);
+        assert_eq!(u64::from(FileTime::from(raw)), 0x0123_4567_89AB_CDEF);
+    }
+
+    #[test]
+    fn test_date_ticks() {
+        for &d in &[0.0, 43000.5, -1.25, -700.75] {
+            assert_eq!(f64::from(ticks_to_date(date_to_ticks(Date::from(d)))), d);
+        }
+        //1601-01-01 is the DateTime epoch
+        assert_eq!(date_to_ticks(Date::from(-109205.0)), 0);
+    }
+
+    #[test]
+    fn test_date_excel() {
+        let sys = ExcelDateSystem::Excel1900;
+        //1900-01-01 and 1900-02-28 sit one day off, 1900-03-01 on
+        assert_eq!(Date::from_excel_serial(1.0, sys).unwrap(), Date(2.0));
+        assert_eq!(Date::from_excel_serial(59.5, sys).unwrap(), Date(60.5));
+        assert_eq!(Date::from_excel_serial(61.0, sys).unwrap(), Date(61.0));
+        assert_eq!(Date::from_excel_serial(43405.25, sys).unwrap(), Date(43405.25));
+        assert_eq!(Date::from_excel_serial(0.5, sys).unwrap(), Date(0.5));
+        match Date::from_excel_serial(60.0, sys) {
+            Err(ValueError::PhantomLeapDay) => {},
+            _ => panic!("expected PhantomLeapDay"),
+        }
+        assert!(Date::from_excel_serial(-1.0, sys).is_err());
+        for &d in &[0.5, 2.0, 60.5, 61.0, 43405.25] {
+            let serial = Date(d).to_excel_serial(sys).unwrap();
+            assert_eq!(Date::from_excel_serial(serial, sys).unwrap(), Date(d));
+        }
+        //1899-12-31 has no serial
+        assert!(Date(1.5).to_excel_serial(sys).is_err());
+
+        let sys = ExcelDateSystem::Excel1904;
+        assert_eq!(Date::from_excel_serial(0.0, sys).unwrap(), Date(1462.0));
+        assert_eq!(Date(43405.25).to_excel_serial(sys).unwrap(), 41943.25);
+        assert!(Date(1461.0).to_excel_serial(sys).is_err());
+    }
+
+    #[test]
+    fn test_date_filetime() {
+        //the Unix epoch is DATE 25569
+        let ft = Date(25569.0).to_filetime().unwrap();
+        assert_eq!(u64::from(ft), FILETIME_UNIX_EPOCH);
+        assert_eq!(Date::from_filetime(ft).unwrap(), Date(25569.0));
+        let ft = Date(-1.25).to_filetime().unwrap();
+        assert_eq!(Date::from_filetime(ft).unwrap(), Date(-1.25));
+
+        assert_eq!(u64::from(Date(-109205.0).to_filetime().unwrap()), 0);
+        assert!(Date(-109206.0).to_filetime().is_err());
+        assert!(Date(::std::f64::NAN).to_filetime().is_err());
+        assert!(Date::from_filetime(FileTime(u64::max_value())).is_err());
+    }
+
+    #[test]
+    fn test_guid() {
+        let g = Guid::new(0x6B29_FC40, 0xCA47, 0x1067, [0xB3, 0x1D, 0x00, 0xDD, 0x01, 0x06, 0x62, 0xDA]);
+        assert_eq!(g.to_string(), "{6B29FC40-CA47-1067-B31D-00DD010662DA}");
+        assert_eq!("{6B29FC40-CA47-1067-B31D-00DD010662DA}".parse::<Guid>().unwrap(), g);
+        assert_eq!("6b29fc40-ca47-1067-b31d-00dd010662da".parse::<Guid>().unwrap(), g);
+        assert!("6B29FC40-CA47-1067-B31D00DD010662DA".parse::<Guid>().is_err());
+        assert!("{6B29FC40-CA47-1067-B31D-00DD010662DG}".parse::<Guid>().is_err());
+        assert_eq!(Guid::from(GUID::from(g)), g);
+    }
+
     #[test]
     fn c_decimal() {
         let d = Decimal::new(0xFFFFFFFFFFFF, 0);
@@ -673,6 +1435,63 @@ mod tests {
         assert_sync::<VariantBool>();
     }
 
+    #[test]
+    fn test_currency_decimal() {
+        assert_eq!(Currency::from_decimal(Decimal::new(1234, 2)).unwrap(), Currency(123400));
+        assert_eq!(Currency::from_decimal(Decimal::new(-5, 0)).unwrap(), Currency(-50000));
+        assert_eq!(Currency::try_from(Decimal::new(1_000_000, 6)).unwrap(), Currency(10000));
+        match Currency::from_decimal(Decimal::new(12345, 5)) {
+            Err(CurrencyError::TooPrecise{..}) => {},
+            _ => panic!("expected TooPrecise"),
+        }
+        match Currency::from_decimal(Decimal::new(i64::max_value(), 0)) {
+            Err(CurrencyError::Overflow{..}) => {},
+            _ => panic!("expected Overflow"),
+        }
+        //the whole CY range converts, both ways
+        for &raw in &[i64::min_value(), -1, 0, 123400, i64::max_value()] {
+            let dec = Currency(raw).to_decimal();
+            assert_eq!(Currency::from_decimal(dec).unwrap(), Currency(raw));
+        }
+        assert_eq!(Decimal::from(Currency(123400)), Decimal::new(123400, 4));
+    }
+
+    #[test]
+    fn test_date_systemtime() {
+        let st = Date(2.5).to_systemtime().unwrap();
+        assert_eq!((st.wYear, st.wMonth, st.wDay, st.wDayOfWeek), (1900, 1, 1, 1));
+        assert_eq!((st.wHour, st.wMinute, st.wSecond, st.wMilliseconds), (12, 0, 0, 0));
+        assert_eq!(Date::from_systemtime(st).unwrap(), Date(2.5));
+
+        assert!(Date(::std::f64::NAN).to_systemtime().is_err());
+        assert!(Date(3e6).to_systemtime().is_err());
+        let mut bad = st;
+        bad.wMonth = 13;
+        assert!(Date::from_systemtime(bad).is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_date_chrono() {
+        let noon = NaiveDate::from_ymd_opt(1900, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        assert_eq!(Date::try_from(noon).unwrap(), Date(2.5));
+        assert_eq!(NaiveDateTime::try_from(Date(2.5)).unwrap(), noon);
+
+        //before the epoch the fraction still counts forward from midnight
+        let six = NaiveDate::from_ymd_opt(1899, 12, 29).unwrap().and_hms_opt(6, 0, 0).unwrap();
+        assert_eq!(NaiveDateTime::try_from(Date(-1.25)).unwrap(), six);
+        assert_eq!(Date::try_from(six).unwrap(), Date(-1.25));
+
+        let utc = DateTime::<Utc>::try_from(Date(2.5)).unwrap();
+        assert_eq!(utc.naive_utc(), noon);
+        assert_eq!(Date::try_from(utc).unwrap(), Date(2.5));
+
+        assert!(NaiveDateTime::try_from(Date(::std::f64::NAN)).is_err());
+        assert!(NaiveDateTime::try_from(Date(3e6)).is_err());
+        let early = NaiveDate::from_ymd_opt(99, 12, 31).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert!(Date::try_from(early).is_err());
+    }
+
     #[cfg(feature = "impl_tryfrom")]
     #[cfg_attr(feature = "impl_tryfrom", test)]
     fn test_tryfrom() {
@@ -0,0 +1,741 @@
+//! # Value
+//! Full-fidelity dynamic view of a VARIANT.
+//!
+//! [`Variants`] covers the common scalars only. [`VariantValue`] is the general
+//! "whatever this VARIANT holds" type:
+//! - strings are kept as UTF-16 so they survive unchanged;
+//! - DECIMAL, EMPTY and NULL have their own arms;
+//! - interface pointers are held with a reference of their own;
+//! - arrays of any dimension keep their bounds;
+//! - `VT_BYREF` values are read through their pointer.
+//!
+//! Vartypes with no arm of their own, such as `VT_RECORD`, are kept as a deep copy
+//! of the VARIANT in [`VariantValue::Other`].
+//!
+//! [`Variants`]: enum.Variants.html
+//! [`VariantValue`]: enum.VariantValue.html
+//! [`VariantValue::Other`]: enum.VariantValue.html#variant.Other
+use std::convert::TryFrom;
+use std::mem;
+use std::ptr;
+
+use widestring::U16String;
+
+use winapi::ctypes::c_void;
+use winapi::shared::wtypes::{
+    BSTR,
+    DECIMAL,
+    VARIANT_BOOL,
+    VT_ARRAY,
+    VT_BOOL,
+    VT_BSTR,
+    VT_BYREF,
+    VT_CY,
+    VT_DATE,
+    VT_DECIMAL,
+    VT_DISPATCH,
+    VT_EMPTY,
+    VT_ERROR,
+    VT_I1,
+    VT_I2,
+    VT_I4,
+    VT_I8,
+    VT_INT,
+    VT_NULL,
+    VT_R4,
+    VT_R8,
+    VT_TYPEMASK,
+    VT_UI1,
+    VT_UI2,
+    VT_UI4,
+    VT_UI8,
+    VT_UINT,
+    VT_UNKNOWN,
+    VT_VARIANT,
+};
+use winapi::um::oaidl::{IDispatch, SAFEARRAY, SAFEARRAYBOUND, VARIANT, VARIANT_n3};
+use winapi::um::oleauto::VariantCopy;
+use winapi::um::unknwnbase::IUnknown;
+
+use super::array::{
+    check_elem_size,
+    safearray_shape,
+    SafeArrayCreate,
+    SafeArrayDestructor,
+    SafeArrayLock,
+    SafeArrayPtrOfIndex,
+    SafeArrayPutElement,
+    SafeArrayUnlock,
+};
+use super::dtor::{ClearVariant, ComDestructor, FreeVariant, OwnedPtr};
+use super::errors::{
+    FromSafeArrayError,
+    FromVariantError,
+    IntoSafeArrayError,
+    IntoSafeArrElemError,
+    IntoVariantError,
+};
+use super::leak_tracking;
+use super::limits::DecodeLimits;
+use super::ptr::Ptr;
+use super::stats::ConversionStats;
+use super::types::{Currency, Date, DecWrapper, Int, SCode, UInt};
+use super::variant::{new_variant, VariantExt, Variants, VtEmpty, VtNull};
+
+/// Dynamically typed value for any VARIANT.
+///
+/// Read one with [`from_variant`](#method.from_variant), then match on the arm.
+/// Unlike [`Variants`](enum.Variants.html), every vartype can be read. Vartypes without
+/// an arm of their own become [`Other`](#variant.Other).
+#[derive(Debug)]
+pub enum VariantValue {
+    /// VT_EMPTY
+    Empty,
+    /// VT_NULL
+    Null,
+    /// VT_I1
+    I8(i8),
+    /// VT_I2
+    I16(i16),
+    /// VT_I4
+    I32(i32),
+    /// VT_I8
+    I64(i64),
+    /// VT_UI1
+    U8(u8),
+    /// VT_UI2
+    U16(u16),
+    /// VT_UI4
+    U32(u32),
+    /// VT_UI8
+    U64(u64),
+    /// VT_INT
+    Int(Int),
+    /// VT_UINT
+    UInt(UInt),
+    /// VT_R4
+    F32(f32),
+    /// VT_R8
+    F64(f64),
+    /// VT_BOOL
+    Bool(bool),
+    /// VT_ERROR
+    SCode(SCode),
+    /// VT_CY
+    Currency(Currency),
+    /// VT_DATE
+    Date(Date),
+    /// VT_DECIMAL
+    Decimal(DecWrapper),
+    /// VT_BSTR, kept as UTF-16 so unpaired surrogates and interior NULs survive
+    String(U16String),
+    /// VT_UNKNOWN. `None` for a null pointer.
+    Unknown(Option<OwnedPtr<IUnknown, ComDestructor>>),
+    /// VT_DISPATCH. `None` for a null pointer.
+    Dispatch(Option<OwnedPtr<IDispatch, ComDestructor>>),
+    /// VT_ARRAY of any element type that has an arm of its own
+    Array(ValueArray),
+    /// A `VT_BYREF` value, read through its pointer into a copy.
+    /// Converting it back writes the value itself; see [`ByRefExt`](trait.ByRefExt.html)
+    /// for building by-reference VARIANTs.
+    ByRef(Box<VariantValue>),
+    /// A deep copy of a VARIANT whose vartype has no arm of its own, such as `VT_RECORD`
+    Other(OwnedPtr<VARIANT, FreeVariant>),
+}
+
+/// The contents of a `VT_ARRAY` VARIANT
+#[derive(Debug)]
+pub struct ValueArray {
+    /// Element vartype, without `VT_ARRAY`
+    pub vartype: u32,
+    /// `(lower bound, element count)` per dimension, dimension 1 first, as
+    /// [`safearray_shape`](fn.safearray_shape.html) returns them
+    pub bounds: Vec<(i32, u32)>,
+    /// Every element, with the last dimension varying fastest
+    pub elements: Vec<VariantValue>,
+}
+
+impl ValueArray {
+    /// A 1-D array with a lower bound of 0
+    pub fn vector(vartype: u32, elements: Vec<VariantValue>) -> ValueArray {
+        ValueArray {
+            vartype: vartype,
+            bounds: vec![(0, elements.len() as u32)],
+            elements: elements,
+        }
+    }
+
+    /// An N x 2 `VT_VARIANT` array, one row per entry with the key in the first column, 
+    /// the shape `map_into_safearray` writes
+    #[cfg(any(feature = "json", feature = "serde"))]
+    pub(crate) fn keyed(entries: Vec<(U16String, VariantValue)>) -> ValueArray {
+        let rows = entries.len() as u32;
+        let mut elements = Vec::with_capacity(entries.len() * 2);
+        for (key, value) in entries {
+            elements.push(VariantValue::String(key));
+            elements.push(value);
+        }
+        ValueArray {
+            vartype: VT_VARIANT,
+            bounds: vec![(0, rows), (0, 2)],
+            elements: elements,
+        }
+    }
+
+    /// Whether the array has the shape `keyed` builds: N x 2 `VT_VARIANT` with string keys
+    #[cfg(any(feature = "json", feature = "serde"))]
+    pub(crate) fn is_keyed(&self) -> bool {
+        self.vartype == VT_VARIANT && self.bounds.len() == 2 && self.bounds[1].1 == 2
+            && self.elements.chunks(2).all(|pair| pair[0].vartype() == VT_BSTR)
+    }
+
+    fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        let expected = self.bounds.iter().fold(1usize, |n, &(_, count)| n.saturating_mul(count as usize));
+        if expected != self.elements.len() {
+            return Err(IntoVariantError::from(
+                IntoSafeArrayError::ElementCountMismatch{expected: expected, found: self.elements.len()}
+            ));
+        }
+        //SafeArrayCreate takes the bounds in dimension order, like `self.bounds`
+        let mut sab: Vec<SAFEARRAYBOUND> = self.bounds.iter()
+            .map(|&(l_bound, count)| SAFEARRAYBOUND { cElements: count, lLbound: l_bound })
+            .collect();
+        let psa = unsafe { SafeArrayCreate(self.vartype as u16, sab.len() as u32, sab.as_mut_ptr()) };
+        if psa.is_null() {
+            return Err(IntoVariantError::from(IntoSafeArrayError::SafeArrayCreateFailed));
+        }
+        let mut sad = SafeArrayDestructor::new(psa);
+        ConversionStats::record_safearray(expected * slot_size(self.vartype).unwrap_or(0));
+
+        let mut ixs: Vec<i32> = self.bounds.iter().map(|&(l_bound, _)| l_bound).collect();
+        for (ix, elem) in self.elements.into_iter().enumerate() {
+            if let Err(ee) = put_element(psa, self.vartype, &ixs, elem) {
+                return Err(IntoVariantError::from(IntoSafeArrayError::from_element_err(ee, ix)));
+            }
+            next_index(&mut ixs, &self.bounds);
+        }
+        sad.inner = ptr::null_mut();
+
+        let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
+        unsafe { *n3.parray_mut() = psa };
+        Ok(new_variant(VT_ARRAY | self.vartype, n3))
+    }
+}
+
+impl VariantValue {
+    /// Reads whatever `var` holds. The VARIANT is cleared afterwards and left `VT_EMPTY`.
+    pub fn from_variant(var: Ptr<VARIANT>) -> Result<VariantValue, FromVariantError> {
+        let _clear = OwnedPtr::<VARIANT, ClearVariant>::new(var);
+        unsafe { VariantValue::read(var.as_ptr()) }
+    }
+
+    /// Reads whatever `src` holds into an independent value, leaving `src` untouched
+    pub unsafe fn read(src: *const VARIANT) -> Result<VariantValue, FromVariantError> {
+        let vt = (*src).n1.n2().vt as u32;
+        let base = vt & VT_TYPEMASK;
+        let slot = &(*src).n1.n2().n3 as *const VARIANT_n3 as *const c_void;
+
+        let value = if vt & VT_ARRAY != 0 {
+            let psa = if vt & VT_BYREF != 0 {
+                let ppsa = *(*src).n1.n2().n3.pparray();
+                if ppsa.is_null() {
+                    return Err(FromVariantError::CVoidPtrNull);
+                }
+                *ppsa
+            } else {
+                *(*src).n1.n2().n3.parray()
+            };
+            read_array(base, psa)?.map(VariantValue::Array)
+        } else if vt & VT_BYREF != 0 {
+            let pval = *(*src).n1.n2().n3.byref();
+            if pval.is_null() {
+                return Err(FromVariantError::CVoidPtrNull);
+            }
+            read_slot(base, pval)?
+        } else {
+            match vt {
+                VT_EMPTY => Some(VariantValue::Empty),
+                VT_NULL => Some(VariantValue::Null),
+                //DECIMAL overlays the whole VARIANT
+                VT_DECIMAL => read_slot(VT_DECIMAL, src as *const c_void)?,
+                VT_VARIANT => None,
+                _ => read_slot(vt, slot)?,
+            }
+        };
+
+        match value {
+            Some(value) if vt & VT_BYREF != 0 => Ok(VariantValue::ByRef(Box::new(value))),
+            Some(value) => Ok(value),
+            None => copy_variant(src).map(VariantValue::Other),
+        }
+    }
+
+    /// The vartype this value was read from. `into_variant` produces the same one,
+    /// except that `ByRef` values are written by value.
+    pub fn vartype(&self) -> u32 {
+        match *self {
+            VariantValue::Empty => VT_EMPTY,
+            VariantValue::Null => VT_NULL,
+            VariantValue::I8(_) => VT_I1,
+            VariantValue::I16(_) => VT_I2,
+            VariantValue::I32(_) => VT_I4,
+            VariantValue::I64(_) => VT_I8,
+            VariantValue::U8(_) => VT_UI1,
+            VariantValue::U16(_) => VT_UI2,
+            VariantValue::U32(_) => VT_UI4,
+            VariantValue::U64(_) => VT_UI8,
+            VariantValue::Int(_) => VT_INT,
+            VariantValue::UInt(_) => VT_UINT,
+            VariantValue::F32(_) => VT_R4,
+            VariantValue::F64(_) => VT_R8,
+            VariantValue::Bool(_) => VT_BOOL,
+            VariantValue::SCode(_) => VT_ERROR,
+            VariantValue::Currency(_) => VT_CY,
+            VariantValue::Date(_) => VT_DATE,
+            VariantValue::Decimal(_) => VT_DECIMAL,
+            VariantValue::String(_) => VT_BSTR,
+            VariantValue::Unknown(_) => VT_UNKNOWN,
+            VariantValue::Dispatch(_) => VT_DISPATCH,
+            VariantValue::Array(ref arr) => VT_ARRAY | arr.vartype,
+            VariantValue::ByRef(ref val) => VT_BYREF | val.vartype(),
+            VariantValue::Other(ref pvar) => unsafe { (*pvar.as_ptr()).n1.n2().vt as u32 },
+        }
+    }
+
+    /// An integer, floating point, currency or decimal value, or a reference to one
+    pub fn is_numeric(&self) -> bool {
+        match *self {
+            VariantValue::I8(_) | VariantValue::I16(_) | VariantValue::I32(_) | VariantValue::I64(_) |
+            VariantValue::U8(_) | VariantValue::U16(_) | VariantValue::U32(_) | VariantValue::U64(_) |
+            VariantValue::Int(_) | VariantValue::UInt(_) | VariantValue::F32(_) | VariantValue::F64(_) |
+            VariantValue::Currency(_) | VariantValue::Decimal(_) => true,
+            VariantValue::ByRef(ref val) => val.is_numeric(),
+            _ => false,
+        }
+    }
+
+    /// A `VT_BSTR` value, or a reference to one
+    pub fn is_string(&self) -> bool {
+        match *self {
+            VariantValue::String(_) => true,
+            VariantValue::ByRef(ref val) => val.is_string(),
+            _ => false,
+        }
+    }
+
+    /// An `IUnknown` or `IDispatch` pointer, including a null one, or a reference to one
+    pub fn is_object(&self) -> bool {
+        match *self {
+            VariantValue::Unknown(_) | VariantValue::Dispatch(_) => true,
+            VariantValue::ByRef(ref val) => val.is_object(),
+            _ => false,
+        }
+    }
+
+    /// Converts the value into a `Ptr<VARIANT>` of the matching vartype
+    pub fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        match self {
+            VariantValue::Empty => VtEmpty{}.into_variant(),
+            VariantValue::Null => VtNull{}.into_variant(),
+            VariantValue::I8(val) => val.into_variant(),
+            VariantValue::I16(val) => val.into_variant(),
+            VariantValue::I32(val) => val.into_variant(),
+            VariantValue::I64(val) => val.into_variant(),
+            VariantValue::U8(val) => val.into_variant(),
+            VariantValue::U16(val) => val.into_variant(),
+            VariantValue::U32(val) => val.into_variant(),
+            VariantValue::U64(val) => val.into_variant(),
+            VariantValue::Int(val) => val.into_variant(),
+            VariantValue::UInt(val) => val.into_variant(),
+            VariantValue::F32(val) => val.into_variant(),
+            VariantValue::F64(val) => val.into_variant(),
+            VariantValue::Bool(val) => val.into_variant(),
+            VariantValue::SCode(val) => val.into_variant(),
+            VariantValue::Currency(val) => val.into_variant(),
+            VariantValue::Date(val) => val.into_variant(),
+            VariantValue::Decimal(val) => val.into_variant(),
+            VariantValue::String(val) => val.into_variant(),
+            VariantValue::Unknown(punk) => punk.into_variant(),
+            VariantValue::Dispatch(pdisp) => pdisp.into_variant(),
+            VariantValue::Array(arr) => arr.into_variant(),
+            VariantValue::ByRef(val) => val.into_variant(),
+            VariantValue::Other(pvar) => Ok(pvar.into_inner()),
+        }
+    }
+}
+
+impl From<Variants> for VariantValue {
+    fn from(val: Variants) -> VariantValue {
+        match val {
+            Variants::Empty => VariantValue::Empty,
+            Variants::Null => VariantValue::Null,
+            Variants::I64(val) => VariantValue::I64(val),
+            Variants::I32(val) => VariantValue::I32(val),
+            Variants::U8(val) => VariantValue::U8(val),
+            Variants::I16(val) => VariantValue::I16(val),
+            Variants::F32(val) => VariantValue::F32(val),
+            Variants::F64(val) => VariantValue::F64(val),
+            Variants::Bool(val) => VariantValue::Bool(val),
+            Variants::SCode(val) => VariantValue::SCode(val),
+            Variants::Currency(val) => VariantValue::Currency(val),
+            Variants::Date(val) => VariantValue::Date(val),
+            Variants::String(val) => VariantValue::String(U16String::from_str(&val)),
+            Variants::WideString(val) => VariantValue::String(val),
+            Variants::I8(val) => VariantValue::I8(val),
+            Variants::U16(val) => VariantValue::U16(val),
+            Variants::U32(val) => VariantValue::U32(val),
+            Variants::U64(val) => VariantValue::U64(val),
+            Variants::Int(val) => VariantValue::Int(val),
+            Variants::UInt(val) => VariantValue::UInt(val),
+            Variants::Decimal(val) => VariantValue::Decimal(val),
+            Variants::Unknown(punk) => VariantValue::Unknown(punk),
+            Variants::Dispatch(pdisp) => VariantValue::Dispatch(pdisp),
+            Variants::Array(items) => VariantValue::Array(
+                ValueArray::vector(VT_VARIANT, items.into_iter().map(VariantValue::from).collect())
+            ),
+        }
+    }
+}
+
+/// Narrows to the `Variants` arms. Values with no `Variants` arm are handed back
+/// unchanged as the error; strings are converted lossily.
+impl TryFrom<VariantValue> for Variants {
+    type Error = VariantValue;
+    fn try_from(val: VariantValue) -> Result<Variants, VariantValue> {
+        match val {
+            VariantValue::Empty => Ok(Variants::Empty),
+            VariantValue::Null => Ok(Variants::Null),
+            VariantValue::I64(val) => Ok(Variants::I64(val)),
+            VariantValue::I32(val) => Ok(Variants::I32(val)),
+            VariantValue::U8(val) => Ok(Variants::U8(val)),
+            VariantValue::I16(val) => Ok(Variants::I16(val)),
+            VariantValue::F32(val) => Ok(Variants::F32(val)),
+            VariantValue::F64(val) => Ok(Variants::F64(val)),
+            VariantValue::Bool(val) => Ok(Variants::Bool(val)),
+            VariantValue::SCode(val) => Ok(Variants::SCode(val)),
+            VariantValue::Currency(val) => Ok(Variants::Currency(val)),
+            VariantValue::Date(val) => Ok(Variants::Date(val)),
+            VariantValue::String(val) => Ok(Variants::from_wide(val)),
+            VariantValue::I8(val) => Ok(Variants::I8(val)),
+            VariantValue::U16(val) => Ok(Variants::U16(val)),
+            VariantValue::U32(val) => Ok(Variants::U32(val)),
+            VariantValue::U64(val) => Ok(Variants::U64(val)),
+            VariantValue::Int(val) => Ok(Variants::Int(val)),
+            VariantValue::UInt(val) => Ok(Variants::UInt(val)),
+            VariantValue::Decimal(val) => Ok(Variants::Decimal(val)),
+            VariantValue::Unknown(punk) => Ok(Variants::Unknown(punk)),
+            VariantValue::Dispatch(pdisp) => Ok(Variants::Dispatch(pdisp)),
+            VariantValue::Array(ref arr) if !fits_variants_array(arr) => Err(val),
+            VariantValue::Array(arr) => Ok(Variants::Array(
+                arr.elements.into_iter().map(|elem| Variants::try_from(elem).expect("checked by fits_variants_array")).collect()
+            )),
+            other => Err(other),
+        }
+    }
+}
+
+/// Every element of a 1-D `arr` has a `Variants` arm
+fn fits_variants_array(arr: &ValueArray) -> bool {
+    arr.bounds.len() == 1 && arr.elements.iter().all(|elem| match *elem {
+        VariantValue::Array(ref inner) => fits_variants_array(inner),
+        VariantValue::ByRef(_) | VariantValue::Other(_) => false,
+        _ => true,
+    })
+}
+
+/// Decodes the `VT_ARRAY` and `VT_BYREF` forms for `Variants::from_variant`: 1-D arrays of any
+/// element type with a `Variants` arm, and pointers to such values, which are dereferenced.
+/// The VARIANT is cleared afterwards.
+pub(crate) fn variants_from_value(var: Ptr<VARIANT>) -> Result<Variants, FromVariantError> {
+    let vt = unsafe { (*var.as_ptr()).n1.n2().vt } as u32;
+    if slot_size(vt & VT_TYPEMASK).is_none() {
+        return Err(FromVariantError::UnknownVarType{vartype: vt});
+    }
+    let val = match VariantValue::from_variant(var)? {
+        VariantValue::ByRef(val) => *val,
+        val => val,
+    };
+    match val {
+        VariantValue::Array(ref arr) if arr.bounds.len() != 1 => {
+            Err(FromVariantError::from(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: arr.bounds.len() as u32}))
+        },
+        val => Variants::try_from(val).map_err(|_| FromVariantError::UnknownVarType{vartype: vt}),
+    }
+}
+
+/// Size of one value of `vt` as stored by reference or in an array, `None` for vartypes
+/// `VariantValue` has no arm for
+fn slot_size(vt: u32) -> Option<usize> {
+    match vt {
+        VT_I1 | VT_UI1 => Some(1),
+        VT_I2 | VT_UI2 => Some(2),
+        VT_BOOL => Some(mem::size_of::<VARIANT_BOOL>()),
+        VT_I4 | VT_UI4 | VT_INT | VT_UINT | VT_R4 | VT_ERROR => Some(4),
+        VT_I8 | VT_UI8 | VT_R8 | VT_CY | VT_DATE => Some(8),
+        VT_BSTR => Some(mem::size_of::<BSTR>()),
+        VT_UNKNOWN | VT_DISPATCH => Some(mem::size_of::<*mut IUnknown>()),
+        VT_DECIMAL => Some(mem::size_of::<DECIMAL>()),
+        VT_VARIANT => Some(mem::size_of::<VARIANT>()),
+        _ => None,
+    }
+}
+
+/// Reads a `vt` value stored at `p`, `None` if `vt` has no arm
+unsafe fn read_slot(vt: u32, p: *const c_void) -> Result<Option<VariantValue>, FromVariantError> {
+    let size = match slot_size(vt) {
+        Some(size) => size,
+        None => return Ok(None),
+    };
+    match vt {
+        VT_VARIANT => return VariantValue::read(p as *const VARIANT).map(Some),
+        VT_DECIMAL => return Ok(Some(VariantValue::Decimal(DecWrapper::from(*(p as *const DECIMAL))))),
+        VT_UNKNOWN => return Ok(Some(VariantValue::Unknown(Ptr::with_checked(*(p as *const *mut IUnknown)).map(OwnedPtr::add_ref)))),
+        VT_DISPATCH => return Ok(Some(VariantValue::Dispatch(Ptr::with_checked(*(p as *const *mut IDispatch)).map(OwnedPtr::add_ref)))),
+        _ => {},
+    }
+    //a shallow copy in a VARIANT of its own lets the VariantExt impls read it in place
+    let mut tmp: VARIANT = mem::zeroed();
+    ptr::copy_nonoverlapping(p as *const u8, &mut tmp.n1.n2_mut().n3 as *mut VARIANT_n3 as *mut u8, size);
+    tmp.n1.n2_mut().vt = vt as u16;
+    let src = &tmp as *const VARIANT;
+    let value = match vt {
+        VT_I1 => VariantValue::I8(i8::read_raw(src)?),
+        VT_I2 => VariantValue::I16(i16::read_raw(src)?),
+        VT_I4 => VariantValue::I32(i32::read_raw(src)?),
+        VT_I8 => VariantValue::I64(i64::read_raw(src)?),
+        VT_UI1 => VariantValue::U8(u8::read_raw(src)?),
+        VT_UI2 => VariantValue::U16(u16::read_raw(src)?),
+        VT_UI4 => VariantValue::U32(u32::read_raw(src)?),
+        VT_UI8 => VariantValue::U64(u64::read_raw(src)?),
+        VT_INT => VariantValue::Int(Int::read_raw(src)?),
+        VT_UINT => VariantValue::UInt(UInt::read_raw(src)?),
+        VT_R4 => VariantValue::F32(f32::read_raw(src)?),
+        VT_R8 => VariantValue::F64(f64::read_raw(src)?),
+        VT_BOOL => VariantValue::Bool(bool::read_raw(src)?),
+        VT_ERROR => VariantValue::SCode(SCode::read_raw(src)?),
+        VT_CY => VariantValue::Currency(Currency::read_raw(src)?),
+        VT_DATE => VariantValue::Date(Date::read_raw(src)?),
+        VT_BSTR => VariantValue::String(U16String::read_raw(src)?),
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+/// Reads every element of `psa`, `None` if `vt` has no arm. The array stays owned by the caller.
+unsafe fn read_array(vt: u32, psa: *mut SAFEARRAY) -> Result<Option<ValueArray>, FromVariantError> {
+    if psa.is_null() {
+        return Err(FromVariantError::ArrayPtrNull);
+    }
+    let size = match slot_size(vt) {
+        Some(size) => size,
+        None => return Ok(None),
+    };
+    let _depth = DecodeLimits::enter_array()?;
+    let bounds = safearray_shape(psa)?;
+    let len = bounds.iter().fold(1usize, |n, &(_, count)| n.saturating_mul(count as usize));
+    DecodeLimits::check_elements(len)?;
+    check_elem_size(psa, size)?;
+
+    let hr = SafeArrayLock(psa);
+    if hr != 0 {
+        return Err(FromVariantError::from(FromSafeArrayError::AccessDataFailed{hr: hr}));
+    }
+    let mut elements = Vec::with_capacity(len);
+    let mut ixs: Vec<i32> = bounds.iter().map(|&(l_bound, _)| l_bound).collect();
+    let mut ret = Ok(());
+    for _ in 0..len {
+        let mut pelem: *mut c_void = ptr::null_mut();
+        let hr = SafeArrayPtrOfIndex(psa, ixs.as_ptr(), &mut pelem);
+        if hr != 0 {
+            ret = Err(FromVariantError::from(FromSafeArrayError::AccessDataFailed{hr: hr}));
+            break;
+        }
+        match read_slot(vt, pelem) {
+            Ok(Some(value)) => elements.push(value),
+            Ok(None) => unreachable!("slot_size and read_slot disagree"),
+            Err(fve) => {
+                ret = Err(fve);
+                break;
+            }
+        }
+        next_index(&mut ixs, &bounds);
+    }
+    SafeArrayUnlock(psa);
+    ret?;
+    Ok(Some(ValueArray { vartype: vt, bounds: bounds, elements: elements }))
+}
+
+/// Steps `ixs` to the next element, the last dimension fastest
+fn next_index(ixs: &mut [i32], bounds: &[(i32, u32)]) {
+    for dim in (0..ixs.len()).rev() {
+        let (l_bound, count) = bounds[dim];
+        if ((ixs[dim] - l_bound + 1) as u32) < count {
+            ixs[dim] += 1;
+            return;
+        }
+        ixs[dim] = l_bound;
+    }
+}
+
+/// Converts `elem` and copies it into `psa` at `ixs`
+fn put_element(psa: *mut SAFEARRAY, vt: u32, ixs: &[i32], elem: VariantValue) -> Result<(), IntoSafeArrElemError> {
+    let pvar = elem.into_variant().map_err(IntoSafeArrElemError::from)?;
+    let _free = OwnedPtr::<VARIANT, FreeVariant>::new(pvar);
+    let found = unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32;
+    if vt != VT_VARIANT && found != vt {
+        return Err(IntoSafeArrElemError::VarTypeDoesNotMatch{expected: vt, found: found});
+    }
+    //SafeArrayPutElement copies: the VARIANT itself, the BSTR or interface pointer, or the value
+    let pv = unsafe {
+        match vt {
+            VT_VARIANT | VT_DECIMAL => pvar.as_ptr() as *mut c_void,
+            VT_BSTR => *(*pvar.as_ptr()).n1.n2().n3.bstrVal() as *mut c_void,
+            VT_UNKNOWN | VT_DISPATCH => *(*pvar.as_ptr()).n1.n2().n3.punkVal() as *mut c_void,
+            _ => &mut (*pvar.as_ptr()).n1.n2_mut().n3 as *mut VARIANT_n3 as *mut c_void,
+        }
+    };
+    let hr = unsafe { SafeArrayPutElement(psa, ixs.as_ptr(), pv) };
+    if hr != 0 {
+        return Err(IntoSafeArrElemError::PutElementFailed{hr: hr});
+    }
+    Ok(())
+}
+
+/// Boxed `VariantCopy` of `src`
+unsafe fn copy_variant(src: *const VARIANT) -> Result<OwnedPtr<VARIANT, FreeVariant>, FromVariantError> {
+    let mut var = Box::new(mem::zeroed::<VARIANT>());
+    let hr = VariantCopy(&mut *var, src as *mut VARIANT);
+    if hr != 0 {
+        return Err(FromVariantError::VariantCopyFailed{hr: hr});
+    }
+    leak_tracking::contents_adopted(&*var);
+    ConversionStats::record_variant();
+    Ok(OwnedPtr::new(Ptr::with_checked(Box::into_raw(var)).unwrap()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rust_decimal::Decimal;
+    use super::super::byref::ByRefExt;
+    use super::super::dtor::PtrDestructor;
+
+    //from_variant leaves the box VT_EMPTY, free it too so the scope balances
+    fn roundtrip(val: VariantValue) -> VariantValue {
+        let pvar = val.into_variant().unwrap();
+        let back = VariantValue::from_variant(pvar).unwrap();
+        FreeVariant::destroy(pvar);
+        back
+    }
+
+    #[test]
+    fn test_scalars() {
+        leak_tracking::assert_balanced(|| {
+            let w = U16String::from_vec(vec![0x61u16, 0xD800, 0]);
+            match roundtrip(VariantValue::String(w.clone())) {
+                VariantValue::String(s) => assert_eq!(s, w),
+                other => panic!("expected a string, got {:?}", other),
+            }
+            match roundtrip(VariantValue::Null) {
+                VariantValue::Null => {},
+                other => panic!("expected null, got {:?}", other),
+            }
+            let dec = DecWrapper::new(Decimal::new(1234, 2));
+            match roundtrip(VariantValue::Decimal(dec)) {
+                VariantValue::Decimal(found) => assert_eq!(found, dec),
+                other => panic!("expected a decimal, got {:?}", other),
+            }
+        });
+    }
+
+    #[test]
+    fn test_arrays() {
+        leak_tracking::assert_balanced(|| {
+            let grid = ValueArray {
+                vartype: VT_I4,
+                bounds: vec![(1, 2), (0, 3)],
+                elements: (0..6).map(VariantValue::I32).collect(),
+            };
+            let nested = ValueArray::vector(VT_VARIANT, vec![
+                VariantValue::from(Variants::from("x")),
+                VariantValue::Array(grid),
+            ]);
+            let outer = match roundtrip(VariantValue::Array(nested)) {
+                VariantValue::Array(arr) => arr,
+                other => panic!("expected an array, got {:?}", other),
+            };
+            assert_eq!((outer.vartype, outer.bounds.clone()), (VT_VARIANT, vec![(0, 2)]));
+            let mut elements = outer.elements.into_iter();
+            match Variants::try_from(elements.next().unwrap()) {
+                Ok(Variants::String(s)) => assert_eq!(s, "x"),
+                other => panic!("expected a string, got {:?}", other),
+            }
+            match elements.next().unwrap() {
+                VariantValue::Array(grid) => {
+                    assert_eq!(grid.bounds, vec![(1, 2), (0, 3)]);
+                    let found: Vec<i32> = grid.elements.into_iter().map(|e| match e {
+                        VariantValue::I32(val) => val,
+                        other => panic!("expected an i32, got {:?}", other),
+                    }).collect();
+                    assert_eq!(found, vec![0, 1, 2, 3, 4, 5]);
+                },
+                other => panic!("expected an array, got {:?}", other),
+            }
+        });
+    }
+
+    #[test]
+    fn test_shape() {
+        use super::super::array::get_bounds;
+        use super::super::table::{TableLayout, VariantTable};
+        let rows = ValueArray {
+            vartype: VT_VARIANT,
+            bounds: vec![(0, 3), (0, 2)],
+            elements: (0..6i32).map(|n| VariantValue::from(Variants::from(n))).collect(),
+        };
+        let pvar = VariantValue::Array(rows).into_variant().unwrap();
+        let psa = unsafe { *(*pvar.as_ptr()).n1.n2().n3.parray() };
+        assert_eq!(get_bounds(psa, 1).unwrap(), (0, 2));
+        assert_eq!(get_bounds(psa, 2).unwrap(), (0, 1));
+        // the same rows and columns VariantTable and map_from_safearray read
+        let table = VariantTable::from_variant(pvar, TableLayout::RowsOnly).unwrap();
+        assert_eq!(table.get(1, "Column1"), Some(&Variants::from(2i32)));
+    }
+
+    #[test]
+    fn test_count_mismatch() {
+        let arr = ValueArray { vartype: VT_I4, bounds: vec![(0, 3)], elements: vec![VariantValue::I32(1)] };
+        match VariantValue::Array(arr).into_variant() {
+            Err(IntoVariantError::SafeArrConvFailed(_)) => {},
+            _ => panic!("expected an element count error"),
+        }
+    }
+
+    #[test]
+    fn test_accessors() {
+        let byref = VariantValue::ByRef(Box::new(VariantValue::I32(42)));
+        assert_eq!(byref.vartype(), VT_BYREF | VT_I4);
+        assert!(byref.is_numeric() && !byref.is_string() && !byref.is_object());
+        let arr = VariantValue::Array(ValueArray::vector(VT_BSTR, vec![VariantValue::String(U16String::from_str("a"))]));
+        assert_eq!(arr.vartype(), VT_ARRAY | VT_BSTR);
+        assert!(!arr.is_numeric() && !arr.is_string());
+        let s = VariantValue::ByRef(Box::new(VariantValue::String(U16String::from_str("s"))));
+        assert!(s.is_string() && !s.is_numeric());
+        assert!(VariantValue::Dispatch(None).is_object() && VariantValue::Unknown(None).is_object());
+        assert!(!VariantValue::Bool(true).is_numeric() && !VariantValue::Null.is_object());
+        assert_eq!(VariantValue::Decimal(DecWrapper::new(Decimal::new(1, 0))).vartype(), VT_DECIMAL);
+    }
+
+    #[test]
+    fn test_byref() {
+        let mut n = 42i32;
+        let mut byref = n.byref_variant().unwrap();
+        match unsafe { VariantValue::read(byref.as_ptr()) }.unwrap() {
+            VariantValue::ByRef(inner) => match *inner {
+                VariantValue::I32(42) => {},
+                other => panic!("expected 42, got {:?}", other),
+            },
+            other => panic!("expected a byref, got {:?}", other),
+        }
+    }
+}
@@ -1,11 +1,13 @@
 //! Variant conversions
 //! 
-//! This module contains the trait [`VariantExt`] and the types [`Variant`], [`VtEmpty`], [`VtNull`].
+//! This module contains the trait [`VariantExt`] and the types [`Variant`], [`Variants`], [`RawByRef`], [`VtEmpty`], [`VtNull`].
 //! 
 //! It implements [`VariantExt`] for many built in types to enable conversions to VARIANT.  
 //! 
 //! [`VariantExt`]: trait.VariantExt.html
 //! [`Variant`]: struct.Variant.html
+//! [`Variants`]: enum.Variants.html
+//! [`RawByRef`]: struct.RawByRef.html
 //! [`VtEmpty`]: struct.VtEmpty.html
 //! [`VtNull`]: struct.VtNull.html
 
@@ -130,22 +132,34 @@
 *  VT_BYREF            [V]           void* for local use
 *  VT_BSTR_BLOB                      Reserved for system use
 */
-use std::marker::PhantomData;
+use std::convert::TryFrom;
+use std::collections::VecDeque;
 use std::mem;
-use std::ptr::{NonNull, null_mut};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ptr::{self, NonNull, null_mut};
+use std::slice;
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 
 use rust_decimal::Decimal;
 
+#[cfg(feature = "small_strings")]
+use smallstr::SmallString;
+#[cfg(feature = "smallvec")]
+use smallvec::{Array, SmallVec};
+
 use widestring::U16String;
 
 use winapi::ctypes::c_void;
 use winapi::shared::wtypes::{
-    CY, DATE, DECIMAL,
+    CLIPDATA, CY, DATE, DECIMAL, DECIMAL_NEG,
     VARIANT_BOOL,
     VT_ARRAY, 
     VT_BSTR, 
     VT_BOOL,
     VT_BYREF, 
+    VT_CF, 
     VT_CY,
     VT_DATE,
     VT_DECIMAL, 
@@ -170,15 +184,26 @@ use winapi::shared::wtypes::{
     VT_VARIANT, 
 };
 use winapi::shared::wtypesbase::SCODE;
+use winapi::um::combaseapi::{CoTaskMemAlloc, CoTaskMemFree};
 use winapi::um::oaidl::{IDispatch,  __tagVARIANT, SAFEARRAY, VARIANT, VARIANT_n3, VARIANT_n1};
-use winapi::um::oleauto::VariantClear;
+use winapi::um::oleauto::{SysAllocStringLen, VariantClear, VariantCopy};
+#[cfg(feature = "small_strings")]
+use winapi::um::oleauto::SysStringLen;
 use winapi::um::unknwnbase::IUnknown;
 
 use super::array::{SafeArrayElement, SafeArrayExt};
 use super::bstr::BStringExt;
-use super::errors::{IntoVariantError, FromVariantError};
+use super::dtor::{ClearVariant, ComDestructor, OwnedPtr, PtrDestructor};
+use super::errors::{BStringError, IntoVariantError, FromVariantError, ValueError};
+use super::leak_tracking;
+use super::limits::DecodeLimits;
+use super::options::{ConvertOptions, NonePolicy};
 use super::ptr::Ptr;
-use super::types::{Date, DecWrapper, Currency, Int, SCode, UInt, VariantBool };
+use super::stats::ConversionStats;
+#[cfg(feature = "chrono")]
+use super::types::{date_from_naive, naive_from_date};
+use super::types::{ClipData, Date, DecWrapper, Currency, Guid, Int, SCode, UInt, VariantBool, CY_SCALE};
+use super::value::variants_from_value;
 
 const VT_PUI1:      u32 = VT_BYREF | VT_UI1;
 const VT_PI2:       u32 = VT_BYREF | VT_I2;
@@ -200,6 +225,7 @@ const VT_PUI2:      u32 = VT_BYREF | VT_UI2;
 const VT_PUI4:      u32 = VT_BYREF | VT_UI4;
 const VT_PINT:      u32 = VT_BYREF | VT_INT;
 const VT_PUINT:     u32 = VT_BYREF | VT_UINT;
+const VT_ARRAY_VARIANT: u32 = VT_ARRAY | VT_VARIANT;
 
 /// Trait implemented to convert the type into a VARIANT
 /// Do not implement this yourself without care. 
@@ -212,6 +238,64 @@ pub trait VariantExt: Sized { //Would like Clone, but *mut IDispatch and *mut IU
 
     /// Convert a value of type T into a Ptr<VARIANT>
     fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError>;
+
+    /// Like `from_variant`, with `opts` in force for the conversion
+    fn from_variant_with(var: Ptr<VARIANT>, opts: &ConvertOptions) -> Result<Self, FromVariantError> {
+        opts.scoped(|| Self::from_variant(var))
+    }
+
+    /// Like `into_variant`, with `opts` in force for the conversion
+    fn into_variant_with(self, opts: &ConvertOptions) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        opts.scoped(|| self.into_variant())
+    }
+
+    /// Writes `self` into caller-owned storage. See [`raw::write_variant`](raw/fn.write_variant.html).
+    /// 
+    /// The default moves the contents out of the box `into_variant` returns; 
+    /// the built-in impls write in place without allocating a box.
+    unsafe fn write_raw(self, out: *mut VARIANT) -> Result<(), IntoVariantError> {
+        let pvar = self.into_variant()?;
+        leak_tracking::variant_freed(pvar.as_ptr());
+        ptr::write(out, *Box::from_raw(pvar.as_ptr()));
+        Ok(())
+    }
+
+    /// Reads a value out of caller-owned storage. See [`raw::read_variant`](raw/fn.read_variant.html).
+    /// 
+    /// The default decodes a `VariantCopy` of `src`; the built-in impls read `src` in place, 
+    /// except for arrays. Interface pointers come back with a reference of their own.
+    unsafe fn read_raw(src: *const VARIANT) -> Result<Self, FromVariantError> {
+        read_raw_copy(src)
+    }
+}
+
+/// Decodes a deep copy of `src`, so `from_variant` can consume or clear it without touching `src`
+pub(crate) unsafe fn read_raw_copy<T: VariantExt>(src: *const VARIANT) -> Result<T, FromVariantError> {
+    let mut tmp: VARIANT = mem::zeroed();
+    let hr = VariantCopy(&mut tmp, src as *mut VARIANT);
+    if hr != 0 {
+        return Err(FromVariantError::VariantCopyFailed{hr: hr});
+    }
+    leak_tracking::contents_adopted(&tmp);
+    let vt = tmp.n1.n2().vt as u32;
+    let ret = T::from_variant(Ptr::with_checked(&mut tmp as *mut VARIANT).unwrap());
+    //decoded arrays destroy the copy themselves, anything else the copy still owns
+    if ret.is_ok() && vt & VT_ARRAY == 0 {
+        leak_tracking::variant_cleared(&tmp);
+        VariantClear(&mut tmp);
+    }
+    ret
+}
+
+/// `read_raw` leaves `src` holding its reference, so an interface read out of it takes one of its own
+unsafe fn add_interface_ref(src: *const VARIANT) {
+    let n2 = (*src).n1.n2();
+    if n2.vt as u32 == VT_UNKNOWN || n2.vt as u32 == VT_DISPATCH {
+        let punk = *n2.n3.punkVal();
+        if !punk.is_null() {
+            (*punk).AddRef();
+        }
+    }
 }
 
 /// Helper struct to wrap a VARIANT compatible type into a VT_VARIANT marked VARIANT
@@ -265,6 +349,7 @@ impl<T: VariantExt> Variant<T> {
             *n_ptr = tv;
         };
         let var = Box::new(VARIANT{ n1: n1 });
+        ConversionStats::record_variant();
         Ok(Ptr::with_checked(Box::into_raw(var)).unwrap())
     }
 
@@ -303,31 +388,46 @@ impl<T: VariantExt> AsMut<T> for Variant<T> {
     }
 }
 
+/// Clears a VARIANT on early return with `ClearVariant`. The memory belongs to the 
+/// caller - `from_variant` never frees what it was handed.
 struct VariantDestructor {
     inner: *mut VARIANT, 
-    _marker: PhantomData<VARIANT>
 }
 
 impl VariantDestructor {
     fn new(p: *mut VARIANT) -> VariantDestructor {
         VariantDestructor {
             inner: p, 
-            _marker: PhantomData
         }
     }
 }
 
 impl Drop for VariantDestructor {
     fn drop(&mut self) {
-        if self.inner.is_null() {
-            return;
+        if let Some(p) = Ptr::with_checked(self.inner) {
+            ClearVariant::destroy(p);
         }
-        unsafe { VariantClear(self.inner)};
-        unsafe { let _dtor = *self.inner;}
         self.inner = null_mut();
     }
 }
 
+/// Allocates a `VARIANT` of vartype `vt` holding `n3`
+pub(crate) fn new_variant(vt: u32, n3: VARIANT_n3) -> Ptr<VARIANT> {
+    let mut n1: VARIANT_n1 = unsafe {mem::zeroed()};
+    let tv = __tagVARIANT { vt: vt as u16, 
+                    wReserved1: 0, 
+                    wReserved2: 0, 
+                    wReserved3: 0, 
+                    n3: n3};
+    unsafe {
+        let n_ptr = n1.n2_mut();
+        *n_ptr = tv;
+    };
+    let var = Box::new(VARIANT{ n1: n1 });
+    ConversionStats::record_variant();
+    Ptr::with_checked(Box::into_raw(var)).unwrap()
+}
+
 macro_rules! variant_impl {
     (
         impl $(<$tn:ident : $tc:ident>)* VariantExt for $t:ty {
@@ -347,31 +447,86 @@ macro_rules! variant_impl {
                 let mut n1 = unsafe {(*var).n1};
                 let vt = unsafe{n1.n2()}.vt;
                 if vt as u32 != Self::VARTYPE {
+                    //ConvertOptions may allow widening or zeroing VT_EMPTY/VT_NULL
+                    if let Some(mut coerced) = ConvertOptions::coerce(var, vt as u32, Self::VARTYPE)? {
+                        #[allow(unused_mut)]
+                        let mut n1 = coerced.n1;
+                        let ret = variant_impl!(@read $n_name, $un_n, $from, n1);
+                        unsafe { VariantClear(&mut coerced) };
+                        var_d.inner = null_mut();
+                        return ret;
+                    }
                     return Err(FromVariantError::VarTypeDoesNotMatch{expected: Self::VARTYPE, found: vt as u32})
                 }
                 let ret = variant_impl!(@read $n_name, $un_n, $from, n1);
+                if Self::VARTYPE & VT_ARRAY != 0 {
+                    //from_safearray destroyed the array either way, don't leave it dangling
+                    unsafe { (*var).n1.n2_mut().vt = VT_EMPTY as u16 };
+                }
 
                 var_d.inner = null_mut();
                 ret
             }
 
             fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
-                #[allow(unused_mut)]
-                let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
-                let mut n1: VARIANT_n1 = unsafe {mem::zeroed()};
-                variant_impl!(@write $n_name, $un_n_mut, $into, n3, n1, self);
-                let tv = __tagVARIANT { vt: <Self as VariantExt>::VARTYPE as u16, 
-                                wReserved1: 0, 
-                                wReserved2: 0, 
-                                wReserved3: 0, 
-                                n3: n3};
-                unsafe {
-                    let n_ptr = n1.n2_mut();
-                    *n_ptr = tv;
-                };
+                let n1 = variant_impl!(@build $n_name, $un_n_mut, $into, self);
                 let var = Box::new(VARIANT{ n1: n1 });
+                ConversionStats::record_variant();
                 Ok(Ptr::with_checked(Box::into_raw(var)).unwrap())
             }
+
+            unsafe fn write_raw(self, out: *mut VARIANT) -> Result<(), IntoVariantError> {
+                let n1 = variant_impl!(@build $n_name, $un_n_mut, $into, self);
+                ptr::write(out, VARIANT{ n1: n1 });
+                //the contents now belong to the caller's storage
+                leak_tracking::variant_cleared(out);
+                Ok(())
+            }
+
+            unsafe fn read_raw(src: *const VARIANT) -> Result<Self, FromVariantError> {
+                if Self::VARTYPE & VT_ARRAY != 0 {
+                    return read_raw_copy(src);
+                }
+                #[allow(unused_mut)]
+                let mut n1 = (*src).n1;
+                let vt = n1.n2().vt;
+                if vt as u32 != Self::VARTYPE {
+                    if let Some(mut coerced) = ConvertOptions::coerce(src, vt as u32, Self::VARTYPE)? {
+                        #[allow(unused_mut)]
+                        let mut n1 = coerced.n1;
+                        let ret = variant_impl!(@read $n_name, $un_n, $from, n1);
+                        if ret.is_ok() {
+                            add_interface_ref(&coerced);
+                        }
+                        VariantClear(&mut coerced);
+                        return ret;
+                    }
+                    return Err(FromVariantError::VarTypeDoesNotMatch{expected: Self::VARTYPE, found: vt as u32})
+                }
+                let ret = variant_impl!(@read $n_name, $un_n, $from, n1);
+                if ret.is_ok() {
+                    add_interface_ref(src);
+                }
+                ret
+            }
+        }
+    };
+    (@build $n_name:ident, $un_n_mut:ident, $into:expr, $slf:expr) => {
+        {
+            #[allow(unused_mut)]
+            let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
+            let mut n1: VARIANT_n1 = unsafe {mem::zeroed()};
+            variant_impl!(@write $n_name, $un_n_mut, $into, n3, n1, $slf);
+            let tv = __tagVARIANT { vt: <Self as VariantExt>::VARTYPE as u16, 
+                            wReserved1: 0, 
+                            wReserved2: 0, 
+                            wReserved3: 0, 
+                            n3: n3};
+            unsafe {
+                let n_ptr = n1.n2_mut();
+                *n_ptr = tv;
+            };
+            n1
         }
     };
     (@read n3, $un_n:ident, $from:expr, $n1:ident) => {
@@ -443,16 +598,28 @@ variant_impl!{
     impl VariantExt for f32 {
         VARTYPE = VT_R4;
         n3, fltVal, fltVal_mut
-        from => {|n_ptr: &f32| Ok(*n_ptr)}
-        into => {|slf: f32| -> Result<_, IntoVariantError> {Ok(slf)}}
+        from => {|n_ptr: &f32| {
+            ConvertOptions::check_float(f64::from(*n_ptr)).map_err(|v| FromVariantError::NonFiniteFloat{value: v})?;
+            Ok(*n_ptr)
+        }}
+        into => {|slf: f32| -> Result<_, IntoVariantError> {
+            ConvertOptions::check_float(f64::from(slf)).map_err(|v| IntoVariantError::NonFiniteFloat{value: v})?;
+            Ok(slf)
+        }}
     }
 }
 variant_impl!{
     impl VariantExt for f64 {
         VARTYPE = VT_R8;
         n3, dblVal, dblVal_mut
-        from => {|n_ptr: &f64| Ok(*n_ptr)}
-        into => {|slf: f64| -> Result<_, IntoVariantError> {Ok(slf)}}
+        from => {|n_ptr: &f64| {
+            ConvertOptions::check_float(*n_ptr).map_err(|v| FromVariantError::NonFiniteFloat{value: v})?;
+            Ok(*n_ptr)
+        }}
+        into => {|slf: f64| -> Result<_, IntoVariantError> {
+            ConvertOptions::check_float(slf).map_err(|v| IntoVariantError::NonFiniteFloat{value: v})?;
+            Ok(slf)
+        }}
     }
 }
 variant_impl!{
@@ -491,11 +658,37 @@ variant_impl!{
         into => {|slf: Date| -> Result<_, IntoVariantError> {Ok(DATE::from(slf))}}
     }
 }
+// chrono date-times travel as VT_DATE, to the nearest millisecond
+#[cfg(feature = "chrono")]
+variant_impl!{
+    impl VariantExt for NaiveDateTime {
+        VARTYPE = VT_DATE;
+        n3, date, date_mut
+        from => {|n_ptr: &DATE| naive_from_date(Date::from(*n_ptr)).map_err(|_| FromVariantError::DateOutOfRange{value: *n_ptr})}
+        into => {|slf: NaiveDateTime| -> Result<_, IntoVariantError> {
+            date_from_naive(slf).map(DATE::from).map_err(|_| IntoVariantError::DateOutOfRange)
+        }}
+    }
+}
+#[cfg(feature = "chrono")]
+variant_impl!{
+    impl VariantExt for DateTime<Utc> {
+        VARTYPE = VT_DATE;
+        n3, date, date_mut
+        from => {|n_ptr: &DATE| naive_from_date(Date::from(*n_ptr))
+            .map(|dt| Utc.from_utc_datetime(&dt))
+            .map_err(|_| FromVariantError::DateOutOfRange{value: *n_ptr})}
+        into => {|slf: DateTime<Utc>| -> Result<_, IntoVariantError> {
+            date_from_naive(slf.naive_utc()).map(DATE::from).map_err(|_| IntoVariantError::DateOutOfRange)
+        }}
+    }
+}
 variant_impl!{
     impl VariantExt for String {
         VARTYPE = VT_BSTR;
         n3, bstrVal, bstrVal_mut
         from => {|n_ptr: &*mut u16| {
+            DecodeLimits::check_bstr(*n_ptr)?;
             let bstr = U16String::from_bstr(*n_ptr);
             Ok(bstr.to_string_lossy())
         }}
@@ -508,11 +701,56 @@ variant_impl!{
         }}
     }
 }
+// Lossless BSTR conversion - unlike `String`, unpaired surrogates survive. 
+// Both directions use the BSTR length prefix (`SysStringLen`/`SysAllocStringLen`), 
+// so interior NULs are kept and the length always round-trips exactly.
+variant_impl!{
+    impl VariantExt for U16String {
+        VARTYPE = VT_BSTR;
+        n3, bstrVal, bstrVal_mut
+        from => {|n_ptr: &*mut u16| {
+            DecodeLimits::check_bstr(*n_ptr)?;
+            Ok(U16String::from_bstr(*n_ptr))
+        }}
+        into => {|mut slf: U16String|{
+            match slf.allocate_bstr(){
+                Ok(ptr) => Ok(ptr.as_ptr()), 
+                Err(bse) => Err(IntoVariantError::from(bse))
+            }
+        }}
+    }
+}
+// Network addresses travel as their canonical string form, as WMI networking 
+// classes expect, and are parsed back on decode. GUIDs go the same way, in the 
+// registry format scripting clients use.
+macro_rules! addr_variant_impl {
+    ($($t:ty),*) => {$(
+        variant_impl!{
+            impl VariantExt for $t {
+                VARTYPE = VT_BSTR;
+                n3, bstrVal, bstrVal_mut
+                from => {|n_ptr: &*mut u16| {
+                    DecodeLimits::check_bstr(*n_ptr)?;
+                    let s = U16String::from_bstr(*n_ptr).to_string_lossy();
+                    s.trim().parse::<$t>().map_err(|_| FromVariantError::ParseFailed{input: s, target: stringify!($t)})
+                }}
+                into => {|slf: $t|{
+                    let mut bstr = U16String::from_str(&slf.to_string());
+                    match bstr.allocate_bstr(){
+                        Ok(ptr) => Ok(ptr.as_ptr()), 
+                        Err(bse) => Err(IntoVariantError::from(bse))
+                    }
+                }}
+            }
+        }
+    )*};
+}
+addr_variant_impl!(IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, Guid);
 variant_impl!{
     impl VariantExt for Ptr<IUnknown> {
         VARTYPE = VT_UNKNOWN;
         n3, punkVal, punkVal_mut
-        from => {|n_ptr: &* mut IUnknown| Ok(Ptr::with_checked(*n_ptr).unwrap())}
+        from => {|n_ptr: &*mut IUnknown| Ptr::with_checked(*n_ptr).ok_or(FromVariantError::UnknownPtrNull)}
         into => {|slf: Ptr<IUnknown>| -> Result<_, IntoVariantError> {Ok(slf.as_ptr())}}
     }
 }
@@ -520,10 +758,52 @@ variant_impl!{
     impl VariantExt for Ptr<IDispatch> {
         VARTYPE = VT_DISPATCH;
         n3, pdispVal, pdispVal_mut
-        from => {|n_ptr: &*mut IDispatch| Ok(Ptr::with_checked(*n_ptr).unwrap())}
+        from => {|n_ptr: &*mut IDispatch| Ptr::with_checked(*n_ptr).ok_or(FromVariantError::DispatchPtrNull)}
         into => {|slf: Ptr<IDispatch>| -> Result<_, IntoVariantError> { Ok(slf.as_ptr()) }}
     }
 }
+// A null interface pointer is a legal VARIANT - VB's `Nothing` - and maps to `None`
+variant_impl!{
+    impl VariantExt for Option<Ptr<IUnknown>> {
+        VARTYPE = VT_UNKNOWN;
+        n3, punkVal, punkVal_mut
+        from => {|n_ptr: &*mut IUnknown| Ok(Ptr::with_checked(*n_ptr))}
+        into => {|slf: Option<Ptr<IUnknown>>| -> Result<_, IntoVariantError> {
+            Ok(slf.map_or(null_mut(), |p| p.as_ptr()))
+        }}
+    }
+}
+variant_impl!{
+    impl VariantExt for Option<Ptr<IDispatch>> {
+        VARTYPE = VT_DISPATCH;
+        n3, pdispVal, pdispVal_mut
+        from => {|n_ptr: &*mut IDispatch| Ok(Ptr::with_checked(*n_ptr))}
+        into => {|slf: Option<Ptr<IDispatch>>| -> Result<_, IntoVariantError> {
+            Ok(slf.map_or(null_mut(), |p| p.as_ptr()))
+        }}
+    }
+}
+// The VARIANT's reference passes to the `OwnedPtr`, which releases it when dropped
+variant_impl!{
+    impl VariantExt for Option<OwnedPtr<IUnknown, ComDestructor>> {
+        VARTYPE = VT_UNKNOWN;
+        n3, punkVal, punkVal_mut
+        from => {|n_ptr: &*mut IUnknown| Ok(Ptr::with_checked(*n_ptr).map(OwnedPtr::new))}
+        into => {|slf: Option<OwnedPtr<IUnknown, ComDestructor>>| -> Result<_, IntoVariantError> {
+            Ok(slf.map_or(null_mut(), OwnedPtr::into_raw))
+        }}
+    }
+}
+variant_impl!{
+    impl VariantExt for Option<OwnedPtr<IDispatch, ComDestructor>> {
+        VARTYPE = VT_DISPATCH;
+        n3, pdispVal, pdispVal_mut
+        from => {|n_ptr: &*mut IDispatch| Ok(Ptr::with_checked(*n_ptr).map(OwnedPtr::new))}
+        into => {|slf: Option<OwnedPtr<IDispatch, ComDestructor>>| -> Result<_, IntoVariantError> {
+            Ok(slf.map_or(null_mut(), OwnedPtr::into_raw))
+        }}
+    }
+}
 variant_impl!{
     impl VariantExt for Box<u8> {
         VARTYPE = VT_PUI1;
@@ -568,8 +848,12 @@ variant_impl!{
     impl VariantExt for Box<f32> {
         VARTYPE = VT_PR4;
         n3, pfltVal, pfltVal_mut
-        from => {|n_ptr: &* mut f32| Ok(Box::new(**n_ptr))}
+        from => {|n_ptr: &* mut f32| {
+            ConvertOptions::check_float(f64::from(**n_ptr)).map_err(|v| FromVariantError::NonFiniteFloat{value: v})?;
+            Ok(Box::new(**n_ptr))
+        }}
         into => {|slf: Box<f32>| -> Result<_, IntoVariantError> {
+            ConvertOptions::check_float(f64::from(*slf)).map_err(|v| IntoVariantError::NonFiniteFloat{value: v})?;
             Ok(Box::into_raw(slf))
         }}
     }
@@ -578,8 +862,12 @@ variant_impl!{
     impl VariantExt for Box<f64> {
         VARTYPE = VT_PR8;
         n3, pdblVal, pdblVal_mut
-        from => {|n_ptr: &* mut f64| Ok(Box::new(**n_ptr))}
+        from => {|n_ptr: &* mut f64| {
+            ConvertOptions::check_float(**n_ptr).map_err(|v| FromVariantError::NonFiniteFloat{value: v})?;
+            Ok(Box::new(**n_ptr))
+        }}
         into => {|slf: Box<f64>| -> Result<_, IntoVariantError> {
+            ConvertOptions::check_float(*slf).map_err(|v| IntoVariantError::NonFiniteFloat{value: v})?;
             Ok(Box::into_raw(slf))
         }}
     }
@@ -638,6 +926,7 @@ variant_impl!{
         VARTYPE = VT_PBSTR;
         n3, pbstrVal, pbstrVal_mut
         from => {|n_ptr: &*mut *mut u16| {
+            DecodeLimits::check_bstr(**n_ptr)?;
             let bstr = U16String::from_bstr(**n_ptr);
             Ok(Box::new(bstr.to_string_lossy()))
         }}
@@ -705,7 +994,7 @@ variant_impl!{
 }
 variant_impl!{
     impl<T: SafeArrayElement> VariantExt for Vec<T>{
-        VARTYPE = VT_ARRAY;
+        VARTYPE = VT_ARRAY | T::SFTYPE;
         n3, parray, parray_mut
         from => {
             |n_ptr: &*mut SAFEARRAY| {
@@ -729,6 +1018,92 @@ variant_impl!{
         }
     }
 }
+// Other sequence containers share the `Vec<T>` path. Encoding drains the container 
+// straight into the SAFEARRAY; decoding reuses the decoded `Vec`'s buffer where the 
+// container allows it.
+macro_rules! seq_variant_impl {
+    ($(impl<$tn:ident $(, $an:ident)*> for $t:ty where [$($bound:tt)*] {
+        from => $from_vec:expr;
+        into => |$slf:ident| $iter:expr;
+    })*) => {$(
+        impl<$tn: SafeArrayElement $(, $an)*> VariantExt for $t where $($bound)* {
+            const VARTYPE: u32 = VT_ARRAY | $tn::SFTYPE;
+
+            fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+                Vec::<$tn>::from_variant(var).map($from_vec)
+            }
+
+            fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+                let $slf = self;
+                let psa = $iter.into_safearray()?;
+                let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
+                unsafe { *n3.parray_mut() = psa.as_ptr() };
+                Ok(new_variant(Self::VARTYPE, n3))
+            }
+        }
+    )*};
+}
+seq_variant_impl! {
+    impl<T> for VecDeque<T> where [] {
+        from => VecDeque::from;
+        into => |slf| slf.into_iter();
+    }
+    impl<T> for Box<[T]> where [] {
+        from => Vec::into_boxed_slice;
+        into => |slf| slf.into_vec().into_iter();
+    }
+}
+#[cfg(feature = "smallvec")]
+seq_variant_impl! {
+    impl<T, A> for SmallVec<A> where [A: Array<Item=T>] {
+        from => SmallVec::from_vec;
+        into => |slf| slf.into_iter();
+    }
+}
+// BSTRs that fit the inline buffer decode without touching the heap, 
+// transcoding straight from the BSTR's UTF-16 (unpaired surrogates become U+FFFD, as for `String`)
+#[cfg(feature = "small_strings")]
+impl<A: Array<Item=u8>> VariantExt for SmallString<A> {
+    const VARTYPE: u32 = VT_BSTR;
+
+    fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+        let var = var.as_ptr();
+        let mut var_d = VariantDestructor::new(var);
+        let n1 = unsafe {(*var).n1};
+        let vt = unsafe {n1.n2()}.vt as u32;
+        if vt != VT_BSTR {
+            return Err(FromVariantError::VarTypeDoesNotMatch{expected: VT_BSTR, found: vt});
+        }
+        let bstr = unsafe { *n1.n2().n3.bstrVal() };
+        DecodeLimits::check_bstr(bstr)?;
+        let units: &[u16] = if bstr.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(bstr, SysStringLen(bstr) as usize) }
+        };
+        let mut out = SmallString::new();
+        for c in char::decode_utf16(units.iter().cloned()) {
+            out.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+        }
+        var_d.inner = null_mut();
+        Ok(out)
+    }
+
+    fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        let len = self.encode_utf16().count();
+        let bstr = unsafe { SysAllocStringLen(null_mut(), len as u32) };
+        if bstr.is_null() {
+            return Err(IntoVariantError::from(BStringError::AllocateFailed{len: len}));
+        }
+        for (ix, unit) in self.encode_utf16().enumerate() {
+            unsafe { *bstr.add(ix) = unit };
+        }
+        ConversionStats::record_bstr(len);
+        let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
+        unsafe { *n3.bstrVal_mut() = bstr };
+        Ok(new_variant(VT_BSTR, n3))
+    }
+}
 variant_impl!{
     impl VariantExt for Ptr<c_void> {
         VARTYPE = VT_BYREF;
@@ -894,6 +1269,64 @@ variant_impl!{
     }
 }
 
+/// Largest magnitude the 96-bit mantissa of a DECIMAL holds
+const DECIMAL_MAX: u128 = (1 << 96) - 1;
+
+pub(crate) fn decimal_from_parts(magnitude: u128, negative: bool) -> Result<DECIMAL, IntoVariantError> {
+    if magnitude > DECIMAL_MAX {
+        return Err(IntoVariantError::DecimalOverflow{magnitude: magnitude});
+    }
+    Ok(DECIMAL {
+        wReserved: 0, 
+        scale: 0, 
+        sign: if negative {DECIMAL_NEG} else {0}, 
+        Hi32: (magnitude >> 64) as u32, 
+        Lo64: magnitude as u64
+    })
+}
+
+/// The whole-number magnitude and sign of a DECIMAL, `None` if it has a fraction
+pub(crate) fn decimal_to_parts(dec: &DECIMAL) -> Option<(u128, bool)> {
+    let mut magnitude = (u128::from(dec.Hi32) << 64) | u128::from(dec.Lo64);
+    for _ in 0..dec.scale {
+        if magnitude % 10 != 0 {
+            return None;
+        }
+        magnitude /= 10;
+    }
+    Some((magnitude, dec.sign == DECIMAL_NEG && magnitude != 0))
+}
+
+// 128-bit integers travel as VT_DECIMAL, which holds any magnitude below 2^96
+variant_impl!{
+    impl VariantExt for i128 {
+        VARTYPE = VT_DECIMAL;
+        n1, decVal, decVal_mut
+        from => {|n_ptr: &DECIMAL| match decimal_to_parts(n_ptr) {
+            //a magnitude below 2^96 always fits
+            Some((magnitude, negative)) => Ok(if negative {-(magnitude as i128)} else {magnitude as i128}),
+            None => Err(FromVariantError::DecimalOutOfRange{target: "i128"}),
+        }}
+        into => {|slf: i128| -> Result<_, IntoVariantError> {
+            let magnitude = if slf < 0 {(slf as u128).wrapping_neg()} else {slf as u128};
+            decimal_from_parts(magnitude, slf < 0)
+        }}
+    }
+}
+variant_impl!{
+    impl VariantExt for u128 {
+        VARTYPE = VT_DECIMAL;
+        n1, decVal, decVal_mut
+        from => {|n_ptr: &DECIMAL| match decimal_to_parts(n_ptr) {
+            Some((magnitude, false)) => Ok(magnitude),
+            _ => Err(FromVariantError::DecimalOutOfRange{target: "u128"}),
+        }}
+        into => {|slf: u128| -> Result<_, IntoVariantError> {
+            decimal_from_parts(slf, false)
+        }}
+    }
+}
+
 /// Helper type for VT_EMPTY variants
 #[derive(Clone, Copy, Debug)]
 pub struct VtEmpty{}
@@ -918,6 +1351,7 @@ impl VariantExt for VtEmpty {
             *n_ptr = tv;
         };
         let var = Box::new(VARIANT{ n1: n1 });
+        ConversionStats::record_variant();
         Ok(Ptr::with_checked(Box::into_raw(var)).unwrap())
     }
     fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
@@ -942,6 +1376,7 @@ impl VariantExt for VtNull {
             *n_ptr = tv;
         };
         let var = Box::new(VARIANT{ n1: n1 });
+        ConversionStats::record_variant();
         Ok(Ptr::with_checked(Box::into_raw(var)).unwrap())
     }
     fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
@@ -950,9 +1385,659 @@ impl VariantExt for VtNull {
     }
 }
 
+macro_rules! option_variant_impl {
+    ($($t:ty),*) => {$(
+        impl VariantExt for Option<$t> {
+            const VARTYPE: u32 = <$t as VariantExt>::VARTYPE;
+
+            fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+                let vt = unsafe {(*var.as_ptr()).n1.n2().vt} as u32;
+                match vt {
+                    VT_NULL | VT_EMPTY => {
+                        ClearVariant::destroy(var);
+                        Ok(None)
+                    },
+                    _ => <$t>::from_variant(var).map(Some),
+                }
+            }
+
+            fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+                match self {
+                    Some(val) => val.into_variant(),
+                    None => match ConvertOptions::current().none_policy {
+                        NonePolicy::Null => VtNull{}.into_variant(),
+                        NonePolicy::Empty => VtEmpty{}.into_variant(),
+                    },
+                }
+            }
+        }
+    )*};
+}
+// `None` encodes as `VT_NULL`, or `VT_EMPTY` under `NonePolicy::Empty`; both decode as `None`.
+// Optional interface pointers are not listed here - for those `None` is a null pointer.
+option_variant_impl!(i8, i16, i32, i64, u8, u16, u32, u64, f32, f64, bool, SCode, Currency, Date, Int, UInt,
+                     DecWrapper, Decimal, String, U16String, Guid);
+
+/// `Err` encodes as a `VT_ERROR` variant and `Ok` as the value itself - the way automation 
+/// servers report a failed cell or item, Excel's `#N/A` among them. A `VT_ERROR` variant 
+/// decodes as `Err`, anything else as `T`; so for `Result<SCode, SCode>` every read is an `Err`. 
+impl<T: VariantExt> VariantExt for Result<T, SCode> {
+    const VARTYPE: u32 = T::VARTYPE;
+
+    fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+        let vt = unsafe {(*var.as_ptr()).n1.n2().vt} as u32;
+        match vt {
+            VT_ERROR => SCode::from_variant(var).map(Err),
+            _ => T::from_variant(var).map(Ok),
+        }
+    }
+
+    fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        match self {
+            Ok(val) => val.into_variant(),
+            Err(sc) => sc.into_variant(),
+        }
+    }
+}
+
+/// Opaque `VT_BYREF` payload - the vartype and the pointer it carries. 
+/// 
+/// Byref variants whose inner type this crate doesn't know (including the 
+/// TYPEDESC-only `VT_VOID` and `VT_PTR`) can be captured with this and passed 
+/// through or handed back unchanged. The pointee is never read or freed. 
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RawByRef {
+    vt: u32, 
+    ptr: *mut c_void,
+}
+
+impl RawByRef {
+    /// Wraps `ptr` as a byref to `inner_vt`. `VT_BYREF` is added to `inner_vt` if it is missing.
+    pub fn new(inner_vt: u32, ptr: *mut c_void) -> RawByRef {
+        RawByRef {
+            vt: inner_vt | VT_BYREF, 
+            ptr: ptr,
+        }
+    }
+
+    /// The full vartype, including `VT_BYREF`
+    pub fn vartype(&self) -> u32 {
+        self.vt
+    }
+
+    /// The vartype of the pointee, without `VT_BYREF`
+    pub fn inner_vartype(&self) -> u32 {
+        self.vt & !VT_BYREF
+    }
+
+    /// The pointer carried by the variant
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr
+    }
+}
+
+impl VariantExt for RawByRef {
+    /// Any vartype with `VT_BYREF` set is accepted, this is only the flag itself
+    const VARTYPE: u32 = VT_BYREF;
+    fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
+        unsafe {
+            let n_ptr = n3.byref_mut();
+            *n_ptr = self.ptr;
+        }
+        Ok(new_variant(self.vt, n3))
+    }
+    fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+        let mut n1 = unsafe {(*var.as_ptr()).n1};
+        let vt = unsafe {n1.n2()}.vt as u32;
+        if vt & VT_BYREF == 0 {
+            return Err(FromVariantError::VarTypeDoesNotMatch{expected: VT_BYREF, found: vt});
+        }
+        let ptr = unsafe { *n1.n2_mut().n3.byref() };
+        if ptr.is_null() {
+            return Err(FromVariantError::CVoidPtrNull);
+        }
+        Ok(RawByRef{vt: vt, ptr: ptr})
+    }
+}
+
+// Only Windows clipboard formats are supported, the first four bytes of pClipData hold the CF_* value
+const CF_WINDOWS: i32 = -1;
+
+/// VT_CF is a PROPVARIANT type - the `CLIPDATA` pointer sits in the same slot as `byref`. 
+/// Both the `CLIPDATA` and its payload are allocated with `CoTaskMemAlloc`, as `PropVariantClear` expects. 
+impl VariantExt for ClipData {
+    const VARTYPE: u32 = VT_CF;
+    fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        let len = mem::size_of::<u32>() + self.bytes.len();
+        let pdata = unsafe { CoTaskMemAlloc(len) } as *mut u8;
+        if pdata.is_null() {
+            return Err(IntoVariantError::CoTaskMemAllocFailed{len: len});
+        }
+        let pcd = unsafe { CoTaskMemAlloc(mem::size_of::<CLIPDATA>()) } as *mut CLIPDATA;
+        if pcd.is_null() {
+            unsafe { CoTaskMemFree(pdata as *mut c_void) };
+            return Err(IntoVariantError::CoTaskMemAllocFailed{len: mem::size_of::<CLIPDATA>()});
+        }
+        let format = self.format.to_le_bytes();
+        unsafe {
+            ptr::copy_nonoverlapping(format.as_ptr(), pdata, format.len());
+            ptr::copy_nonoverlapping(self.bytes.as_ptr(), pdata.offset(format.len() as isize), self.bytes.len());
+            *pcd = CLIPDATA {
+                cbSize: (mem::size_of::<i32>() + len) as u32, 
+                ulClipFmt: CF_WINDOWS, 
+                pClipData: pdata,
+            };
+        }
+
+        let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
+        unsafe {
+            let n_ptr = n3.byref_mut();
+            *n_ptr = pcd as *mut c_void;
+        }
+        Ok(new_variant(Self::VARTYPE, n3))
+    }
+    fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+        let pvar = var.as_ptr();
+        let mut n1 = unsafe {(*pvar).n1};
+        let vt = unsafe {n1.n2()}.vt as u32;
+        if vt != Self::VARTYPE {
+            return Err(FromVariantError::VarTypeDoesNotMatch{expected: Self::VARTYPE, found: vt});
+        }
+        let pcd = unsafe { *n1.n2_mut().n3.byref() } as *mut CLIPDATA;
+        if pcd.is_null() {
+            return Err(FromVariantError::CVoidPtrNull);
+        }
+
+        let cd = unsafe { *pcd };
+        let len = (cd.cbSize as usize).saturating_sub(mem::size_of::<i32>());
+        let ret = if cd.ulClipFmt != CF_WINDOWS || len < mem::size_of::<u32>() || cd.pClipData.is_null() {
+            Err(FromVariantError::ClipFormatUnsupported{format: cd.ulClipFmt})
+        } else {
+            let data = unsafe { slice::from_raw_parts(cd.pClipData, len) };
+            let mut format = [0u8; 4];
+            format.copy_from_slice(&data[..4]);
+            Ok(ClipData::new(u32::from_le_bytes(format), data[4..].to_vec()))
+        };
+
+        //the payload is consumed either way, VariantClear doesn't know about VT_CF so free it here
+        unsafe {
+            CoTaskMemFree(cd.pClipData as *mut c_void);
+            CoTaskMemFree(pcd as *mut c_void);
+            (*pvar).n1.n2_mut().vt = VT_EMPTY as u16;
+        }
+        ret
+    }
+}
+
+/// Dynamically typed value for the scalar and interface VARIANT types. 
+/// 
+/// Use this when the vartype of an incoming `VARIANT` isn't known ahead of 
+/// time, or to build heterogeneous argument lists. 
+/// 
+/// The interface arms own one reference: cloning adds one with `AddRef` and dropping 
+/// releases it. Two interface arms are equal when they hold the same pointer. 
+/// 
+/// There are no `VT_BYREF` arms: decoding reads through the pointer into the arm for 
+/// the pointee, so out-parameters and by-reference property puts decode like by-value ones. 
+/// 
+/// A `VT_BSTR` decodes as `String` when it is valid UTF-16 and as `WideString` when it 
+/// isn't, so BSTRs holding unpaired surrogates or binary data round-trip unchanged. 
+#[derive(Clone, Debug, PartialEq)]
+pub enum Variants {
+    /// VT_EMPTY, e.g. an unset value or an omitted optional argument
+    Empty, 
+    /// VT_NULL, e.g. a database NULL
+    Null, 
+    /// VT_I8
+    I64(i64), 
+    /// VT_I4
+    I32(i32), 
+    /// VT_UI1
+    U8(u8), 
+    /// VT_I2
+    I16(i16), 
+    /// VT_R4
+    F32(f32), 
+    /// VT_R8
+    F64(f64), 
+    /// VT_BOOL
+    Bool(bool), 
+    /// VT_ERROR
+    SCode(SCode), 
+    /// VT_CY
+    Currency(Currency), 
+    /// VT_DATE
+    Date(Date), 
+    /// VT_BSTR
+    String(String), 
+    /// VT_BSTR that isn't valid UTF-16, kept as its raw code units
+    WideString(U16String), 
+    /// VT_I1
+    I8(i8), 
+    /// VT_UI2
+    U16(u16), 
+    /// VT_UI4
+    U32(u32), 
+    /// VT_UI8
+    U64(u64), 
+    /// VT_INT
+    Int(Int), 
+    /// VT_UINT
+    UInt(UInt),
+    /// VT_DECIMAL
+    Decimal(DecWrapper),
+    /// VT_UNKNOWN. `None` for a null pointer.
+    Unknown(Option<OwnedPtr<IUnknown, ComDestructor>>),
+    /// VT_DISPATCH. `None` for a null pointer, VB's `Nothing`.
+    Dispatch(Option<OwnedPtr<IDispatch, ComDestructor>>),
+    /// A 1-D array. Encodes as `VT_ARRAY | VT_VARIANT`; 1-D arrays of any element type 
+    /// with a `Variants` arm decode into it.
+    Array(Vec<Variants>),
+}
+
+// Encode-only impls for borrowed values: the VARIANT gets a copy and the value stays 
+// where it is, so wrappers held in structs need not be cloned or moved out. There is 
+// nothing to decode into, so `from_variant` frees the VARIANT and returns `BorrowedTarget`.
+macro_rules! variant_ref_impl {
+    ($t:ty, $vt:expr, |$slf:ident| $encode:expr) => {
+        impl<'r> VariantExt for &'r $t {
+            const VARTYPE: u32 = $vt;
+            fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+                let _var_d = VariantDestructor::new(var.as_ptr());
+                Err(FromVariantError::BorrowedTarget{vartype: $vt})
+            }
+
+            fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+                let $slf = self;
+                $encode
+            }
+        }
+    };
+}
+
+variant_ref_impl!(SCode, VT_ERROR, |slf| (*slf).into_variant());
+variant_ref_impl!(Currency, VT_CY, |slf| (*slf).into_variant());
+variant_ref_impl!(Date, VT_DATE, |slf| (*slf).into_variant());
+variant_ref_impl!(Int, VT_INT, |slf| (*slf).into_variant());
+variant_ref_impl!(UInt, VT_UINT, |slf| (*slf).into_variant());
+variant_ref_impl!(str, VT_BSTR, |slf| bstr_variant(&U16String::from_str(slf)));
+variant_ref_impl!(String, VT_BSTR, |slf| bstr_variant(&U16String::from_str(slf)));
+variant_ref_impl!(U16String, VT_BSTR, |slf| bstr_variant(slf));
+variant_ref_impl!(DecWrapper, VT_DECIMAL, |slf| Ok(decimal_variant(DECIMAL::from(slf))));
+variant_ref_impl!(Decimal, VT_DECIMAL, |slf| Ok(decimal_variant(DECIMAL::from(DecWrapper::new(*slf)))));
+
+fn bstr_variant(s: &U16String) -> Result<Ptr<VARIANT>, IntoVariantError> {
+    let bstr = unsafe { SysAllocStringLen(s.as_ptr(), s.len() as u32) };
+    if bstr.is_null() {
+        return Err(IntoVariantError::from(BStringError::AllocateFailed{len: s.len()}));
+    }
+    ConversionStats::record_bstr(s.len());
+    let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
+    unsafe { *n3.bstrVal_mut() = bstr };
+    Ok(new_variant(VT_BSTR, n3))
+}
+
+// DECIMAL overlays the whole VARIANT, its first field doubling as `vt`
+fn decimal_variant(dec: DECIMAL) -> Ptr<VARIANT> {
+    let mut n1: VARIANT_n1 = unsafe {mem::zeroed()};
+    unsafe {
+        *n1.decVal_mut() = dec;
+        n1.n2_mut().vt = VT_DECIMAL as u16;
+    }
+    let var = Box::new(VARIANT{ n1: n1 });
+    ConversionStats::record_variant();
+    Ptr::with_checked(Box::into_raw(var)).unwrap()
+}
+
+// Unit arms go first, each with the marker type that converts it, and arms sharing a 
+// vartype last, with the function that picks between them on decode
+macro_rules! variants_impl {
+    (
+        $($unit:ident => $uvt:ident as $ut:ident),* ; 
+        $($arm:ident($t:ty) => $vt:ident),* ; 
+        $($sarm:ident($st:ty)),* => $svt:ident by $sdecode:ident
+    ) => {
+        impl Variants {
+            /// Converts the `Variants` into a `Ptr<VARIANT>` of the matching vartype
+            pub fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+                match self {
+                    $(Variants::$unit => $ut{}.into_variant(),)*
+                    $(Variants::$arm(val) => val.into_variant(),)*
+                    $(Variants::$sarm(val) => val.into_variant(),)*
+                }
+            }
+
+            /// The vartype `into_variant` produces for this value
+            pub fn vartype(&self) -> u32 {
+                match *self {
+                    $(Variants::$unit => $uvt,)*
+                    $(Variants::$arm(_) => $vt,)*
+                    $(Variants::$sarm(_) => $svt,)*
+                }
+            }
+
+            /// Converts a `Ptr<VARIANT>` into a `Variants`, choosing the arm by the vartype it holds
+            pub fn from_variant(var: Ptr<VARIANT>) -> Result<Variants, FromVariantError> {
+                let vt = unsafe {(*var.as_ptr()).n1.n2().vt} as u32;
+                match vt {
+                    $($uvt => {
+                        $ut::from_variant(var)?;
+                        Ok(Variants::$unit)
+                    },)*
+                    $($vt => Ok(Variants::$arm(<$t as VariantExt>::from_variant(var)?)),)*
+                    $svt => $sdecode(var),
+                    _ if vt & (VT_ARRAY | VT_BYREF) != 0 => variants_from_value(var),
+                    _ => Err(FromVariantError::UnknownVarType{vartype: vt})
+                }
+            }
+        }
+
+        $(
+            impl From<$ut> for Variants {
+                fn from(_: $ut) -> Variants {
+                    Variants::$unit
+                }
+            }
+        )*
+
+        $(
+            impl From<$t> for Variants {
+                fn from(val: $t) -> Variants {
+                    Variants::$arm(val)
+                }
+            }
+        )*
+
+        $(
+            impl From<$st> for Variants {
+                fn from(val: $st) -> Variants {
+                    Variants::$sarm(val)
+                }
+            }
+        )*
+    };
+}
+
+variants_impl!{
+    Empty => VT_EMPTY as VtEmpty, 
+    Null => VT_NULL as VtNull;
+    I64(i64) => VT_I8, 
+    I32(i32) => VT_I4, 
+    U8(u8) => VT_UI1, 
+    I16(i16) => VT_I2, 
+    F32(f32) => VT_R4, 
+    F64(f64) => VT_R8, 
+    Bool(bool) => VT_BOOL, 
+    SCode(SCode) => VT_ERROR, 
+    Currency(Currency) => VT_CY, 
+    Date(Date) => VT_DATE, 
+    I8(i8) => VT_I1, 
+    U16(u16) => VT_UI2, 
+    U32(u32) => VT_UI4, 
+    U64(u64) => VT_UI8, 
+    Int(Int) => VT_INT, 
+    UInt(UInt) => VT_UINT, 
+    Decimal(DecWrapper) => VT_DECIMAL, 
+    Unknown(Option<OwnedPtr<IUnknown, ComDestructor>>) => VT_UNKNOWN, 
+    Dispatch(Option<OwnedPtr<IDispatch, ComDestructor>>) => VT_DISPATCH, 
+    Array(Vec<Variants>) => VT_ARRAY_VARIANT;
+    String(String), 
+    WideString(U16String) => VT_BSTR by variants_from_bstr
+}
+
+fn variants_from_bstr(var: Ptr<VARIANT>) -> Result<Variants, FromVariantError> {
+    Ok(Variants::from_wide(U16String::from_variant(var)?))
+}
+
+impl Variants {
+    /// `Variants::String` when `val` is valid UTF-16, `Variants::WideString` otherwise
+    pub fn from_wide(val: U16String) -> Variants {
+        match String::from_utf16(val.as_slice()) {
+            Ok(s) => Variants::String(s), 
+            Err(_) => Variants::WideString(val),
+        }
+    }
+
+    /// `Variants::Currency` parsed from a decimal string such as `"12.34"`
+    pub fn currency_str(s: &str) -> Result<Variants, ValueError> {
+        Ok(Variants::Currency(s.parse()?))
+    }
+
+    /// `Variants::Currency` from a float, rounded to four decimal places
+    pub fn currency_f64(val: f64) -> Result<Variants, ValueError> {
+        Ok(Variants::Currency(Currency::from_f64(val)?))
+    }
+
+    /// `Variants::Date` from a `chrono::NaiveDateTime`
+    #[cfg(feature = "chrono")]
+    pub fn from_date(dt: NaiveDateTime) -> Result<Variants, ValueError> {
+        Ok(Variants::Date(date_from_naive(dt)?))
+    }
+
+    /// `Variants::Date` from a raw OLE DATE value (days since 1899-12-30)
+    pub fn ole_date(val: f64) -> Variants {
+        Variants::Date(Date::from(val))
+    }
+
+    /// `Variants::SCode` from a raw SCODE/HRESULT value
+    pub fn scode(val: i32) -> Variants {
+        Variants::SCode(SCode::from(val))
+    }
+
+    /// `Variants::Int` (VT_INT) from an `i32`
+    pub fn int(val: i32) -> Variants {
+        Variants::Int(Int::from(val))
+    }
+
+    /// `Variants::UInt` (VT_UINT) from a `u32`
+    pub fn uint(val: u32) -> Variants {
+        Variants::UInt(UInt::from(val))
+    }
+
+    /// An integer, floating point, currency or decimal value
+    pub fn is_numeric(&self) -> bool {
+        match *self {
+            Variants::I8(_) | Variants::I16(_) | Variants::I32(_) | Variants::I64(_) | 
+            Variants::U8(_) | Variants::U16(_) | Variants::U32(_) | Variants::U64(_) | 
+            Variants::Int(_) | Variants::UInt(_) | Variants::F32(_) | Variants::F64(_) | 
+            Variants::Currency(_) | Variants::Decimal(_) => true,
+            _ => false,
+        }
+    }
+
+    /// A `VT_BSTR` value
+    pub fn is_string(&self) -> bool {
+        match *self {
+            Variants::String(_) | Variants::WideString(_) => true,
+            _ => false,
+        }
+    }
+
+    /// An `IUnknown` or `IDispatch` pointer, including a null one
+    pub fn is_object(&self) -> bool {
+        match *self {
+            Variants::Unknown(_) | Variants::Dispatch(_) => true,
+            _ => false,
+        }
+    }
+
+    /// `Variants::Unknown` holding a new reference on `punk`
+    pub fn unknown(punk: Ptr<IUnknown>) -> Variants {
+        Variants::Unknown(Some(OwnedPtr::add_ref(punk)))
+    }
+
+    /// `Variants::Dispatch` holding a new reference on `pdisp`
+    pub fn dispatch(pdisp: Ptr<IDispatch>) -> Variants {
+        Variants::Dispatch(Some(OwnedPtr::add_ref(pdisp)))
+    }
+
+    /// `Variants::Decimal` (VT_DECIMAL) from a `rust_decimal::Decimal`
+    pub fn decimal(val: Decimal) -> Variants {
+        Variants::Decimal(DecWrapper::new(val))
+    }
+}
+
+impl From<Decimal> for Variants {
+    fn from(val: Decimal) -> Variants {
+        Variants::decimal(val)
+    }
+}
+
+/// Loose conversions in the spirit of VB's `CLng`, `CDbl`, `CBool` and `CStr`, for data 
+/// from scripting clients whose exact vartype doesn't matter. Each returns `None` when the 
+/// value has no sensible reading as the target type - `Null`, objects, arrays, text that 
+/// doesn't parse, or a number out of range. Nothing is rounded: `2.5` is not an integer.
+impl Variants {
+    /// Integers in range, integral floats, currency and decimals, `Bool` as -1 or 0, 
+    /// `Empty` as 0, and strings that parse as an integer
+    pub fn to_i64(&self) -> Option<i64> {
+        match *self {
+            Variants::Empty => Some(0), 
+            Variants::I8(v) => Some(i64::from(v)), 
+            Variants::I16(v) => Some(i64::from(v)), 
+            Variants::I32(v) => Some(i64::from(v)), 
+            Variants::I64(v) => Some(v), 
+            Variants::U8(v) => Some(i64::from(v)), 
+            Variants::U16(v) => Some(i64::from(v)), 
+            Variants::U32(v) => Some(i64::from(v)), 
+            Variants::U64(v) => i64::try_from(v).ok(), 
+            Variants::Int(v) => Some(i64::from(i32::from(v))), 
+            Variants::UInt(v) => Some(i64::from(u32::from(v))), 
+            Variants::Bool(v) => Some(if v { -1 } else { 0 }), 
+            Variants::F32(v) => float_to_i64(f64::from(v)), 
+            Variants::F64(v) => float_to_i64(v), 
+            Variants::Currency(cy) => {
+                let units = i64::from(cy);
+                if units % CY_SCALE == 0 { Some(units / CY_SCALE) } else { None }
+            }, 
+            Variants::Decimal(dec) => trim_fraction(&Decimal::from(dec).to_string()).parse().ok(), 
+            Variants::String(ref s) => s.trim().parse().ok(), 
+            _ => None,
+        }
+    }
+
+    /// `to_i64`, narrowed to `i32` when in range
+    pub fn to_i32(&self) -> Option<i32> {
+        self.to_i64().and_then(|v| i32::try_from(v).ok())
+    }
+
+    /// `to_i64` for non-negative values, plus `U64` and strings beyond `i64::MAX`
+    pub fn to_u64(&self) -> Option<u64> {
+        match *self {
+            Variants::U64(v) => Some(v), 
+            Variants::String(ref s) => s.trim().parse().ok(), 
+            _ => self.to_i64().and_then(|v| u64::try_from(v).ok()),
+        }
+    }
+
+    /// Any number, currency, decimal or date (as its OLE day count), `Bool` as -1 or 0, 
+    /// `Empty` as 0, and strings that parse as a float
+    pub fn to_f64(&self) -> Option<f64> {
+        match *self {
+            Variants::Empty => Some(0.0), 
+            Variants::I8(v) => Some(f64::from(v)), 
+            Variants::I16(v) => Some(f64::from(v)), 
+            Variants::I32(v) => Some(f64::from(v)), 
+            Variants::I64(v) => Some(v as f64), 
+            Variants::U8(v) => Some(f64::from(v)), 
+            Variants::U16(v) => Some(f64::from(v)), 
+            Variants::U32(v) => Some(f64::from(v)), 
+            Variants::U64(v) => Some(v as f64), 
+            Variants::Int(v) => Some(f64::from(i32::from(v))), 
+            Variants::UInt(v) => Some(f64::from(u32::from(v))), 
+            Variants::Bool(v) => Some(if v { -1.0 } else { 0.0 }), 
+            Variants::F32(v) => Some(f64::from(v)), 
+            Variants::F64(v) => Some(v), 
+            Variants::Currency(cy) => Some(i64::from(cy) as f64 / CY_SCALE as f64), 
+            Variants::Date(d) => Some(f64::from(d)), 
+            Variants::Decimal(dec) => Decimal::from(dec).to_string().parse().ok(), 
+            Variants::String(ref s) => s.trim().parse().ok(), 
+            _ => None,
+        }
+    }
+
+    /// `Bool` itself, any number as non-zero, `Empty` as false, and the strings 
+    /// "True" and "False" in any case or a number in text
+    pub fn to_bool(&self) -> Option<bool> {
+        match *self {
+            Variants::Bool(v) => Some(v), 
+            Variants::String(ref s) => {
+                let t = s.trim();
+                if t.eq_ignore_ascii_case("true") {
+                    Some(true)
+                } else if t.eq_ignore_ascii_case("false") {
+                    Some(false)
+                } else {
+                    self.to_f64().map(|v| v != 0.0)
+                }
+            }, 
+            Variants::Date(_) => None, 
+            _ => self.to_f64().map(|v| v != 0.0),
+        }
+    }
+
+    /// The text of a string, number, currency or decimal, `Bool` as "True" or "False" 
+    /// and `Empty` as "". Dates have no single text form and give `None`.
+    pub fn to_string_lossy(&self) -> Option<String> {
+        match *self {
+            Variants::Empty => Some(String::new()), 
+            Variants::String(ref s) => Some(s.clone()), 
+            Variants::WideString(ref w) => Some(w.to_string_lossy()), 
+            Variants::Bool(v) => Some(String::from(if v { "True" } else { "False" })), 
+            Variants::I8(v) => Some(v.to_string()), 
+            Variants::I16(v) => Some(v.to_string()), 
+            Variants::I32(v) => Some(v.to_string()), 
+            Variants::I64(v) => Some(v.to_string()), 
+            Variants::U8(v) => Some(v.to_string()), 
+            Variants::U16(v) => Some(v.to_string()), 
+            Variants::U32(v) => Some(v.to_string()), 
+            Variants::U64(v) => Some(v.to_string()), 
+            Variants::Int(v) => Some(i32::from(v).to_string()), 
+            Variants::UInt(v) => Some(u32::from(v).to_string()), 
+            Variants::F32(v) => Some(v.to_string()), 
+            Variants::F64(v) => Some(v.to_string()), 
+            Variants::Currency(cy) => {
+                let units = i64::from(cy);
+                let abs = units.unsigned_abs();
+                let s = format!("{}{}.{:04}", if units < 0 { "-" } else { "" }, abs / CY_SCALE as u64, abs % CY_SCALE as u64);
+                Some(String::from(trim_fraction(&s)))
+            }, 
+            Variants::Decimal(dec) => Some(String::from(trim_fraction(&Decimal::from(dec).to_string()))), 
+            _ => None,
+        }
+    }
+}
+
+fn float_to_i64(v: f64) -> Option<i64> {
+    //2^63 is exactly representable, anything at or beyond it is out of range
+    if v.fract() == 0.0 && v >= -9_223_372_036_854_775_808.0 && v < 9_223_372_036_854_775_808.0 {
+        Some(v as i64)
+    } else {
+        None
+    }
+}
+
+// Drops trailing zeros after the decimal point, and the point itself if nothing is left
+fn trim_fraction(s: &str) -> &str {
+    if !s.contains('.') {
+        return s;
+    }
+    s.trim_end_matches('0').trim_end_matches('.')
+}
+
+impl<'s> From<&'s str> for Variants {
+    fn from(val: &str) -> Variants {
+        Variants::String(String::from(val))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use super::super::dtor::FreeVariant;
+    use winapi::shared::wtypes::VT_VOID;
+    use winapi::um::oleauto::SysStringLen;
     macro_rules! validate_variant {
         ($t:ident, $val:expr, $vt:expr) => {
             let v = $val;
@@ -971,6 +2056,167 @@ mod test {
             assert_eq!(v, var.unwrap());
             };
     }
+    #[test]
+    fn test_bstr_interior_nul() {
+        let s = String::from("a\0b\0");
+        let pvar = s.clone().into_variant().unwrap();
+        let len = unsafe { SysStringLen(*(*pvar.as_ptr()).n1.n2().n3.bstrVal()) };
+        assert_eq!(len, 4);
+        assert_eq!(String::from_variant(pvar).unwrap(), s);
+
+        let w = U16String::from_vec(vec![0x61u16, 0, 0xD800, 0]);
+        let pvar = w.clone().into_variant().unwrap();
+        assert_eq!(U16String::from_variant(pvar).unwrap(), w);
+    }
+
+    #[test]
+    fn test_ref_impls() {
+        let cy = Currency::from(12500);
+        let pvar = (&cy).into_variant().unwrap();
+        assert_eq!(Currency::from_variant(pvar).unwrap(), cy);
+
+        let w = U16String::from_vec(vec![0x61u16, 0xD800]);
+        let pvar = (&w).into_variant().unwrap();
+        assert_eq!(U16String::from_variant(pvar).unwrap(), w);
+        let pvar = "text".into_variant().unwrap();
+        assert_eq!(String::from_variant(pvar).unwrap(), "text");
+
+        let dec = DecWrapper::new(Decimal::new(-1234, 2));
+        let pvar = (&dec).into_variant().unwrap();
+        let n1 = unsafe { (*pvar.as_ptr()).n1 };
+        assert_eq!(unsafe { n1.n2() }.vt as u32, VT_DECIMAL);
+        assert_eq!(DecWrapper::from(unsafe { *n1.decVal() }), dec);
+        match <&Currency>::from_variant(pvar) {
+            Err(FromVariantError::BorrowedTarget{vartype}) => assert_eq!(vartype, VT_CY), 
+            _ => panic!("expected BorrowedTarget"),
+        }
+    }
+
+    #[test]
+    fn test_addresses() {
+        let ip: IpAddr = "fe80::1".parse().unwrap();
+        let pvar = ip.into_variant().unwrap();
+        assert_eq!(String::from_variant(pvar).unwrap(), "fe80::1");
+        let pvar = ip.into_variant().unwrap();
+        assert_eq!(IpAddr::from_variant(pvar).unwrap(), ip);
+        let sa: SocketAddr = "10.0.0.2:8080".parse().unwrap();
+        let pvar = sa.into_variant().unwrap();
+        assert_eq!(SocketAddr::from_variant(pvar).unwrap(), sa);
+        let pvar = String::from(" 192.168.1.1 ").into_variant().unwrap();
+        assert_eq!(Ipv4Addr::from_variant(pvar).unwrap(), Ipv4Addr::new(192, 168, 1, 1));
+        let g: Guid = "{00020400-0000-0000-C000-000000000046}".parse().unwrap();
+        let pvar = g.into_variant().unwrap();
+        assert_eq!(Guid::from_variant(pvar).unwrap(), g);
+        let pvar = String::from("not an address").into_variant().unwrap();
+        match Ipv6Addr::from_variant(pvar) {
+            Err(FromVariantError::ParseFailed{target, ..}) => assert_eq!(target, "Ipv6Addr"), 
+            _ => panic!("expected a parse failure"),
+        }
+    }
+
+    #[test]
+    fn test_containers() {
+        let mut queue: VecDeque<i32> = (1..=4).collect();
+        queue.rotate_left(1);
+        let pvar = queue.clone().into_variant().unwrap();
+        assert_eq!(VecDeque::<i32>::from_variant(pvar).unwrap(), queue);
+        let psa = queue.drain(..).into_safearray().unwrap();
+        assert_eq!(ExactSizeIterator::<Item=i32>::from_safearray(psa.as_ptr()).unwrap(), vec![2, 3, 4, 1]);
+
+        let boxed: Box<[f64]> = vec![0.5, 1.5].into_boxed_slice();
+        let pvar = boxed.clone().into_variant().unwrap();
+        assert_eq!(<Box<[f64]>>::VARTYPE, VT_ARRAY | VT_R8);
+        assert_eq!(<Box<[f64]>>::from_variant(pvar).unwrap(), boxed);
+    }
+
+    #[test]
+    fn test_vec_vartype() {
+        assert_eq!(Vec::<i32>::VARTYPE, VT_ARRAY | VT_I4);
+        assert_eq!(Vec::<String>::VARTYPE, VT_ARRAY | VT_VARIANT);
+        let pvar = vec![1i32, 2].into_variant().unwrap();
+        assert_eq!(unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32, VT_ARRAY | VT_I4);
+        assert_eq!(Vec::<i32>::from_variant(pvar).unwrap(), vec![1, 2]);
+    }
+
+    #[cfg(feature = "small_strings")]
+    #[test]
+    fn test_small_string() {
+        let pvar = String::from("short").into_variant().unwrap();
+        let small = SmallString::<[u8; 16]>::from_variant(pvar).unwrap();
+        assert_eq!(&*small, "short");
+        assert!(!small.spilled());
+        let pvar = small.into_variant().unwrap();
+        assert_eq!(String::from_variant(pvar).unwrap(), "short");
+        let pvar = String::from("longer than sixteen bytes").into_variant().unwrap();
+        let small = SmallString::<[u8; 16]>::from_variant(pvar).unwrap();
+        assert_eq!(&*small, "longer than sixteen bytes");
+        assert!(small.spilled());
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn test_smallvec() {
+        let sv: SmallVec<[u8; 4]> = SmallVec::from_slice(&[1, 2, 3]);
+        let pvar = sv.clone().into_variant().unwrap();
+        assert_eq!(SmallVec::<[u8; 4]>::from_variant(pvar).unwrap(), sv);
+    }
+
+    #[test]
+    fn test_variants_constructors() {
+        assert_eq!(Variants::currency_str("12.34").unwrap(), Variants::Currency(Currency::from(123400)));
+        assert!(Variants::currency_str("twelve").is_err());
+        assert_eq!(Variants::scode(-1), Variants::SCode(SCode::from(-1)));
+        assert_eq!(Variants::ole_date(1.5), Variants::Date(Date::from(1.5)));
+        assert_eq!(Variants::decimal(Decimal::new(5, 1)), Variants::Decimal(DecWrapper::new(Decimal::new(5, 1))));
+        assert_eq!(Variants::from(Decimal::new(5, 1)), Variants::decimal(Decimal::new(5, 1)));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use chrono::NaiveDate;
+        let dt = NaiveDate::from_ymd_opt(2018, 11, 5).unwrap().and_hms_milli_opt(13, 45, 10, 250).unwrap();
+        validate_variant!(NaiveDateTime, dt, VT_DATE);
+        let utc = Utc.from_utc_datetime(&dt);
+        let pvar = utc.into_variant().unwrap();
+        assert_eq!(DateTime::<Utc>::from_variant(pvar).unwrap(), utc);
+
+        let pvar = Date::from(1e7).into_variant().unwrap();
+        match NaiveDateTime::from_variant(pvar) {
+            Err(FromVariantError::DateOutOfRange{value}) => assert_eq!(value, 1e7),
+            _ => panic!("expected DateOutOfRange"),
+        }
+    }
+
+    #[test]
+    fn test_int128() {
+        validate_variant!(i128, -(1i128 << 95), VT_DECIMAL);
+        validate_variant!(u128, (1u128 << 96) - 1, VT_DECIMAL);
+        match (1u128 << 96).into_variant() {
+            Err(IntoVariantError::DecimalOverflow{magnitude}) => assert_eq!(magnitude, 1 << 96),
+            _ => panic!("expected DecimalOverflow"),
+        }
+        assert!(i128::min_value().into_variant().is_err());
+
+        //whole numbers with a scale still read, fractions and negatives for u128 don't
+        let pvar = Decimal::new(1200, 2).into_variant().unwrap();
+        assert_eq!(u128::from_variant(pvar).unwrap(), 12);
+        let pvar = Decimal::new(1201, 2).into_variant().unwrap();
+        assert!(i128::from_variant(pvar).is_err());
+        let pvar = Decimal::new(-5, 0).into_variant().unwrap();
+        match u128::from_variant(pvar) {
+            Err(FromVariantError::DecimalOutOfRange{target}) => assert_eq!(target, "u128"),
+            _ => panic!("expected DecimalOutOfRange"),
+        }
+    }
+
+    #[test]
+    fn test_decimal() {
+        validate_variant!(Decimal, Decimal::new(-123_456_789, 4), VT_DECIMAL);
+        let pvar = vec![Decimal::new(1, 2), Decimal::new(-3, 0)].into_variant().unwrap();
+        assert_eq!(Vec::<Decimal>::from_variant(pvar).unwrap(), vec![Decimal::new(1, 2), Decimal::new(-3, 0)]);
+    }
+
     #[test]
     fn test_i64() {
         validate_variant!(i64, 1337i64, VT_I8);
@@ -1005,6 +2251,50 @@ mod test {
         validate_variant!(bool, false, VT_BOOL);
     }
 
+    #[test]
+    fn test_bool_nonstandard() {
+        //a C server writing 1 instead of VARIANT_TRUE
+        let pvar = true.into_variant().unwrap();
+        unsafe { *(*pvar.as_ptr()).n1.n2_mut().n3.boolVal_mut() = 1 };
+        assert_eq!(bool::from_variant(pvar).unwrap(), true);
+        let pvar = Box::new(false).into_variant().unwrap();
+        unsafe { **(*pvar.as_ptr()).n1.n2_mut().n3.pboolVal_mut() = 1 };
+        assert_eq!(*Box::<bool>::from_variant(pvar).unwrap(), true);
+    }
+
+    #[test]
+    fn test_option() {
+        let pvar = None::<i32>.into_variant().unwrap();
+        assert_eq!(unsafe {(*pvar.as_ptr()).n1.n2().vt} as u32, VT_NULL);
+        assert_eq!(Option::<i32>::from_variant(pvar).unwrap(), None);
+
+        let opts = ConvertOptions::new().none_policy(NonePolicy::Empty);
+        let pvar = None::<String>.into_variant_with(&opts).unwrap();
+        assert_eq!(unsafe {(*pvar.as_ptr()).n1.n2().vt} as u32, VT_EMPTY);
+        assert_eq!(Option::<String>::from_variant(pvar).unwrap(), None);
+
+        let pvar = Some(17i32).into_variant().unwrap();
+        assert_eq!(Option::<i32>::from_variant(pvar).unwrap(), Some(17));
+
+        //a present value of the wrong type is still an error, not None
+        let pvar = String::from("x").into_variant().unwrap();
+        assert!(Option::<Date>::from_variant(pvar).is_err());
+    }
+
+    #[test]
+    fn test_result() {
+        use super::super::codes::XL_ERR_NA;
+        let pvar = Err::<f64, SCode>(XL_ERR_NA).into_variant().unwrap();
+        assert_eq!(unsafe {(*pvar.as_ptr()).n1.n2().vt} as u32, VT_ERROR);
+        assert_eq!(Result::<f64, SCode>::from_variant(pvar).unwrap(), Err(XL_ERR_NA));
+
+        let pvar = Ok::<f64, SCode>(2.5).into_variant().unwrap();
+        assert_eq!(Result::<f64, SCode>::from_variant(pvar).unwrap(), Ok(2.5));
+
+        let pvar = String::from("x").into_variant().unwrap();
+        assert!(Result::<f64, SCode>::from_variant(pvar).is_err());
+    }
+
     #[test]
     fn test_scode() {
         validate_variant!(SCode, SCode::from(137), VT_ERROR);
@@ -1146,4 +2436,145 @@ mod test {
         fn assert_sync<T: Sync>() {}
         assert_sync::<Variant<i64>>();
     }
+    #[test]
+    fn test_variants() {
+        let vals = vec![Variants::from(1337i32), Variants::from("dynamic"), Variants::from(true), Variants::from(Currency::from(137)), Variants::decimal(Decimal::new(-12345, 3))];
+        for v in vals {
+            let pvar = v.clone().into_variant().unwrap();
+            let back = Variants::from_variant(pvar).unwrap();
+            assert_eq!(v, back);
+        }
+    }
+    #[test]
+    fn test_variants_unknown() {
+        let pvar = RawByRef::new(VT_VOID, null_mut()).into_variant().unwrap();
+        match Variants::from_variant(pvar) {
+            Err(FromVariantError::UnknownVarType{vartype}) => assert_eq!(vartype, VT_BYREF | VT_VOID), 
+            _ => panic!("Error")
+        }
+    }
+    #[test]
+    fn test_variants_byref() {
+        let mut n = 7i32;
+        let pvar = RawByRef::new(VT_I4, &mut n as *mut i32 as *mut c_void).into_variant().unwrap();
+        assert_eq!(Variants::from_variant(pvar).unwrap(), Variants::I32(7));
+        assert_eq!(n, 7);
+    }
+    #[test]
+    fn test_variants_wide_string() {
+        let w = U16String::from_vec(vec![0x61u16, 0xD800, 0, 0x62]);
+        let pvar = w.clone().into_variant().unwrap();
+        let v = Variants::from_variant(pvar).unwrap();
+        assert_eq!(v, Variants::WideString(w.clone()));
+        assert!(v.is_string());
+        assert_eq!(v.vartype(), VT_BSTR);
+        let pvar = v.into_variant().unwrap();
+        assert_eq!(U16String::from_variant(pvar).unwrap(), w);
+
+        let pvar = String::from("a\u{0}b").into_variant().unwrap();
+        assert_eq!(Variants::from_variant(pvar).unwrap(), Variants::String(String::from("a\u{0}b")));
+    }
+    #[test]
+    fn test_variants_empty_null() {
+        let pvar = VtNull{}.into_variant().unwrap();
+        assert_eq!(Variants::from_variant(pvar).unwrap(), Variants::Null);
+        for v in vec![Variants::Empty, Variants::Null, Variants::from(VtEmpty{})] {
+            let pvar = v.clone().into_variant().unwrap();
+            assert_eq!(Variants::from_variant(pvar).unwrap(), v);
+        }
+    }
+    #[test]
+    fn test_variants_array() {
+        leak_tracking::assert_balanced(|| {
+            let v = Variants::Array(vec![Variants::I32(1), Variants::from("two"), Variants::Array(vec![Variants::Null])]);
+            let pvar = v.clone().into_variant().unwrap();
+            assert_eq!(unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32, VT_ARRAY | VT_VARIANT);
+            assert_eq!(Variants::from_variant(pvar).unwrap(), v);
+            //from_variant leaves the box VT_EMPTY, free it too so the scope balances
+            assert_eq!(unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32, VT_EMPTY);
+            FreeVariant::destroy(pvar);
+
+            let pvar = vec![3i16, 4].into_variant().unwrap();
+            assert_eq!(Variants::from_variant(pvar).unwrap(), Variants::Array(vec![Variants::I16(3), Variants::I16(4)]));
+            FreeVariant::destroy(pvar);
+        });
+    }
+    #[test]
+    fn test_variants_classify() {
+        let v = Variants::Array(vec![Variants::decimal(Decimal::new(1, 0))]);
+        assert_eq!(v.vartype(), VT_ARRAY | VT_VARIANT);
+        assert!(!v.is_numeric());
+        let pvar = Variants::Null.into_variant().unwrap();
+        assert_eq!(Variants::Null.vartype(), unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32);
+        assert!(Variants::decimal(Decimal::new(1, 0)).is_numeric() && Variants::U8(1).is_numeric());
+        assert!(!Variants::Bool(true).is_numeric() && !Variants::ole_date(1.0).is_numeric());
+        assert!(Variants::from("s").is_string() && !Variants::Null.is_string());
+        assert!(Variants::Dispatch(None).is_object() && !Variants::I32(0).is_object());
+    }
+    #[test]
+    fn test_variants_coercion() {
+        assert_eq!(Variants::I16(-3).to_i64(), Some(-3));
+        assert_eq!(Variants::from(" 42 ").to_i32(), Some(42));
+        assert_eq!(Variants::F64(2.0).to_i64(), Some(2));
+        assert_eq!(Variants::F64(2.5).to_i64(), None);
+        assert_eq!(Variants::U64(u64::max_value()).to_i64(), None);
+        assert_eq!(Variants::U64(u64::max_value()).to_u64(), Some(u64::max_value()));
+        assert_eq!(Variants::I32(-1).to_u64(), None);
+        assert_eq!(Variants::Currency(Currency::from(120000)).to_i64(), Some(12));
+        assert_eq!(Variants::decimal(Decimal::new(1200, 2)).to_i64(), Some(12));
+        assert_eq!(Variants::Bool(true).to_i64(), Some(-1));
+        assert_eq!(Variants::Null.to_i64(), None);
+        assert_eq!(Variants::Empty.to_f64(), Some(0.0));
+        assert_eq!(Variants::Currency(Currency::from(12345)).to_f64(), Some(1.2345));
+        assert_eq!(Variants::from("1e3").to_f64(), Some(1000.0));
+        assert_eq!(Variants::from("TRUE").to_bool(), Some(true));
+        assert_eq!(Variants::from("0").to_bool(), Some(false));
+        assert_eq!(Variants::U8(3).to_bool(), Some(true));
+        assert_eq!(Variants::from("maybe").to_bool(), None);
+        assert_eq!(Variants::Currency(Currency::from(-12500)).to_string_lossy(), Some(String::from("-1.25")));
+        assert_eq!(Variants::decimal(Decimal::new(1200, 2)).to_string_lossy(), Some(String::from("12")));
+        assert_eq!(Variants::Bool(false).to_string_lossy(), Some(String::from("False")));
+        assert_eq!(Variants::Null.to_string_lossy(), None);
+    }
+    #[test]
+    fn test_variants_nothing() {
+        for v in vec![Variants::Unknown(None), Variants::Dispatch(None)] {
+            let pvar = v.clone().into_variant().unwrap();
+            assert_eq!(Variants::from_variant(pvar).unwrap(), v);
+        }
+    }
+    #[test]
+    fn test_nothing() {
+        let pvar = None::<Ptr<IDispatch>>.into_variant().unwrap();
+        assert_eq!(unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32, VT_DISPATCH);
+        match Ptr::<IDispatch>::from_variant(pvar) {
+            Err(FromVariantError::DispatchPtrNull) => {}, 
+            _ => panic!("Error")
+        }
+        let pvar = None::<Ptr<IDispatch>>.into_variant().unwrap();
+        assert!(Option::<Ptr<IDispatch>>::from_variant(pvar).unwrap().is_none());
+        let pvar = None::<Ptr<IUnknown>>.into_variant().unwrap();
+        assert!(Option::<Ptr<IUnknown>>::from_variant(pvar).unwrap().is_none());
+    }
+    #[test]
+    fn test_raw_byref() {
+        let mut payload = 42i32;
+        let raw = RawByRef::new(VT_VOID, &mut payload as *mut i32 as *mut c_void);
+        assert_eq!(raw.inner_vartype(), VT_VOID);
+        let pvar = raw.into_variant().unwrap();
+        let back = RawByRef::from_variant(pvar).unwrap();
+        assert_eq!(raw, back);
+        assert_eq!(back.vartype(), VT_BYREF | VT_VOID);
+        assert_eq!(unsafe {*(back.as_ptr() as *mut i32)}, 42);
+    }
+    #[test]
+    fn test_raw_byref_not_byref() {
+        let pvar = 12i32.into_variant().unwrap();
+        assert!(RawByRef::from_variant(pvar).is_err());
+    }
+    #[test]
+    fn test_clipdata() {
+        let cd = ClipData::new(13, vec![0x41, 0, 0x42, 0, 0, 0]);
+        validate_variant!(ClipData, cd, VT_CF);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,159 @@
+//! # VArray
+//! `Vec<Variants>` as a single `VT_ARRAY | VT_VARIANT` variant. 
+//! 
+//! Putting boxed VARIANTs into a `VT_VARIANT` SAFEARRAY element by element copies 
+//! each one, leaving the originals - and any BSTRs they hold - to be freed separately. 
+//! These helpers move each element's contents into its array slot instead, so the 
+//! outer VARIANT owns the array and the array owns every element: one `VariantClear` 
+//! on the outer VARIANT releases the whole chain.
+use std::mem;
+use std::ptr;
+
+use winapi::ctypes::c_void;
+use winapi::shared::wtypes::{VT_ARRAY, VT_VARIANT};
+use winapi::um::oaidl::{VARIANT, VARIANT_n3};
+
+use super::array::{
+    check_elem_size, 
+    get_bounds, 
+    SafeArrayAccessData, 
+    SafeArrayCreateVector, 
+    SafeArrayDestructor, 
+    SafeArrayGetDim, 
+    SafeArrayUnaccessData,
+};
+use super::dtor::{ClearVariant, OwnedPtr};
+use super::errors::{FromSafeArrayError, FromVariantError, IntoSafeArrayError, IntoVariantError};
+use super::leak_tracking;
+use super::limits::DecodeLimits;
+use super::ptr::Ptr;
+use super::stats::ConversionStats;
+use super::variant::{new_variant, Variants};
+
+const VT_ARRAY_VARIANT: u32 = VT_ARRAY | VT_VARIANT;
+
+/// Converts `items` into a `VT_ARRAY | VT_VARIANT` VARIANT that owns every element
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{variant_to_variants, variants_to_variant, Variants};
+/// 
+/// fn main() {
+///     let row = vec![Variants::from("Widget"), Variants::I32(3), Variants::F64(9.5)];
+///     let pvar = variants_to_variant(row.clone()).unwrap();
+///     assert_eq!(variant_to_variants(pvar).unwrap(), row);
+/// }
+/// ```
+pub fn variants_to_variant(items: Vec<Variants>) -> Result<Ptr<VARIANT>, IntoVariantError> {
+    let psa = unsafe { SafeArrayCreateVector(VT_VARIANT as u16, 0, items.len() as u32) };
+    if psa.is_null() {
+        return Err(IntoVariantError::from(IntoSafeArrayError::SafeArrayCreateFailed));
+    }
+    //on failure this destroys the array along with the elements already moved in
+    let mut sad = SafeArrayDestructor::new(psa);
+    ConversionStats::record_safearray(items.len() * mem::size_of::<VARIANT>());
+
+    let mut data: *mut c_void = ptr::null_mut();
+    let hr = unsafe { SafeArrayAccessData(psa, &mut data) };
+    if hr != 0 {
+        return Err(IntoVariantError::from(IntoSafeArrayError::AccessDataFailed{hr: hr}));
+    }
+    let data = data as *mut VARIANT;
+    for (ix, item) in items.into_iter().enumerate() {
+        match item.into_variant() {
+            Ok(pvar) => unsafe {
+                //move the contents into the slot and free only the box
+                leak_tracking::variant_freed(pvar.as_ptr());
+                let boxed = Box::from_raw(pvar.as_ptr());
+                ptr::write(data.add(ix), *boxed);
+            }, 
+            Err(ive) => {
+                unsafe { SafeArrayUnaccessData(psa) };
+                return Err(ive);
+            }
+        }
+    }
+    unsafe { SafeArrayUnaccessData(psa) };
+    sad.inner = ptr::null_mut();
+
+    let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
+    unsafe { *n3.parray_mut() = psa };
+    Ok(new_variant(VT_ARRAY_VARIANT, n3))
+}
+
+/// Decodes every element of a `VT_ARRAY | VT_VARIANT` VARIANT. 
+/// 
+/// The VARIANT's contents are released afterwards - the array and every nested 
+/// element - and it is left `VT_EMPTY`.
+pub fn variant_to_variants(var: Ptr<VARIANT>) -> Result<Vec<Variants>, FromVariantError> {
+    let pvar = var.as_ptr();
+    let mut n1 = unsafe {(*pvar).n1};
+    let vt = unsafe {n1.n2()}.vt as u32;
+    if vt != VT_ARRAY_VARIANT {
+        return Err(FromVariantError::VarTypeDoesNotMatch{expected: VT_ARRAY_VARIANT, found: vt});
+    }
+    let psa = unsafe { *n1.n2_mut().n3.parray() };
+    if psa.is_null() {
+        return Err(FromVariantError::ArrayPtrNull);
+    }
+    //clearing the outer VARIANT destroys the array and each element in it, leaving it VT_EMPTY
+    let _clear = OwnedPtr::<VARIANT, ClearVariant>::new(var);
+
+    let sa_dims = unsafe { SafeArrayGetDim(psa) };
+    if sa_dims != 1 {
+        return Err(FromVariantError::from(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims}));
+    }
+    let _depth = DecodeLimits::enter_array()?;
+    let (l_bound, r_bound) = get_bounds(psa, 1)?;
+    let len = (r_bound - l_bound + 1).max(0) as usize;
+    DecodeLimits::check_elements(len)?;
+    check_elem_size(psa, mem::size_of::<VARIANT>())?;
+
+    let mut data: *mut c_void = ptr::null_mut();
+    let hr = unsafe { SafeArrayAccessData(psa, &mut data) };
+    if hr != 0 {
+        return Err(FromVariantError::from(FromSafeArrayError::AccessDataFailed{hr: hr}));
+    }
+    let data = data as *mut VARIANT;
+    //decoding in place leaves each slot owned by the array
+    let items = (0..len)
+        .map(|ix| Variants::from_variant(Ptr::with_checked(unsafe { data.add(ix) }).unwrap()))
+        .collect();
+    unsafe { SafeArrayUnaccessData(psa) };
+    items
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use winapi::shared::wtypes::VT_EMPTY;
+    use super::super::variant::VariantExt;
+    #[test]
+    fn test_roundtrip() {
+        let items = vec![Variants::I32(-4), Variants::from("nested"), Variants::Bool(true), Variants::F64(0.5)];
+        let pvar = variants_to_variant(items.clone()).unwrap();
+        let vt = unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32;
+        assert_eq!(vt, VT_ARRAY_VARIANT);
+        assert_eq!(variant_to_variants(pvar).unwrap(), items);
+        let vt = unsafe { (*pvar.as_ptr()).n1.n2().vt } as u32;
+        assert_eq!(vt, VT_EMPTY);
+    }
+
+    #[test]
+    fn test_empty() {
+        let pvar = variants_to_variant(Vec::new()).unwrap();
+        assert_eq!(variant_to_variants(pvar).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn test_wrong_vartype() {
+        let pvar = 1i32.into_variant().unwrap();
+        match variant_to_variants(pvar) {
+            Err(FromVariantError::VarTypeDoesNotMatch{found, ..}) => assert_eq!(found, 3),
+            _ => panic!("expected a vartype mismatch"),
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! # View
+//! Locked, in-place access to the data of numeric SAFEARRAYs. 
+//! 
+//! Arrays received from COM can be sorted, mapped over or filled directly in 
+//! their own storage and handed back, without a SAFEARRAY -> Vec -> SAFEARRAY 
+//! round trip. The array stays locked (via `SafeArrayAccessData`) for as long 
+//! as the view lives. `SafeArrayDataGuard` is the read-only counterpart, for 
+//! scanning an array that is only borrowed.
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::slice;
+
+use winapi::ctypes::c_void;
+use winapi::um::oaidl::SAFEARRAY;
+
+use super::array::{check_elem_size, get_vartype, safearray_len, SafeArrayAccessData, SafeArrayElement, SafeArrayUnaccessData};
+use super::errors::FromSafeArrayError;
+use super::ptr::Ptr;
+
+/// Element types whose SAFEARRAY storage is laid out exactly like `[Self]`
+/// 
+/// # Safety
+/// 
+/// The views hand out the data of a `SFTYPE` array as `&[Self]`/`&mut [Self]`, checking only 
+/// the element size. `Self` must have exactly the layout `SFTYPE` elements are stored with, 
+/// and every bit pattern such an array can hold must be a valid `Self`.
+pub unsafe trait ArrayData: SafeArrayElement + Copy {}
+
+unsafe impl ArrayData for i8 {}
+unsafe impl ArrayData for i16 {}
+unsafe impl ArrayData for i32 {}
+unsafe impl ArrayData for u8 {}
+unsafe impl ArrayData for u16 {}
+unsafe impl ArrayData for u32 {}
+unsafe impl ArrayData for f32 {}
+unsafe impl ArrayData for f64 {}
+
+/// Mutable view over the elements of a locked SAFEARRAY. 
+/// 
+/// Multi-dimensional arrays are viewed flat, in storage order (leftmost dimension varies fastest).
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{SafeArrayExt, SafeArrayViewMut};
+/// 
+/// fn main() {
+///     let mut psa = vec![3i32, 1, 2].into_iter().into_safearray().unwrap();
+///     {
+///         let mut view = SafeArrayViewMut::<i32>::new(&mut psa).unwrap();
+///         view.sort();
+///         view.map(|n| *n *= 10);
+///     }
+///     let v = ExactSizeIterator::<Item=i32>::from_safearray(psa.as_ptr()).unwrap();
+///     assert_eq!(v, vec![10, 20, 30]);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SafeArrayViewMut<'a, T: ArrayData + 'a> {
+    psa: *mut SAFEARRAY, 
+    data: *mut T, 
+    len: usize, 
+    _marker: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T: ArrayData> SafeArrayViewMut<'a, T> {
+    /// Locks the array and checks that its vartype matches `T`
+    pub fn new(psa: &'a mut Ptr<SAFEARRAY>) -> Result<SafeArrayViewMut<'a, T>, FromSafeArrayError> {
+        let psa = psa.as_ptr();
+        let (data, len) = access_data::<T>(psa)?;
+        Ok(SafeArrayViewMut {
+            psa: psa, 
+            data: data, 
+            len: len, 
+            _marker: PhantomData,
+        })
+    }
+
+    /// Number of elements across all dimensions
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// True if the array holds no elements
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The elements as a slice
+    pub fn as_slice(&self) -> &[T] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.data, self.len) }
+    }
+
+    /// The elements as a mutable slice
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        if self.len == 0 {
+            return &mut [];
+        }
+        unsafe { slice::from_raw_parts_mut(self.data, self.len) }
+    }
+
+    /// Sets every element to `value`
+    pub fn fill(&mut self, value: T) {
+        for elem in self.as_mut_slice() {
+            *elem = value;
+        }
+    }
+
+    /// Calls `f` on every element in place
+    pub fn map<F: FnMut(&mut T)>(&mut self, f: F) {
+        self.as_mut_slice().iter_mut().for_each(f);
+    }
+
+    /// Sorts the elements with a comparator function
+    pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&mut self, compare: F) {
+        self.as_mut_slice().sort_by(compare);
+    }
+}
+
+impl<'a, T: ArrayData + Ord> SafeArrayViewMut<'a, T> {
+    /// Sorts the elements in ascending order
+    pub fn sort(&mut self) {
+        self.as_mut_slice().sort();
+    }
+}
+
+impl<'a, T: ArrayData> Deref for SafeArrayViewMut<'a, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<'a, T: ArrayData> DerefMut for SafeArrayViewMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<'a, T: ArrayData> Drop for SafeArrayViewMut<'a, T> {
+    fn drop(&mut self) {
+        unsafe { SafeArrayUnaccessData(self.psa) };
+    }
+}
+
+/// Read-only view over the elements of a locked SAFEARRAY, dereferencing to `&[T]`. 
+/// 
+/// Only borrows the array, so it can be used on arrays owned elsewhere, e.g. an 
+/// `[in]` parameter. Use `SafeArrayViewMut` to write. 
+/// 
+/// ## Example
+/// 
+/// ```
+/// extern crate oaidl;
+/// 
+/// use oaidl::{SafeArrayDataGuard, SafeArrayExt};
+/// 
+/// fn main() {
+///     let psa = vec![1.5f64, 2.5, 3.0].into_iter().into_safearray().unwrap();
+///     {
+///         let data = SafeArrayDataGuard::<f64>::new(&psa).unwrap();
+///         assert_eq!(data.iter().sum::<f64>(), 7.0);
+///     }
+///     ExactSizeIterator::<Item=f64>::from_safearray(psa.as_ptr()).unwrap();
+/// }
+/// ```
+pub struct SafeArrayDataGuard<'a, T: ArrayData + 'a> {
+    psa: *mut SAFEARRAY, 
+    data: *const T, 
+    len: usize, 
+    _marker: PhantomData<&'a [T]>,
+}
+
+impl<'a, T: ArrayData> SafeArrayDataGuard<'a, T> {
+    /// Locks the array and checks that its vartype matches `T`
+    pub fn new(psa: &'a Ptr<SAFEARRAY>) -> Result<SafeArrayDataGuard<'a, T>, FromSafeArrayError> {
+        let psa = psa.as_ptr();
+        let (data, len) = access_data::<T>(psa)?;
+        Ok(SafeArrayDataGuard {
+            psa: psa, 
+            data: data, 
+            len: len, 
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<'a, T: ArrayData> Deref for SafeArrayDataGuard<'a, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.data, self.len) }
+    }
+}
+
+impl<'a, T: ArrayData> Drop for SafeArrayDataGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe { SafeArrayUnaccessData(self.psa) };
+    }
+}
+
+/// Checks `psa` holds `T` and locks its data
+fn access_data<T: ArrayData>(psa: *mut SAFEARRAY) -> Result<(*mut T, usize), FromSafeArrayError> {
+    let vt = get_vartype(psa)?;
+    if vt != T::SFTYPE {
+        return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: T::SFTYPE, found: vt});
+    }
+    check_elem_size(psa, mem::size_of::<T>())?;
+    let len = safearray_len(psa)?;
+
+    let mut data: *mut c_void = ptr::null_mut();
+    let hr = unsafe { SafeArrayAccessData(psa, &mut data) };
+    if hr != 0 {
+        return Err(FromSafeArrayError::AccessDataFailed{hr: hr});
+    }
+    Ok((data as *mut T, len))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::SafeArrayExt;
+    #[test]
+    fn test_in_place() {
+        let mut psa = vec![2.5f64, -1.0, 0.5].into_iter().into_safearray().unwrap();
+        {
+            let mut view = SafeArrayViewMut::<f64>::new(&mut psa).unwrap();
+            assert_eq!(view.len(), 3);
+            view.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            view.map(|n| *n *= 2.0);
+            assert_eq!(view.as_slice(), &[-2.0, 1.0, 5.0]);
+            view.fill(0.0);
+        }
+        let v = ExactSizeIterator::<Item=f64>::from_safearray(psa.as_ptr()).unwrap();
+        assert_eq!(v, vec![0.0; 3]);
+    }
+
+    #[test]
+    fn test_data_guard() {
+        let mut psa = vec![4u16, 5, 6].into_iter().into_safearray().unwrap();
+        {
+            let mut view = SafeArrayViewMut::<u16>::new(&mut psa).unwrap();
+            view[0] = 7;
+        }
+        {
+            let data = SafeArrayDataGuard::<u16>::new(&psa).unwrap();
+            assert_eq!(&*data, &[7, 5, 6]);
+            let again = SafeArrayDataGuard::<u16>::new(&psa).unwrap();
+            assert_eq!(again.len(), 3);
+        }
+        ExactSizeIterator::<Item=u16>::from_safearray(psa.as_ptr()).unwrap();
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let mut psa = vec![1i32].into_iter().into_safearray().unwrap();
+        match SafeArrayViewMut::<u8>::new(&mut psa) {
+            Err(FromSafeArrayError::VarTypeDoesNotMatch{..}) => {}, 
+            _ => panic!("expected a vartype mismatch"),
+        };
+    }
+}
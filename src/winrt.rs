@@ -0,0 +1,253 @@
+//! # WinRT
+//! `Variants` to and from Windows Runtime `PropertyValue` boxes.
+//!
+//! WinRT components pass dynamically typed values as an `IInspectable` implementing
+//! `Windows.Foundation.IPropertyValue`. These conversions box a `Variants` through
+//! the `PropertyValue` activation factory and unbox one by its `PropertyType`, so code
+//! bridging automation and WinRT can keep a single dynamic value model.
+//!
+//! The caller must have initialized the Windows Runtime (`RoInitialize`) on the thread.
+//! `Currency` and `SCode` have no `PropertyType` and are rejected; `I8` widens to `Int16`,
+//! `Int`/`UInt` to `Int32`/`UInt32`, and `Date` maps to `DateTime`.
+use std::ptr::null_mut;
+use std::slice;
+
+use widestring::U16String;
+
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::GUID;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::wtypes::{VT_ARRAY, VT_CY, VT_DECIMAL, VT_DISPATCH, VT_EMPTY, VT_ERROR, VT_NULL, VT_UNKNOWN, VT_VARIANT};
+use winapi::winrt::hstring::HSTRING;
+use winapi::winrt::inspectable::{IInspectable, IInspectableVtbl};
+use winapi::winrt::roapi::RoGetActivationFactory;
+use winapi::winrt::winstring::{WindowsCreateString, WindowsDeleteString, WindowsGetStringRawBuffer};
+use winapi::Interface;
+
+use super::dtor::{ComDestructor, OwnedPtr};
+use super::errors::WinRtError;
+use super::ptr::Ptr;
+use super::types::{date_to_ticks, ticks_to_date};
+use super::variant::Variants;
+
+const PROPERTY_VALUE_CLASS: &str = "Windows.Foundation.PropertyValue";
+
+// Windows.Foundation.PropertyType
+const PT_EMPTY: i32 = 0;
+const PT_UINT8: i32 = 1;
+const PT_INT16: i32 = 2;
+const PT_UINT16: i32 = 3;
+const PT_INT32: i32 = 4;
+const PT_UINT32: i32 = 5;
+const PT_INT64: i32 = 6;
+const PT_UINT64: i32 = 7;
+const PT_SINGLE: i32 = 8;
+const PT_DOUBLE: i32 = 9;
+const PT_BOOLEAN: i32 = 11;
+const PT_STRING: i32 = 12;
+const PT_DATETIME: i32 = 14;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DateTime {
+    universal_time: i64,
+}
+
+type Out = *mut *mut IInspectable;
+
+#[repr(C)]
+struct IPropertyValueStaticsVtbl {
+    _parent: IInspectableVtbl,
+    _create_empty: unsafe extern "system" fn(this: *mut IPropertyValueStatics, out: Out) -> HRESULT,
+    create_uint8: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: u8, out: Out) -> HRESULT,
+    create_int16: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: i16, out: Out) -> HRESULT,
+    create_uint16: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: u16, out: Out) -> HRESULT,
+    create_int32: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: i32, out: Out) -> HRESULT,
+    create_uint32: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: u32, out: Out) -> HRESULT,
+    create_int64: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: i64, out: Out) -> HRESULT,
+    create_uint64: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: u64, out: Out) -> HRESULT,
+    create_single: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: f32, out: Out) -> HRESULT,
+    create_double: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: f64, out: Out) -> HRESULT,
+    _create_char16: *const c_void,
+    create_boolean: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: u8, out: Out) -> HRESULT,
+    create_string: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: HSTRING, out: Out) -> HRESULT,
+    _create_inspectable: *const c_void,
+    _create_guid: *const c_void,
+    create_date_time: unsafe extern "system" fn(this: *mut IPropertyValueStatics, v: DateTime, out: Out) -> HRESULT,
+}
+
+#[repr(C)]
+struct IPropertyValueStatics {
+    vtbl: *const IPropertyValueStaticsVtbl,
+}
+
+impl Interface for IPropertyValueStatics {
+    fn uuidof() -> GUID {
+        GUID { Data1: 0x629b_dbc8, Data2: 0xd932, Data3: 0x4ff4, Data4: [0x96, 0xb9, 0x8d, 0x96, 0xc5, 0xc1, 0xe8, 0x58] }
+    }
+}
+
+#[repr(C)]
+struct IPropertyValueVtbl {
+    _parent: IInspectableVtbl,
+    get_type: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut i32) -> HRESULT,
+    _get_is_numeric_scalar: *const c_void,
+    get_uint8: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut u8) -> HRESULT,
+    get_int16: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut i16) -> HRESULT,
+    get_uint16: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut u16) -> HRESULT,
+    get_int32: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut i32) -> HRESULT,
+    get_uint32: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut u32) -> HRESULT,
+    get_int64: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut i64) -> HRESULT,
+    get_uint64: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut u64) -> HRESULT,
+    get_single: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut f32) -> HRESULT,
+    get_double: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut f64) -> HRESULT,
+    _get_char16: *const c_void,
+    get_boolean: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut u8) -> HRESULT,
+    get_string: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut HSTRING) -> HRESULT,
+    _get_guid: *const c_void,
+    get_date_time: unsafe extern "system" fn(this: *mut IPropertyValue, out: *mut DateTime) -> HRESULT,
+}
+
+#[repr(C)]
+struct IPropertyValue {
+    vtbl: *const IPropertyValueVtbl,
+}
+
+impl Interface for IPropertyValue {
+    fn uuidof() -> GUID {
+        GUID { Data1: 0x4bd6_82dd, Data2: 0x7554, Data3: 0x40e9, Data4: [0x9a, 0x9b, 0x82, 0x65, 0x4e, 0xde, 0x7e, 0x62] }
+    }
+}
+
+/// Owned HSTRING, deleted on drop
+struct HString(HSTRING);
+
+impl HString {
+    fn new(s: &U16String) -> Result<HString, WinRtError> {
+        let mut h: HSTRING = null_mut();
+        let hr = unsafe { WindowsCreateString(s.as_ptr(), s.len() as u32, &mut h) };
+        if hr < 0 {
+            return Err(WinRtError::HStringFailed{hr: hr});
+        }
+        Ok(HString(h))
+    }
+
+    fn to_u16string(&self) -> U16String {
+        let mut len: u32 = 0;
+        let buf = unsafe { WindowsGetStringRawBuffer(self.0, &mut len) };
+        if buf.is_null() || len == 0 {
+            return U16String::new();
+        }
+        U16String::from_vec(unsafe { slice::from_raw_parts(buf, len as usize) })
+    }
+}
+
+impl Drop for HString {
+    fn drop(&mut self) {
+        //deleting a null HSTRING is a no-op
+        unsafe { WindowsDeleteString(self.0) };
+    }
+}
+
+fn check(hr: HRESULT) -> Result<(), WinRtError> {
+    if hr < 0 { Err(WinRtError::CallFailed{hr: hr}) } else { Ok(()) }
+}
+
+fn statics() -> Result<OwnedPtr<IPropertyValueStatics, ComDestructor>, WinRtError> {
+    let class = HString::new(&U16String::from_str(PROPERTY_VALUE_CLASS))?;
+    let mut factory: *mut c_void = null_mut();
+    let hr = unsafe { RoGetActivationFactory(class.0, &IPropertyValueStatics::uuidof(), &mut factory) };
+    match Ptr::with_checked(factory as *mut IPropertyValueStatics) {
+        Some(p) if hr >= 0 => Ok(OwnedPtr::new(p)),
+        _ => Err(WinRtError::ActivationFailed{hr: hr}),
+    }
+}
+
+/// Boxes `val` as a WinRT `PropertyValue`
+///
+/// Fails with `WinRtError::NoEquivalent` for `Empty`, `Null`, `Currency`, `SCode`, `Decimal`
+/// the interface arms and `Array`.
+pub fn property_value_from_variants(val: &Variants) -> Result<OwnedPtr<IInspectable, ComDestructor>, WinRtError> {
+    let factory = statics()?;
+    let f = factory.as_ptr();
+    let create = unsafe { &*(*f).vtbl };
+    let mut out: *mut IInspectable = null_mut();
+    let hr = unsafe { match *val {
+        Variants::U8(v) => (create.create_uint8)(f, v, &mut out),
+        Variants::I8(v) => (create.create_int16)(f, i16::from(v), &mut out),
+        Variants::I16(v) => (create.create_int16)(f, v, &mut out),
+        Variants::U16(v) => (create.create_uint16)(f, v, &mut out),
+        Variants::I32(v) => (create.create_int32)(f, v, &mut out),
+        Variants::Int(v) => (create.create_int32)(f, i32::from(v), &mut out),
+        Variants::U32(v) => (create.create_uint32)(f, v, &mut out),
+        Variants::UInt(v) => (create.create_uint32)(f, u32::from(v), &mut out),
+        Variants::I64(v) => (create.create_int64)(f, v, &mut out),
+        Variants::U64(v) => (create.create_uint64)(f, v, &mut out),
+        Variants::F32(v) => (create.create_single)(f, v, &mut out),
+        Variants::F64(v) => (create.create_double)(f, v, &mut out),
+        Variants::Bool(v) => (create.create_boolean)(f, v as u8, &mut out),
+        Variants::String(ref s) => {
+            let h = HString::new(&U16String::from_str(s))?;
+            (create.create_string)(f, h.0, &mut out)
+        },
+        Variants::WideString(ref w) => {
+            let h = HString::new(w)?;
+            (create.create_string)(f, h.0, &mut out)
+        },
+        Variants::Date(d) => (create.create_date_time)(f, DateTime { universal_time: date_to_ticks(d) }, &mut out),
+        Variants::Empty => return Err(WinRtError::NoEquivalent{vartype: VT_EMPTY}),
+        Variants::Null => return Err(WinRtError::NoEquivalent{vartype: VT_NULL}),
+        Variants::Currency(_) => return Err(WinRtError::NoEquivalent{vartype: VT_CY}),
+        Variants::SCode(_) => return Err(WinRtError::NoEquivalent{vartype: VT_ERROR}),
+        Variants::Decimal(_) => return Err(WinRtError::NoEquivalent{vartype: VT_DECIMAL}),
+        Variants::Unknown(_) => return Err(WinRtError::NoEquivalent{vartype: VT_UNKNOWN}),
+        Variants::Dispatch(_) => return Err(WinRtError::NoEquivalent{vartype: VT_DISPATCH}),
+        Variants::Array(_) => return Err(WinRtError::NoEquivalent{vartype: VT_ARRAY | VT_VARIANT}),
+    }};
+    match Ptr::with_checked(out) {
+        Some(p) if hr >= 0 => Ok(OwnedPtr::new(p)),
+        _ => Err(WinRtError::CreateFailed{hr: hr}),
+    }
+}
+
+/// Unboxes a WinRT `PropertyValue`. A null pointer or a `PropertyType` of `Empty` is an error,
+/// as is any `PropertyType` without a `Variants` arm (arrays, `Guid`, `TimeSpan`, geometry).
+/// `Char16` values are rejected too.
+pub fn variants_from_property_value(val: &Ptr<IInspectable>) -> Result<Variants, WinRtError> {
+    let punk = Ptr::with_checked(val.as_ptr() as *mut _).unwrap();
+    let pv = match punk.query::<IPropertyValue>() {
+        Ok(pv) => pv,
+        Err(e) => return Err(WinRtError::NotAPropertyValue{hr: e.hr()}),
+    };
+    let p = pv.as_ptr();
+    let get = unsafe { &*(*p).vtbl };
+    let mut pt: i32 = PT_EMPTY;
+    check(unsafe { (get.get_type)(p, &mut pt) })?;
+    macro_rules! get {
+        ($f:ident, $init:expr, $arm:expr) => {{
+            let mut v = $init;
+            check(unsafe { (get.$f)(p, &mut v) })?;
+            Ok($arm(v))
+        }};
+    }
+    match pt {
+        PT_UINT8 => get!(get_uint8, 0u8, Variants::U8),
+        PT_INT16 => get!(get_int16, 0i16, Variants::I16),
+        PT_UINT16 => get!(get_uint16, 0u16, Variants::U16),
+        PT_INT32 => get!(get_int32, 0i32, Variants::I32),
+        PT_UINT32 => get!(get_uint32, 0u32, Variants::U32),
+        PT_INT64 => get!(get_int64, 0i64, Variants::I64),
+        PT_UINT64 => get!(get_uint64, 0u64, Variants::U64),
+        PT_SINGLE => get!(get_single, 0f32, Variants::F32),
+        PT_DOUBLE => get!(get_double, 0f64, Variants::F64),
+        PT_BOOLEAN => get!(get_boolean, 0u8, |b: u8| Variants::Bool(b != 0)),
+        PT_STRING => {
+            let mut h = HString(null_mut());
+            check(unsafe { (get.get_string)(p, &mut h.0) })?;
+            Ok(Variants::from_wide(h.to_u16string()))
+        },
+        PT_DATETIME => get!(get_date_time, DateTime { universal_time: 0 }, |dt: DateTime| Variants::Date(ticks_to_date(dt.universal_time))),
+        _ => Err(WinRtError::UnsupportedPropertyType{property_type: pt}),
+    }
+}
+